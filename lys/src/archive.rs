@@ -0,0 +1,276 @@
+use crate::db::{Season, decompress, season_start};
+use crate::utils::ok;
+use anyhow::Error;
+use chrono::{Datelike, Local};
+use serde::{Deserialize, Serialize};
+use sqlite::{Connection, State};
+use std::path::{Path, PathBuf};
+
+/// Manifeste signé d'une saison scellée, écrit à côté de sa base
+/// (`2025/autumn/autumn.seal`, signature dans `autumn.sig`).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Seal {
+    pub shard: String,
+    pub sealed: String,
+    pub public_key: String,
+    pub hash: String,
+    pub size: u64,
+    pub commits: i64,
+    // Niveau zstd des blobs recompressés au scellement, s'il y en a eu
+    pub level: Option<i32>,
+}
+
+fn digest(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+fn seal_path(db: &Path) -> PathBuf {
+    db.with_extension("seal")
+}
+
+/// Vrai si la saison `shard` (chemin relatif à `.lys/db`) a été scellée.
+pub fn is_sealed(db_dir: &Path, shard: &str) -> bool {
+    seal_path(&db_dir.join(shard)).exists()
+}
+
+/// Base d'une saison écrite comme son dossier : `2025/autumn` -> `2025/autumn/autumn.db`.
+pub fn shard(spec: &str) -> Result<String, Error> {
+    let spec = spec.trim_matches('/');
+    season_start(spec)
+        .ok_or_else(|| anyhow::anyhow!("'{spec}' is not a season (expected <year>/<season>)."))?;
+    let (_, name) = spec.split_once('/').unwrap_or_default();
+    Ok(format!("{spec}/{name}.db"))
+}
+
+// Recompresse les blobs référencés par la saison, et les morceaux de ceux qui
+// sont découpés ; le hash porte sur le contenu brut, il ne change donc pas
+fn recompress(conn: &Connection, schema: &str, level: i32) -> Result<(usize, usize), Error> {
+    let mut objects = Vec::new();
+    for (table, query) in [
+        (
+            "blobs",
+            format!(
+                "SELECT hash, content FROM store.blobs
+                 WHERE content IS NOT NULL AND id IN (SELECT blob_id FROM {schema}.manifest)"
+            ),
+        ),
+        (
+            "chunks",
+            format!(
+                "SELECT hash, content FROM store.chunks WHERE hash IN
+                 (SELECT bc.chunk_hash FROM store.blob_chunks bc
+                  JOIN store.blobs b ON b.hash = bc.blob_hash
+                  WHERE b.id IN (SELECT blob_id FROM {schema}.manifest))"
+            ),
+        ),
+    ] {
+        let mut stmt = conn.prepare(query)?;
+        while let Ok(State::Row) = stmt.next() {
+            objects.push((
+                table,
+                stmt.read::<String, _>(0)?,
+                stmt.read::<Vec<u8>, _>(1)?,
+            ));
+        }
+    }
+    let (mut rewritten, mut saved) = (0, 0);
+    conn.execute("BEGIN TRANSACTION;")?;
+    for (table, hash, stored) in objects {
+        let raw = decompress(&stored);
+        // Un objet déjà abîmé est laissé à `lys verify`
+        if digest(&raw) != hash {
+            continue;
+        }
+        let packed = zstd::encode_all(raw.as_slice(), level)?;
+        if packed.len() >= stored.len() {
+            continue;
+        }
+        let mut stmt = conn.prepare(format!(
+            "UPDATE store.{table} SET content = ? WHERE hash = ?"
+        ))?;
+        stmt.bind((1, packed.as_slice()))?;
+        stmt.bind((2, hash.as_str()))?;
+        stmt.next()?;
+        rewritten += 1;
+        saved += stored.len() - packed.len();
+    }
+    conn.execute("COMMIT;")?;
+    Ok((rewritten, saved))
+}
+
+/// `lys archive <year>/<season> [--level N]` : compacte une saison close,
+/// écrit son manifeste signé et la passe en lecture seule.
+pub fn archive(
+    conn: &Connection,
+    root: &Path,
+    spec: &str,
+    level: Option<i32>,
+) -> Result<Seal, Error> {
+    let shard = shard(spec)?;
+    let spec = shard.rsplit_once('/').map_or(spec, |(dir, _)| dir);
+    if spec == format!("{}/{}", Local::now().year(), Season::current()) {
+        return Err(anyhow::anyhow!(
+            "{spec} is the current season and cannot be archived."
+        ));
+    }
+    let path = root.join(".lys/db").join(&shard);
+    if !path.is_file() {
+        return Err(anyhow::anyhow!("No season database for {spec}."));
+    }
+    if seal_path(&path).exists() {
+        return Err(anyhow::anyhow!("{spec} is already sealed."));
+    }
+    if let Some(level) = level
+        && !zstd::compression_level_range().contains(&level)
+    {
+        return Err(anyhow::anyhow!("Invalid zstd level {level}."));
+    }
+
+    let schema = crate::history::attach(conn, &shard)?;
+    if let Some(level) = level {
+        let (rewritten, saved) = recompress(conn, &schema, level)?;
+        if saved > 0 {
+            conn.execute("VACUUM store;")?;
+        }
+        ok(format!(
+            "Recompressed {rewritten} object(s) at zstd level {level}, {saved} bytes saved"
+        )
+        .as_str());
+    }
+    let before = path.metadata()?.len();
+    conn.execute(format!("VACUUM {schema};"))?;
+    // Sans WAL, tout le contenu est dans le fichier scellé
+    conn.execute(format!("PRAGMA {schema}.journal_mode = DELETE;"))?;
+    let mut stmt = conn.prepare(format!("SELECT COUNT(*) FROM {schema}.commits"))?;
+    stmt.next()?;
+    let commits = stmt.read::<i64, _>(0)?;
+    drop(stmt);
+
+    let content = std::fs::read(&path)?;
+    let seal = Seal {
+        shard: shard.clone(),
+        sealed: chrono::Utc::now().to_rfc3339(),
+        public_key: crate::crypto::public_key(root)
+            .map(hex::encode)
+            .unwrap_or_default(),
+        hash: digest(&content),
+        size: content.len() as u64,
+        commits,
+        level,
+    };
+    let manifest = serde_json::to_vec_pretty(&seal)?;
+    let signature =
+        crate::crypto::sign_message(root, &digest(&manifest)).map_err(|e| anyhow::anyhow!(e))?;
+    std::fs::write(path.with_extension("sig"), signature)?;
+    std::fs::write(seal_path(&path), &manifest)?;
+    let mut permissions = path.metadata()?.permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&path, permissions)?;
+    ok(format!(
+        "Sealed {spec}: {commits} commit(s), {before} -> {} bytes (blake3 {})",
+        seal.size,
+        &seal.hash[..7]
+    )
+    .as_str());
+    Ok(seal)
+}
+
+/// Saisons scellées dont la base ne correspond plus au manifeste, ou dont le
+/// manifeste n'est pas signé par une clé de confiance : `(saison, raison)`.
+pub fn check(db_dir: &Path, keys: &[Vec<u8>]) -> Vec<(String, String)> {
+    let pattern = format!("{}/**/*.seal", db_dir.display());
+    let mut broken = Vec::new();
+    for path in glob::glob(&pattern)
+        .map(|paths| paths.filter_map(|path| path.ok()).collect::<Vec<_>>())
+        .unwrap_or_default()
+    {
+        let name = path
+            .strip_prefix(db_dir)
+            .unwrap_or(&path)
+            .with_extension("db")
+            .to_string_lossy()
+            .to_string();
+        let manifest = std::fs::read(&path).unwrap_or_default();
+        let signature = std::fs::read_to_string(path.with_extension("sig")).unwrap_or_default();
+        if !keys
+            .iter()
+            .any(|key| crate::crypto::verify_with_key(key, &digest(&manifest), signature.trim()))
+        {
+            broken.push((name, "seal is not signed by a trusted key".to_string()));
+            continue;
+        }
+        let Ok(seal) = serde_json::from_slice::<Seal>(&manifest) else {
+            broken.push((name, "seal is unreadable".to_string()));
+            continue;
+        };
+        let reason = match std::fs::read(db_dir.join(&seal.shard)) {
+            Err(_) => "database is missing",
+            Ok(content) if content.len() as u64 != seal.size || digest(&content) != seal.hash => {
+                "database changed since it was sealed"
+            }
+            Ok(_) => continue,
+        };
+        broken.push((seal.shard, reason.to_string()));
+    }
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsck::Issue;
+    use crate::utils::AUTHOR;
+
+    // Une saison close scellée reste lisible, puis toute retouche est signalée
+    #[test]
+    fn seals_and_verifies_a_season() {
+        let (repo, conn) = crate::utils::test_repo();
+
+        std::fs::write("a.txt", "sealed\n".repeat(200)).unwrap();
+        crate::vcs::commit(&conn, "old season", AUTHOR).unwrap();
+        let (_, head) = crate::vcs::get_branch_head_info(&conn, "main").unwrap();
+        conn.execute("PRAGMA wal_checkpoint(TRUNCATE);").unwrap();
+        drop(conn);
+        let now = format!("{}/{}", Local::now().year(), Season::current());
+        let current = repo.path().join(".lys/db").join(shard(&now).unwrap());
+        let target = repo.path().join(".lys/db/2001/winter/winter.db");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::rename(&current, &target).unwrap();
+
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let refused = archive(&conn, repo.path(), &now, None).is_err();
+        let seal = archive(&conn, repo.path(), "2001/winter", Some(19)).unwrap();
+        let again = archive(&conn, repo.path(), "2001/winter/", None).is_err();
+        drop(conn);
+
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let tree = crate::db::commit_tree_hash(&conn, &head).unwrap();
+        let intact = crate::fsck::run(&conn, repo.path(), true, None).unwrap();
+        drop(conn);
+        let mut permissions = target.metadata().unwrap().permissions();
+        let readonly = permissions.readonly();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&target, permissions).unwrap();
+        let mut content = std::fs::read(&target).unwrap();
+        content.extend_from_slice(b"tampered");
+        std::fs::write(&target, content).unwrap();
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let tampered = crate::fsck::run(&conn, repo.path(), false, None).unwrap();
+
+        assert!(refused);
+        assert!(again);
+        assert!(readonly);
+        assert_eq!(seal.commits, 1);
+        assert_eq!(seal.level, Some(19));
+        assert!(tree.is_some());
+        assert!(intact.issues.is_empty(), "{:?}", intact.issues);
+        assert_eq!(
+            tampered.issues,
+            vec![Issue::BrokenSeal {
+                shard: "2001/winter/winter.db".to_string(),
+                reason: "database changed since it was sealed".to_string(),
+            }]
+        );
+    }
+}
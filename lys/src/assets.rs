@@ -0,0 +1,307 @@
+use crate::diff::is_binary;
+use similar::TextDiff;
+use sqlite::{Connection, Error, State};
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Similarité minimale (en %) pour qu'un fichier modifié en changeant de nom
+/// soit vu comme un renommage plutôt qu'une suppression et un ajout.
+pub const THRESHOLD: u8 = 50;
+
+// Au-delà, seuls les renommages à contenu identique sont cherchés
+const MAX_PAIRS: usize = 1_000;
+
+// Durée maximale d'un diff entre deux candidats
+const DIFF_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Un fichier disparu (`from`) retrouvé sous un autre nom (`to`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pair {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub score: u8,
+}
+
+// Contenu texte d'un candidat avec le compte de ses lignes, calculé une fois
+// pour toutes les paires où il figure
+struct Sample {
+    text: String,
+    lines: HashMap<u64, u32>,
+    count: usize,
+}
+
+impl Sample {
+    // `None` pour un contenu binaire, jamais comparé
+    fn new(content: &[u8]) -> Option<Sample> {
+        if is_binary(content) {
+            return None;
+        }
+        let text = String::from_utf8_lossy(content).into_owned();
+        let mut lines = HashMap::new();
+        let mut count = 0;
+        for line in text.split_inclusive('\n') {
+            let mut hasher = DefaultHasher::new();
+            line.hash(&mut hasher);
+            *lines.entry(hasher.finish()).or_insert(0) += 1;
+            count += 1;
+        }
+        Some(Sample { text, lines, count })
+    }
+}
+
+/// Pourcentage de lignes communes entre deux contenus, 0 s'il ne peut pas
+/// atteindre `THRESHOLD` (tailles trop éloignées, contenu binaire).
+pub fn similarity(old: &[u8], new: &[u8]) -> u8 {
+    if old == new {
+        return 100;
+    }
+    match (Sample::new(old), Sample::new(new)) {
+        (Some(old), Some(new)) => score(&old, &new),
+        _ => 0,
+    }
+}
+
+fn score(old: &Sample, new: &Sample) -> u8 {
+    if old.text == new.text {
+        return 100;
+    }
+    let (small, large) = (
+        old.text.len().min(new.text.len()),
+        old.text.len().max(new.text.len()),
+    );
+    if small * 100 < large * THRESHOLD as usize {
+        return 0;
+    }
+    // Le diff ne peut apparier plus de lignes que les deux côtés n'en ont en
+    // commun : borne haute sans diff, qui écarte la plupart des paires
+    let common: u32 = old
+        .lines
+        .iter()
+        .map(|(line, n)| (*n).min(new.lines.get(line).copied().unwrap_or(0)))
+        .sum();
+    if (common as usize) * 200 < (old.count + new.count) * THRESHOLD as usize {
+        return 0;
+    }
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(&old.text, &new.text);
+    (diff.ratio() * 100.0) as u8
+}
+
+/// Apparie les fichiers disparus et apparus, `(chemin, hash)` : contenu
+/// identique d'abord (même nom de fichier de préférence), puis les paires les
+/// plus semblables au-dessus de `THRESHOLD`. `old` et `new` lisent un contenu
+/// de chaque côté ; `None` l'écarte de la recherche par similarité.
+pub fn renames(
+    deleted: &[(PathBuf, String)],
+    added: &[(PathBuf, String)],
+    old: impl Fn(&Path, &str) -> Option<Vec<u8>>,
+    new: impl Fn(&Path, &str) -> Option<Vec<u8>>,
+) -> Vec<Pair> {
+    let mut pairs = Vec::new();
+    let mut deleted: Vec<&(PathBuf, String)> = deleted.iter().collect();
+    let mut added: Vec<&(PathBuf, String)> = added.iter().collect();
+    deleted.sort();
+    added.sort();
+
+    deleted.retain(|(from, hash)| {
+        let exact = added
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, other))| other == hash)
+            .min_by_key(|(_, (to, _))| to.file_name() != from.file_name())
+            .map(|(index, _)| index);
+        let Some(index) = exact else {
+            return true;
+        };
+        let (to, _) = added.remove(index);
+        pairs.push(Pair {
+            from: from.clone(),
+            to: to.clone(),
+            score: 100,
+        });
+        false
+    });
+
+    if !deleted.is_empty() && !added.is_empty() && deleted.len() * added.len() <= MAX_PAIRS {
+        let sample = |content: Option<Vec<u8>>| content.and_then(|bytes| Sample::new(&bytes));
+        let before: Vec<_> = deleted
+            .iter()
+            .map(|(path, hash)| sample(old(path, hash)))
+            .collect();
+        let after: Vec<_> = added
+            .iter()
+            .map(|(path, hash)| sample(new(path, hash)))
+            .collect();
+        let mut scores = Vec::new();
+        for (i, before) in before.iter().enumerate() {
+            for (j, after) in after.iter().enumerate() {
+                if let (Some(before), Some(after)) = (before, after) {
+                    let score = score(before, after);
+                    if score >= THRESHOLD {
+                        scores.push((Reverse(score), i, j));
+                    }
+                }
+            }
+        }
+        scores.sort();
+        let (mut gone, mut found) = (vec![false; before.len()], vec![false; after.len()]);
+        for (Reverse(score), i, j) in scores {
+            if gone[i] || found[j] {
+                continue;
+            }
+            (gone[i], found[j]) = (true, true);
+            pairs.push(Pair {
+                from: deleted[i].0.clone(),
+                to: added[j].0.clone(),
+                score,
+            });
+        }
+    }
+    pairs.sort_by(|a, b| a.to.cmp(&b.to));
+    pairs
+}
+
+/// Asset du fichier `path` dans sa version `hash`, d'après le manifest de
+/// toutes les saisons. `None` s'il a été enregistré sans identité (asset 0).
+pub fn asset_of(conn: &Connection, path: &Path, hash: &str) -> Result<Option<i64>, Error> {
+    let mut asset = None;
+    crate::history::each_season(conn, |schema| {
+        if asset.is_some() {
+            return Ok(());
+        }
+        let mut stmt = conn.prepare(format!(
+            "SELECT m.asset_id FROM {schema}.manifest m JOIN store.blobs b ON b.id = m.blob_id
+             WHERE m.file_path = ? AND b.hash = ? AND m.asset_id != 0
+             ORDER BY m.commit_id DESC LIMIT 1"
+        ))?;
+        stmt.bind((1, path.to_string_lossy().as_ref()))?;
+        stmt.bind((2, hash))?;
+        if let Ok(State::Row) = stmt.next() {
+            asset = Some(stmt.read(0)?);
+        }
+        Ok(())
+    })?;
+    Ok(asset)
+}
+
+/// Assets de tous les fichiers dont un nom, actuel ou ancien, correspond au
+/// motif `LIKE` : l'historique d'un fichier continue à travers ses renommages.
+pub fn matching(conn: &Connection, pattern: &str) -> Result<BTreeSet<i64>, Error> {
+    let mut assets = BTreeSet::new();
+    crate::history::each_season(conn, |schema| {
+        let mut stmt = conn.prepare(format!(
+            "SELECT DISTINCT asset_id FROM {schema}.manifest
+             WHERE file_path LIKE ? COLLATE NOCASE AND asset_id != 0"
+        ))?;
+        stmt.bind((1, pattern))?;
+        while let Ok(State::Row) = stmt.next() {
+            assets.insert(stmt.read::<i64, _>(0)?);
+        }
+        Ok(())
+    })?;
+    Ok(assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+    use crate::vcs::FileStatus;
+
+    // Un fichier renommé tel quel, puis renommé en étant modifié, garde son
+    // asset ; une copie en reçoit un nouveau
+    #[test]
+    fn follows_files_across_renames() {
+        let (_repo, conn) = crate::utils::test_repo();
+        let head = || crate::vcs::get_branch_head_info(&conn, "main").unwrap().1;
+
+        let lines: String = (0..20).map(|n| format!("line {n}\n")).collect();
+        std::fs::write("a.txt", &lines).unwrap();
+        std::fs::write("keep.txt", "kept\n").unwrap();
+        crate::vcs::commit(&conn, "add", AUTHOR).unwrap();
+        let first = head();
+        std::fs::rename("a.txt", "b.txt").unwrap();
+        let moved = crate::vcs::status(&conn, ".", "main").unwrap();
+        crate::vcs::commit(&conn, "move", AUTHOR).unwrap();
+        std::fs::remove_file("b.txt").unwrap();
+        std::fs::write("c.txt", lines.replace("line 7\n", "line seven\n")).unwrap();
+        let edited = crate::vcs::status(&conn, ".", "main").unwrap();
+        crate::vcs::commit(&conn, "move and edit", AUTHOR).unwrap();
+        std::fs::copy("keep.txt", "copy.txt").unwrap();
+        crate::vcs::commit(&conn, "copy", AUTHOR).unwrap();
+        let last = head();
+
+        let hash = |path: &str| crate::vcs::hash_entry(Path::new(path)).unwrap();
+        let original = asset_of(
+            &conn,
+            Path::new("a.txt"),
+            &blake3::hash(lines.as_bytes()).to_hex(),
+        )
+        .unwrap()
+        .unwrap();
+        let renamed = asset_of(&conn, Path::new("c.txt"), &hash("c.txt")).unwrap();
+        let kept = asset_of(&conn, Path::new("keep.txt"), &hash("keep.txt")).unwrap();
+        let copied = asset_of(&conn, Path::new("copy.txt"), &hash("copy.txt")).unwrap();
+        let names = crate::diff::render(
+            &conn,
+            &crate::diff::Side::Commit(first),
+            &crate::diff::Side::Commit(last.clone()),
+            &[],
+            crate::diff::Format::NameStatus,
+        )
+        .unwrap();
+        let history = crate::history::ancestry(&conn, &[last]).unwrap();
+        let followed: Vec<(String, PathBuf)> =
+            crate::vcs::follow_renames(&conn, history, Path::new("c.txt"))
+                .unwrap()
+                .into_iter()
+                .map(|(entry, path)| (entry.message, path))
+                .collect();
+        let query = crate::db::CommitQuery {
+            file: Some("a.txt".to_string()),
+            ..Default::default()
+        };
+        let (found, _) = crate::db::query_commits(&conn, &query, 1, 10).unwrap();
+
+        assert!(
+            matches!(&moved[..], [FileStatus::Renamed(from, to, asset)]
+                if from == Path::new("a.txt") && to == Path::new("b.txt") && *asset == original),
+            "{moved:?}"
+        );
+        assert!(
+            matches!(&edited[..], [FileStatus::Renamed(from, to, asset)]
+                if from == Path::new("b.txt") && to == Path::new("c.txt") && *asset == original),
+            "{edited:?}"
+        );
+        assert_eq!(renamed, Some(original));
+        assert!(kept.is_some() && copied.is_some() && kept != copied);
+        assert_eq!(names, "R095\ta.txt\tc.txt\nC100\tkeep.txt\tcopy.txt\n");
+        assert_eq!(
+            followed,
+            [
+                ("move and edit".to_string(), PathBuf::from("c.txt")),
+                ("move".to_string(), PathBuf::from("b.txt")),
+                ("add".to_string(), PathBuf::from("a.txt")),
+            ]
+        );
+        let messages: Vec<&str> = found.iter().map(|row| row.message.as_str()).collect();
+        assert_eq!(messages, ["move and edit", "move", "add"]);
+    }
+
+    // Seules les paires qui partagent assez de lignes passent par le diff
+    #[test]
+    fn scores_only_close_contents() {
+        let lines: String = (0..20).map(|n| format!("line {n}\n")).collect();
+        let other: String = (0..20).map(|n| format!("other {n}\n")).collect();
+        let edited = lines.replace("line 3\n", "line three\n");
+        assert_eq!(similarity(lines.as_bytes(), lines.as_bytes()), 100);
+        assert_eq!(similarity(lines.as_bytes(), edited.as_bytes()), 95);
+        assert_eq!(similarity(lines.as_bytes(), other.as_bytes()), 0);
+        assert_eq!(similarity(lines.as_bytes(), b"\0\x01binary"), 0);
+    }
+}
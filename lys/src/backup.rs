@@ -0,0 +1,499 @@
+use crate::db::{config, decompress, insert_blob_with_conn, write_config};
+use crate::utils::{ko, ok};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use sqlite::{Connection, State};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Clé de config : dernière destination de `lys backup`, reprise par défaut.
+pub const BACKUP_PATH: &str = "backup_path";
+
+/// Un fichier de `.lys/` (base de saison, identité) et son empreinte.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileEntry {
+    pub path: String,
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Une ligne de `store.grafts`, déjà signée par `lys prune` : sans elle, les
+/// commits greffés d'un dépôt élagué ne se vérifient plus.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GraftEntry {
+    pub commit_hash: String,
+    pub original: String,
+    pub parents: String,
+    pub signature: String,
+    pub created_at: Option<String>,
+}
+
+/// Manifeste signé d'une génération de sauvegarde. Les objets eux-mêmes sont
+/// partagés entre générations : `objects/` pour les blobs, `files/` pour les
+/// fichiers, tous nommés par leur hash blake3.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Generation {
+    pub generation: u64,
+    pub created: String,
+    pub public_key: String,
+    pub files: Vec<FileEntry>,
+    pub blobs: Vec<String>,
+    // Id de chaque blob dans store.blobs, dans l'ordre de `blobs` : les
+    // manifest des saisons y renvoient, un élagage y laisse des trous
+    #[serde(default)]
+    pub blob_ids: Vec<i64>,
+    // Lignes de store.assets (id, uuid, created_at), référencées par le manifest
+    pub assets: Vec<(i64, String, Option<String>)>,
+    // Absent des générations écrites avant l'élagage
+    #[serde(default)]
+    pub grafts: Vec<GraftEntry>,
+}
+
+fn object_path(dest: &Path, hash: &str) -> PathBuf {
+    dest.join("objects")
+        .join(&hash[..2.min(hash.len())])
+        .join(hash)
+}
+
+fn file_path(dest: &Path, hash: &str) -> PathBuf {
+    dest.join("files").join(hash)
+}
+
+fn manifest_path(dest: &Path, generation: u64) -> PathBuf {
+    dest.join("generations").join(format!("{generation}.json"))
+}
+
+fn digest(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+/// Numéros des générations présentes, dans l'ordre.
+pub fn generations(dest: &Path) -> Vec<u64> {
+    let mut found: Vec<u64> = std::fs::read_dir(dest.join("generations"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    if path.extension()? != "json" {
+                        return None;
+                    }
+                    path.file_stem()?.to_str()?.parse().ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    found.sort_unstable();
+    found
+}
+
+/// Contenu d'un blob sauvegardé, seulement si son hash est intact.
+pub fn read_blob(dest: &Path, hash: &str) -> Option<Vec<u8>> {
+    let content = decompress(&std::fs::read(object_path(dest, hash)).ok()?);
+    (digest(&content) == hash).then_some(content)
+}
+
+// Écrit un objet s'il n'existe pas encore : c'est ce qui rend la sauvegarde incrémentale
+fn store_object(path: &Path, mut content: impl Read) -> Result<bool, Error> {
+    if path.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Écriture puis renommage : un objet n'est jamais visible à moitié écrit
+    let partial = path.with_extension("partial");
+    std::io::copy(&mut content, &mut std::fs::File::create(&partial)?)?;
+    std::fs::rename(&partial, path)?;
+    Ok(true)
+}
+
+// Bases de saison, sceaux et clé publique ; store.db est sauvegardé blob par
+// blob. La clé privée n'y est jamais : qui détient la sauvegarde pourrait
+// signer au nom du dépôt. Elle se conserve à part, volontairement.
+fn repository_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for pattern in [
+        ".lys/db/**/*.db",
+        ".lys/db/**/*.seal",
+        ".lys/db/**/*.sig",
+        ".lys/identity/public.key",
+    ] {
+        let pattern = format!("{}/{pattern}", root.display());
+        if let Ok(paths) = glob::glob(&pattern) {
+            files.extend(
+                paths
+                    .filter_map(|path| path.ok())
+                    .filter(|path| path.is_file() && !path.ends_with("store.db")),
+            );
+        }
+    }
+    files.sort();
+    files
+}
+
+/// `lys backup [<dest>]` : écrit les nouveaux blobs et fichiers puis une
+/// génération signée qui les liste tous. `.lys/identity/secret.key` n'est pas
+/// sauvegardée : sa copie de secours relève d'une démarche séparée.
+pub fn backup(conn: &Connection, root: &Path, dest: &Path) -> Result<Generation, Error> {
+    // Les pages encore dans le WAL doivent être dans les fichiers copiés
+    conn.execute("PRAGMA wal_checkpoint(TRUNCATE);")?;
+    let (mut written, mut bytes) = (0usize, 0usize);
+
+    let mut files = Vec::new();
+    for path in repository_files(root) {
+        let content = std::fs::read(&path)?;
+        let hash = digest(&content);
+        if store_object(&file_path(dest, &hash), content.as_slice())? {
+            written += 1;
+            bytes += content.len();
+        }
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        files.push(FileEntry {
+            path: relative.to_string_lossy().to_string(),
+            hash,
+            size: content.len() as u64,
+        });
+    }
+
+    let (mut blob_ids, mut blobs) = (Vec::new(), Vec::new());
+    let mut stmt = conn.prepare("SELECT id, hash FROM store.blobs ORDER BY id")?;
+    while let Ok(State::Row) = stmt.next() {
+        blob_ids.push(stmt.read::<i64, _>(0)?);
+        blobs.push(stmt.read::<String, _>(1)?);
+    }
+    for hash in &blobs {
+        let path = object_path(dest, hash);
+        if path.exists() {
+            continue;
+        }
+        // Le contenu est copié tel qu'il est stocké (compressé) ; un blob découpé
+        // est recompressé d'un seul tenant, en flux
+        let mut stmt = conn.prepare("SELECT content FROM store.blobs WHERE hash = ?")?;
+        stmt.bind((1, hash.as_str()))?;
+        if let Ok(State::Row) = stmt.next() {
+            match stmt.read::<Option<Vec<u8>>, _>(0)? {
+                Some(content) => store_object(&path, content.as_slice())?,
+                None => match crate::chunks::open(conn, hash)? {
+                    Some(blob) => store_object(&path, zstd::stream::read::Encoder::new(blob, 0)?)?,
+                    None => continue,
+                },
+            };
+            written += 1;
+            bytes += path.metadata()?.len() as usize;
+        }
+    }
+
+    let mut assets = Vec::new();
+    let mut stmt = conn.prepare("SELECT id, uuid, created_at FROM store.assets ORDER BY id")?;
+    while let Ok(State::Row) = stmt.next() {
+        assets.push((
+            stmt.read::<i64, _>(0)?,
+            stmt.read::<String, _>(1)?,
+            stmt.read::<Option<String>, _>(2)?,
+        ));
+    }
+
+    let mut grafts = Vec::new();
+    let mut stmt = conn.prepare(
+        "SELECT commit_hash, original, parents, signature, created_at
+         FROM store.grafts ORDER BY commit_hash",
+    )?;
+    while let Ok(State::Row) = stmt.next() {
+        grafts.push(GraftEntry {
+            commit_hash: stmt.read(0)?,
+            original: stmt.read(1)?,
+            parents: stmt.read(2)?,
+            signature: stmt.read(3)?,
+            created_at: stmt.read(4)?,
+        });
+    }
+
+    let generation = Generation {
+        generation: generations(dest).last().map_or(1, |last| last + 1),
+        created: chrono::Utc::now().to_rfc3339(),
+        public_key: crate::crypto::public_key(root)
+            .map(hex::encode)
+            .unwrap_or_default(),
+        files,
+        blobs,
+        blob_ids,
+        assets,
+        grafts,
+    };
+    // Le manifeste est écrit en dernier : une sauvegarde interrompue n'en a pas
+    let manifest = serde_json::to_vec_pretty(&generation)?;
+    let signature =
+        crate::crypto::sign_message(root, &digest(&manifest)).map_err(|e| anyhow::anyhow!(e))?;
+    let path = manifest_path(dest, generation.generation);
+    std::fs::create_dir_all(dest.join("generations"))?;
+    std::fs::write(path.with_extension("sig"), signature)?;
+    store_object(&path, manifest.as_slice())?;
+    write_config(conn, BACKUP_PATH, &dest.to_string_lossy())?;
+    ok(format!(
+        "Generation {} written to {}: {written} new object(s), {bytes} bytes",
+        generation.generation,
+        dest.display()
+    )
+    .as_str());
+    Ok(generation)
+}
+
+/// Charge une génération (la dernière par défaut) si elle est signée par l'une
+/// des clés données.
+pub fn load(dest: &Path, generation: Option<u64>, keys: &[Vec<u8>]) -> Result<Generation, Error> {
+    let number = match generation {
+        Some(number) => number,
+        None => *generations(dest)
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("No backup found in {}.", dest.display()))?,
+    };
+    let path = manifest_path(dest, number);
+    let manifest = std::fs::read(&path)
+        .map_err(|_| anyhow::anyhow!("Generation {number} not found in {}.", dest.display()))?;
+    let signature = std::fs::read_to_string(path.with_extension("sig")).unwrap_or_default();
+    if !keys
+        .iter()
+        .any(|key| crate::crypto::verify_with_key(key, &digest(&manifest), signature.trim()))
+    {
+        return Err(anyhow::anyhow!(
+            "Generation {number} is not signed by a trusted key."
+        ));
+    }
+    Ok(serde_json::from_slice(&manifest)?)
+}
+
+/// Contenu d'un fichier sauvegardé, seulement s'il est intact.
+pub fn read_file(dest: &Path, file: &FileEntry) -> Option<Vec<u8>> {
+    let content = std::fs::read(file_path(dest, &file.hash)).ok()?;
+    (digest(&content) == file.hash).then_some(content)
+}
+
+/// Comme `load`, puis vérifie chaque objet référencé par la génération.
+pub fn verify(dest: &Path, generation: Option<u64>, keys: &[Vec<u8>]) -> Result<Generation, Error> {
+    let generation = load(dest, generation, keys)?;
+    let number = generation.generation;
+    let mut broken = 0;
+    for file in &generation.files {
+        if read_file(dest, file).is_none() {
+            ko(format!("missing or corrupted file {}", file.path).as_str());
+            broken += 1;
+        }
+    }
+    for hash in &generation.blobs {
+        if read_blob(dest, hash).is_none() {
+            ko(format!("missing or corrupted blob {}", &hash[..7.min(hash.len())]).as_str());
+            broken += 1;
+        }
+    }
+    if broken > 0 {
+        return Err(anyhow::anyhow!(
+            "Generation {number} has {broken} damaged object(s)."
+        ));
+    }
+    ok(format!(
+        "Generation {number} is intact: {} file(s), {} blob(s)",
+        generation.files.len(),
+        generation.blobs.len()
+    )
+    .as_str());
+    Ok(generation)
+}
+
+// Seul un chemin relatif sous `.lys/`, sans `..`, peut être restauré
+fn restorable(path: &str) -> bool {
+    let components: Vec<Component> = Path::new(path).components().collect();
+    components.len() > 1
+        && components[0] == Component::Normal(".lys".as_ref())
+        && components.iter().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// `lys restore-backup <dest> [--generation N] [--key <hex>]` : reconstruit
+/// `.lys/` dans `root`. Un `.lys/` existant est mis de côté, jamais écrasé.
+/// La génération doit être signée par notre clé, ou par `key` quand le dépôt
+/// n'en a plus : celle inscrite dans la sauvegarde ne prouve rien.
+pub fn restore(
+    root: &Path,
+    dest: &Path,
+    generation: Option<u64>,
+    key: Option<&str>,
+) -> Result<(), Error> {
+    let mut keys: Vec<Vec<u8>> = crate::crypto::public_key(root).into_iter().collect();
+    if let Some(key) = key {
+        keys.push(hex::decode(key).map_err(|_| anyhow::anyhow!("Invalid key '{key}'."))?);
+    }
+    if keys.is_empty() {
+        return Err(anyhow::anyhow!(
+            "This directory has no key to check the backup: pass --key <hex> of the repository."
+        ));
+    }
+    let generation = verify(dest, generation, &keys)?;
+    if let Some(file) = generation.files.iter().find(|f| !restorable(&f.path)) {
+        return Err(anyhow::anyhow!(
+            "Generation {} lists a file outside .lys/: {}",
+            generation.generation,
+            file.path
+        ));
+    }
+
+    let lys = root.join(".lys");
+    if lys.exists() {
+        let aside = root.join(format!(
+            ".lys.before-restore-{}",
+            chrono::Utc::now().timestamp()
+        ));
+        std::fs::rename(&lys, &aside)?;
+        ok(format!("Previous .lys moved to {}", aside.display()).as_str());
+    }
+    for file in &generation.files {
+        let target = root.join(&file.path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(file_path(dest, &file.hash), &target)?;
+    }
+
+    // store.db repart de son schéma, puis reçoit les blobs, les assets et les greffes
+    let conn = Connection::open(":memory:")?;
+    let store = root.join(".lys/db/store.db");
+    let mut attach = conn.prepare("ATTACH DATABASE ? AS store;")?;
+    attach.bind((1, store.to_string_lossy().as_ref()))?;
+    attach.next()?;
+    drop(attach);
+    conn.execute(crate::db::LYS_INIT)?;
+    conn.execute(crate::db::LYS_MIGRATIONS)?;
+    conn.execute("BEGIN TRANSACTION;")?;
+    for hash in &generation.blobs {
+        let content =
+            read_blob(dest, hash).ok_or_else(|| anyhow::anyhow!("Blob {hash} vanished."))?;
+        insert_blob_with_conn(&conn, hash, &content)?;
+    }
+    // Insérés dans l'ordre, les blobs ont des ids 1..n au plus égaux aux leurs :
+    // en partant du dernier, chacun reprend le sien sans heurter les autres
+    if generation.blob_ids.len() == generation.blobs.len() {
+        for (hash, id) in generation.blobs.iter().zip(&generation.blob_ids).rev() {
+            let mut stmt = conn.prepare("UPDATE store.blobs SET id = ? WHERE hash = ?")?;
+            stmt.bind((1, *id))?;
+            stmt.bind((2, hash.as_str()))?;
+            stmt.next()?;
+        }
+    }
+    for (id, uuid, created_at) in &generation.assets {
+        let mut stmt =
+            conn.prepare("INSERT INTO store.assets (id, uuid, created_at) VALUES (?, ?, ?)")?;
+        stmt.bind((1, *id))?;
+        stmt.bind((2, uuid.as_str()))?;
+        stmt.bind((3, created_at.as_deref()))?;
+        stmt.next()?;
+    }
+    for graft in &generation.grafts {
+        let mut stmt = conn.prepare(
+            "INSERT INTO store.grafts (commit_hash, original, parents, signature, created_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )?;
+        stmt.bind((1, graft.commit_hash.as_str()))?;
+        stmt.bind((2, graft.original.as_str()))?;
+        stmt.bind((3, graft.parents.as_str()))?;
+        stmt.bind((4, graft.signature.as_str()))?;
+        stmt.bind((5, graft.created_at.as_deref()))?;
+        stmt.next()?;
+    }
+    conn.execute("COMMIT;")?;
+    ok(format!(
+        "Restored generation {} ({} blob(s)) into {}",
+        generation.generation,
+        generation.blobs.len(),
+        lys.display()
+    )
+    .as_str());
+    if !lys.join("identity/secret.key").exists() {
+        ko("Backups hold no private key: put .lys/identity/secret.key back from its own copy");
+    }
+    Ok(())
+}
+
+/// Destination donnée, sinon celle de la dernière sauvegarde.
+pub fn destination(conn: &Connection, dest: Option<&str>) -> Result<PathBuf, Error> {
+    match dest {
+        Some(dest) => Ok(PathBuf::from(dest)),
+        None => {
+            let saved = config(conn, BACKUP_PATH)?;
+            if saved.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "No backup destination given and none used before."
+                ));
+            }
+            Ok(PathBuf::from(saved))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Deux générations incrémentales, réparation d'un blob perdu, restauration
+    #[test]
+    fn backs_up_repairs_and_restores() {
+        let (repo, conn) = crate::utils::test_repo();
+        let (media, rebuilt) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let keys = vec![crate::crypto::public_key(repo.path()).unwrap()];
+
+        std::fs::write("a.txt", "one\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        let (_, first) = crate::vcs::get_branch_head_info(&conn, "main").unwrap();
+        backup(&conn, repo.path(), media.path()).unwrap();
+        let objects = || {
+            let pattern = format!("{}/objects/*/*", media.path().display());
+            glob::glob(&pattern).unwrap().count()
+        };
+        let saved = objects();
+
+        std::fs::write("b.txt", "two\n").unwrap();
+        crate::vcs::commit(&conn, "second", AUTHOR).unwrap();
+        let second = backup(&conn, repo.path(), media.path()).unwrap();
+        assert_eq!(second.generation, 2);
+        assert_eq!(generations(media.path()), vec![1, 2]);
+        // Seul le nouveau blob est écrit
+        assert_eq!(objects(), saved + 1);
+        verify(media.path(), None, &keys).unwrap();
+
+        // Un blob disparu du store revient depuis la sauvegarde
+        let lost = blake3::hash(b"two\n").to_hex().to_string();
+        conn.execute(format!("DELETE FROM store.blobs WHERE hash = '{lost}'"))
+            .unwrap();
+        let source = crate::fsck::Source::Backup(media.path().to_path_buf());
+        crate::fsck::run(&conn, repo.path(), true, Some(&source)).unwrap();
+        let repaired = crate::vcs::get_blob_bytes_by_hash(&conn, &lost).unwrap();
+        assert_eq!(repaired.as_deref(), Some(&b"two\n"[..]));
+
+        // Sans clé locale ni --key, rien n'est restauré ; la clé privée n'est
+        // jamais sauvegardée
+        assert!(restore(rebuilt.path(), media.path(), Some(1), None).is_err());
+        let key = hex::encode(&keys[0]);
+        restore(rebuilt.path(), media.path(), Some(1), Some(&key)).unwrap();
+        assert!(rebuilt.path().join(".lys/identity/public.key").exists());
+        assert!(!rebuilt.path().join(".lys/identity/secret.key").exists());
+        let restored = crate::db::connect_lys(rebuilt.path()).unwrap();
+        let (_, head) = crate::vcs::get_branch_head_info(&restored, "main").unwrap();
+        assert_eq!(head, first);
+
+        // Un manifeste signé qui sort de .lys/ est refusé
+        for path in [
+            "../escape",
+            "/tmp/escape",
+            ".lys/../escape",
+            "notes.txt",
+            ".lys",
+        ] {
+            assert!(!restorable(path), "{path}");
+        }
+        assert!(restorable(".lys/db/store.db"));
+
+        // Un objet altéré fait échouer la vérification
+        let file = &second.files[0];
+        std::fs::write(file_path(media.path(), &file.hash), b"tampered").unwrap();
+        assert!(verify(media.path(), Some(2), &keys).is_err());
+    }
+}
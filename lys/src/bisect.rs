@@ -0,0 +1,362 @@
+use crate::db::{config, get_current_branch, remove_config, write_config};
+use crate::merge::{commit_state, to_string_map};
+use crate::utils::{ko, ok};
+use crate::vcs::{
+    get_branch_head_info, hash_entry, reconstruct_to_path, resolve_commit, status, write_state_diff,
+};
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::path::Path;
+use std::process::Command;
+
+// Clés de config décrivant une bisection en cours (listes séparées par '\n')
+pub const BISECT_BAD: &str = "bisect_bad";
+pub const BISECT_GOOD: &str = "bisect_good";
+pub const BISECT_SKIP: &str = "bisect_skip";
+pub const BISECT_BRANCH: &str = "bisect_branch";
+pub const BISECT_CURRENT: &str = "bisect_current";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Verdict {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl Verdict {
+    fn label(self) -> &'static str {
+        match self {
+            Verdict::Good => "good",
+            Verdict::Bad => "bad",
+            Verdict::Skip => "skipped",
+        }
+    }
+}
+
+/// Prochaine étape d'une bisection.
+enum Step {
+    Test(String, usize),
+    Found(String),
+    Ambiguous(Vec<String>),
+}
+
+fn list(conn: &Connection, key: &str) -> Result<Vec<String>, Error> {
+    Ok(config(conn, key)?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn push(conn: &Connection, key: &str, hash: &str) -> Result<(), Error> {
+    let mut values = list(conn, key)?;
+    if !values.iter().any(|v| v == hash) {
+        values.push(hash.to_string());
+    }
+    write_config(conn, key, &values.join("\n"))?;
+    Ok(())
+}
+
+fn require_bisecting(conn: &Connection) -> Result<(), Error> {
+    if config(conn, BISECT_BAD)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Not bisecting. Start with 'lys bisect start <bad> <good>'."
+        ));
+    }
+    Ok(())
+}
+
+fn resolve(conn: &Connection, reference: &str) -> Result<String, Error> {
+    resolve_commit(conn, reference)?
+        .ok_or_else(|| anyhow::anyhow!("Reference '{reference}' not found."))
+}
+
+/// Commits suspects (ancêtres du mauvais, pas des bons) et choix du milieu.
+fn next_step(conn: &Connection) -> Result<Step, Error> {
+    let bad = config(conn, BISECT_BAD)?;
+    let goods = list(conn, BISECT_GOOD)?;
+    let skipped = list(conn, BISECT_SKIP)?;
+    // Ordre topologique, parents d'abord : `bad` est le dernier
+    let suspects = crate::transfer::missing_commits(conn, std::slice::from_ref(&bad), &goods)?;
+    let testable: Vec<&String> = suspects
+        .iter()
+        .filter(|h| **h != bad && !skipped.contains(h))
+        .collect();
+    if let Some(middle) = testable.get(testable.len() / 2) {
+        return Ok(Step::Test((*middle).clone(), testable.len()));
+    }
+    let mut left: Vec<String> = suspects
+        .into_iter()
+        .filter(|h| *h == bad || skipped.contains(h))
+        .collect();
+    if left.len() <= 1 {
+        Ok(Step::Found(bad))
+    } else {
+        left.reverse();
+        Ok(Step::Ambiguous(left))
+    }
+}
+
+fn describe(conn: &Connection, hash: &str) -> Result<(), Error> {
+    let mut stmt = conn.prepare("SELECT author, timestamp, message FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        println!("commit {hash}");
+        println!("Author: {}", stmt.read::<String, _>(0)?);
+        println!("Date:   {}", stmt.read::<String, _>(1)?);
+        println!();
+        for line in stmt.read::<String, _>(2)?.lines() {
+            println!("    {line}");
+        }
+    }
+    Ok(())
+}
+
+// Les fichiers suivis du commit extrait doivent être intacts avant de bouger
+fn ensure_clean(conn: &Connection, hash: &str) -> Result<(), Error> {
+    for (path, (blob, _)) in commit_state(conn, hash)? {
+        if hash_entry(&path).ok().as_deref() != Some(blob.as_str()) {
+            return Err(anyhow::anyhow!(
+                "'{}' was changed. Commit, stash or restore it before bisecting further.",
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Met l'arbre de travail sur `to` depuis `from` (HEAD détachée)
+fn move_worktree(conn: &Connection, from: &str, to: &str) -> Result<(), Error> {
+    let current = to_string_map(&commit_state(conn, from)?);
+    let target = to_string_map(&commit_state(conn, to)?);
+    write_state_diff(conn, &current, &target)?;
+    write_config(conn, BISECT_CURRENT, to)?;
+    Ok(())
+}
+
+/// Extrait le prochain commit à tester, ou annonce le premier mauvais.
+fn advance(conn: &Connection) -> Result<Option<String>, Error> {
+    match next_step(conn)? {
+        Step::Test(hash, left) => {
+            let steps = usize::BITS - left.leading_zeros();
+            ensure_clean(conn, &config(conn, BISECT_CURRENT)?)?;
+            move_worktree(conn, &config(conn, BISECT_CURRENT)?, &hash)?;
+            ok(format!(
+                "Bisecting: {left} revision(s) left to test (roughly {steps} step(s)), now at {}",
+                &hash[..7]
+            )
+            .as_str());
+            Ok(None)
+        }
+        Step::Found(hash) => {
+            ok(format!("{} is the first bad commit", &hash[..7]).as_str());
+            describe(conn, &hash)?;
+            Ok(Some(hash))
+        }
+        Step::Ambiguous(hashes) => {
+            ko("There are only skipped commits left to test.");
+            ko("The first bad commit could be any of:");
+            for hash in &hashes {
+                println!("{hash}");
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// `lys bisect start <bad> <good>` : mémorise les bornes et extrait le milieu.
+pub fn start(conn: &Connection, bad: &str, good: &str) -> Result<(), Error> {
+    if !config(conn, BISECT_BAD)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A bisection is already in progress. Run 'lys bisect reset' first."
+        ));
+    }
+    let branch = get_current_branch(conn)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+    if head.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Run 'lys bisect start' from a branch with commits."
+        ));
+    }
+    let root = std::env::current_dir()?;
+    if !status(conn, &root.to_string_lossy(), &branch)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Working tree has changes. Commit or stash before bisecting."
+        ));
+    }
+    let bad = resolve(conn, bad)?;
+    let good = resolve(conn, good)?;
+    if crate::merge::is_ancestor(conn, &bad, &good)? {
+        return Err(anyhow::anyhow!(
+            "The bad commit {} is an ancestor of the good one {}.",
+            &bad[..7],
+            &good[..7]
+        ));
+    }
+
+    write_config(conn, BISECT_BRANCH, &branch)?;
+    write_config(conn, BISECT_CURRENT, &head)?;
+    write_config(conn, BISECT_BAD, &bad)?;
+    write_config(conn, BISECT_GOOD, &good)?;
+    write_config(conn, "current_branch", "DETACHED")?;
+    crate::oplog::record(conn, "bisect")?;
+    advance(conn)?;
+    Ok(())
+}
+
+fn record(conn: &Connection, hash: &str, verdict: Verdict) -> Result<(), Error> {
+    match verdict {
+        Verdict::Good => push(conn, BISECT_GOOD, hash),
+        Verdict::Bad => {
+            write_config(conn, BISECT_BAD, hash)?;
+            Ok(())
+        }
+        Verdict::Skip => push(conn, BISECT_SKIP, hash),
+    }
+}
+
+/// `lys bisect good|bad|skip [<rev>]` : le commit extrait par défaut.
+pub fn mark(conn: &Connection, verdict: Verdict, rev: Option<&str>) -> Result<(), Error> {
+    require_bisecting(conn)?;
+    let hash = match rev {
+        Some(reference) => resolve(conn, reference)?,
+        None => config(conn, BISECT_CURRENT)?,
+    };
+    record(conn, &hash, verdict)?;
+    advance(conn)?;
+    Ok(())
+}
+
+/// `lys bisect reset` : retour sur la branche de départ.
+pub fn reset(conn: &Connection) -> Result<(), Error> {
+    require_bisecting(conn)?;
+    let branch = config(conn, BISECT_BRANCH)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+    let current = config(conn, BISECT_CURRENT)?;
+    ensure_clean(conn, &current)?;
+    move_worktree(conn, &current, &head)?;
+    write_config(conn, "current_branch", &branch)?;
+    for key in [
+        BISECT_BAD,
+        BISECT_GOOD,
+        BISECT_SKIP,
+        BISECT_BRANCH,
+        BISECT_CURRENT,
+    ] {
+        remove_config(conn, key)?;
+    }
+    crate::oplog::record(conn, "bisect reset")?;
+    ok(format!("Back on '{branch}'").as_str());
+    Ok(())
+}
+
+// Teste `hash` dans un dossier temporaire : code 0 bon, 125 à sauter, < 128 mauvais
+fn test_commit(
+    conn: &Connection,
+    root: &Path,
+    hash: &str,
+    command: Option<&str>,
+    hooks: bool,
+) -> Result<Verdict, Error> {
+    let tree = crate::db::commit_tree_hash(conn, hash)?
+        .ok_or_else(|| anyhow::anyhow!("Commit {hash} not found."))?;
+    let dir = tempfile::tempdir()?;
+    reconstruct_to_path(conn, &tree, dir.path())?;
+
+    if hooks {
+        // Les hooks de l'arbre de travail s'appliquent à chaque version testée
+        if root.join("lys").is_file() {
+            std::fs::copy(root.join("lys"), dir.path().join("lys"))?;
+        }
+        let previous = std::env::current_dir()?;
+        std::env::set_current_dir(dir.path())?;
+        let result = crate::utils::run_hooks();
+        std::env::set_current_dir(previous)?;
+        if result.is_err() {
+            return Ok(Verdict::Bad);
+        }
+    }
+    let Some(command) = command else {
+        return Ok(Verdict::Good);
+    };
+    let status = if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .args(["/C", command])
+            .current_dir(dir.path())
+            .status()?
+    } else {
+        Command::new("sh")
+            .args(["-c", command])
+            .current_dir(dir.path())
+            .status()?
+    };
+    match status.code() {
+        Some(0) => Ok(Verdict::Good),
+        Some(125) => Ok(Verdict::Skip),
+        Some(code) if code < 128 => Ok(Verdict::Bad),
+        _ => Err(anyhow::anyhow!(
+            "'{command}' was interrupted on {}; bisection stopped.",
+            &hash[..7]
+        )),
+    }
+}
+
+/// `lys bisect run [<cmd>] [--hooks]` : classe chaque milieu automatiquement
+/// sans toucher à l'arbre de travail. Renvoie le premier mauvais commit.
+pub fn run(
+    conn: &Connection,
+    root: &Path,
+    command: Option<&str>,
+    hooks: bool,
+) -> Result<Option<String>, Error> {
+    require_bisecting(conn)?;
+    if command.is_none() && !hooks {
+        return Err(anyhow::anyhow!("Give a command to run or --hooks."));
+    }
+    while let Step::Test(hash, left) = next_step(conn)? {
+        let verdict = test_commit(conn, root, &hash, command, hooks)?;
+        ok(format!("{} is {} ({left} left)", &hash[..7], verdict.label()).as_str());
+        record(conn, &hash, verdict)?;
+    }
+    let first_bad = advance(conn)?;
+    ok("Run 'lys bisect reset' to go back to your branch.");
+    Ok(first_bad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Six commits, le quatrième casse `flag` : trouvé à la main puis par `run`
+    #[test]
+    fn finds_first_bad_commit() {
+        let (repo, conn) = crate::utils::test_repo();
+        let mut commits = Vec::new();
+        for i in 0..6 {
+            std::fs::write("flag", if i < 3 { "good" } else { "bad" }).unwrap();
+            std::fs::write("counter", i.to_string()).unwrap();
+            crate::vcs::commit(&conn, &format!("step {i}"), AUTHOR).unwrap();
+            commits.push(get_branch_head_info(&conn, "main").unwrap().1);
+        }
+
+        start(&conn, "main", &commits[0]).unwrap();
+        while !matches!(next_step(&conn).unwrap(), Step::Found(_)) {
+            let verdict = if std::fs::read_to_string("flag").unwrap() == "good" {
+                Verdict::Good
+            } else {
+                Verdict::Bad
+            };
+            mark(&conn, verdict, None).unwrap();
+        }
+        assert_eq!(config(&conn, BISECT_BAD).unwrap(), commits[3]);
+        reset(&conn).unwrap();
+        assert_eq!(get_current_branch(&conn).unwrap(), "main");
+        assert_eq!(std::fs::read_to_string("counter").unwrap(), "5");
+
+        start(&conn, "main", &commits[0]).unwrap();
+        let found = run(&conn, repo.path(), Some("grep -q good flag"), false).unwrap();
+        reset(&conn).unwrap();
+        assert_eq!(found, Some(commits[3].clone()));
+    }
+}
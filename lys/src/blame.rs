@@ -0,0 +1,182 @@
+use crate::db::{commit_parents, commit_tree_hash, get_current_branch};
+use crate::vcs::{flatten_tree, get_blob_bytes_by_hash, get_branch_head_info, resolve_commit};
+use anyhow::Error;
+use similar::{DiffOp, TextDiff};
+use sqlite::{Connection, State};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Une ligne du fichier et le commit qui l'a introduite.
+pub struct BlameLine {
+    pub commit: String,
+    pub author: String,
+    pub timestamp: String,
+    pub number: usize,
+    pub content: String,
+}
+
+/// Versions successives de `path` le long de la lignée (premiers parents) de
+/// `head`, de la plus ancienne à la plus récente : `(commit, blob)`.
+/// Le manifest ne garde que les fichiers modifiés par chaque commit.
+fn versions(conn: &Connection, head: &str, path: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT c.hash, b.hash FROM manifest m
+         JOIN commits c ON c.id = m.commit_id
+         JOIN store.blobs b ON b.id = m.blob_id
+         WHERE m.file_path = ?",
+    )?;
+    stmt.bind((1, path))?;
+    let mut changes = HashMap::new();
+    while let Ok(State::Row) = stmt.next() {
+        changes.insert(stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?);
+    }
+
+    let mut chain = Vec::new();
+    let mut current = Some(head.to_string());
+    while let Some(hash) = current {
+        if let Some(blob) = changes.get(&hash) {
+            chain.push((hash.clone(), blob.clone()));
+        }
+        current = commit_parents(conn, &hash)?.into_iter().next();
+    }
+    chain.reverse();
+
+    // Le contenu de référence est celui de l'arbre de `head`
+    let tree =
+        commit_tree_hash(conn, head)?.ok_or_else(|| anyhow::anyhow!("Commit {head} not found."))?;
+    let mut state = HashMap::new();
+    flatten_tree(conn, &tree, PathBuf::new(), &mut state)?;
+    let Some((blob, _)) = state.get(Path::new(path)) else {
+        return Err(anyhow::anyhow!(
+            "'{path}' does not exist in {}.",
+            &head[..7.min(head.len())]
+        ));
+    };
+    if chain.last().map(|(_, b)| b) != Some(blob) {
+        chain.push((head.to_string(), blob.clone()));
+    }
+    Ok(chain)
+}
+
+fn text(conn: &Connection, path: &str, blob: &str) -> Result<String, Error> {
+    let bytes = get_blob_bytes_by_hash(conn, blob)?
+        .ok_or_else(|| anyhow::anyhow!("Blob {blob} is missing."))?;
+    String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("'{path}' is a binary file."))
+}
+
+/// Attribue chaque ligne de `path` (tel qu'il est dans `head`) au commit qui
+/// l'a introduite, en comparant les versions successives ligne à ligne.
+pub fn blame(conn: &Connection, path: &str, head: &str) -> Result<Vec<BlameLine>, Error> {
+    let path = path.trim_start_matches("./");
+    let mut owners: Vec<usize> = Vec::new();
+    let mut previous = String::new();
+    let chain = versions(conn, head, path)?;
+    for (index, (_, blob)) in chain.iter().enumerate() {
+        let current = text(conn, path, blob)?;
+        let diff = TextDiff::from_lines(&previous, &current);
+        let mut next = Vec::new();
+        for op in diff.ops() {
+            match *op {
+                DiffOp::Equal { old_index, len, .. } => {
+                    next.extend_from_slice(&owners[old_index..old_index + len]);
+                }
+                DiffOp::Insert { new_len, .. } | DiffOp::Replace { new_len, .. } => {
+                    next.extend(std::iter::repeat_n(index, new_len));
+                }
+                DiffOp::Delete { .. } => {}
+            }
+        }
+        owners = next;
+        previous = current;
+    }
+
+    let mut meta: HashMap<usize, (String, String)> = HashMap::new();
+    let mut lines = Vec::new();
+    for (number, (content, owner)) in previous.split_inclusive('\n').zip(owners).enumerate() {
+        let commit = &chain[owner].0;
+        if let std::collections::hash_map::Entry::Vacant(entry) = meta.entry(owner) {
+            let mut stmt = conn.prepare("SELECT author, timestamp FROM commits WHERE hash = ?")?;
+            stmt.bind((1, commit.as_str()))?;
+            if let Ok(State::Row) = stmt.next() {
+                entry.insert((stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?));
+            } else {
+                entry.insert((String::new(), String::new()));
+            }
+        }
+        let (author, timestamp) = &meta[&owner];
+        lines.push(BlameLine {
+            commit: commit.clone(),
+            author: author.split('<').next().unwrap_or("").trim().to_string(),
+            timestamp: timestamp.clone(),
+            number: number + 1,
+            content: content.trim_end_matches(['\n', '\r']).to_string(),
+        });
+    }
+    Ok(lines)
+}
+
+/// `lys blame <path> [--rev <ref>]` : HEAD de la branche courante par défaut.
+pub fn print(conn: &Connection, path: &str, rev: Option<&str>) -> Result<(), Error> {
+    let head = match rev {
+        Some(reference) => resolve_commit(conn, reference)?
+            .ok_or_else(|| anyhow::anyhow!("Reference '{reference}' not found."))?,
+        None => get_branch_head_info(conn, &get_current_branch(conn)?)?.1,
+    };
+    if head.is_empty() {
+        return Err(anyhow::anyhow!("No commits yet."));
+    }
+    let lines = blame(conn, path, &head)?;
+    let author_width = lines.iter().map(|l| l.author.len()).max().unwrap_or(0);
+    let number_width = lines.len().to_string().len();
+    for line in lines {
+        println!(
+            "{} ({:<author_width$} {} {:>number_width$}) {}",
+            &line.commit[..7.min(line.commit.len())],
+            line.author,
+            line.timestamp.get(..10).unwrap_or(&line.timestamp),
+            line.number,
+            line.content
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Trois commits : chaque ligne garde le commit qui l'a écrite
+    #[test]
+    fn attributes_lines_to_their_commit() {
+        let repo = tempfile::tempdir().unwrap();
+        let cwd = crate::utils::cwd_guard();
+        std::env::set_current_dir(repo.path()).unwrap();
+        crate::crypto::generate_keypair(repo.path()).unwrap();
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let mut heads = Vec::new();
+        for (content, message) in [
+            ("one\ntwo\n", "first"),
+            ("one\n2\ntwo\n", "second"),
+            ("zero\none\n2\n", "third"),
+        ] {
+            std::fs::write("notes.txt", content).unwrap();
+            std::fs::write("other.txt", message).unwrap();
+            crate::vcs::commit(&conn, message, AUTHOR).unwrap();
+            heads.push(get_branch_head_info(&conn, "main").unwrap().1);
+        }
+        drop(cwd);
+
+        let lines = blame(&conn, "notes.txt", &heads[2]).unwrap();
+        let owners: Vec<_> = lines.iter().map(|l| l.commit.as_str()).collect();
+        assert_eq!(owners, [&heads[2], &heads[0], &heads[1]]);
+        assert_eq!(lines[1].content, "one");
+        assert_eq!(lines[0].author, "Tester");
+
+        // Une révision plus ancienne ne voit pas les commits suivants
+        let lines = blame(&conn, "notes.txt", &heads[1]).unwrap();
+        let owners: Vec<_> = lines.iter().map(|l| l.commit.as_str()).collect();
+        assert_eq!(owners, [&heads[0], &heads[1], &heads[0]]);
+        assert!(blame(&conn, "missing.txt", &heads[2]).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use crate::db::get_current_branch;
+use crate::merge::is_ancestor;
+use crate::transfer::{
+    Batch, FetchRequest, Pack, apply_pack, build_batch, check_batch, check_pack, local_refs,
+    set_branch_head, trusted_keys, write_pack,
+};
+use crate::utils::{ko, ok};
+use crate::vcs::{
+    get_branch_head_info, get_commit_id_by_hash, get_manifest_map, resolve_commit, status,
+    write_state_diff,
+};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use sqlite::Connection;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Première ligne de tout fichier bundle
+const MAGIC: &str = "# lys bundle v1";
+
+/// En-tête signé d'un bundle : ce qu'il faut déjà avoir, ce qu'il apporte, et
+/// l'empreinte du flux d'objets qui suit.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BundleHeader {
+    // Commits absents du bundle dont dépendent ceux qu'il contient
+    pub prerequisites: Vec<String>,
+    // Têtes de branches incluses
+    pub heads: BTreeMap<String, String>,
+    pub commits: usize,
+    pub created: String,
+    pub public_key: String,
+    // blake3 du flux compressé (zstd) de commits, arbres et blobs
+    pub payload: String,
+}
+
+/// Un bundle lu et vérifié, dont les blobs restent à lire dans `pack`.
+pub struct Bundle {
+    pub header: BundleHeader,
+    pub batch: Batch,
+    pub pack: Pack<File>,
+}
+
+// `main` ou `base..main` : la tête incluse et la base que le destinataire possède
+fn parse_spec(conn: &Connection, spec: &str) -> Result<(String, String, Option<String>), Error> {
+    let (base, name) = match spec.split_once("..") {
+        Some((base, name)) => (Some(base), name),
+        None => (None, spec),
+    };
+    let name = if name.is_empty() || name == "HEAD" {
+        get_current_branch(conn)?
+    } else {
+        name.to_string()
+    };
+    let (_, head) = get_branch_head_info(conn, &name)?;
+    if head.is_empty() {
+        return Err(anyhow::anyhow!(
+            "'{name}' is not a branch with commits; bundles carry branch heads."
+        ));
+    }
+    let base = match base {
+        Some(base) => Some(
+            resolve_commit(conn, base)?
+                .ok_or_else(|| anyhow::anyhow!("Unknown revision '{base}'."))?,
+        ),
+        None => None,
+    };
+    Ok((name, head, base))
+}
+
+/// `lys bundle create <file> [refs...]` : toutes les branches par défaut.
+pub fn create(
+    conn: &Connection,
+    root: &Path,
+    file: &Path,
+    specs: &[String],
+) -> Result<BundleHeader, Error> {
+    let mut heads = BTreeMap::new();
+    let mut bases = Vec::new();
+    if specs.is_empty() {
+        heads = local_refs(conn)?;
+    }
+    for spec in specs {
+        let (name, head, base) = parse_spec(conn, spec)?;
+        heads.insert(name, head);
+        bases.extend(base);
+    }
+    let batch = build_batch(
+        conn,
+        &FetchRequest {
+            wants: heads.values().cloned().collect(),
+            haves: bases,
+            depth: None,
+            since: None,
+        },
+    )?;
+    if batch.commits.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Nothing to bundle: the receiver already has these commits."
+        ));
+    }
+
+    // Les prérequis réels : parents des commits inclus qui n'y sont pas
+    let included: HashSet<&str> = batch.commits.iter().map(|c| c.hash.as_str()).collect();
+    let mut prerequisites = Vec::new();
+    for parent in batch.commits.iter().flat_map(|c| &c.parents) {
+        if !included.contains(parent.as_str()) && !prerequisites.contains(parent) {
+            prerequisites.push(parent.clone());
+        }
+    }
+
+    let mut payload = tempfile::tempfile()?;
+    write_pack(conn, &batch, &batch.blobs, &mut payload)?;
+    payload.rewind()?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut payload, &mut hasher)?;
+    payload.rewind()?;
+    let header = BundleHeader {
+        prerequisites,
+        heads,
+        commits: batch.commits.len(),
+        created: chrono::Utc::now().to_rfc3339(),
+        public_key: crate::crypto::public_key(root)
+            .map(hex::encode)
+            .unwrap_or_default(),
+        payload: hasher.finalize().to_hex().to_string(),
+    };
+    let line = serde_json::to_string(&header)?;
+    let signature = crate::crypto::sign_message(root, &line).map_err(|e| anyhow::anyhow!(e))?;
+    let mut out = File::create(file)?;
+    out.write_all(format!("{MAGIC}\n{line}\n{signature}\n").as_bytes())?;
+    std::io::copy(&mut payload, &mut out)?;
+    ok(format!(
+        "{} commit(s), {} head(s) written to {} ({} bytes)",
+        header.commits,
+        header.heads.len(),
+        file.display(),
+        out.stream_position()?
+    )
+    .as_str());
+    Ok(header)
+}
+
+// Lit les trois lignes d'en-tête ; le flux binaire commence juste après
+fn split(reader: &mut impl BufRead) -> Result<(String, String), Error> {
+    let mut line = || -> Result<String, Error> {
+        let mut part = Vec::new();
+        reader.read_until(b'\n', &mut part)?;
+        if part.pop() != Some(b'\n') {
+            return Err(anyhow::anyhow!("Bundle is truncated."));
+        }
+        Ok(String::from_utf8(part)?)
+    };
+    if line().ok().as_deref() != Some(MAGIC) {
+        return Err(anyhow::anyhow!("Not a lys bundle."));
+    }
+    Ok((line()?, line()?))
+}
+
+/// Lit un bundle et vérifie tout ce qui peut l'être avant de l'importer :
+/// signature de l'en-tête par une clé de confiance, empreinte du flux, hash
+/// et signatures des commits, présence locale des prérequis. Les blobs et les
+/// arbres sont vérifiés en lisant `pack`.
+pub fn read(
+    conn: &Connection,
+    root: &Path,
+    file: &Path,
+    key: Option<&str>,
+) -> Result<Bundle, Error> {
+    let mut reader = BufReader::new(File::open(file)?);
+    let (line, signature) = split(&mut reader)?;
+    let header: BundleHeader = serde_json::from_str(&line)?;
+    let keys = trusted_keys(conn, root, key);
+    if !keys
+        .iter()
+        .any(|k| crate::crypto::verify_with_key(k, &line, &signature))
+    {
+        return Err(anyhow::anyhow!(
+            "Bundle is not signed by a trusted key (signer: {}).",
+            header.public_key
+        ));
+    }
+    let start = reader.stream_position()?;
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    if hasher.finalize().to_hex().as_str() != header.payload {
+        return Err(anyhow::anyhow!("Bundle content is corrupted."));
+    }
+
+    let missing: Vec<&String> = header
+        .prerequisites
+        .iter()
+        .filter(|hash| crate::history::find(conn, hash).is_none())
+        .collect();
+    if !missing.is_empty() {
+        for hash in &missing {
+            ko(format!("missing prerequisite {hash}").as_str());
+        }
+        return Err(anyhow::anyhow!(
+            "This repository lacks {} commit(s) the bundle builds on.",
+            missing.len()
+        ));
+    }
+    reader.seek(SeekFrom::Start(start))?;
+    let mut pack = Pack::new(reader.into_inner())?;
+    let batch: Batch = pack.header()?;
+    check_batch(conn, &batch, &keys)?;
+    let included: HashSet<&str> = batch.commits.iter().map(|c| c.hash.as_str()).collect();
+    if let Some((name, _)) = header.heads.iter().find(|(_, hash)| {
+        !included.contains(hash.as_str()) && crate::history::find(conn, hash).is_none()
+    }) {
+        return Err(anyhow::anyhow!("Head '{name}' is not in the bundle."));
+    }
+    Ok(Bundle {
+        header,
+        batch,
+        pack,
+    })
+}
+
+/// `lys bundle verify <file>`.
+pub fn verify(conn: &Connection, root: &Path, file: &Path, key: Option<&str>) -> Result<(), Error> {
+    let mut bundle = read(conn, root, file, key)?;
+    // Les blobs ne sont vérifiés qu'à leur lecture
+    let keys = trusted_keys(conn, root, key);
+    check_pack(conn, &bundle.batch, &mut bundle.pack, &keys)?;
+    for (name, hash) in &bundle.header.heads {
+        ok(format!("{} {name}", &hash[..7]).as_str());
+    }
+    ok(format!(
+        "{} is valid: {} commit(s), {} prerequisite(s) present",
+        file.display(),
+        bundle.header.commits,
+        bundle.header.prerequisites.len()
+    )
+    .as_str());
+    Ok(())
+}
+
+/// `lys bundle unbundle <file>` : importe les objets, crée les branches absentes
+/// et avance en avance rapide celles qui existent. Une branche qui a divergé
+/// garde sa tête locale.
+pub fn unbundle(
+    conn: &Connection,
+    root: &Path,
+    file: &Path,
+    key: Option<&str>,
+) -> Result<(), Error> {
+    let mut bundle = read(conn, root, file, key)?;
+    let current = get_current_branch(conn)?;
+    let clean = status(conn, &root.to_string_lossy(), &current)?.is_empty();
+    let keys = trusted_keys(conn, root, key);
+    let added = apply_pack(conn, &bundle.batch, &mut bundle.pack, &keys)?;
+    ok(format!("{added} commit(s) imported from {}", file.display()).as_str());
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    let mut moved = Vec::new();
+    for (name, hash) in &bundle.header.heads {
+        let (local_id, local) = get_branch_head_info(conn, name)?;
+        if local == *hash || (!local.is_empty() && is_ancestor(conn, hash, &local)?) {
+            ok(format!("{name}: up to date").as_str());
+            continue;
+        }
+        if !local.is_empty() && !is_ancestor(conn, &local, hash)? {
+            ko(format!("{name}: diverged, kept local head {}", &local[..7]).as_str());
+            continue;
+        }
+        if *name == current && !clean {
+            ko(format!(
+                "{name}: working tree has changes, not moved to {}",
+                &hash[..7]
+            )
+            .as_str());
+            continue;
+        }
+        set_branch_head(conn, name, hash)?;
+        ok(format!("{name}: {}..{}", local.get(..7).unwrap_or(""), &hash[..7]).as_str());
+        if *name == current {
+            moved.push((local_id, hash.clone()));
+        }
+    }
+    crate::oplog::record(conn, "unbundle")?;
+    conn.execute("COMMIT;")?;
+
+    // La branche courante avance : l'arbre de travail suit
+    for (local_id, hash) in moved {
+        let current_files = get_manifest_map(conn, local_id)?;
+        let target_files = get_manifest_map(conn, get_commit_id_by_hash(conn, &hash)?)?;
+        write_state_diff(conn, &current_files, &target_files)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Un site envoie son historique complet, puis un incrément, par fichier
+    #[test]
+    fn bundles_travel_between_repositories() {
+        let (site_a, conn_a) = crate::utils::test_repo();
+        let (site_b, media) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        crate::crypto::generate_keypair(site_b.path()).unwrap();
+        let conn_b = crate::db::connect_lys(site_b.path()).unwrap();
+        let key_a = hex::encode(crate::crypto::public_key(site_a.path()).unwrap());
+
+        std::fs::write("a.txt", "one\n").unwrap();
+        crate::vcs::commit(&conn_a, "first", AUTHOR).unwrap();
+        let full = media.path().join("full.bundle");
+        create(&conn_a, site_a.path(), &full, &[]).unwrap();
+        std::fs::write("a.txt", "two\n").unwrap();
+        crate::vcs::commit(&conn_a, "second", AUTHOR).unwrap();
+        let increment = media.path().join("increment.bundle");
+        let header = create(&conn_a, site_a.path(), &increment, &["main~1..main".into()]).unwrap();
+        assert_eq!(header.prerequisites.len(), 1);
+
+        std::env::set_current_dir(site_b.path()).unwrap();
+        // Sans la clé du site A, rien n'est accepté ; l'incrément seul ne suffit pas
+        assert!(read(&conn_b, site_b.path(), &full, None).is_err());
+        assert!(read(&conn_b, site_b.path(), &increment, Some(&key_a)).is_err());
+        unbundle(&conn_b, site_b.path(), &full, Some(&key_a)).unwrap();
+        assert_eq!(std::fs::read_to_string("a.txt").unwrap(), "one\n");
+        unbundle(&conn_b, site_b.path(), &increment, Some(&key_a)).unwrap();
+        let content = std::fs::read_to_string("a.txt").unwrap();
+
+        // Un octet modifié dans le flux est détecté
+        let mut bytes = std::fs::read(&increment).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&increment, bytes).unwrap();
+        let tampered = read(&conn_a, site_a.path(), &increment, None);
+        assert_eq!(content, "two\n");
+        assert_eq!(
+            get_branch_head_info(&conn_a, "main").unwrap().1,
+            get_branch_head_info(&conn_b, "main").unwrap().1
+        );
+        assert!(tampered.is_err());
+    }
+}
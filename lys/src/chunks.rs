@@ -0,0 +1,431 @@
+use crate::db::{compress, decompress};
+use sqlite::{Connection, Error, State};
+use std::collections::VecDeque;
+use std::io::{Cursor, ErrorKind, Read};
+
+/// Clé de config : taille (octets) à partir de laquelle un blob est découpé.
+pub const THRESHOLD: &str = "chunk_threshold";
+pub const DEFAULT_THRESHOLD: u64 = 4 * 1024 * 1024;
+
+// Bornes FastCDC : des morceaux de 256 Kio en moyenne
+const MIN_SIZE: usize = 64 * 1024;
+const AVG_SIZE: usize = 256 * 1024;
+const MAX_SIZE: usize = 1024 * 1024;
+// Chunking normalisé : masque plus strict avant la taille moyenne, plus lâche après.
+// Les bits de poids fort dépendent des 64 derniers octets lus.
+const MASK_S: u64 = u64::MAX << (64 - 20);
+const MASK_L: u64 = u64::MAX << (64 - 16);
+const GEAR: [u64; 256] = gear();
+
+// Table « gear » fixe (splitmix64) : les coupures doivent rester les mêmes d'une version à l'autre
+const fn gear() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6c79_7320_6364_6300;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Longueur du premier morceau de `data` (FastCDC).
+pub fn cut(data: &[u8]) -> usize {
+    if data.len() <= MIN_SIZE {
+        return data.len();
+    }
+    let normal = AVG_SIZE.min(data.len());
+    let end = MAX_SIZE.min(data.len());
+    let mut hash = 0u64;
+    for (i, byte) in data.iter().enumerate().take(end).skip(MIN_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        let mask = if i < normal { MASK_S } else { MASK_L };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Découpe un flux en morceaux, sans garder plus de `MAX_SIZE` octets en mémoire.
+pub struct Chunker<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::with_capacity(MAX_SIZE),
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Chunker<R> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.buffer.len() < MAX_SIZE {
+            let start = self.buffer.len();
+            self.buffer.resize(MAX_SIZE, 0);
+            match self.reader.read(&mut self.buffer[start..]) {
+                Ok(read) => {
+                    self.buffer.truncate(start + read);
+                    self.done = read == 0;
+                }
+                Err(e) => {
+                    self.buffer.truncate(start);
+                    if e.kind() != ErrorKind::Interrupted {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let end = cut(&self.buffer);
+        Some(Ok(self.buffer.drain(..end).collect()))
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error {
+        code: Some(1),
+        message: Some(e.to_string()),
+    }
+}
+
+// `store.` sur une connexion de dépôt ; rien sur une connexion ouverte
+// directement sur store.db (import Git)
+fn prefix(conn: &Connection) -> &'static str {
+    let attached = conn
+        .prepare("SELECT 1 FROM pragma_database_list WHERE name = 'store'")
+        .and_then(|mut stmt| stmt.next())
+        .is_ok_and(|state| state == State::Row);
+    if attached { "store." } else { "" }
+}
+
+/// Seuil de découpage du dépôt (`chunk_threshold`, 4 Mio par défaut).
+pub fn threshold(conn: &Connection) -> u64 {
+    // Pas de table config sur une connexion au seul store.db
+    conn.prepare("SELECT value FROM config WHERE key = ?")
+        .and_then(|mut stmt| {
+            stmt.bind((1, THRESHOLD))?;
+            match stmt.next()? {
+                State::Row => stmt.read::<String, _>(0),
+                State::Done => Ok(String::new()),
+            }
+        })
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_THRESHOLD)
+}
+
+/// Un blob écrit par `write` : combien de morceaux il compte, et combien
+/// n'étaient pas déjà dans le store.
+#[derive(Debug)]
+pub struct Stored {
+    pub hash: String,
+    pub size: u64,
+    pub chunks: usize,
+    pub new_chunks: usize,
+}
+
+/// Enregistre `reader` comme un blob découpé : morceaux dédupliqués par leur
+/// blake3, puis la liste ordonnée du blob. Le hash du blob est celui du
+/// contenu entier, toujours calculé au passage ; un `hash` attendu qui
+/// diffère fait échouer l'écriture sans rien garder.
+pub fn write(conn: &Connection, reader: impl Read, hash: Option<&str>) -> Result<Stored, Error> {
+    let schema = prefix(conn);
+    conn.execute("SAVEPOINT chunks;")?;
+    let outcome = (|| -> Result<Stored, Error> {
+        let mut hasher = blake3::Hasher::new();
+        let mut list = Vec::new();
+        let (mut size, mut new_chunks) = (0, 0);
+        for chunk in Chunker::new(reader) {
+            let chunk = chunk.map_err(io_error)?;
+            hasher.update(&chunk);
+            let chunk_hash = blake3::hash(&chunk).to_hex().to_string();
+            let mut stmt = conn.prepare(format!("SELECT 1 FROM {schema}chunks WHERE hash = ?"))?;
+            stmt.bind((1, chunk_hash.as_str()))?;
+            if stmt.next()? == State::Done {
+                let mut stmt = conn.prepare(format!(
+                    "INSERT INTO {schema}chunks (hash, content, size) VALUES (?, ?, ?)"
+                ))?;
+                stmt.bind((1, chunk_hash.as_str()))?;
+                stmt.bind((2, &compress(&chunk)[..]))?;
+                stmt.bind((3, chunk.len() as i64))?;
+                stmt.next()?;
+                new_chunks += 1;
+            }
+            size += chunk.len() as u64;
+            list.push(chunk_hash);
+        }
+        let computed = hasher.finalize().to_hex().to_string();
+        if let Some(expected) = hash.filter(|expected| *expected != computed) {
+            return Err(Error {
+                code: Some(1),
+                message: Some(format!(
+                    "Blob content does not match its hash {expected} (got {computed})."
+                )),
+            });
+        }
+        let hash = computed;
+
+        // Un blob déjà présent, entier ou découpé, garde sa forme
+        let mut stmt = conn.prepare(format!(
+            "INSERT OR IGNORE INTO {schema}blobs (hash, content, size) VALUES (?, NULL, ?)"
+        ))?;
+        stmt.bind((1, hash.as_str()))?;
+        stmt.bind((2, size as i64))?;
+        stmt.next()?;
+        if conn.change_count() > 0 {
+            let mut stmt = conn.prepare(format!(
+                "DELETE FROM {schema}blob_chunks WHERE blob_hash = ?"
+            ))?;
+            stmt.bind((1, hash.as_str()))?;
+            stmt.next()?;
+            for (position, chunk_hash) in list.iter().enumerate() {
+                let mut stmt = conn.prepare(format!(
+                    "INSERT INTO {schema}blob_chunks (blob_hash, position, chunk_hash)
+                     VALUES (?, ?, ?)"
+                ))?;
+                stmt.bind((1, hash.as_str()))?;
+                stmt.bind((2, position as i64))?;
+                stmt.bind((3, chunk_hash.as_str()))?;
+                stmt.next()?;
+            }
+        }
+        Ok(Stored {
+            hash,
+            size,
+            chunks: list.len(),
+            new_chunks,
+        })
+    })();
+    match outcome {
+        Ok(stored) => {
+            conn.execute("RELEASE chunks;")?;
+            Ok(stored)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK TO chunks; RELEASE chunks;")?;
+            Err(e)
+        }
+    }
+}
+
+/// Morceaux d'un blob découpé, dans l'ordre (vide pour un blob entier).
+pub fn chunk_list(conn: &Connection, hash: &str) -> Result<Vec<String>, Error> {
+    let mut stmt = conn.prepare(format!(
+        "SELECT chunk_hash FROM {}blob_chunks WHERE blob_hash = ? ORDER BY position",
+        prefix(conn)
+    ))?;
+    stmt.bind((1, hash))?;
+    let mut list = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        list.push(stmt.read::<String, _>(0)?);
+    }
+    Ok(list)
+}
+
+/// Contenu d'un morceau, décompressé.
+pub fn read_chunk(conn: &Connection, hash: &str) -> Result<Option<Vec<u8>>, Error> {
+    let mut stmt = conn.prepare(format!(
+        "SELECT content FROM {}chunks WHERE hash = ?",
+        prefix(conn)
+    ))?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(decompress(&stmt.read::<Vec<u8>, _>(0)?)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Lecture en flux d'un blob : d'un bloc s'il est entier, morceau par
+/// morceau s'il est découpé.
+pub struct Reader<'a> {
+    conn: &'a Connection,
+    chunks: VecDeque<String>,
+    current: Cursor<Vec<u8>>,
+}
+
+impl Read for Reader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            let Some(next) = self.chunks.pop_front() else {
+                return Ok(0);
+            };
+            let content = read_chunk(self.conn, &next)
+                .map_err(std::io::Error::other)?
+                .ok_or_else(|| {
+                    std::io::Error::new(ErrorKind::NotFound, format!("chunk {next} is missing"))
+                })?;
+            self.current = Cursor::new(content);
+        }
+    }
+}
+
+/// Ouvre un blob du store, `None` s'il n'y est pas.
+pub fn open<'a>(conn: &'a Connection, hash: &str) -> Result<Option<Reader<'a>>, Error> {
+    let schema = prefix(conn);
+    let mut stmt = conn.prepare(format!("SELECT content FROM {schema}blobs WHERE hash = ?"))?;
+    stmt.bind((1, hash))?;
+    if stmt.next()? == State::Done {
+        return Ok(None);
+    }
+    let (chunks, current) = match stmt.read::<Option<Vec<u8>>, _>(0)? {
+        Some(content) => (VecDeque::new(), decompress(&content)),
+        None => (chunk_list(conn, hash)?.into(), Vec::new()),
+    };
+    Ok(Some(Reader {
+        conn,
+        chunks,
+        current: Cursor::new(current),
+    }))
+}
+
+/// Contenu entier d'un blob, reconstitué s'il est découpé.
+pub fn read(conn: &Connection, hash: &str) -> Result<Option<Vec<u8>>, Error> {
+    let Some(mut reader) = open(conn, hash)? else {
+        return Ok(None);
+    };
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content).map_err(io_error)?;
+    Ok(Some(content))
+}
+
+/// Retire les listes des blobs supprimés (ou redevenus entiers) puis les
+/// morceaux qu'aucune liste n'utilise plus. Renvoie le nombre de morceaux retirés.
+pub fn collect(conn: &Connection) -> Result<usize, Error> {
+    let schema = prefix(conn);
+    conn.execute(format!(
+        "DELETE FROM {schema}blob_chunks WHERE blob_hash NOT IN
+         (SELECT hash FROM {schema}blobs WHERE content IS NULL)"
+    ))?;
+    conn.execute(format!(
+        "DELETE FROM {schema}chunks WHERE hash NOT IN (SELECT chunk_hash FROM {schema}blob_chunks)"
+    ))?;
+    Ok(conn.change_count())
+}
+
+/// Occupation du store : blobs (dont découpés), morceaux, taille des
+/// contenus et place réellement occupée une fois compressés et dédupliqués.
+#[derive(Debug, Default)]
+pub struct Usage {
+    pub blobs: i64,
+    pub chunked: i64,
+    pub chunks: i64,
+    pub logical: i64,
+    pub stored: i64,
+}
+
+pub fn usage(conn: &Connection) -> Result<Usage, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT COUNT(*), COUNT(*) - COUNT(content), COALESCE(SUM(size), 0),
+                COALESCE(SUM(length(content)), 0)
+         FROM store.blobs",
+    )?;
+    stmt.next()?;
+    let mut usage = Usage {
+        blobs: stmt.read(0)?,
+        chunked: stmt.read(1)?,
+        logical: stmt.read(2)?,
+        stored: stmt.read(3)?,
+        ..Usage::default()
+    };
+    let mut stmt =
+        conn.prepare("SELECT COUNT(*), COALESCE(SUM(length(content)), 0) FROM store.chunks")?;
+    stmt.next()?;
+    usage.chunks = stmt.read(0)?;
+    usage.stored += stmt.read::<i64, _>(1)?;
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Contenu pseudo-aléatoire reproductible (xorshift), incompressible
+    fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            data.extend_from_slice(&seed.to_le_bytes());
+        }
+        data.truncate(len);
+        data
+    }
+
+    // Un octet modifié au milieu d'un gros fichier n'ajoute que quelques morceaux
+    #[test]
+    fn one_byte_edit_stores_few_new_chunks() {
+        let repo = tempfile::tempdir().unwrap();
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let mut content = noise(16 * 1024 * 1024, 0x5eed);
+
+        let first = write(&conn, content.as_slice(), None).unwrap();
+        content[9 * 1024 * 1024] ^= 0xff;
+        let second = write(&conn, Cursor::new(&content), None).unwrap();
+        let again = write(&conn, content.as_slice(), None).unwrap();
+        let read_back = read(&conn, &second.hash).unwrap().unwrap();
+        let usage = usage(&conn).unwrap();
+
+        assert_eq!(first.new_chunks, first.chunks);
+        assert!(first.chunks > 30, "{first:?}");
+        assert_eq!(second.hash, blake3::hash(&content).to_hex().to_string());
+        assert!(second.new_chunks <= 3, "{second:?}");
+        assert_eq!(again.new_chunks, 0);
+        assert!(read_back == content);
+        assert_eq!((usage.blobs, usage.chunked), (2, 2));
+
+        // Seuls les morceaux propres à la première version sont libérés
+        let kept = chunk_list(&conn, &second.hash).unwrap();
+        let dropped = chunk_list(&conn, &first.hash)
+            .unwrap()
+            .into_iter()
+            .filter(|chunk| !kept.contains(chunk))
+            .count();
+        conn.execute(format!(
+            "DELETE FROM store.blobs WHERE hash = '{}'",
+            first.hash
+        ))
+        .unwrap();
+        assert_eq!(collect(&conn).unwrap(), dropped);
+        assert!(read(&conn, &second.hash).unwrap().unwrap() == content);
+    }
+
+    // Un hash annoncé qui ne correspond pas au contenu n'enregistre rien
+    #[test]
+    fn refuses_a_wrong_hash() {
+        let repo = tempfile::tempdir().unwrap();
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let content = noise(64 * 1024, 0xbad);
+        let wrong = blake3::hash(b"something else").to_hex().to_string();
+        let right = blake3::hash(&content).to_hex().to_string();
+
+        let refused = write(&conn, content.as_slice(), Some(&wrong));
+        let accepted = write(&conn, content.as_slice(), Some(&right)).unwrap();
+
+        assert!(refused.is_err());
+        assert!(open(&conn, &wrong).unwrap().is_none());
+        assert_eq!(accepted.hash, right);
+        assert!(read(&conn, &right).unwrap().unwrap() == content);
+        assert_eq!(usage(&conn).unwrap().blobs, 1);
+    }
+}
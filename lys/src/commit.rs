@@ -21,6 +21,8 @@ pub struct Log {
     pub message: String,
     pub at: String,
     pub signature: String,
+    pub parents: Vec<String>,
+    pub refs: Vec<String>,
     pub changes: Vec<(String, FileChange)>,
 }
 
@@ -28,7 +30,15 @@ impl Display for Log {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let x = self.author.split("<").collect::<Vec<&str>>();
         let author = x[0].trim().to_string();
-        writeln!(f, "\n{author} at {} ({})\n", self.at, self.signature)?;
+        let refs = if self.refs.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", self.refs.join(", "))
+        };
+        writeln!(f, "\n{author} at {} ({}){refs}\n", self.at, self.signature)?;
+        if self.parents.len() > 1 {
+            writeln!(f, "Merge: {}\n", self.parents.join(" "))?;
+        }
         writeln!(f, "{}\n", self.message)?;
 
         if !self.changes.is_empty() {
@@ -95,6 +105,17 @@ fn print_tree(f: &mut Formatter<'_>, node: &Tree, prefix: &str, is_root: bool) -
                     let m = mode.map(|v| crate::vcs::format_mode(v)).unwrap_or_default();
                     format!("{m} ~ +{added} -{deleted}")
                 }
+                Some(FileChange::Renamed {
+                    from,
+                    copy,
+                    added,
+                    deleted,
+                    mode,
+                }) => {
+                    let m = mode.map(crate::vcs::format_mode).unwrap_or_default();
+                    let letter = if *copy { 'C' } else { 'R' };
+                    format!("{m} {letter} +{added} -{deleted} {from} ->")
+                }
                 _ => String::new(),
             };
             writeln!(f, "{prefix}{connector} {marker} {name}")?;
@@ -128,6 +149,14 @@ pub enum FileChange {
         deleted: usize,
         mode: Option<i64>,
     },
+    // Renommé (ou copié) depuis `from`, éventuellement modifié
+    Renamed {
+        from: String,
+        copy: bool,
+        added: usize,
+        deleted: usize,
+        mode: Option<i64>,
+    },
 }
 
 fn commit_justify(text: &str, width: usize) -> String {
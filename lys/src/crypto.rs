@@ -1,4 +1,4 @@
-use crate::utils::{ko_audit_commit, ok, ok_audit_commit};
+use crate::utils::{ko, ko_audit_commit, ok, ok_audit_commit};
 use ed25519_dalek::Signature;
 use ed25519_dalek::SigningKey;
 use ed25519_dalek::VerifyingKey;
@@ -106,63 +106,229 @@ pub fn verify_signature(
     }
 }
 
+/// Clé publique du dépôt (32 octets bruts), telle que générée par `lys keygen`.
+pub fn public_key(root_path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(root_path.join(".lys/identity/public.key")).map_err(|e| e.to_string())
+}
+
+/// Vérifie une signature hexadécimale avec une clé publique donnée.
+/// Contrairement à `verify_transfer`, une clé ou une signature mal formée donne `false`.
+pub fn verify_with_key(public_key: &[u8], message: &str, signature_hex: &str) -> bool {
+    let Ok(key_bytes) = <[u8; 32]>::try_from(public_key) else {
+        return false;
+    };
+    let Ok(verifying_key) = VerifyingKey::from_bytes(&key_bytes) else {
+        return false;
+    };
+    let Some(signature) = hex::decode(signature_hex)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+    verifying_key.verify(message.as_bytes(), &signature).is_ok()
+}
+
 pub fn audit(conn: &Connection) -> Result<bool, sqlite::Error> {
     println!();
-    // On récupère Hash et Signature
-    let query = "SELECT hash, signature FROM commits ORDER BY id ASC";
-    let mut stmt = conn.prepare(query)?;
-
     let root_path = std::env::current_dir().unwrap();
+    // Notre clé, celles des remotes et de `trusted_keys` : un clone contient des commits d'autres nœuds
+    let keys = crate::transfer::trusted_keys(conn, &root_path, None);
+    // Un clone partiel (--depth/--since) n'a pas les parents de ses commits les plus anciens
+    let shallow = crate::db::config(conn, crate::transfer::SHALLOW).unwrap_or_default();
+    let shallow: Vec<&str> = shallow.split_whitespace().collect();
+    // Un commit greffé par `lys prune` se recalcule sur ses parents d'origine
+    let grafts = crate::prune::grafts(conn, &keys);
     let mut errors = 0;
     let mut unsigned = 0;
     let mut valid = 0;
+    let mut legacy = 0;
+    let mut broken = 0;
+
+    // Toutes les saisons : un commit scellé dans une ancienne reste audité
+    crate::history::each_season(conn, |schema| {
+        // Hash, Signature et tout ce qui entre dans le hash Merkle
+        let mut stmt = conn.prepare(format!(
+            "SELECT hash, signature, tree_hash, author, message, timestamp FROM {schema}.commits
+             ORDER BY id ASC"
+        ))?;
+        let mut rows = Vec::new();
+        while let Ok(State::Row) = stmt.next() {
+            rows.push((
+                stmt.read::<String, _>(0)?,
+                stmt.read::<Option<String>, _>(1).ok().flatten(), // Peut être NULL
+                stmt.read::<String, _>(2)?,
+                stmt.read::<String, _>(3)?,
+                stmt.read::<String, _>(4)?,
+                stmt.read::<String, _>(5)?,
+            ));
+        }
 
-    while let Ok(State::Row) = stmt.next() {
-        let hash: String = stmt.read(0)?;
-        let signature_opt: Option<String> = stmt.read(1).ok(); // Peut être NULL
+        for (hash, signature_opt, tree_hash, author, message, timestamp) in rows {
+            // 1. Le DAG : le hash doit se recalculer depuis ses parents et son contenu
+            let parents = crate::db::commit_parents(conn, &hash)?;
+            let hashed = crate::prune::hashed_parents(&grafts, &hash, &parents);
+            let expected =
+                crate::vcs::compute_commit_hash(hashed, &tree_hash, &author, &message, &timestamp);
+            if expected != hash {
+                if is_legacy_hash(&hash, hashed, &tree_hash, &author, &message, &timestamp) {
+                    // Commit antérieur au DAG : ancien format de hash, accepté
+                    legacy += 1;
+                } else {
+                    ko(&format!("{} hash does not match its content", &hash[0..7]));
+                    broken += 1;
+                }
+            }
+            for parent in &parents {
+                if !commit_exists(conn, parent) && !shallow.contains(&hash.as_str()) {
+                    ko(&format!("{} has a missing parent {parent}", &hash[0..7]));
+                    broken += 1;
+                }
+            }
 
-        if let Some(signature) = signature_opt {
-            // Commit signé : on vérifie
-            match verify_signature(&root_path, &hash, &signature) {
-                Ok(true) => {
+            if let Some(signature) = signature_opt {
+                // Commit signé : on vérifie
+                if keys
+                    .iter()
+                    .any(|key| verify_with_key(key, &hash, &signature))
+                {
                     // C'est vide, on ne dit rien pour ne pas polluer, ou juste un petit point
                     ok_audit_commit(&hash[0..7]);
                     valid += 1;
-                }
-                Ok(false) | Err(_) => {
+                } else {
                     ko_audit_commit(&hash[0..7]);
                     errors += 1;
                 }
+            } else {
+                // Commit non signé (vieux commits avant la feature)
+                unsigned += 1;
             }
-        } else {
-            // Commit non signé (vieux commits avant la feature)
-            unsigned += 1;
         }
-    }
+        Ok(())
+    })?;
     println!();
     let total = errors + unsigned + valid;
-    if errors > 0 {
-        println!("{}",format!(
-            "Validated ({valid}/{total}) Unsigned ({unsigned}) Errors ({errors}) Total ({total})"
-        )
-        .as_str());
-        println!();
-        return Ok(false);
-    } else {
-        println!("{}",format!(
-            "Validated ({valid}/{total}) Unsigned ({unsigned}) Errors ({errors}) Total ({total})"
-        )
-        .as_str());
-    }
+    let summary = format!(
+        "Validated ({valid}/{total}) Unsigned ({unsigned}) Errors ({errors}) Legacy hashes ({legacy}) Broken links ({broken}) Total ({total})"
+    );
+    println!("{summary}");
     println!();
-    Ok(true)
+    Ok(errors == 0 && broken == 0)
+}
+
+// Formats de hash utilisés avant le DAG (commit local et import git)
+pub(crate) fn is_legacy_hash(
+    hash: &str,
+    parents: &[String],
+    tree_hash: &str,
+    author: &str,
+    message: &str,
+    timestamp: &str,
+) -> bool {
+    // Les anciens formats ne couvraient qu'un parent : un merge a le hash du DAG
+    if parents.len() > 1 {
+        return false;
+    }
+    let local = blake3::hash(format!("{tree_hash}{author}{message}").as_bytes());
+    if local.to_hex().as_str() == hash {
+        return true;
+    }
+    let Ok(at) = chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S") else {
+        return false;
+    };
+    let parent = parents.first().map(String::as_str).unwrap_or("");
+    let seconds = at.and_utc().timestamp();
+    let imported =
+        blake3::hash(format!("{parent}{author}{message}{seconds}{tree_hash}").as_bytes());
+    imported.to_hex().as_str() == hash
+}
+
+// Un parent peut vivre dans n'importe quelle saison (voir `history`)
+fn commit_exists(conn: &Connection, hash: &str) -> bool {
+    crate::history::find(conn, hash).is_some()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::AUTHOR;
     use tempfile::tempdir;
 
+    // Le hash d'un merge couvre ses deux parents dans l'ordre : `log` les
+    // montre, l'audit refuse qu'on en change un
+    #[test]
+    fn commit_hash_covers_every_parent() {
+        let (_repo, conn) = crate::utils::test_repo();
+        let head = |conn: &Connection| crate::vcs::get_branch_head_info(conn, "main").unwrap().1;
+        std::fs::write("a.txt", "base\n").unwrap();
+        crate::vcs::commit(&conn, "base", AUTHOR).unwrap();
+        let base = head(&conn);
+        crate::vcs::create_branch(&conn, "feature").unwrap();
+        crate::vcs::checkout(&conn, "feature").unwrap();
+        std::fs::write("b.txt", "feature\n").unwrap();
+        crate::vcs::commit(&conn, "feature", AUTHOR).unwrap();
+        let (_, feature) = crate::vcs::get_branch_head_info(&conn, "feature").unwrap();
+        crate::vcs::checkout(&conn, "main").unwrap();
+        std::fs::write("c.txt", "main\n").unwrap();
+        crate::vcs::commit(&conn, "main", AUTHOR).unwrap();
+        let ours = head(&conn);
+        assert!(crate::merge::merge_branch(&conn, "feature", "main").unwrap());
+        let merge = head(&conn);
+
+        let entry = crate::history::commit(&conn, &merge).unwrap().unwrap();
+        let hash = |parents: &[String]| {
+            crate::vcs::compute_commit_hash(
+                parents,
+                &entry.tree,
+                &entry.author,
+                &entry.message,
+                &entry.timestamp,
+            )
+        };
+        let swapped = hash(&[feature.clone(), ours.clone()]);
+        let (logs, _) = crate::vcs::log_entries(&conn, 1, 10, None).unwrap();
+        let clean = audit(&conn).unwrap();
+        conn.execute(format!(
+            "UPDATE commit_parents SET parent_hash = '{base}'
+             WHERE commit_hash = '{merge}' AND position = 1"
+        ))
+        .unwrap();
+        let tampered = audit(&conn).unwrap();
+
+        assert_eq!(entry.parents, vec![ours.clone(), feature.clone()]);
+        assert_eq!(hash(&entry.parents), merge);
+        assert_ne!(swapped, merge);
+        let short = |hash: &str| hash[..7].to_string();
+        assert_eq!(logs[0].parents, vec![short(&ours), short(&feature)]);
+        assert!(logs[0].to_string().contains(&format!(
+            "Merge: {} {}",
+            short(&ours),
+            short(&feature)
+        )));
+        assert!(clean);
+        assert!(!tampered);
+
+        // L'ancien format, qui ignore les parents, n'est admis qu'avec un seul
+        let legacy = blake3::hash(b"treeauthormessage").to_hex().to_string();
+        let at = "2020-01-01 00:00:00";
+        assert!(is_legacy_hash(
+            &legacy,
+            std::slice::from_ref(&base),
+            "tree",
+            "author",
+            "message",
+            at
+        ));
+        assert!(!is_legacy_hash(
+            &legacy,
+            &[base, ours],
+            "tree",
+            "author",
+            "message",
+            at
+        ));
+    }
+
     #[test]
     fn test_keygen_sign_verify() {
         let dir = tempdir().unwrap();
@@ -107,6 +107,137 @@ pub const LYS_INIT: &str = "CREATE TABLE IF NOT EXISTS tree_nodes (
     INSERT OR IGNORE INTO config (key, value) VALUES ('current_branch', 'main');
 ";
 
+// Tables arrivées après LYS_INIT : exécutées à chaque connexion (idempotent)
+// pour que les dépôts existants en profitent sans réinitialisation.
+pub const LYS_MIGRATIONS: &str = "
+    -- Parents ordonnés d'un commit (le premier est la branche cible d'un merge)
+    CREATE TABLE IF NOT EXISTS commit_parents (
+        commit_hash TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        parent_hash TEXT NOT NULL,
+        PRIMARY KEY (commit_hash, position)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS idx_commit_parents_parent ON commit_parents(parent_hash);
+    -- Reprise des commits qui n'avaient que la colonne parent_hash
+    INSERT OR IGNORE INTO commit_parents (commit_hash, position, parent_hash)
+        SELECT hash, 0, parent_hash FROM commits
+        WHERE parent_hash IS NOT NULL AND parent_hash != ''
+          AND NOT EXISTS (SELECT 1 FROM commit_parents cp WHERE cp.commit_hash = commits.hash);
+
+    -- Tags (lus par tag_list et le web, jusqu'ici jamais créés)
+    CREATE TABLE IF NOT EXISTS tags (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT UNIQUE NOT NULL,
+        commit_id INTEGER NOT NULL,
+        description TEXT,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        FOREIGN KEY (commit_id) REFERENCES commits(id)
+    );
+
+    -- Stashes : modifications mises de côté, hors de toute branche
+    CREATE TABLE IF NOT EXISTS stashes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        message TEXT NOT NULL,
+        branch TEXT NOT NULL,
+        base_commit TEXT NOT NULL,      -- HEAD au moment du push
+        tree_hash TEXT NOT NULL,        -- Arbre des fichiers nouveaux/modifiés
+        deleted TEXT NOT NULL,          -- Chemins supprimés (JSON)
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );
+
+    -- Remotes nommés : transport lys-http, git ou path, et clé publique de confiance
+    CREATE TABLE IF NOT EXISTS remotes (
+        name TEXT PRIMARY KEY,
+        url TEXT NOT NULL,
+        transport TEXT NOT NULL,
+        public_key TEXT,                -- hex, épinglée au premier fetch si absente
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );
+    -- Refs de suivi (origin/main) : des hashes, le commit peut vivre dans une autre saison
+    CREATE TABLE IF NOT EXISTS remote_refs (
+        remote TEXT NOT NULL,
+        branch TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (remote, branch)
+    );
+
+    -- Correspondance commit Lys -> commit Git (export incrémental, rempli aussi par l'import)
+    CREATE TABLE IF NOT EXISTS git_map (
+        lys_hash TEXT PRIMARY KEY,
+        git_oid TEXT NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS idx_git_map_oid ON git_map(git_oid);
+
+    -- Objets irrécupérables retirés par `lys verify --repair`, gardés tels que trouvés
+    CREATE TABLE IF NOT EXISTS quarantine (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,             -- blob ou tree
+        hash TEXT NOT NULL,
+        content BLOB,                   -- lignes de tree_nodes (JSON) pour un arbre
+        reason TEXT NOT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    );
+
+    -- Catalogue de l'historique : saison (base sous .lys/db) de chaque commit
+    CREATE TABLE IF NOT EXISTS store.history (
+        hash TEXT PRIMARY KEY,
+        shard TEXT NOT NULL
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS store.idx_history_shard ON history(shard);
+    -- Saisons déjà indexées : taille et date d'écriture lors de l'indexation
+    CREATE TABLE IF NOT EXISTS store.shards (
+        path TEXT PRIMARY KEY,
+        stamp TEXT NOT NULL
+    ) WITHOUT ROWID;
+
+    -- Index du répertoire de travail : stat de chaque fichier lors de son hachage
+    CREATE TABLE IF NOT EXISTS store.worktree (
+        path TEXT PRIMARY KEY,
+        size INTEGER NOT NULL,
+        mtime INTEGER NOT NULL,         -- nanosecondes
+        inode INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        checked INTEGER NOT NULL        -- moment du hachage, en nanosecondes
+    ) WITHOUT ROWID;
+
+    -- Gros blobs découpés (FastCDC) : leur content est NULL et leur contenu est
+    -- la suite ordonnée de leurs morceaux, dédupliqués par blake3
+    CREATE TABLE IF NOT EXISTS store.chunks (
+        hash TEXT PRIMARY KEY,
+        content BLOB NOT NULL,          -- compressé (zstd)
+        size INTEGER NOT NULL
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS store.blob_chunks (
+        blob_hash TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        chunk_hash TEXT NOT NULL,
+        PRIMARY KEY (blob_hash, position)
+    ) WITHOUT ROWID;
+    CREATE INDEX IF NOT EXISTS store.idx_blob_chunks_chunk ON blob_chunks(chunk_hash);
+
+    -- Greffes de `lys prune` : un commit dont des ancêtres ont été supprimés garde
+    -- ses parents d'origine (ceux de son hash), signés avec ses nouveaux parents
+    CREATE TABLE IF NOT EXISTS store.grafts (
+        commit_hash TEXT PRIMARY KEY,
+        original TEXT NOT NULL,         -- parents séparés par des espaces
+        parents TEXT NOT NULL,
+        signature TEXT NOT NULL,
+        created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+    ) WITHOUT ROWID;
+";
+
+// Ajoute une colonne si elle manque (ALTER TABLE n'a pas de IF NOT EXISTS)
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<(), Error> {
+    let mut stmt = conn.prepare(format!("PRAGMA table_info({table})"))?;
+    while let Ok(State::Row) = stmt.next() {
+        if stmt.read::<String, _>("name")? == column {
+            return Ok(());
+        }
+    }
+    conn.execute(format!("ALTER TABLE {table} ADD COLUMN {column} {decl};"))
+}
+
 #[derive(Default)]
 pub struct CommitQuery {
     pub author: Option<String>,
@@ -120,6 +251,7 @@ pub struct CommitQuery {
 }
 
 pub struct CommitQueryResult {
+    pub schema: String, // base de saison : `id` n'a de sens que dans celle-ci
     pub id: i64,
     pub hash: String,
     pub author: String,
@@ -152,6 +284,23 @@ pub fn config(conn: &Connection, key: &str) -> Result<String, Error> {
     }
     Ok(String::new())
 }
+// Variante silencieuse de set_config pour l'état interne (merge en cours, etc.)
+pub fn write_config(conn: &Connection, key: &str, value: &str) -> Result<(), Error> {
+    let query = "INSERT OR REPLACE INTO config (key, value) VALUES (?, ?)";
+    let mut stmt = conn.prepare(query)?;
+    stmt.bind((1, key))?;
+    stmt.bind((2, value))?;
+    stmt.next()?;
+    Ok(())
+}
+
+pub fn remove_config(conn: &Connection, key: &str) -> Result<(), Error> {
+    let mut stmt = conn.prepare("DELETE FROM config WHERE key = ?")?;
+    stmt.bind((1, key))?;
+    stmt.next()?;
+    Ok(())
+}
+
 pub fn set_config(conn: &Connection, key: &str, value: &str) -> Result<(), Error> {
     let query = "INSERT OR REPLACE INTO config (key, value) VALUES (?, ?)";
     let mut stmt = conn.prepare(query)?;
@@ -226,17 +375,72 @@ pub fn branch_head_id(conn: &Connection, branch: &str) -> Result<Option<i64>, Er
     }
 }
 
+// Parents d'un commit dans l'ordre (commit_parents, sinon l'ancienne colonne parent_hash)
+pub fn commit_parents(conn: &Connection, hash: &str) -> Result<Vec<String>, Error> {
+    let mut parents = Vec::new();
+    let mut stmt = conn.prepare(
+        "SELECT parent_hash FROM commit_parents WHERE commit_hash = ? ORDER BY position ASC",
+    )?;
+    stmt.bind((1, hash))?;
+    while let Ok(State::Row) = stmt.next() {
+        parents.push(stmt.read::<String, _>(0)?);
+    }
+    if !parents.is_empty() {
+        return Ok(parents);
+    }
+
+    let mut stmt = conn.prepare("SELECT parent_hash FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next()
+        && let Some(parent) = stmt.read::<Option<String>, _>(0)?
+        && !parent.is_empty()
+    {
+        parents.push(parent);
+    } else if let Some(entry) = crate::history::commit(conn, hash)?
+        && entry.schema != "main"
+    {
+        // Commit d'une autre saison
+        parents = entry.parents;
+    }
+    Ok(parents)
+}
+
+pub fn insert_commit_parents(
+    conn: &Connection,
+    commit_hash: &str,
+    parents: &[String],
+) -> Result<(), Error> {
+    for (position, parent) in parents.iter().enumerate() {
+        let mut stmt = conn.prepare(
+            "INSERT OR IGNORE INTO commit_parents (commit_hash, position, parent_hash) VALUES (?, ?, ?)",
+        )?;
+        stmt.bind((1, commit_hash))?;
+        stmt.bind((2, position as i64))?;
+        stmt.bind((3, parent.as_str()))?;
+        stmt.next()?;
+    }
+    Ok(())
+}
+
+pub fn commit_tree_hash(conn: &Connection, hash: &str) -> Result<Option<String>, Error> {
+    let mut stmt = conn.prepare("SELECT tree_hash FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<String, _>(0)?))
+    } else {
+        Ok(crate::history::commit(conn, hash)?.map(|entry| entry.tree))
+    }
+}
+
 pub fn query_commits(
     conn: &Connection,
     query: &CommitQuery,
     page: usize,
     limit: usize,
 ) -> Result<(Vec<CommitQueryResult>, i64), Error> {
+    // `{schema}` est remplacé par chaque base de saison interrogée
     let mut clauses: Vec<String> = Vec::new();
     let mut params: Vec<String> = Vec::new();
-    let mut cte_sql = String::new();
-    let mut cte_params: Vec<String> = Vec::new();
-    let mut from_clause = String::from("commits");
 
     if let Some(branch) = query
         .branch
@@ -244,20 +448,20 @@ pub fn query_commits(
         .map(|v| v.trim())
         .filter(|v| !v.is_empty())
     {
-        if let Some(head_id) = branch_head_id(conn, branch)? {
-            cte_sql = String::from(
-                "WITH RECURSIVE branch_commits(id, hash, parent_hash) AS ( \
-                 SELECT id, hash, parent_hash FROM commits WHERE id = ? \
-                 UNION ALL \
-                 SELECT c.id, c.hash, c.parent_hash FROM commits c \
-                 JOIN branch_commits bc ON c.hash = bc.parent_hash \
-                 ) ",
-            );
-            cte_params.push(head_id.to_string());
-            from_clause = String::from("commits JOIN branch_commits bc ON commits.id = bc.id");
-        } else {
+        let Some(head) = crate::history::branch_head(conn, branch) else {
             return Ok((Vec::new(), 0));
+        };
+        // L'historique de la branche traverse les saisons : on le matérialise
+        conn.execute(
+            "CREATE TEMP TABLE IF NOT EXISTS branch_commits (hash TEXT PRIMARY KEY);
+             DELETE FROM temp.branch_commits;",
+        )?;
+        for entry in crate::history::ancestry(conn, &[head])? {
+            let mut stmt = conn.prepare("INSERT OR IGNORE INTO temp.branch_commits VALUES (?)")?;
+            stmt.bind((1, entry.hash.as_str()))?;
+            stmt.next()?;
         }
+        clauses.push("hash IN (SELECT hash FROM temp.branch_commits)".to_string());
     }
 
     if let Some(tag) = query
@@ -308,8 +512,15 @@ pub fn query_commits(
         .map(|v| v.trim())
         .filter(|v| !v.is_empty())
     {
-        clauses.push("EXISTS (SELECT 1 FROM manifest WHERE manifest.commit_id = commits.id AND file_path LIKE ? COLLATE NOCASE)".to_string());
-        params.push(format!("%{file}%"));
+        // Un fichier renommé garde son asset : ses commits sous d'autres noms suivent
+        let pattern = format!("%{file}%");
+        let assets = crate::assets::matching(conn, &pattern)?
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        clauses.push(format!("EXISTS (SELECT 1 FROM {{schema}}.manifest m WHERE m.commit_id = commits.id AND (file_path LIKE ? COLLATE NOCASE OR m.asset_id IN ({assets})))"));
+        params.push(pattern);
     }
     if let Some(after) = query
         .after
@@ -336,70 +547,58 @@ pub fn query_commits(
         format!(" WHERE {}", clauses.join(" AND "))
     };
 
-    let count_sql = if cte_sql.is_empty() {
-        format!("SELECT COUNT(*) FROM {from_clause}{where_clause}")
-    } else {
-        format!("{cte_sql}SELECT COUNT(*) FROM {from_clause}{where_clause}")
-    };
-    let mut count_stmt = conn.prepare(count_sql)?;
-    let mut bind_idx = 1;
-    for value in cte_params.iter() {
-        count_stmt.bind((bind_idx, value.as_str()))?;
-        bind_idx += 1;
-    }
-    for value in params.iter() {
-        count_stmt.bind((bind_idx, value.as_str()))?;
-        bind_idx += 1;
-    }
-    let total = if let Ok(State::Row) = count_stmt.next() {
-        count_stmt.read::<i64, _>(0).unwrap_or(0)
-    } else {
-        0
-    };
-
-    let offset = (page.saturating_sub(1) * limit) as i64;
-    let data_sql = if cte_sql.is_empty() {
-        format!(
-            "SELECT id, hash, author, message, timestamp FROM {from_clause}{where_clause} ORDER BY id DESC LIMIT ? OFFSET ?"
-        )
-    } else {
-        format!(
-            "{cte_sql}SELECT id, hash, author, message, timestamp FROM {from_clause}{where_clause} ORDER BY id DESC LIMIT ? OFFSET ?"
-        )
-    };
-    let mut stmt = conn.prepare(data_sql)?;
-    let mut bind_idx = 1;
-    for value in cte_params.iter() {
-        stmt.bind((bind_idx, value.as_str()))?;
-        bind_idx += 1;
-    }
-    for value in params.iter() {
-        stmt.bind((bind_idx, value.as_str()))?;
-        bind_idx += 1;
-    }
-    stmt.bind((bind_idx, limit as i64))?;
-    stmt.bind((bind_idx + 1, offset))?;
-
+    // Chaque saison donne au plus `offset + limit` lignes, fusionnées par date
+    let offset = page.saturating_sub(1) * limit;
+    let mut total = 0;
     let mut rows = Vec::new();
-    while let Ok(State::Row) = stmt.next() {
-        rows.push(CommitQueryResult {
-            id: stmt.read::<i64, _>("id").unwrap_or(0),
-            hash: stmt.read::<String, _>("hash").unwrap_or_default(),
-            author: stmt.read::<String, _>("author").unwrap_or_default(),
-            message: stmt.read::<String, _>("message").unwrap_or_default(),
-            timestamp: stmt.read::<String, _>("timestamp").unwrap_or_default(),
-        });
-    }
+    crate::history::each_season(conn, |schema| {
+        let where_clause = where_clause.replace("{schema}", schema);
+        let mut count_stmt = conn.prepare(format!(
+            "SELECT COUNT(*) FROM {schema}.commits AS commits{where_clause}"
+        ))?;
+        for (idx, value) in params.iter().enumerate() {
+            count_stmt.bind((idx + 1, value.as_str()))?;
+        }
+        if let Ok(State::Row) = count_stmt.next() {
+            total += count_stmt.read::<i64, _>(0).unwrap_or(0);
+        }
+
+        let mut stmt = conn.prepare(format!(
+            "SELECT id, hash, author, message, timestamp FROM {schema}.commits AS commits{where_clause} ORDER BY timestamp DESC, id DESC LIMIT ?"
+        ))?;
+        for (idx, value) in params.iter().enumerate() {
+            stmt.bind((idx + 1, value.as_str()))?;
+        }
+        stmt.bind((params.len() + 1, (offset + limit) as i64))?;
+        while let Ok(State::Row) = stmt.next() {
+            rows.push(CommitQueryResult {
+                schema: schema.to_string(),
+                id: stmt.read::<i64, _>("id").unwrap_or(0),
+                hash: stmt.read::<String, _>("hash").unwrap_or_default(),
+                author: stmt.read::<String, _>("author").unwrap_or_default(),
+                message: stmt.read::<String, _>("message").unwrap_or_default(),
+                timestamp: stmt.read::<String, _>("timestamp").unwrap_or_default(),
+            });
+        }
+        Ok(())
+    })?;
 
+    rows.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    let mut seen = std::collections::HashSet::new();
+    rows.retain(|row| seen.insert(row.hash.clone()));
+    let rows = rows.into_iter().skip(offset).take(limit).collect();
     Ok((rows, total))
 }
 
 pub fn commit_files_preview(
     conn: &Connection,
+    schema: &str,
     commit_id: i64,
     max_files: usize,
 ) -> Result<(Vec<String>, i64), Error> {
-    let mut count_stmt = conn.prepare("SELECT COUNT(*) FROM manifest WHERE commit_id = ?")?;
+    let mut count_stmt = conn.prepare(format!(
+        "SELECT COUNT(*) FROM {schema}.manifest WHERE commit_id = ?"
+    ))?;
     count_stmt.bind((1, commit_id))?;
     let total = if let Ok(State::Row) = count_stmt.next() {
         count_stmt.read::<i64, _>(0).unwrap_or(0)
@@ -407,9 +606,9 @@ pub fn commit_files_preview(
         0
     };
 
-    let mut stmt = conn.prepare(
-        "SELECT file_path FROM manifest WHERE commit_id = ? ORDER BY file_path ASC LIMIT ?",
-    )?;
+    let mut stmt = conn.prepare(format!(
+        "SELECT file_path FROM {schema}.manifest WHERE commit_id = ? ORDER BY file_path ASC LIMIT ?"
+    ))?;
     stmt.bind((1, commit_id))?;
     stmt.bind((2, max_files as i64))?;
 
@@ -506,6 +705,26 @@ impl Season {
             Self::Autumn => (Self::Summer, current_year),
         }
     }
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "winter" => Some(Self::Winter),
+            "spring" => Some(Self::Spring),
+            "summer" => Some(Self::Summer),
+            "autumn" => Some(Self::Autumn),
+            _ => None,
+        }
+    }
+
+    // Premier mois de la saison (même découpage que current())
+    pub fn first_month(&self) -> u32 {
+        match self {
+            Self::Winter => 1,
+            Self::Spring => 4,
+            Self::Summer => 7,
+            Self::Autumn => 10,
+        }
+    }
+
     pub fn after() -> Self {
         match Local::now().month() {
             1..=3 => Self::Spring,
@@ -516,6 +735,14 @@ impl Season {
     }
 }
 
+/// Date de début (`YYYY-MM-DD`) d'une saison écrite comme son dossier : `2025/autumn`.
+pub fn season_start(spec: &str) -> Option<String> {
+    let (year, name) = spec.split_once('/')?;
+    let year: i32 = year.parse().ok()?;
+    let season = Season::parse(name)?;
+    Some(format!("{year:04}-{:02}-01", season.first_month()))
+}
+
 impl Display for Season {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -527,70 +754,6 @@ impl Display for Season {
     }
 }
 
-// Dans src/db.rs
-
-pub fn verify(conn: &Connection, deep: bool) -> Result<(), Box<dyn std::error::Error>> {
-    ok("Starting repository integrity verification...");
-    if deep {
-        ok("Deep mode enabled: Recalculating all checksums...");
-    }
-
-    // On récupère les objets référencés par les commits (ignorant les dossiers)
-    let query = "SELECT DISTINCT hash, name FROM tree_nodes WHERE mode != 16384 AND mode != 16400 AND mode != 49152";
-    let mut stmt = conn.prepare(query)?;
-
-    let mut missing = 0;
-    let mut corrupted = 0;
-    let mut total = 0;
-
-    while let Ok(State::Row) = stmt.next() {
-        total += 1;
-        let expected_hash: String = stmt.read(0)?;
-        let name: String = stmt.read(1)?;
-
-        // On récupère le contenu pour vérifier l'existence
-        let mut check_stmt =
-            conn.prepare("SELECT content FROM store.blobs WHERE hash = ? LIMIT 1")?;
-        check_stmt.bind((1, expected_hash.as_str()))?;
-
-        if let Ok(State::Row) = check_stmt.next() {
-            if deep {
-                // VERIFICATION PROFONDE : On décompresse et on rehache
-                let compressed: Vec<u8> = check_stmt.read(0)?;
-                let decompressed = decompress(&compressed); // Ta fonction de décompression
-
-                let actual_hash = blake3::hash(&decompressed).to_hex().to_string();
-
-                if actual_hash != expected_hash {
-                    corrupted += 1;
-                    crate::utils::ko(&format!(
-                        "CORRUPTED: Hash mismatch for '{name}' (Expected: {}, Actual: {})",
-                        &expected_hash[..7],
-                        &actual_hash[..7]
-                    ));
-                }
-            }
-        } else {
-            missing += 1;
-            crate::utils::ko(&format!(
-                "MISSING: Data for '{name}' (hash: {})",
-                &expected_hash[..7]
-            ));
-        }
-    }
-
-    // Rapport final
-    if missing == 0 && corrupted == 0 {
-        ok(&format!(
-            "Verification success! All {total} objects are intact.",
-        ));
-    } else {
-        crate::utils::ko(&format!(
-            "Integrity report: {missing} missing, {corrupted} corrupted / {total} total",
-        ));
-    }
-    Ok(())
-}
 pub fn connect_lys(root_path: &Path) -> Result<Connection, Error> {
     let db_dir = root_path.join(".lys/db");
     let store_path = db_dir.join("store.db");
@@ -617,6 +780,9 @@ pub fn connect_lys(root_path: &Path) -> Result<Connection, Error> {
     if conn.execute("SELECT 1 FROM tree_nodes LIMIT 1;").is_err() {
         conn.execute(LYS_INIT)?;
     }
+    conn.execute(LYS_MIGRATIONS)?;
+    // Chaque opération pointe vers celle qu'elle remplace (undo/redo)
+    ensure_column(&conn, "operations_log", "parent_id", "INTEGER")?;
     // 2.5 RESET DES TODOS
     let _ = crate::todo::check_and_reset_todos(&conn);
     // 3. RECONSOLIDATION DYNAMIQUE
@@ -624,19 +790,26 @@ pub fn connect_lys(root_path: &Path) -> Result<Connection, Error> {
         let attach_query = format!("ATTACH DATABASE '{}' AS old;", prev_db.display());
         conn.execute(attach_query)?;
     }
+    // Les autres saisons sont cataloguées, puis attachées à la demande
+    let _ = crate::history::index(&conn);
     // Performances
     conn.execute("PRAGMA foreign_keys = ON;")?;
-    conn.execute("PRAGMA journal_mode = WAL;")?;
+    // Les autres saisons gardent leur journal : une saison scellée ne s'écrit plus
+    conn.execute("PRAGMA main.journal_mode = WAL; PRAGMA store.journal_mode = WAL;")?;
     Ok(conn)
 }
 
 // Cherche récursivement la base .db la plus récente dans .lys/db
 fn find_latest_db(db_root: &Path, current_path: &Path) -> Option<PathBuf> {
     let pattern = format!("{}/**/*.db", db_root.display());
+    // glob renvoie `.lys/...` pour une racine `.` : on compare les chemins absolus
+    let current_path = current_path.canonicalize().ok();
     let mut dbs: Vec<PathBuf> = glob::glob(&pattern)
         .ok()?
         .filter_map(|res| res.ok())
-        .filter(|path| path != current_path && !path.to_string_lossy().contains("store.db"))
+        .filter(|path| {
+            path.canonicalize().ok() != current_path && !path.to_string_lossy().contains("store.db")
+        })
         .collect();
     // On trie par date de modification (la plus récente d'abord)
     dbs.sort_by(|a, b| {
@@ -733,6 +906,10 @@ pub fn get_unique_contributors(conn: &Connection) -> Result<Vec<(String, i64)>,
 
 // Dans src/db.rs
 pub fn insert_blob_with_conn(conn: &Connection, hash: &str, content: &[u8]) -> Result<(), Error> {
+    // Au-delà du seuil, le blob est découpé en morceaux dédupliqués
+    if content.len() as u64 >= crate::chunks::threshold(conn) {
+        return crate::chunks::write(conn, content, Some(hash)).map(|_| ());
+    }
     let compressed = compress(content); // Ta fonction de compression existante
     let insert = |sql: &str| -> Result<(), Error> {
         let mut stmt = conn.prepare(sql)?;
@@ -771,6 +948,7 @@ pub fn prune_orphans(conn: &Connection) -> Result<usize, Error> {
         conn.execute(
             "DELETE FROM store.blobs WHERE hash NOT IN (SELECT DISTINCT hash FROM tree_nodes)",
         )?;
+        crate::chunks::collect(conn)?;
 
         ok("Please wait");
         // 3. Optionnel : On libère l'espace disque sur le fichier .db (VACUUM)
@@ -780,80 +958,3 @@ pub fn prune_orphans(conn: &Connection) -> Result<usize, Error> {
 
     Ok(count as usize)
 }
-
-pub fn prune(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
-    ok("Starting prune");
-
-    conn.execute("BEGIN TRANSACTION;")?;
-
-    // 1. Supprimer les vieux commits (Plus vieux que 2 ans)
-    // On utilise la fonction datetime d'SQLite pour cibler la colonne timestamp
-    let del_commits = "DELETE FROM commits WHERE timestamp < datetime('now', '-2 years');";
-    conn.execute(del_commits)?;
-
-    // 2. Créer une table temporaire pour lister les hashes à CONSERVER
-    // On utilise un Merkle Tree récursif pour trouver tous les descendants des commits restants
-    conn.execute("CREATE TEMP TABLE live_hashes(hash TEXT PRIMARY KEY);")?;
-
-    // A. On commence par les racines (tree_hash) des commits survivants
-    conn.execute("INSERT OR IGNORE INTO live_hashes (hash) SELECT tree_hash FROM commits;")?;
-
-    // B. Propagation récursive : on cherche tous les fichiers et sous-dossiers liés
-    // On boucle jusqu'à ce que le nombre de hashes vivants n'évolue plus
-    loop {
-        let count_before = {
-            let mut stmt = conn.prepare("SELECT COUNT(*) FROM live_hashes")?;
-            stmt.next()?;
-            stmt.read::<i64, _>(0)?
-        };
-
-        // On insère les enfants des dossiers déjà marqués comme vivants
-        conn.execute(
-            "
-            INSERT OR IGNORE INTO live_hashes (hash)
-            SELECT hash FROM tree_nodes
-            WHERE parent_tree_hash IN (SELECT hash FROM live_hashes);
-        ",
-        )?;
-
-        let count_after = {
-            let mut stmt = conn.prepare("SELECT COUNT(*) FROM live_hashes")?;
-            stmt.next()?;
-            stmt.read::<i64, _>(0)?
-        };
-
-        if count_before == count_after {
-            break;
-        }
-    }
-
-    // 3. Nettoyage de la structure (tree_nodes)
-    // On supprime les dossiers qui n'ont plus de parent vivant
-    conn.execute(
-        "DELETE FROM tree_nodes WHERE parent_tree_hash NOT IN (SELECT hash FROM live_hashes);",
-    )?;
-
-    // 4. Nettoyage des données binaires (store.blobs)
-    let before_blobs = {
-        let mut stmt = conn.prepare("SELECT COUNT(*) FROM store.blobs")?;
-        stmt.next()?;
-        stmt.read::<i64, _>(0)?
-    };
-
-    // On supprime les contenus qui ne sont plus référencés par aucun nœud vivant
-    conn.execute("DELETE FROM store.blobs WHERE hash NOT IN (SELECT hash FROM live_hashes);")?;
-
-    let after_blobs = {
-        let mut stmt = conn.prepare("SELECT COUNT(*) FROM store.blobs")?;
-        stmt.next()?;
-        stmt.read::<i64, _>(0)?
-    };
-
-    conn.execute("COMMIT;")?;
-    ok(format!("Blobs deleted : {}", before_blobs - after_blobs).as_str());
-
-    // 5. Compression physique de la base de données
-    ok("Optimisation");
-    conn.execute("VACUUM;")?;
-    Ok(())
-}
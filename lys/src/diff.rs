@@ -0,0 +1,586 @@
+use crate::db::get_current_branch;
+use crate::merge::commit_state;
+use crate::vcs::{
+    FileStatus, count_line_changes, get_blob_bytes_by_hash, get_head_state, hash_entry, read_entry,
+    resolve_commit, status,
+};
+use anyhow::Error;
+use similar::{ChangeTag, DiffOp, TextDiff};
+use sqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Un côté de la comparaison : un commit ou l'arbre de travail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Side {
+    Commit(String),
+    WorkTree,
+}
+
+/// Format de sortie de `lys diff`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Patch,
+    WordDiff,
+    Stat,
+    NameOnly,
+    NameStatus,
+}
+
+/// Un fichier qui diffère entre les deux côtés.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    // A (ajout), D (suppression), M (modification), R (renommage) ou C (copie)
+    pub status: char,
+    pub old_path: Option<PathBuf>,
+    pub new_path: Option<PathBuf>,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    // Part de contenu commune avec l'original d'un renommage ou d'une copie (%)
+    pub similarity: u8,
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or(Path::new(""))
+    }
+}
+
+type Files = HashMap<PathBuf, String>;
+
+/// Lit le contenu d'un fichier d'un côté de la comparaison, par chemin et hash.
+pub type Content<'a> = &'a dyn Fn(&Path, &str) -> Option<Vec<u8>>;
+
+/// Côtés désignés par les arguments : rien (HEAD / arbre de travail), `A`
+/// (A / arbre de travail), `A B` ou `A..B`.
+pub fn sides(conn: &Connection, revs: &[String]) -> Result<(Side, Side), Error> {
+    let resolve = |reference: &str| -> Result<Side, Error> {
+        let reference = if reference.is_empty() {
+            "HEAD"
+        } else {
+            reference
+        };
+        resolve_commit(conn, reference)?
+            .map(Side::Commit)
+            .ok_or_else(|| anyhow::anyhow!("Unknown revision '{reference}'."))
+    };
+    match revs {
+        [] => Ok((resolve("HEAD")?, Side::WorkTree)),
+        [range] if range.contains("..") => {
+            let (a, b) = range.split_once("..").unwrap_or_default();
+            Ok((resolve(a)?, resolve(b)?))
+        }
+        [a] => Ok((resolve(a)?, Side::WorkTree)),
+        [a, b] => Ok((resolve(a)?, resolve(b)?)),
+        _ => Err(anyhow::anyhow!("Give at most two revisions.")),
+    }
+}
+
+fn files(conn: &Connection, side: &Side) -> Result<Files, Error> {
+    match side {
+        Side::Commit(hash) => Ok(commit_state(conn, hash)?
+            .into_iter()
+            .map(|(path, (hash, _))| (path, hash))
+            .collect()),
+        Side::WorkTree => {
+            // État de HEAD corrigé par le statut de l'arbre de travail
+            let branch = get_current_branch(conn)?;
+            let mut files: Files = get_head_state(conn, &branch)?
+                .into_iter()
+                .map(|(path, (hash, _))| (path, hash))
+                .collect();
+            let root = std::env::current_dir()?;
+            for change in status(conn, &root.to_string_lossy(), &branch)? {
+                match change {
+                    // Un dossier vide n'a pas de contenu à comparer
+                    FileStatus::New(path) if path.is_dir() => {}
+                    FileStatus::New(path) | FileStatus::Modified(path, _) => {
+                        let hash = hash_entry(&path)?;
+                        files.insert(path, hash);
+                    }
+                    FileStatus::Deleted(path, _) => {
+                        files.remove(&path);
+                    }
+                    FileStatus::Renamed(from, to, _) => {
+                        files.remove(&from);
+                        let hash = hash_entry(&to)?;
+                        files.insert(to, hash);
+                    }
+                    FileStatus::Unchanged => {}
+                }
+            }
+            Ok(files)
+        }
+    }
+}
+
+// Un chemin filtré par `-- paths` : le chemin lui-même ou un dossier parent
+fn selected(path: &Path, filters: &[PathBuf]) -> bool {
+    filters.is_empty() || filters.iter().any(|f| path.starts_with(f))
+}
+
+/// Fichiers ajoutés, supprimés, modifiés, renommés ou copiés (contenu
+/// identique) de `old` à `new`, triés par chemin.
+pub fn changes(old: &Files, new: &Files, filters: &[PathBuf]) -> Vec<Change> {
+    changes_with(old, new, filters, &|_, _| None, &|_, _| None)
+}
+
+/// Comme `changes`, en retrouvant aussi les renommages avec modifications :
+/// `old_content` et `new_content` lisent les fichiers de chaque côté.
+pub fn changes_with(
+    old: &Files,
+    new: &Files,
+    filters: &[PathBuf],
+    old_content: Content,
+    new_content: Content,
+) -> Vec<Change> {
+    let mut out = Vec::new();
+    let mut added = Vec::new();
+    for (path, hash) in new {
+        match old.get(path) {
+            Some(old_hash) if old_hash == hash => {}
+            Some(old_hash) => out.push(Change {
+                status: 'M',
+                old_path: Some(path.clone()),
+                new_path: Some(path.clone()),
+                old_hash: Some(old_hash.clone()),
+                new_hash: Some(hash.clone()),
+                similarity: 0,
+            }),
+            None => added.push((path.clone(), hash.clone())),
+        }
+    }
+    let mut deleted: Vec<(PathBuf, String)> = old
+        .iter()
+        .filter(|(path, _)| !new.contains_key(*path))
+        .map(|(path, hash)| (path.clone(), hash.clone()))
+        .collect();
+
+    let pairs = crate::assets::renames(&deleted, &added, old_content, new_content);
+    deleted.retain(|(path, _)| !pairs.iter().any(|pair| pair.from == *path));
+    added.retain(|(path, _)| !pairs.iter().any(|pair| pair.to == *path));
+    for pair in pairs {
+        out.push(Change {
+            status: 'R',
+            old_hash: old.get(&pair.from).cloned(),
+            new_hash: new.get(&pair.to).cloned(),
+            old_path: Some(pair.from),
+            new_path: Some(pair.to),
+            similarity: pair.score,
+        });
+    }
+    for (path, hash) in deleted {
+        out.push(Change {
+            status: 'D',
+            old_path: Some(path),
+            new_path: None,
+            old_hash: Some(hash),
+            new_hash: None,
+            similarity: 0,
+        });
+    }
+    // Un nouveau fichier identique à un fichier de l'ancien côté en est une copie
+    let sources: BTreeMap<&PathBuf, &String> = old.iter().collect();
+    for (path, hash) in added {
+        let source = sources
+            .iter()
+            .find(|(_, other)| ***other == hash)
+            .map(|(source, _)| (*source).clone());
+        out.push(Change {
+            status: if source.is_some() { 'C' } else { 'A' },
+            similarity: if source.is_some() { 100 } else { 0 },
+            old_hash: source.as_ref().map(|_| hash.clone()),
+            old_path: source,
+            new_path: Some(path),
+            new_hash: Some(hash),
+        });
+    }
+    out.retain(|c| {
+        selected(c.path(), filters) || c.old_path.as_deref().is_some_and(|p| selected(p, filters))
+    });
+    out.sort_by(|a, b| a.path().cmp(b.path()));
+    out
+}
+
+fn content(conn: &Connection, side: &Side, path: Option<&Path>, hash: Option<&str>) -> Vec<u8> {
+    match (side, path, hash) {
+        (Side::WorkTree, Some(path), Some(_)) => read_entry(path).unwrap_or_default(),
+        (_, _, Some(hash)) => get_blob_bytes_by_hash(conn, hash)
+            .ok()
+            .flatten()
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn is_binary(bytes: &[u8]) -> bool {
+    content_inspector::inspect(bytes).is_binary()
+}
+
+pub(crate) fn header(out: &mut String, change: &Change) {
+    let old = change
+        .old_path
+        .as_deref()
+        .unwrap_or(change.path())
+        .display();
+    let new = change
+        .new_path
+        .as_deref()
+        .unwrap_or(change.path())
+        .display();
+    let _ = writeln!(out, "diff --git a/{old} b/{new}");
+    let kind = match change.status {
+        'R' => "rename",
+        'C' => "copy",
+        _ => return,
+    };
+    let _ = writeln!(
+        out,
+        "similarity index {}%\n{kind} from {old}\n{kind} to {new}",
+        change.similarity
+    );
+}
+
+fn file_names(change: &Change) -> (String, String) {
+    let old = match &change.old_path {
+        Some(p) if change.status != 'A' => format!("a/{}", p.display()),
+        _ => "/dev/null".to_string(),
+    };
+    let new = match &change.new_path {
+        Some(p) if change.status != 'D' => format!("b/{}", p.display()),
+        _ => "/dev/null".to_string(),
+    };
+    (old, new)
+}
+
+// Patch unifié (3 lignes de contexte) lisible par `patch -p1` et `git apply`
+pub(crate) fn patch(out: &mut String, change: &Change, old: &str, new: &str) {
+    let (old_name, new_name) = file_names(change);
+    let diff = TextDiff::from_lines(old, new);
+    let _ = write!(
+        out,
+        "{}",
+        diff.unified_diff()
+            .context_radius(3)
+            .missing_newline_hint(true)
+            .header(&old_name, &new_name)
+    );
+}
+
+fn word_diff(out: &mut String, change: &Change, old: &str, new: &str) {
+    let (old_name, new_name) = file_names(change);
+    let _ = writeln!(out, "--- {old_name}\n+++ {new_name}");
+    let diff = TextDiff::from_lines(old, new);
+    let (old_lines, new_lines) = (diff.old_slices(), diff.new_slices());
+    for group in diff.grouped_ops(3) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let (old_range, new_range) = (
+            first.old_range().start..last.old_range().end,
+            first.new_range().start..last.new_range().end,
+        );
+        let _ = writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            old_range.start + 1,
+            old_range.len(),
+            new_range.start + 1,
+            new_range.len()
+        );
+        for op in &group {
+            let before: String = old_lines[op.old_range()].concat();
+            let after: String = new_lines[op.new_range()].concat();
+            if let DiffOp::Equal { .. } = op {
+                out.push_str(&before);
+                continue;
+            }
+            for word in TextDiff::from_words(&before, &after).iter_all_changes() {
+                match word.tag() {
+                    ChangeTag::Equal => out.push_str(word.value()),
+                    ChangeTag::Delete => {
+                        let _ = write!(out, "[-{}-]", word.value());
+                    }
+                    ChangeTag::Insert => {
+                        let _ = write!(out, "{{+{}+}}", word.value());
+                    }
+                }
+            }
+        }
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+}
+
+fn stat(conn: &Connection, old: &Side, new: &Side, changes: &[Change]) -> String {
+    let mut rows = Vec::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for change in changes {
+        let name = match (change.status, &change.old_path) {
+            ('R' | 'C', Some(old_path)) => {
+                format!("{} => {}", old_path.display(), change.path().display())
+            }
+            _ => change.path().display().to_string(),
+        };
+        let before = content(
+            conn,
+            old,
+            change.old_path.as_deref(),
+            change.old_hash.as_deref(),
+        );
+        let after = content(
+            conn,
+            new,
+            change.new_path.as_deref(),
+            change.new_hash.as_deref(),
+        );
+        if is_binary(&before) || is_binary(&after) {
+            rows.push((name, None));
+            continue;
+        }
+        let (added, deleted) = count_line_changes(&before, &after);
+        insertions += added;
+        deletions += deleted;
+        rows.push((name, Some((added, deleted))));
+    }
+
+    let width = rows
+        .iter()
+        .map(|(n, _)| n.chars().count())
+        .max()
+        .unwrap_or(0);
+    let largest = rows
+        .iter()
+        .filter_map(|(_, c)| c.map(|(a, d)| a + d))
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (name, counts) in rows {
+        match counts {
+            Some((added, deleted)) => {
+                // Barre limitée à 50 colonnes, proportionnelle au plus gros fichier
+                let scale = |n: usize| {
+                    if largest <= 50 {
+                        n
+                    } else {
+                        (n * 50).div_ceil(largest)
+                    }
+                };
+                let _ = writeln!(
+                    out,
+                    " {name:<width$} | {:>5} {}{}",
+                    added + deleted,
+                    "+".repeat(scale(added)),
+                    "-".repeat(scale(deleted))
+                );
+            }
+            None => {
+                let _ = writeln!(out, " {name:<width$} |   Bin");
+            }
+        }
+    }
+    let _ = writeln!(
+        out,
+        " {} file(s) changed, {insertions} insertion(s)(+), {deletions} deletion(s)(-)",
+        changes.len()
+    );
+    out
+}
+
+/// Texte complet de `lys diff` entre `old` et `new` pour le format demandé.
+pub fn render(
+    conn: &Connection,
+    old: &Side,
+    new: &Side,
+    filters: &[PathBuf],
+    format: Format,
+) -> Result<String, Error> {
+    let changes = changes_with(
+        &files(conn, old)?,
+        &files(conn, new)?,
+        filters,
+        &|path, hash| Some(content(conn, old, Some(path), Some(hash))),
+        &|path, hash| Some(content(conn, new, Some(path), Some(hash))),
+    );
+    let mut out = String::new();
+    match format {
+        Format::NameOnly => {
+            for change in &changes {
+                let _ = writeln!(out, "{}", change.path().display());
+            }
+        }
+        Format::NameStatus => {
+            for change in &changes {
+                match (change.status, &change.old_path) {
+                    (status @ ('R' | 'C'), Some(old_path)) => {
+                        let _ = writeln!(
+                            out,
+                            "{status}{:03}\t{}\t{}",
+                            change.similarity,
+                            old_path.display(),
+                            change.path().display()
+                        );
+                    }
+                    (status, _) => {
+                        let _ = writeln!(out, "{status}\t{}", change.path().display());
+                    }
+                }
+            }
+        }
+        Format::Stat => {
+            if !changes.is_empty() {
+                out = stat(conn, old, new, &changes);
+            }
+        }
+        Format::Patch | Format::WordDiff => {
+            for change in &changes {
+                header(&mut out, change);
+                if change.old_hash == change.new_hash {
+                    continue;
+                }
+                let before = content(
+                    conn,
+                    old,
+                    change.old_path.as_deref(),
+                    change.old_hash.as_deref(),
+                );
+                let after = content(
+                    conn,
+                    new,
+                    change.new_path.as_deref(),
+                    change.new_hash.as_deref(),
+                );
+                if is_binary(&before) || is_binary(&after) {
+                    let (old_name, new_name) = file_names(change);
+                    let _ = writeln!(out, "Binary files {old_name} and {new_name} differ");
+                    continue;
+                }
+                let before = String::from_utf8_lossy(&before);
+                let after = String::from_utf8_lossy(&after);
+                if format == Format::WordDiff {
+                    word_diff(&mut out, change, &before, &after);
+                } else {
+                    patch(&mut out, change, &before, &after);
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+// Couleurs seulement pour un terminal : la sortie redirigée reste un patch valide
+fn colorize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let color =
+            if line.starts_with("diff --git") || line.starts_with("+++") || line.starts_with("---")
+            {
+                "\x1b[1m"
+            } else if line.starts_with("@@") {
+                "\x1b[36m"
+            } else if line.starts_with('+') {
+                "\x1b[32m"
+            } else if line.starts_with('-') {
+                "\x1b[31m"
+            } else {
+                ""
+            };
+        if color.is_empty() {
+            out.push_str(line);
+        } else {
+            let body = line.trim_end_matches('\n');
+            let _ = write!(out, "{color}{body}\x1b[0m{}", &line[body.len()..]);
+        }
+    }
+    out
+}
+
+/// `lys diff [<rev>] [<rev>] [-- paths]`.
+pub fn run(
+    conn: &Connection,
+    revs: &[String],
+    paths: &[String],
+    format: Format,
+) -> Result<(), Error> {
+    let (old, new) = sides(conn, revs)?;
+    let filters: Vec<PathBuf> = paths
+        .iter()
+        .map(|p| PathBuf::from(p.trim_start_matches("./").trim_end_matches('/')))
+        .collect();
+    let text = render(conn, &old, &new, &filters, format)?;
+    if format == Format::Patch && std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+        print!("{}", colorize(&text));
+    } else {
+        print!("{text}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    fn files(entries: &[(&str, &str)]) -> Files {
+        entries
+            .iter()
+            .map(|(p, h)| (PathBuf::from(p), h.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn detects_renames_and_filters_paths() {
+        let old = files(&[("a.txt", "h1"), ("src/b.rs", "h2"), ("gone", "h3")]);
+        let new = files(&[("a.txt", "h9"), ("src/c.rs", "h2"), ("new", "h4")]);
+        let found: Vec<(char, String)> = changes(&old, &new, &[])
+            .iter()
+            .map(|c| (c.status, c.path().display().to_string()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ('M', "a.txt".to_string()),
+                ('D', "gone".to_string()),
+                ('A', "new".to_string()),
+                ('R', "src/c.rs".to_string()),
+            ]
+        );
+        let filtered = changes(&old, &new, &[PathBuf::from("src")]);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].old_path, Some(PathBuf::from("src/b.rs")));
+    }
+
+    // Deux commits : HEAD~1..HEAD en patch unifié et en --stat
+    #[test]
+    fn renders_patch_between_revisions() {
+        let (_repo, conn) = crate::utils::test_repo();
+        std::fs::write("a.txt", "one\ntwo\nthree\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        std::fs::write("a.txt", "one\n2\nthree\n").unwrap();
+        std::fs::write("b.txt", "new").unwrap();
+        crate::vcs::commit(&conn, "second", AUTHOR).unwrap();
+        std::fs::write("b.txt", "changed").unwrap();
+
+        let (old, new) = sides(&conn, &["HEAD~1..main".to_string()]).unwrap();
+        let text = render(&conn, &old, &new, &[], Format::Patch).unwrap();
+        let stat = render(&conn, &old, &new, &[], Format::Stat).unwrap();
+        let (head, worktree) = sides(&conn, &[]).unwrap();
+        let names = render(&conn, &head, &worktree, &[], Format::NameStatus).unwrap();
+
+        assert!(
+            text.contains("--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n")
+        );
+        assert!(text.contains(
+            "--- /dev/null\n+++ b/b.txt\n@@ -0,0 +1 @@\n+new\n\\ No newline at end of file\n"
+        ));
+        assert!(stat.contains(" 2 file(s) changed, 2 insertion(s)(+), 1 deletion(s)(-)"));
+        assert_eq!(names, "M\tb.txt\n");
+        assert!(sides(&conn, &["HEAD~5".to_string()]).is_err());
+    }
+}
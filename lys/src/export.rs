@@ -0,0 +1,418 @@
+use crate::db::{commit_parents, get_current_branch, list_tags, tag_hash};
+use crate::utils::{ko, ok};
+use crate::vcs::{get_blob_bytes_by_hash, is_directory};
+use anyhow::Error;
+use git2::{FileMode, Oid, Repository, Signature, Time};
+use sqlite::{Connection, State};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// OID Git déjà associé à un commit Lys (par un export ou un import).
+pub(crate) fn mapped_oid(conn: &Connection, lys_hash: &str) -> Result<Option<String>, Error> {
+    let mut stmt = conn.prepare("SELECT git_oid FROM git_map WHERE lys_hash = ?")?;
+    stmt.bind((1, lys_hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<String, _>(0)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Commit Lys correspondant à un OID Git, s'il est présent dans la base.
+pub(crate) fn lys_hash_for(conn: &Connection, git_oid: &str) -> Result<Option<String>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT m.lys_hash FROM git_map m JOIN commits c ON c.hash = m.lys_hash
+         WHERE m.git_oid = ? LIMIT 1",
+    )?;
+    stmt.bind((1, git_oid))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<String, _>(0)?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub(crate) fn record_oid(conn: &Connection, lys_hash: &str, git_oid: &str) -> Result<(), Error> {
+    let mut stmt =
+        conn.prepare("INSERT OR REPLACE INTO git_map (lys_hash, git_oid) VALUES (?, ?)")?;
+    stmt.bind((1, lys_hash))?;
+    stmt.bind((2, git_oid))?;
+    stmt.next()?;
+    Ok(())
+}
+
+struct CommitRow {
+    tree_hash: String,
+    author: String,
+    message: String,
+    timestamp: String,
+}
+
+fn read_commit(conn: &Connection, hash: &str) -> Result<Option<CommitRow>, Error> {
+    let mut stmt =
+        conn.prepare("SELECT tree_hash, author, message, timestamp FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(CommitRow {
+            tree_hash: stmt.read("tree_hash")?,
+            author: stmt.read("author")?,
+            message: stmt.read("message")?,
+            timestamp: stmt.read("timestamp")?,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// `Nom <email>` (format de `lys config`) ou un simple nom (commits importés).
+fn split_author(author: &str) -> (&str, &str) {
+    let (name, email) = match author.split_once('<') {
+        Some((name, rest)) => (name.trim(), rest.trim_end_matches('>').trim()),
+        None => (author.trim(), ""),
+    };
+    (if name.is_empty() { "Unknown" } else { name }, email)
+}
+
+/// Les commits locaux sont datés en RFC 3339, les imports en `YYYY-MM-DD HH:MM:SS` (UTC).
+fn git_time(timestamp: &str) -> Time {
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(timestamp) {
+        return Time::new(date.timestamp(), date.offset().local_minus_utc() / 60);
+    }
+    let seconds = chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S")
+        .map(|date| date.and_utc().timestamp())
+        .unwrap_or_default();
+    Time::new(seconds, 0)
+}
+
+// Git ne connaît que trois modes de fichier
+fn git_mode(mode: i64) -> i32 {
+    let mode = mode as u32;
+    let kind = if mode & 0o170000 == 0o120000 {
+        FileMode::Link
+    } else if mode & 0o111 != 0 {
+        FileMode::BlobExecutable
+    } else {
+        FileMode::Blob
+    };
+    kind.into()
+}
+
+struct Exporter<'a> {
+    conn: &'a Connection,
+    repo: &'a Repository,
+    trees: HashMap<String, Oid>,
+    commits: HashMap<String, Oid>,
+    written: usize,
+}
+
+impl Exporter<'_> {
+    fn tree(&mut self, hash: &str) -> Result<Oid, Error> {
+        if let Some(oid) = self.trees.get(hash) {
+            return Ok(*oid);
+        }
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, hash, mode, size FROM tree_nodes WHERE parent_tree_hash = ?")?;
+        stmt.bind((1, hash))?;
+        let mut entries = Vec::new();
+        while let Ok(State::Row) = stmt.next() {
+            entries.push((
+                stmt.read::<String, _>("name")?,
+                stmt.read::<String, _>("hash")?,
+                stmt.read::<i64, _>("mode")?,
+                stmt.read::<Option<i64>, _>("size")?,
+            ));
+        }
+
+        let mut builder = self.repo.treebuilder(None)?;
+        for (name, child, mode, size) in entries {
+            if is_directory(self.conn, &child)? {
+                let oid = self.tree(&child)?;
+                builder.insert(&name, oid, FileMode::Tree.into())?;
+                continue;
+            }
+            match get_blob_bytes_by_hash(self.conn, &child)? {
+                Some(content) => {
+                    let oid = self.repo.blob(&content)?;
+                    builder.insert(&name, oid, git_mode(mode))?;
+                }
+                // Un dossier vide n'a pas d'équivalent dans Git
+                None if size.is_none() => {}
+                None => return Err(anyhow::anyhow!("Blob {child} ({name}) is missing.")),
+            }
+        }
+        let oid = builder.write()?;
+        self.trees.insert(hash.to_string(), oid);
+        Ok(oid)
+    }
+
+    /// Écrit `head` et ses ancêtres, parents d'abord. Les commits absents
+    /// (clone superficiel) sont ignorés : leurs enfants deviennent des racines.
+    fn commit(&mut self, head: &str) -> Result<Option<Oid>, Error> {
+        let mut stack = vec![(head.to_string(), false)];
+        while let Some((hash, ready)) = stack.pop() {
+            if self.commits.contains_key(&hash) {
+                continue;
+            }
+            if !ready {
+                if let Some(oid) = mapped_oid(self.conn, &hash)?
+                    && let Ok(oid) = Oid::from_str(&oid)
+                    && self.repo.find_commit(oid).is_ok()
+                {
+                    self.commits.insert(hash, oid);
+                    continue;
+                }
+                let parents = commit_parents(self.conn, &hash)?;
+                stack.push((hash, true));
+                stack.extend(parents.into_iter().map(|parent| (parent, false)));
+                continue;
+            }
+
+            let Some(row) = read_commit(self.conn, &hash)? else {
+                continue;
+            };
+            let parents = commit_parents(self.conn, &hash)?
+                .iter()
+                .filter_map(|parent| self.commits.get(parent))
+                .map(|oid| self.repo.find_commit(*oid))
+                .collect::<Result<Vec<_>, _>>()?;
+            let tree = self.repo.find_tree(self.tree(&row.tree_hash)?)?;
+            let (name, email) = split_author(&row.author);
+            let signature = Signature::new(name, email, &git_time(&row.timestamp))?;
+            let oid = self.repo.commit(
+                None,
+                &signature,
+                &signature,
+                &row.message,
+                &tree,
+                &parents.iter().collect::<Vec<_>>(),
+            )?;
+            record_oid(self.conn, &hash, &oid.to_string())?;
+            self.commits.insert(hash, oid);
+            self.written += 1;
+        }
+        Ok(self.commits.get(head).copied())
+    }
+}
+
+/// Écrit `head` et ses ancêtres manquants dans `repo` ; renvoie l'OID de `head`
+/// et le nombre de commits écrits.
+pub(crate) fn export_history(
+    conn: &Connection,
+    repo: &Repository,
+    head: &str,
+) -> Result<(Option<Oid>, usize), Error> {
+    let mut exporter = Exporter {
+        conn,
+        repo,
+        trees: HashMap::new(),
+        commits: HashMap::new(),
+        written: 0,
+    };
+    let oid = exporter.commit(head)?;
+    Ok((oid, exporter.written))
+}
+
+// Description et date d'un tag annoté (None pour un tag léger)
+fn tag_note(conn: &Connection, name: &str) -> Result<Option<(String, String)>, Error> {
+    let mut stmt = conn.prepare("SELECT description, created_at FROM tags WHERE name = ?")?;
+    stmt.bind((1, name))?;
+    if let Ok(State::Row) = stmt.next()
+        && let Some(description) = stmt.read::<Option<String>, _>(0)?
+    {
+        return Ok(Some((description, stmt.read::<String, _>(1)?)));
+    }
+    Ok(None)
+}
+
+/// Rejoue l'historique dans le dépôt Git `target` (créé au besoin) : commits
+/// avec leurs parents, branches et tags. Les OID déjà produits sont relus dans
+/// `git_map`, un nouvel export n'écrit donc que les commits manquants.
+/// Renvoie le nombre de commits écrits.
+pub fn export_git(conn: &Connection, target: &Path) -> Result<usize, Error> {
+    let (repo, fresh) = match Repository::open(target) {
+        Ok(repo) => (repo, false),
+        Err(_) => (Repository::init(target)?, true),
+    };
+    let head_before = repo.head().ok().and_then(|h| h.target());
+    let mut exporter = Exporter {
+        conn,
+        repo: &repo,
+        trees: HashMap::new(),
+        commits: HashMap::new(),
+        written: 0,
+    };
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    let result = (|| -> Result<(usize, usize), Error> {
+        let branches = crate::transfer::local_refs(conn)?;
+        for (branch, hash) in &branches {
+            if let Some(oid) = exporter.commit(hash)? {
+                repo.reference(&format!("refs/heads/{branch}"), oid, true, "lys export")?;
+            }
+        }
+
+        let mut tags = 0;
+        for name in list_tags(conn) {
+            let Some(hash) = tag_hash(conn, &name) else {
+                continue;
+            };
+            let Some(oid) = exporter.commit(&hash)? else {
+                continue;
+            };
+            let target = repo.find_object(oid, None)?;
+            match tag_note(conn, &name)? {
+                Some((description, created_at)) => {
+                    let tagger = crate::db::config(conn, "author")?;
+                    let (tagger, email) = split_author(&tagger);
+                    let signature = Signature::new(tagger, email, &git_time(&created_at))?;
+                    repo.tag(&name, &target, &signature, &description, true)?;
+                }
+                None => {
+                    repo.tag_lightweight(&name, &target, true)?;
+                }
+            }
+            tags += 1;
+        }
+        Ok((branches.len(), tags))
+    })();
+    let (branches, tags) = match result {
+        Ok(counts) => {
+            conn.execute("COMMIT;")?;
+            counts
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK;")?;
+            return Err(e);
+        }
+    };
+
+    let current = format!("refs/heads/{}", get_current_branch(conn)?);
+    if fresh {
+        if repo.find_reference(&current).is_ok() {
+            repo.set_head(&current)?;
+            if !repo.is_bare() {
+                repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+            }
+        }
+    } else if !repo.is_bare() && repo.head().ok().and_then(|h| h.target()) != head_before {
+        ko(format!(
+            "The checked-out Git branch moved; run 'git reset --hard' in {} to update its files",
+            target.display()
+        )
+        .as_str());
+    }
+
+    ok(format!(
+        "Exported {} new commit(s), {branches} branch(es) and {tags} tag(s) to {}",
+        exporter.written,
+        target.display()
+    )
+    .as_str());
+    Ok(exporter.written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcs::{Node, flatten_tree, insert_into_tree, store_tree_recursive};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    fn lys_commit(conn: &Connection, files: &[(&str, &str)], message: &str, time: i64) {
+        let mut root = Node::Directory {
+            children: BTreeMap::new(),
+        };
+        for (path, content) in files {
+            let hash = blake3::hash(content.as_bytes()).to_hex().to_string();
+            crate::db::insert_blob_with_conn(conn, &hash, content.as_bytes()).unwrap();
+            insert_into_tree(
+                &mut root,
+                Path::new(path),
+                hash,
+                0o100644,
+                content.len() as u64,
+            );
+        }
+        let tree = store_tree_recursive(conn, "ROOT", &root).unwrap();
+        let (_, parent) = crate::vcs::get_branch_head_info(conn, "main").unwrap();
+        let parents: Vec<String> = Some(parent).into_iter().filter(|p| !p.is_empty()).collect();
+        let (id, _) = crate::vcs::commit_manual_with_parent(
+            conn,
+            message,
+            "Tester <tester@lys>",
+            time,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare("INSERT OR REPLACE INTO branches (name, head_commit_id) VALUES ('main', ?)")
+            .unwrap();
+        stmt.bind((1, id)).unwrap();
+        stmt.next().unwrap();
+    }
+
+    fn head_files(conn: &Connection) -> HashMap<PathBuf, String> {
+        let (_, head) = crate::vcs::get_branch_head_info(conn, "main").unwrap();
+        let tree = crate::db::commit_tree_hash(conn, &head).unwrap().unwrap();
+        let mut state = HashMap::new();
+        flatten_tree(conn, &tree, PathBuf::new(), &mut state).unwrap();
+        state
+            .into_iter()
+            .map(|(path, (hash, _))| (path, hash))
+            .collect()
+    }
+
+    // Lys -> Git -> Lys redonne les mêmes arbres, et un second export n'écrit rien
+    #[test]
+    fn round_trips_through_git() {
+        let origin = tempfile::tempdir().unwrap();
+        let git_dir = tempfile::tempdir().unwrap();
+        let back = tempfile::tempdir().unwrap();
+
+        let conn = crate::db::connect_lys(origin.path()).unwrap();
+        lys_commit(&conn, &[("README.md", "# demo\n")], "first", 1_700_000_000);
+        lys_commit(
+            &conn,
+            &[
+                ("README.md", "# demo\nmore\n"),
+                ("src/lib.rs", "pub fn f() {}\n"),
+            ],
+            "second",
+            1_700_000_100,
+        );
+        conn.execute(
+            "INSERT INTO tags (name, commit_id) SELECT 'v1', id FROM commits WHERE message = 'first'",
+        )
+        .unwrap();
+
+        let target = git_dir.path().join("mirror");
+        assert_eq!(export_git(&conn, &target).unwrap(), 2);
+        let repo = Repository::open(&target).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("second"));
+        assert_eq!(head.author().email(), Some("tester@lys"));
+        assert_eq!(head.parent(0).unwrap().message(), Some("first"));
+        let tag = repo
+            .revparse_single("v1")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(tag.id(), head.parent_id(0).unwrap());
+        assert_eq!(export_git(&conn, &target).unwrap(), 0);
+
+        let imported = back.path().join("imported");
+        crate::import::import_from_git(
+            target.to_str().unwrap(),
+            &imported,
+            None,
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+        let imported_conn = crate::db::connect_lys(&imported).unwrap();
+        assert_eq!(head_files(&imported_conn), head_files(&conn));
+    }
+}
@@ -0,0 +1,899 @@
+use crate::backup::BACKUP_PATH;
+use crate::crypto::{is_legacy_hash, verify_with_key};
+use crate::db::{compress, config, insert_blob_with_conn};
+use crate::remote::Remote;
+use crate::transfer::{ALLOW_UNSIGNED, SHALLOW, Spool, TreeRecord, trusted_keys};
+use crate::utils::{ko, ok};
+use crate::vcs::{compute_commit_hash, tree_matches};
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Modes des dossiers : 0o755 pour Lys, ceux de Git pour un import (hash = OID)
+const DIR_MODES: [i64; 4] = [0o755, 16384, 16400, 49152];
+
+/// Où `lys verify --repair` reprend les objets abîmés.
+pub enum Source {
+    Backup(PathBuf),
+    Remote(Remote),
+}
+
+/// Un défaut trouvé par `lys verify`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    MissingBlob {
+        hash: String,
+        name: String,
+    },
+    CorruptedBlob {
+        hash: String,
+        name: String,
+    },
+    MissingTree {
+        schema: String,
+        hash: String,
+        name: String,
+    },
+    CorruptedTree {
+        schema: String,
+        hash: String,
+    },
+    InvalidCommit(String),
+    MissingParent {
+        commit: String,
+        parent: String,
+    },
+    BadSignature(String),
+    Unsigned(String),
+    BrokenRef(String),
+    ManifestMismatch {
+        commit: String,
+        path: String,
+    },
+    BrokenSeal {
+        shard: String,
+        reason: String,
+    },
+}
+
+fn short(hash: &str) -> &str {
+    hash.get(..7).unwrap_or(hash)
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MissingBlob { hash, name } => {
+                write!(f, "MISSING: Data for '{name}' (hash: {})", short(hash))
+            }
+            Issue::CorruptedBlob { hash, name } => {
+                write!(f, "CORRUPTED: Hash mismatch for '{name}' ({})", short(hash))
+            }
+            Issue::MissingTree { hash, name, .. } => {
+                write!(f, "MISSING: Tree {} of {name}", short(hash))
+            }
+            Issue::CorruptedTree { hash, .. } => {
+                write!(
+                    f,
+                    "CORRUPTED: Tree {} does not match its entries",
+                    short(hash)
+                )
+            }
+            Issue::InvalidCommit(hash) => {
+                write!(f, "{} hash does not match its content", short(hash))
+            }
+            Issue::MissingParent { commit, parent } => {
+                write!(f, "{} has a missing parent {parent}", short(commit))
+            }
+            Issue::BadSignature(hash) => {
+                write!(f, "{} is not signed by a trusted key", short(hash))
+            }
+            Issue::Unsigned(hash) => write!(f, "{} is not signed", short(hash)),
+            Issue::BrokenRef(name) => write!(f, "{name} points to a missing commit"),
+            Issue::ManifestMismatch { commit, path } => {
+                write!(
+                    f,
+                    "Manifest of {} disagrees with its tree for '{path}'",
+                    short(commit)
+                )
+            }
+            Issue::BrokenSeal { shard, reason } => write!(f, "SEALED: {shard}: {reason}"),
+        }
+    }
+}
+
+/// Bilan de `lys verify` : les défauts restants après une éventuelle réparation.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+    pub commits: usize,
+    pub trees: usize,
+    pub blobs: usize,
+    pub repaired: usize,
+    pub quarantined: usize,
+    // Objets que plus rien ne référence : signalés, pas des erreurs
+    pub dangling_commits: usize,
+    pub dangling_trees: usize,
+    pub dangling_blobs: usize,
+}
+
+struct Child {
+    name: String,
+    hash: String,
+    mode: i64,
+}
+
+struct CommitRow {
+    schema: String,
+    hash: String,
+    tree: String,
+    author: String,
+    message: String,
+    timestamp: String,
+    signature: Option<String>,
+    parents: Vec<String>,
+}
+
+// hash d'un dossier -> (base de saison qui le contient, enfants)
+type Trees = HashMap<String, (String, Vec<Child>)>;
+
+fn digest(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+fn load_trees(conn: &Connection, schemas: &[String]) -> Result<Trees, Error> {
+    let mut trees = Trees::new();
+    for schema in schemas {
+        let mut stmt = conn.prepare(format!(
+            "SELECT parent_tree_hash, name, hash, mode FROM {schema}.tree_nodes"
+        ))?;
+        while let Ok(State::Row) = stmt.next() {
+            let parent: String = stmt.read(0)?;
+            let entry = trees
+                .entry(parent)
+                .or_insert_with(|| (schema.clone(), Vec::new()));
+            // Un arbre présent dans deux saisons n'est lu que dans la première
+            if entry.0 == *schema {
+                entry.1.push(Child {
+                    name: stmt.read(1)?,
+                    hash: stmt.read(2)?,
+                    mode: stmt.read::<Option<i64>, _>(3)?.unwrap_or(0),
+                });
+            }
+        }
+    }
+    Ok(trees)
+}
+
+fn load_commits(conn: &Connection, schemas: &[String]) -> Result<Vec<CommitRow>, Error> {
+    let mut commits = Vec::new();
+    let mut seen = HashSet::new();
+    for schema in schemas {
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        // Une vieille saison peut ne pas avoir commit_parents : on lit alors parent_hash
+        if let Ok(mut stmt) = conn.prepare(format!(
+            "SELECT commit_hash, parent_hash FROM {schema}.commit_parents ORDER BY commit_hash, position"
+        )) {
+            while let Ok(State::Row) = stmt.next() {
+                parents
+                    .entry(stmt.read(0)?)
+                    .or_default()
+                    .push(stmt.read(1)?);
+            }
+        }
+        let mut stmt = conn.prepare(format!(
+            "SELECT hash, parent_hash, tree_hash, author, message, timestamp, signature
+             FROM {schema}.commits ORDER BY id"
+        ))?;
+        while let Ok(State::Row) = stmt.next() {
+            let hash: String = stmt.read("hash")?;
+            if !seen.insert(hash.clone()) {
+                continue;
+            }
+            let legacy_parent = stmt
+                .read::<Option<String>, _>("parent_hash")?
+                .filter(|parent| !parent.is_empty());
+            commits.push(CommitRow {
+                schema: schema.clone(),
+                parents: parents
+                    .remove(&hash)
+                    .unwrap_or_else(|| legacy_parent.into_iter().collect()),
+                hash,
+                tree: stmt.read("tree_hash")?,
+                author: stmt.read("author")?,
+                message: stmt.read("message")?,
+                timestamp: stmt.read("timestamp")?,
+                signature: stmt
+                    .read::<Option<String>, _>("signature")?
+                    .filter(|signature| !signature.is_empty()),
+            });
+        }
+    }
+    Ok(commits)
+}
+
+// Hash atteint en descendant `path` depuis l'arbre `root`
+fn resolve<'a>(trees: &'a Trees, root: &'a str, path: &str) -> Option<&'a str> {
+    let mut hash = root;
+    for part in path.split('/') {
+        let (_, children) = trees.get(hash)?;
+        hash = children
+            .iter()
+            .find(|child| child.name == part)?
+            .hash
+            .as_str();
+    }
+    Some(hash)
+}
+
+// Hashes d'une colonne, en ignorant une table absente d'une vieille saison
+fn column(conn: &Connection, query: &str) -> Vec<String> {
+    let Ok(mut stmt) = conn.prepare(query) else {
+        return Vec::new();
+    };
+    let mut values = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        if let Ok(Some(value)) = stmt.read::<Option<String>, _>(0) {
+            values.push(value);
+        }
+    }
+    values
+}
+
+// Passe de vérification, sans rien afficher ni modifier
+fn check(conn: &Connection, root: &Path, deep: bool) -> Result<Report, Error> {
+    let schemas = crate::history::schemas(conn);
+    let trees = load_trees(conn, &schemas)?;
+    let commits = load_commits(conn, &schemas)?;
+    let mut issues = Vec::new();
+
+    // 1. Arbres : un hash Lys se recalcule depuis les enfants (un OID Git, non)
+    let mut files: BTreeMap<&str, &str> = BTreeMap::new();
+    let empty_tree = blake3::Hasher::new().finalize().to_hex().to_string();
+    for (hash, (schema, children)) in &trees {
+        let entries = children
+            .iter()
+            .map(|child| (child.name.as_str(), child.hash.as_str(), child.mode));
+        if hash.len() == 64 && !tree_matches(hash, entries) {
+            issues.push(Issue::CorruptedTree {
+                schema: schema.clone(),
+                hash: hash.clone(),
+            });
+        }
+        for child in children {
+            // Un dossier vide n'a pas d'enfants, donc pas de ligne à lui
+            if trees.contains_key(&child.hash) || child.hash == empty_tree {
+                continue;
+            }
+            if DIR_MODES.contains(&child.mode) {
+                issues.push(Issue::MissingTree {
+                    schema: schema.clone(),
+                    hash: child.hash.clone(),
+                    name: format!("directory '{}'", child.name),
+                });
+            } else {
+                files.entry(&child.hash).or_insert(&child.name);
+            }
+        }
+    }
+
+    // 2. Blobs : présents dans le store, morceaux compris s'ils sont découpés,
+    //    et intacts en mode --deep
+    for (hash, name) in &files {
+        let mut stmt = conn.prepare(
+            "SELECT EXISTS (SELECT 1 FROM store.blob_chunks bc
+                            WHERE bc.blob_hash = b.hash AND b.content IS NULL
+                              AND bc.chunk_hash NOT IN (SELECT hash FROM store.chunks))
+             FROM store.blobs b WHERE b.hash = ?",
+        )?;
+        stmt.bind((1, *hash))?;
+        let (hash, name) = (hash.to_string(), name.to_string());
+        if !matches!(stmt.next(), Ok(State::Row)) {
+            issues.push(Issue::MissingBlob { hash, name });
+        } else if stmt.read::<i64, _>(0)? == 1 || (deep && !intact(conn, &hash)?) {
+            issues.push(Issue::CorruptedBlob { hash, name });
+        }
+    }
+
+    // 3. Commits : hash, parents, arbre et signature
+    let known: HashMap<&str, &CommitRow> = commits.iter().map(|c| (c.hash.as_str(), c)).collect();
+    let keys = trusted_keys(conn, root, None);
+    let shallow = config(conn, SHALLOW).unwrap_or_default();
+    let empty_tree = blake3::Hasher::new().finalize().to_hex().to_string();
+    let grafts = crate::prune::grafts(conn, &keys);
+    // Un historique importé de Git n'a pas de signatures : toléré seulement ainsi
+    let allow_unsigned = config(conn, ALLOW_UNSIGNED).unwrap_or_default() == "true";
+    for c in &commits {
+        let hashed = crate::prune::hashed_parents(&grafts, &c.hash, &c.parents);
+        let expected = compute_commit_hash(hashed, &c.tree, &c.author, &c.message, &c.timestamp);
+        if expected != c.hash
+            && !is_legacy_hash(
+                &c.hash,
+                hashed,
+                &c.tree,
+                &c.author,
+                &c.message,
+                &c.timestamp,
+            )
+        {
+            issues.push(Issue::InvalidCommit(c.hash.clone()));
+        }
+        let cut = shallow.split_whitespace().any(|hash| hash == c.hash);
+        for parent in &c.parents {
+            // Un parent absent des saisons attachées peut vivre dans une plus ancienne
+            if !cut
+                && !known.contains_key(parent.as_str())
+                && crate::history::find(conn, parent).is_none()
+            {
+                issues.push(Issue::MissingParent {
+                    commit: c.hash.clone(),
+                    parent: parent.clone(),
+                });
+            }
+        }
+        if c.tree != empty_tree && !trees.contains_key(&c.tree) {
+            issues.push(Issue::MissingTree {
+                schema: c.schema.clone(),
+                hash: c.tree.clone(),
+                name: format!("commit {}", short(&c.hash)),
+            });
+        }
+        match &c.signature {
+            Some(signature)
+                if !keys
+                    .iter()
+                    .any(|key| verify_with_key(key, &c.hash, signature)) =>
+            {
+                issues.push(Issue::BadSignature(c.hash.clone()));
+            }
+            None if !allow_unsigned => issues.push(Issue::Unsigned(c.hash.clone())),
+            _ => {}
+        }
+    }
+
+    // 4. Refs : branches et tags de chaque saison, remotes et stashes comme racines
+    let mut roots = Vec::new();
+    for schema in &schemas {
+        let prefix = if schema == "main" {
+            String::new()
+        } else {
+            format!("{schema}:")
+        };
+        for (kind, table, column) in [
+            ("branch", "branches", "head_commit_id"),
+            ("tag", "tags", "commit_id"),
+        ] {
+            let Ok(mut stmt) = conn.prepare(format!(
+                "SELECT r.name, c.hash FROM {schema}.{table} r
+                 LEFT JOIN {schema}.commits c ON c.id = r.{column}"
+            )) else {
+                continue;
+            };
+            while let Ok(State::Row) = stmt.next() {
+                let name: String = stmt.read(0)?;
+                match stmt.read::<Option<String>, _>(1)? {
+                    Some(hash) => roots.push(hash),
+                    None => issues.push(Issue::BrokenRef(format!("{kind} {prefix}{name}"))),
+                }
+            }
+        }
+    }
+    roots.extend(column(conn, "SELECT hash FROM remote_refs"));
+    roots.extend(column(conn, "SELECT base_commit FROM stashes"));
+
+    // 5. Manifest : chaque ligne doit désigner le blob que l'arbre du commit contient
+    for schema in &schemas {
+        let Ok(mut stmt) = conn.prepare(format!(
+            "SELECT c.hash, c.tree_hash, m.file_path, b.hash FROM {schema}.manifest m
+             JOIN {schema}.commits c ON c.id = m.commit_id
+             LEFT JOIN store.blobs b ON b.id = m.blob_id"
+        )) else {
+            continue;
+        };
+        while let Ok(State::Row) = stmt.next() {
+            let (commit, tree, path): (String, String, String) =
+                (stmt.read(0)?, stmt.read(1)?, stmt.read(2)?);
+            let blob = stmt.read::<Option<String>, _>(3)?;
+            if blob.is_none() || resolve(&trees, &tree, &path) != blob.as_deref() {
+                issues.push(Issue::ManifestMismatch { commit, path });
+            }
+        }
+    }
+
+    // 6. Saisons scellées : la base doit être celle du manifeste signé
+    for (shard, reason) in crate::archive::check(&root.join(".lys/db"), &keys) {
+        issues.push(Issue::BrokenSeal { shard, reason });
+    }
+
+    // 7. Objets pendants : commits hors de toute ref, arbres hors de tout commit,
+    //    blobs hors de tout arbre
+    let mut reached = HashSet::new();
+    while let Some(hash) = roots.pop() {
+        if let Some(commit) = known.get(hash.as_str())
+            && reached.insert(commit.hash.as_str())
+        {
+            roots.extend(commit.parents.iter().cloned());
+        }
+    }
+
+    let mut pending: Vec<String> = commits.iter().map(|c| c.tree.clone()).collect();
+    pending.extend(column(conn, "SELECT tree_hash FROM stashes"));
+    let mut live = HashSet::new();
+    while let Some(hash) = pending.pop() {
+        if let Some((_, children)) = trees.get(&hash)
+            && live.insert(hash)
+        {
+            pending.extend(children.iter().map(|child| child.hash.clone()));
+        }
+    }
+    let dangling_blobs = column(conn, "SELECT hash FROM store.blobs")
+        .iter()
+        .filter(|hash| !files.contains_key(hash.as_str()))
+        .count();
+    Ok(Report {
+        issues,
+        commits: commits.len(),
+        trees: trees.len(),
+        blobs: files.len(),
+        dangling_commits: commits.len() - reached.len(),
+        dangling_trees: trees.len() - live.len(),
+        dangling_blobs,
+        ..Report::default()
+    })
+}
+
+// Objets de remplacement, chacun revérifié avant usage : hash du contenu pour
+// un blob, hash recalculé pour un arbre Lys
+enum Pool {
+    Backup {
+        dest: PathBuf,
+        seasons: Vec<Connection>,
+        _copies: tempfile::TempDir,
+    },
+    Remote {
+        blobs: Spool,
+        trees: HashMap<String, Vec<TreeRecord>>,
+    },
+}
+
+impl Pool {
+    fn open(conn: &Connection, root: &Path, source: &Source) -> Result<Self, Error> {
+        match source {
+            Source::Backup(dest) => {
+                let keys = trusted_keys(conn, root, None);
+                let generation = crate::backup::load(dest, None, &keys)?;
+                // Les bases sauvegardées sont lues depuis une copie, jamais en place
+                let copies = tempfile::tempdir()?;
+                let mut seasons = Vec::new();
+                for (index, file) in generation.files.iter().enumerate() {
+                    if !file.path.ends_with(".db") {
+                        continue;
+                    }
+                    if let Some(content) = crate::backup::read_file(dest, file) {
+                        let path = copies.path().join(format!("{index}.db"));
+                        std::fs::write(&path, content)?;
+                        seasons.push(Connection::open(&path)?);
+                    }
+                }
+                ok(format!(
+                    "Repairing from backup generation {} in {}",
+                    generation.generation,
+                    dest.display()
+                )
+                .as_str());
+                Ok(Pool::Backup {
+                    dest: dest.clone(),
+                    seasons,
+                    _copies: copies,
+                })
+            }
+            Source::Remote(remote) => {
+                ok(format!("Repairing from remote '{}'", remote.name).as_str());
+                let (batch, blobs) = crate::transfer::fetch_all(remote)?;
+                let mut trees: HashMap<String, Vec<TreeRecord>> = HashMap::new();
+                for node in batch.tree_nodes {
+                    trees
+                        .entry(node.parent_tree_hash.clone())
+                        .or_default()
+                        .push(node);
+                }
+                Ok(Pool::Remote { blobs, trees })
+            }
+        }
+    }
+
+    fn blob(&self, hash: &str) -> Option<Vec<u8>> {
+        let content = match self {
+            Pool::Backup { dest, .. } => crate::backup::read_blob(dest, hash)?,
+            Pool::Remote { blobs, .. } => blobs.blob(hash)?,
+        };
+        (digest(&content) == hash).then_some(content)
+    }
+
+    fn tree(&self, hash: &str) -> Option<Vec<TreeRecord>> {
+        let rows = match self {
+            Pool::Backup { seasons, .. } => seasons.iter().find_map(|season| {
+                tree_rows(season, "main", hash)
+                    .ok()
+                    .filter(|rows| !rows.is_empty())
+            })?,
+            Pool::Remote { trees, .. } => trees.get(hash)?.clone(),
+        };
+        let entries = rows
+            .iter()
+            .map(|row| (row.name.as_str(), row.hash.as_str(), row.mode));
+        (hash.len() != 64 || tree_matches(hash, entries)).then_some(rows)
+    }
+}
+
+fn tree_rows(conn: &Connection, schema: &str, hash: &str) -> Result<Vec<TreeRecord>, Error> {
+    let mut stmt = conn.prepare(format!(
+        "SELECT name, hash, mode, size FROM {schema}.tree_nodes WHERE parent_tree_hash = ?"
+    ))?;
+    stmt.bind((1, hash))?;
+    let mut rows = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        rows.push(TreeRecord {
+            parent_tree_hash: hash.to_string(),
+            name: stmt.read("name")?,
+            hash: stmt.read("hash")?,
+            mode: stmt.read::<Option<i64>, _>("mode")?.unwrap_or(0),
+            size: stmt.read::<Option<i64>, _>("size")?.unwrap_or(0),
+        });
+    }
+    Ok(rows)
+}
+
+fn replace_tree(
+    conn: &Connection,
+    schema: &str,
+    hash: &str,
+    rows: &[TreeRecord],
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare(format!(
+        "DELETE FROM {schema}.tree_nodes WHERE parent_tree_hash = ?"
+    ))?;
+    stmt.bind((1, hash))?;
+    stmt.next()?;
+    for row in rows {
+        let mut stmt = conn.prepare(format!(
+            "INSERT INTO {schema}.tree_nodes (parent_tree_hash, name, hash, mode, size)
+             VALUES (?, ?, ?, ?, ?)"
+        ))?;
+        stmt.bind((1, hash))?;
+        stmt.bind((2, row.name.as_str()))?;
+        stmt.bind((3, row.hash.as_str()))?;
+        stmt.bind((4, row.mode))?;
+        stmt.bind((5, row.size))?;
+        stmt.next()?;
+    }
+    Ok(())
+}
+
+// Le blob relu (en flux, morceau par morceau s'il est découpé) a-t-il bien son hash ?
+fn intact(conn: &Connection, hash: &str) -> Result<bool, Error> {
+    let Some(mut blob) = crate::chunks::open(conn, hash)? else {
+        return Ok(false);
+    };
+    let mut hasher = blake3::Hasher::new();
+    Ok(hasher.update_reader(&mut blob).is_ok() && hasher.finalize().to_hex().to_string() == hash)
+}
+
+// Contenu tel que stocké ; un blob découpé est représenté par sa liste de morceaux
+fn stored_blob(conn: &Connection, hash: &str) -> Result<Option<Vec<u8>>, Error> {
+    let mut stmt = conn.prepare("SELECT content FROM store.blobs WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        match stmt.read::<Option<Vec<u8>>, _>(0)? {
+            Some(content) => Ok(Some(content)),
+            None => Ok(Some(
+                crate::chunks::chunk_list(conn, hash)?
+                    .join("\n")
+                    .into_bytes(),
+            )),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+// Un blob abîmé est réécrit sur place, entier (sa liste de morceaux est ensuite libérée) : il garde l'id que référence le manifest
+fn restore_blob(conn: &Connection, hash: &str, content: &[u8]) -> Result<(), Error> {
+    if stored_blob(conn, hash)?.is_none() {
+        insert_blob_with_conn(conn, hash, content)?;
+        return Ok(());
+    }
+    let mut stmt = conn.prepare("UPDATE store.blobs SET content = ?, size = ? WHERE hash = ?")?;
+    stmt.bind((1, &compress(content)[..]))?;
+    stmt.bind((2, content.len() as i64))?;
+    stmt.bind((3, hash))?;
+    stmt.next()?;
+    Ok(())
+}
+
+fn quarantine(
+    conn: &Connection,
+    kind: &str,
+    hash: &str,
+    content: &[u8],
+    reason: &str,
+) -> Result<(), Error> {
+    let mut stmt =
+        conn.prepare("INSERT INTO quarantine (kind, hash, content, reason) VALUES (?, ?, ?, ?)")?;
+    stmt.bind((1, kind))?;
+    stmt.bind((2, hash))?;
+    stmt.bind((3, content))?;
+    stmt.bind((4, reason))?;
+    stmt.next()?;
+    ko(format!("QUARANTINED: {kind} {} ({reason})", short(hash)).as_str());
+    Ok(())
+}
+
+// Un arbre manquant revient avec ses sous-arbres et les blobs absents du store
+fn restore_tree(conn: &Connection, pool: &Pool, schema: &str, hash: &str) -> Result<usize, Error> {
+    let mut restored = 0;
+    let mut pending = vec![hash.to_string()];
+    while let Some(hash) = pending.pop() {
+        if !tree_rows(conn, schema, &hash)?.is_empty() {
+            continue;
+        }
+        let Some(rows) = pool.tree(&hash) else {
+            continue;
+        };
+        replace_tree(conn, schema, &hash, &rows)?;
+        restored += 1;
+        for row in rows {
+            if DIR_MODES.contains(&row.mode) {
+                pending.push(row.hash);
+            } else if stored_blob(conn, &row.hash)?.is_none()
+                && let Some(content) = pool.blob(&row.hash)
+            {
+                insert_blob_with_conn(conn, &row.hash, &content)?;
+                restored += 1;
+            }
+        }
+    }
+    Ok(restored)
+}
+
+// Renvoie (objets réparés, objets mis en quarantaine)
+fn repair(conn: &Connection, pool: &Pool, issues: &[Issue]) -> Result<(usize, usize), Error> {
+    let (mut repaired, mut quarantined) = (0, 0);
+    // Les arbres d'abord : un arbre réparé ne référence plus les blobs d'un arbre altéré
+    for issue in issues {
+        match issue {
+            Issue::CorruptedTree { schema, hash } => {
+                if let Some(rows) = pool.tree(hash) {
+                    replace_tree(conn, schema, hash, &rows)?;
+                    repaired += 1;
+                    ok(format!("REPAIRED: tree {}", short(hash)).as_str());
+                } else {
+                    let found = serde_json::to_vec(&tree_rows(conn, schema, hash)?)?;
+                    quarantine(conn, "tree", hash, &found, "entries do not match the hash")?;
+                    replace_tree(conn, schema, hash, &[])?;
+                    quarantined += 1;
+                }
+            }
+            Issue::MissingTree { schema, hash, .. } => {
+                let restored = restore_tree(conn, pool, schema, hash)?;
+                if restored > 0 {
+                    repaired += restored;
+                    ok(format!(
+                        "REPAIRED: tree {} and {} object(s)",
+                        short(hash),
+                        restored - 1
+                    )
+                    .as_str());
+                }
+            }
+            _ => {}
+        }
+    }
+    for issue in issues {
+        let (Issue::MissingBlob { hash, name } | Issue::CorruptedBlob { hash, name }) = issue
+        else {
+            continue;
+        };
+        if let Some(content) = pool.blob(hash) {
+            restore_blob(conn, hash, &content)?;
+            repaired += 1;
+            ok(format!("REPAIRED: '{name}' restored").as_str());
+        } else if let Some(found) = stored_blob(conn, hash)?
+            && matches!(issue, Issue::CorruptedBlob { .. })
+        {
+            quarantine(
+                conn,
+                "blob",
+                hash,
+                &found,
+                "content does not match the hash",
+            )?;
+            let mut stmt = conn.prepare("DELETE FROM store.blobs WHERE hash = ?")?;
+            stmt.bind((1, hash.as_str()))?;
+            stmt.next()?;
+            quarantined += 1;
+        }
+    }
+    crate::chunks::collect(conn)?;
+    Ok((repaired, quarantined))
+}
+
+/// Source de `--repair` : une sauvegarde (dossier contenant `generations/`) ou
+/// un remote nommé. Par défaut la dernière sauvegarde, sinon `origin`.
+pub fn source(conn: &Connection, name: Option<&str>) -> Result<Source, Error> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => Some(config(conn, BACKUP_PATH)?)
+            .filter(|saved| !saved.is_empty())
+            .unwrap_or_else(|| "origin".to_string()),
+    };
+    if Path::new(&name).join("generations").is_dir() {
+        return Ok(Source::Backup(PathBuf::from(name)));
+    }
+    match crate::remote::find(conn, &name)? {
+        Some(remote) => Ok(Source::Remote(remote)),
+        None => Err(anyhow::anyhow!(
+            "'{name}' is neither a backup directory nor a remote."
+        )),
+    }
+}
+
+/// `lys verify [--deep] [--repair [<source>]]` : arbres recalculés, commits
+/// reliés à leurs arbres et parents, signatures, refs, manifest, dans toutes
+/// les saisons attachées, et sceaux des saisons archivées. Les objets abîmés sont repris de `source`, ceux
+/// qu'elle n'a pas sont mis en quarantaine.
+pub fn run(
+    conn: &Connection,
+    root: &Path,
+    deep: bool,
+    source: Option<&Source>,
+) -> Result<Report, Error> {
+    ok("Starting repository integrity verification...");
+    if deep {
+        ok("Deep mode enabled: Recalculating all checksums...");
+    }
+    let mut report = check(conn, root, deep)?;
+    if let Some(source) = source
+        && !report.issues.is_empty()
+    {
+        let pool = Pool::open(conn, root, source)?;
+        conn.execute("BEGIN TRANSACTION;")?;
+        let outcome = repair(conn, &pool, &report.issues);
+        conn.execute(if outcome.is_ok() {
+            "COMMIT;"
+        } else {
+            "ROLLBACK;"
+        })?;
+        let (repaired, quarantined) = outcome?;
+        report = Report {
+            repaired,
+            quarantined,
+            ..check(conn, root, deep)?
+        };
+    }
+
+    for issue in &report.issues {
+        ko(issue.to_string().as_str());
+    }
+    let objects = format!(
+        "{} commit(s), {} tree(s), {} blob(s)",
+        report.commits, report.trees, report.blobs
+    );
+    if report.issues.is_empty() {
+        ok(format!("Verification success! {objects} are intact.").as_str());
+    } else {
+        ko(format!(
+            "Integrity report: {} problem(s) in {objects}",
+            report.issues.len()
+        )
+        .as_str());
+    }
+    if report.repaired > 0 {
+        ok(format!("{} object(s) repaired.", report.repaired).as_str());
+    }
+    if report.quarantined > 0 {
+        ko(format!("{} object(s) moved to quarantine.", report.quarantined).as_str());
+    }
+    if report.dangling_commits + report.dangling_trees + report.dangling_blobs > 0 {
+        ok(format!(
+            "Unreachable: {} commit(s), {} tree(s), {} blob(s)",
+            report.dangling_commits, report.dangling_trees, report.dangling_blobs
+        )
+        .as_str());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Blob et arbre altérés repris d'une sauvegarde, puis un blob irrécupérable
+    #[test]
+    fn verifies_repairs_and_quarantines() {
+        let (repo, conn) = crate::utils::test_repo();
+        let media = tempfile::tempdir().unwrap();
+        let backup = Source::Backup(media.path().to_path_buf());
+
+        std::fs::create_dir("dir").unwrap();
+        std::fs::write("a.txt", "one\n").unwrap();
+        std::fs::write("dir/b.txt", "two\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        crate::backup::backup(&conn, repo.path(), media.path()).unwrap();
+        let clean = run(&conn, repo.path(), true, None).unwrap();
+
+        let a = digest(b"one\n");
+        let forged = digest(b"forged\n");
+        let mut stmt = conn
+            .prepare("UPDATE store.blobs SET content = ? WHERE hash = ?")
+            .unwrap();
+        stmt.bind((1, &compress(b"evil\n")[..])).unwrap();
+        stmt.bind((2, a.as_str())).unwrap();
+        stmt.next().unwrap();
+        conn.execute(format!(
+            "UPDATE tree_nodes SET hash = '{forged}' WHERE name = 'b.txt'"
+        ))
+        .unwrap();
+        let damaged = run(&conn, repo.path(), true, None).unwrap();
+        let repaired = run(&conn, repo.path(), true, Some(&backup)).unwrap();
+
+        // c.txt n'est pas dans la sauvegarde : son blob altéré part en quarantaine
+        std::fs::write("c.txt", "three\n").unwrap();
+        crate::vcs::commit(&conn, "second", AUTHOR).unwrap();
+        let c = digest(b"three\n");
+        conn.execute(format!(
+            "UPDATE store.blobs SET content = x'00' WHERE hash = '{c}'"
+        ))
+        .unwrap();
+        let lost = run(&conn, repo.path(), true, Some(&backup)).unwrap();
+        let held = column(
+            &conn,
+            &format!("SELECT kind FROM quarantine WHERE hash = '{c}'"),
+        );
+        let restored = crate::vcs::get_blob_bytes_by_hash(&conn, &a).unwrap();
+
+        assert!(clean.issues.is_empty());
+        assert_eq!((clean.commits, clean.blobs), (1, 2));
+        assert!(damaged.issues.contains(&Issue::CorruptedBlob {
+            hash: a.clone(),
+            name: "a.txt".to_string()
+        }));
+        assert!(
+            damaged
+                .issues
+                .iter()
+                .any(|issue| matches!(issue, Issue::CorruptedTree { .. }))
+        );
+        assert!(repaired.issues.is_empty());
+        assert_eq!(repaired.repaired, 2);
+        assert_eq!(restored.as_deref(), Some(&b"one\n"[..]));
+        assert_eq!(lost.quarantined, 1);
+        assert_eq!(held, vec!["blob".to_string()]);
+        assert!(lost.issues.contains(&Issue::MissingBlob {
+            hash: c,
+            name: "c.txt".to_string()
+        }));
+    }
+
+    // Un commit sans signature est un défaut, sauf si le dépôt les tolère
+    #[test]
+    fn reports_unsigned_commits() {
+        let (repo, conn) = crate::utils::test_repo();
+        std::fs::write("a.txt", "one\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        let (_, head) = crate::vcs::get_branch_head_info(&conn, "main").unwrap();
+        let signed = run(&conn, repo.path(), false, None).unwrap();
+
+        conn.execute("UPDATE commits SET signature = NULL").unwrap();
+        let unsigned = run(&conn, repo.path(), false, None).unwrap();
+        crate::db::write_config(&conn, ALLOW_UNSIGNED, "true").unwrap();
+        let allowed = run(&conn, repo.path(), false, None).unwrap();
+
+        assert!(signed.issues.is_empty(), "{:?}", signed.issues);
+        assert_eq!(unsigned.issues, vec![Issue::Unsigned(head)]);
+        assert!(allowed.issues.is_empty(), "{:?}", allowed.issues);
+    }
+}
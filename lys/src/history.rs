@@ -0,0 +1,504 @@
+use sqlite::{Connection, Error, State};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// SQLITE_MAX_ATTACHED : au-delà, on détache une saison chargée à la demande
+const MAX_ATTACHED: usize = 10;
+// Préfixe des bases attachées à la demande (jamais `store` ni `old`)
+const PREFIX: &str = "season_";
+
+/// Un commit et la base de saison qui le contient (`main`, `old` ou `season_*`).
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub schema: String,
+    pub id: i64,
+    pub hash: String,
+    pub parents: Vec<String>,
+    pub tree: String,
+    pub author: String,
+    pub message: String,
+    pub timestamp: String,
+}
+
+fn error(message: String) -> Error {
+    Error {
+        code: Some(1),
+        message: Some(message),
+    }
+}
+
+// (nom, fichier) des bases attachées
+fn attached(conn: &Connection) -> Result<Vec<(String, String)>, Error> {
+    let mut stmt = conn.prepare("PRAGMA database_list")?;
+    let mut list = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        list.push((
+            stmt.read::<String, _>("name")?,
+            stmt.read::<Option<String>, _>("file")?.unwrap_or_default(),
+        ));
+    }
+    Ok(list)
+}
+
+// `.lys/db`, retrouvé depuis le fichier du store attaché
+fn db_dir(conn: &Connection) -> Option<PathBuf> {
+    let (_, file) = attached(conn)
+        .ok()?
+        .into_iter()
+        .find(|(name, _)| name == "store")?;
+    Some(Path::new(&file).parent()?.to_path_buf())
+}
+
+fn main_file(conn: &Connection) -> Option<PathBuf> {
+    let (_, file) = attached(conn)
+        .ok()?
+        .into_iter()
+        .find(|(name, _)| name == "main")?;
+    Path::new(&file).canonicalize().ok()
+}
+
+/// Bases de saison de `.lys/db` (chemins relatifs), la plus récemment écrite d'abord.
+pub fn shards(db_dir: &Path) -> Vec<String> {
+    let pattern = format!("{}/**/*.db", db_dir.display());
+    let mut found: Vec<PathBuf> = glob::glob(&pattern)
+        .map(|paths| paths.filter_map(|path| path.ok()).collect())
+        .unwrap_or_default();
+    found.retain(|path| !path.ends_with("store.db"));
+    found.sort_by_key(|path| {
+        std::cmp::Reverse(path.metadata().and_then(|meta| meta.modified()).ok())
+    });
+    found
+        .iter()
+        .filter_map(|path| {
+            Some(
+                path.strip_prefix(db_dir)
+                    .ok()?
+                    .to_string_lossy()
+                    .to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Nom sous lequel la saison `shard` est attachée, en l'attachant au besoin.
+/// La saison courante est `main`, la précédente souvent déjà attachée en `old`.
+pub fn attach(conn: &Connection, shard: &str) -> Result<String, Error> {
+    let dir = db_dir(conn).ok_or_else(|| error("store is not attached".to_string()))?;
+    let path = dir.join(shard);
+    let target = path
+        .canonicalize()
+        .map_err(|e| error(format!("{}: {e}", path.display())))?;
+    let list = attached(conn)?;
+    if let Some((name, _)) = list
+        .iter()
+        .find(|(_, file)| Path::new(file).canonicalize().ok().as_ref() == Some(&target))
+    {
+        return Ok(name.clone());
+    }
+    let extra = list
+        .iter()
+        .filter(|(name, _)| name != "main" && name != "temp")
+        .count();
+    if extra >= MAX_ATTACHED
+        && let Some((name, _)) = list.iter().find(|(name, _)| name.starts_with(PREFIX))
+    {
+        conn.execute(format!("DETACH DATABASE {name};"))?;
+    }
+    let name: String = format!("{PREFIX}{}", shard.trim_end_matches(".db"))
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    // Le nom du schéma n'est fait que de [A-Za-z0-9_] ; le chemin est lié
+    let mut attach = conn.prepare(format!("ATTACH DATABASE ? AS {name};"))?;
+    attach.bind((1, path.to_string_lossy().as_ref()))?;
+    attach.next()?;
+    Ok(name)
+}
+
+/// Appelle `f` sur la saison courante (`main`) puis sur chaque autre saison,
+/// la plus récente d'abord, en l'attachant au besoin.
+pub fn each_season<F>(conn: &Connection, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(&str) -> Result<(), Error>,
+{
+    f("main")?;
+    let Some(dir) = db_dir(conn) else {
+        return Ok(());
+    };
+    let current = main_file(conn);
+    for shard in shards(&dir) {
+        if dir.join(&shard).canonicalize().ok() == current {
+            continue;
+        }
+        // Une saison illisible ou sans commits ne masque pas les autres
+        let Ok(schema) = attach(conn, &shard) else {
+            continue;
+        };
+        if conn
+            .prepare(format!("SELECT 1 FROM {schema}.commits LIMIT 1"))
+            .is_ok()
+        {
+            f(&schema)?;
+        }
+    }
+    Ok(())
+}
+
+/// Chemin, relatif à `.lys/db`, de la saison attachée sous le nom `schema`.
+pub fn shard_name(conn: &Connection, schema: &str) -> Option<String> {
+    let dir = db_dir(conn)?.canonicalize().ok()?;
+    let (_, file) = attached(conn)
+        .ok()?
+        .into_iter()
+        .find(|(name, _)| name == schema)?;
+    let file = Path::new(&file).canonicalize().ok()?;
+    Some(file.strip_prefix(dir).ok()?.to_string_lossy().to_string())
+}
+
+/// Bases de saison actuellement attachées, la principale d'abord.
+pub fn schemas(conn: &Connection) -> Vec<String> {
+    attached(conn)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| {
+            name != "temp"
+                && conn
+                    .prepare(format!("SELECT 1 FROM {name}.commits LIMIT 1"))
+                    .is_ok()
+        })
+        .collect()
+}
+
+/// Met à jour le catalogue `store.history` (hash -> saison) pour les saisons
+/// écrites depuis leur dernière indexation. La saison courante n'y figure
+/// pas : elle est toujours lue directement.
+pub fn index(conn: &Connection) -> Result<usize, Error> {
+    let Some(dir) = db_dir(conn) else {
+        return Ok(0);
+    };
+    let current = main_file(conn);
+    let mut indexed = 0;
+    for shard in shards(&dir) {
+        let path = dir.join(&shard);
+        let Ok(meta) = path.metadata() else {
+            continue;
+        };
+        if path.canonicalize().ok() == current {
+            continue;
+        }
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_nanos())
+            .unwrap_or(0);
+        let stamp = format!("{}:{modified}", meta.len());
+        let mut stmt = conn.prepare("SELECT stamp FROM store.shards WHERE path = ?")?;
+        stmt.bind((1, shard.as_str()))?;
+        if let Ok(State::Row) = stmt.next()
+            && stmt.read::<String, _>(0)? == stamp
+        {
+            continue;
+        }
+
+        let schema = attach(conn, &shard)?;
+        let mut stmt = conn.prepare("DELETE FROM store.history WHERE shard = ?")?;
+        stmt.bind((1, shard.as_str()))?;
+        stmt.next()?;
+        // Une base sans table commits (abîmée, vide) est seulement notée
+        if let Ok(mut stmt) = conn.prepare(format!(
+            "INSERT OR IGNORE INTO store.history (hash, shard) SELECT hash, ? FROM {schema}.commits"
+        )) {
+            stmt.bind((1, shard.as_str()))?;
+            stmt.next()?;
+        }
+        let mut stmt = conn.prepare(
+            "INSERT INTO store.shards (path, stamp) VALUES (?, ?)
+             ON CONFLICT(path) DO UPDATE SET stamp = excluded.stamp",
+        )?;
+        stmt.bind((1, shard.as_str()))?;
+        stmt.bind((2, stamp.as_str()))?;
+        stmt.next()?;
+        if schema.starts_with(PREFIX) {
+            conn.execute(format!("DETACH DATABASE {schema};"))?;
+        }
+        indexed += 1;
+    }
+    Ok(indexed)
+}
+
+/// Commits dont le hash est `prefix` ou commence par lui, avec leur base : les
+/// saisons attachées d'abord, puis le catalogue. Deux au plus, assez pour
+/// savoir si un début de hash est ambigu.
+pub fn matches(conn: &Connection, prefix: &str) -> Vec<(String, String)> {
+    if prefix.is_empty() {
+        return Vec::new();
+    }
+    // Un hash complet passe par l'index ; un début est comparé tel quel (LIKE
+    // ignorerait la casse et prendrait `_` et `%` pour des jokers)
+    let condition = if prefix.len() == 64 {
+        "hash = ?1"
+    } else {
+        "substr(hash, 1, length(?1)) = ?1"
+    };
+    let lookup = |table: &str, column: &str| -> Vec<(String, String)> {
+        let Ok(mut stmt) = conn.prepare(format!(
+            "SELECT hash, {column} FROM {table} WHERE {condition} LIMIT 2"
+        )) else {
+            return Vec::new();
+        };
+        if stmt.bind((1, prefix)).is_err() {
+            return Vec::new();
+        }
+        let mut rows = Vec::new();
+        while let Ok(State::Row) = stmt.next() {
+            if let (Ok(hash), Ok(place)) = (stmt.read(0), stmt.read(1)) {
+                rows.push((hash, place));
+            }
+        }
+        rows
+    };
+    let mut found: Vec<(String, String)> = Vec::new();
+    for schema in schemas(conn) {
+        for (hash, _) in lookup(&format!("{schema}.commits"), "hash") {
+            if !found.iter().any(|(_, known)| *known == hash) {
+                found.push((schema.clone(), hash));
+            }
+        }
+    }
+    for (hash, shard) in lookup("store.history", "shard") {
+        if !found.iter().any(|(_, known)| *known == hash)
+            && let Ok(schema) = attach(conn, &shard)
+        {
+            found.push((schema, hash));
+        }
+    }
+    found.truncate(2);
+    found
+}
+
+/// Base qui contient le commit désigné par un hash complet ou son début, et son
+/// hash complet.
+pub fn find(conn: &Connection, prefix: &str) -> Option<(String, String)> {
+    matches(conn, prefix).into_iter().next()
+}
+
+fn read_commit(conn: &Connection, schema: &str, hash: &str) -> Result<Option<Entry>, Error> {
+    let mut stmt = conn.prepare(format!(
+        "SELECT id, parent_hash, tree_hash, author, message, timestamp FROM {schema}.commits
+         WHERE hash = ?"
+    ))?;
+    stmt.bind((1, hash))?;
+    if !matches!(stmt.next(), Ok(State::Row)) {
+        return Ok(None);
+    }
+    let mut parents = Vec::new();
+    if let Ok(mut parents_stmt) = conn.prepare(format!(
+        "SELECT parent_hash FROM {schema}.commit_parents WHERE commit_hash = ? ORDER BY position"
+    )) {
+        parents_stmt.bind((1, hash))?;
+        while let Ok(State::Row) = parents_stmt.next() {
+            parents.push(parents_stmt.read::<String, _>(0)?);
+        }
+    }
+    if parents.is_empty()
+        && let Some(parent) = stmt.read::<Option<String>, _>("parent_hash")?
+        && !parent.is_empty()
+    {
+        parents.push(parent);
+    }
+    Ok(Some(Entry {
+        schema: schema.to_string(),
+        id: stmt.read("id")?,
+        hash: hash.to_string(),
+        parents,
+        tree: stmt.read("tree_hash")?,
+        author: stmt.read("author")?,
+        message: stmt.read("message")?,
+        timestamp: stmt.read("timestamp")?,
+    }))
+}
+
+/// Le commit désigné par `hash` (ou son début), quelle que soit sa saison.
+pub fn commit(conn: &Connection, hash: &str) -> Result<Option<Entry>, Error> {
+    match find(conn, hash) {
+        Some((schema, hash)) => read_commit(conn, &schema, &hash),
+        None => Ok(None),
+    }
+}
+
+/// Commits d'une saison attachée, du plus ancien au plus récent.
+pub fn season_commits(conn: &Connection, schema: &str) -> Result<Vec<Entry>, Error> {
+    let mut hashes = Vec::new();
+    let mut stmt = conn.prepare(format!(
+        "SELECT hash FROM {schema}.commits ORDER BY timestamp, id"
+    ))?;
+    while let Ok(State::Row) = stmt.next() {
+        hashes.push(stmt.read::<String, _>(0)?);
+    }
+    let mut entries = Vec::new();
+    for hash in hashes {
+        entries.extend(read_commit(conn, schema, &hash)?);
+    }
+    Ok(entries)
+}
+
+/// Tête de `branch` : la saison courante, sinon la plus récente qui la connaît.
+pub fn branch_head(conn: &Connection, branch: &str) -> Option<String> {
+    let mut head = None;
+    let _ = each_season(conn, |schema| {
+        if head.is_none()
+            && let Ok(mut stmt) = conn.prepare(format!(
+                "SELECT c.hash FROM {schema}.branches b JOIN {schema}.commits c
+                 ON b.head_commit_id = c.id WHERE b.name = ?"
+            ))
+        {
+            stmt.bind((1, branch))?;
+            if let Ok(State::Row) = stmt.next() {
+                head = Some(stmt.read::<String, _>(0)?);
+            }
+        }
+        Ok(())
+    });
+    head
+}
+
+/// Commits atteignables depuis `heads`, toutes saisons confondues, du plus
+/// récent au plus ancien.
+pub fn ancestry(conn: &Connection, heads: &[String]) -> Result<Vec<Entry>, Error> {
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<String> = heads.iter().cloned().collect();
+    let mut entries = Vec::new();
+    while let Some(hash) = queue.pop_front() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        if let Some(entry) = commit(conn, &hash)? {
+            queue.extend(entry.parents.iter().cloned());
+            entries.push(entry);
+        }
+    }
+    entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    Ok(entries)
+}
+
+/// Tous les commits de toutes les saisons, du plus récent au plus ancien.
+pub fn all_commits(conn: &Connection) -> Result<Vec<Entry>, Error> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    each_season(conn, |schema| {
+        let mut stmt = conn.prepare(format!("SELECT hash FROM {schema}.commits"))?;
+        while let Ok(State::Row) = stmt.next() {
+            let hash = stmt.read::<String, _>(0)?;
+            if seen.insert(hash.clone())
+                && let Some(entry) = read_commit(conn, schema, &hash)?
+            {
+                entries.push(entry);
+            }
+        }
+        Ok(())
+    })?;
+    entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    Ok(entries)
+}
+
+/// Base attachée qui contient les entrées de l'arbre `tree` (`main` par défaut).
+/// La saison d'un arbre est celle de son commit, attachée en le résolvant.
+pub fn tree_schema(conn: &Connection, tree: &str) -> String {
+    schemas(conn)
+        .into_iter()
+        .find(|schema| {
+            conn.prepare(format!(
+                "SELECT 1 FROM {schema}.tree_nodes WHERE parent_tree_hash = ? LIMIT 1"
+            ))
+            .and_then(|mut stmt| {
+                stmt.bind((1, tree))?;
+                Ok(matches!(stmt.next(), Ok(State::Row)))
+            })
+            .unwrap_or(false)
+        })
+        .unwrap_or_else(|| "main".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Trois saisons : la plus ancienne n'est ni courante ni attachée en `old`
+    #[test]
+    fn resolves_history_across_seasons() {
+        let (repo, conn) = crate::utils::test_repo();
+        let db = repo.path().join(".lys/db");
+        let current = PathBuf::from(attached(&conn).unwrap().remove(0).1);
+        drop(conn);
+
+        // Chaque saison close est déplacée, avec une date d'écriture croissante
+        let mut heads = Vec::new();
+        for (step, (shard, content)) in [
+            ("2001/winter/winter.db", "one\n"),
+            ("2002/winter/winter.db", "two\n"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let conn = crate::db::connect_lys(repo.path()).unwrap();
+            std::fs::write("a.txt", content).unwrap();
+            crate::vcs::commit(&conn, &format!("commit {step}"), AUTHOR).unwrap();
+            heads.push(crate::vcs::get_branch_head_info(&conn, "main").unwrap().1);
+            conn.execute("PRAGMA wal_checkpoint(TRUNCATE);").unwrap();
+            drop(conn);
+            let target = db.join(shard);
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::rename(&current, &target).unwrap();
+            let written =
+                UNIX_EPOCH + std::time::Duration::from_secs(1_000_000 * (step as u64 + 1));
+            std::fs::File::options()
+                .write(true)
+                .open(&target)
+                .unwrap()
+                .set_modified(written)
+                .unwrap();
+        }
+
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        std::fs::write("a.txt", "three\n").unwrap();
+        crate::vcs::commit(&conn, "commit 2", AUTHOR).unwrap();
+        let (_, head) = crate::vcs::get_branch_head_info(&conn, "main").unwrap();
+        let oldest = crate::db::commit_tree_hash(&conn, &heads[0]).unwrap();
+        let attached_before = schemas(&conn).len();
+        let history = ancestry(&conn, std::slice::from_ref(&head)).unwrap();
+        let parents = crate::db::commit_parents(&conn, &heads[1]).unwrap();
+        let resolved = crate::vcs::resolve_commit(&conn, &heads[0][..10]).unwrap();
+        let grandparent = crate::vcs::resolve_commit(&conn, &format!("{head}~2")).unwrap();
+        let ids = (
+            crate::vcs::get_commit_id_by_hash(&conn, &heads[0]).unwrap(),
+            crate::vcs::get_commit_id_by_hash(&conn, &head).unwrap(),
+        );
+        let audited = crate::crypto::audit(&conn).unwrap();
+        // Un commit altéré dans la plus ancienne saison fait échouer l'audit
+        let (schema, _) = find(&conn, &heads[0]).unwrap();
+        conn.execute(format!(
+            "UPDATE {schema}.commits SET message = 'forged' WHERE hash = '{}'",
+            heads[0]
+        ))
+        .unwrap();
+        let forged = crate::crypto::audit(&conn).unwrap();
+        crate::vcs::checkout(&conn, &heads[0][..10]).unwrap();
+        let restored = std::fs::read_to_string("a.txt").unwrap();
+
+        assert!(oldest.is_some());
+        assert_eq!(attached_before, 3);
+        let hashes: Vec<&str> = history.iter().map(|entry| entry.hash.as_str()).collect();
+        assert_eq!(hashes, vec![head.as_str(), &heads[1], &heads[0]]);
+        assert_eq!(parents, vec![heads[0].clone()]);
+        assert_eq!(restored, "one\n");
+        assert_eq!(resolved.as_ref(), Some(&heads[0]));
+        assert_eq!(grandparent.as_ref(), Some(&heads[0]));
+        assert!(ids.0.is_none() && ids.1.is_some());
+        assert!(audited);
+        assert!(!forged);
+    }
+}
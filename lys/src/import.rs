@@ -69,6 +69,22 @@ fn set_config(
     Ok(())
 }
 
+// libgit2 ne lit en flux que les objets libres : pour un objet empaqueté on
+// renvoie `None` et le blob est chargé, puis découpé par insert_blob_with_conn
+fn stream_blob(
+    repo: &Mutex<Repository>,
+    store_conn: &Mutex<sqlite::Connection>,
+    oid: Oid,
+) -> Option<String> {
+    let repo_guard = repo.lock().ok()?;
+    let odb = repo_guard.odb().ok()?;
+    let (reader, _, _) = odb.reader(oid).ok()?;
+    let store_guard = store_conn.lock().ok()?;
+    crate::chunks::write(&store_guard, reader, None)
+        .ok()
+        .map(|stored| stored.hash)
+}
+
 fn build_vfs_tree_parallel(
     repo: &Mutex<Repository>,
     target_dir: &Path,
@@ -120,10 +136,19 @@ fn build_vfs_tree_parallel(
     let blob_hashes_ptr = Arc::clone(&blob_hashes);
 
     // 2. Traitement des Blobs en parallèle (Correction de la syntaxe de déstructuration)
+    let threshold = crate::chunks::threshold(conn);
     entries
         .par_iter()
-        .for_each(|&(oid, ref _name, kind, _mode, _size)| {
+        .for_each(|&(oid, ref _name, kind, _mode, size)| {
             if let ObjectType::Blob = kind {
+                // Gros blob : découpé en flux quand la base d'objets Git le permet
+                if size >= threshold
+                    && let Some(lys_hash) = stream_blob(repo, store_conn, oid)
+                {
+                    indexed.insert(lys_hash.clone());
+                    blob_hashes_ptr.insert(oid, lys_hash);
+                    return;
+                }
                 let content = {
                     let repo_guard = repo.lock().unwrap();
                     repo_guard.find_blob(oid).map(|b| b.content().to_vec()).ok()
@@ -246,6 +271,8 @@ pub fn import_from_git(
     pb_git.finish_with_message("Git clone complete");
 
     let conn = db::connect_lys(target_dir)?;
+    // L'historique de Git n'est pas signé : fsck et les échanges l'acceptent
+    db::write_config(&conn, crate::transfer::ALLOW_UNSIGNED, "true")?;
     let store_db_path = target_dir.join(".lys/db/store.db");
     let store_conn = Mutex::new(sqlite::open(store_db_path)?);
 
@@ -282,16 +309,17 @@ pub fn import_from_git(
     let indexed_cache = Arc::new(DashSet::new());
     let mut git_map: HashMap<Oid, (String, String)> = HashMap::new();
     for oid in commits_oids {
-        let (tree_oid, author, message, time, parent_oid): (Oid, String, String, i64, Option<Oid>) = {
+        let (tree_oid, author, message, time, parent_oids): (Oid, String, String, i64, Vec<Oid>) = {
             let repo_guard = repo.lock().expect("Failed to lock repo");
             let commit = repo_guard.find_commit(oid)?;
-            let parent = commit.parent(0).ok().map(|p| p.id());
+            // Tous les parents git : un merge garde ses deux branches
+            let parents: Vec<Oid> = commit.parent_ids().collect();
             (
                 commit.tree_id(),
                 commit.author().name().unwrap_or("Unknown").to_string(),
                 commit.message().unwrap_or("").to_string(),
                 commit.time().seconds(),
-                parent,
+                parents,
             )
         };
         let tree_hash_str = tree_oid.to_string();
@@ -307,15 +335,20 @@ pub fn import_from_git(
             &pb_lys,
         )?;
 
-        let parent_hash = parent_oid.and_then(|p| git_map.get(&p).map(|(h, _)| h.clone()));
-        let parent_tree = parent_oid.and_then(|p| git_map.get(&p).map(|(_, t)| t.clone()));
+        let parent_hashes: Vec<String> = parent_oids
+            .iter()
+            .filter_map(|p| git_map.get(p).map(|(h, _)| h.clone()))
+            .collect();
+        let parent_tree = parent_oids
+            .first()
+            .and_then(|p| git_map.get(p).map(|(_, t)| t.clone()));
         let (commit_id, commit_hash) = vcs::commit_manual_with_parent(
             &conn,
             &message,
             &author,
             time,
             &tree_hash_str,
-            parent_hash.as_deref(),
+            &parent_hashes,
         )?;
         insert_manifest_for_commit(
             &conn,
@@ -324,6 +357,7 @@ pub fn import_from_git(
             parent_tree.as_deref(),
             commit_id,
         )?;
+        crate::export::record_oid(&conn, &commit_hash, &oid.to_string())?;
         git_map.insert(oid, (commit_hash, tree_hash_str));
         pb_lys.inc(1);
     }
@@ -375,6 +409,7 @@ pub fn import_from_git(
             }
         }
     }
+    crate::oplog::record(&conn, "import")?;
 
     Ok(())
 }
@@ -438,6 +473,8 @@ pub fn import_from_git_and_purge(
     pb_git.finish_with_message("Git clone complete");
 
     let conn = db::connect_lys(target_dir)?;
+    // L'historique de Git n'est pas signé : fsck et les échanges l'acceptent
+    db::write_config(&conn, crate::transfer::ALLOW_UNSIGNED, "true")?;
     let store_db_path = target_dir.join(".lys/db/store.db");
 
     let store_conn_raw = sqlite::open(store_db_path.to_path_buf())?;
@@ -493,16 +530,17 @@ pub fn import_from_git_and_purge(
     let indexed_cache = Arc::new(DashSet::new());
     let mut git_map: HashMap<Oid, (String, String)> = HashMap::new();
     for oid in commits_oids {
-        let (tree_oid, author, message, time, parent_oid) = {
+        let (tree_oid, author, message, time, parent_oids) = {
             let repo_guard = repo.lock().expect("Failed to lock repo");
             let commit = repo_guard.find_commit(oid)?;
-            let parent = commit.parent(0).ok().map(|p| p.id());
+            // Tous les parents git : un merge garde ses deux branches
+            let parents: Vec<Oid> = commit.parent_ids().collect();
             (
                 commit.tree_id(),
                 commit.author().name().unwrap_or("Unknown").to_string(),
                 commit.message().unwrap_or("").to_string(),
                 commit.time().seconds(),
-                parent,
+                parents,
             )
         };
         let tree_hash_str = tree_oid.to_string();
@@ -518,15 +556,20 @@ pub fn import_from_git_and_purge(
             &pb_lys,
         )?;
 
-        let parent_hash = parent_oid.and_then(|p| git_map.get(&p).map(|(h, _)| h.clone()));
-        let parent_tree = parent_oid.and_then(|p| git_map.get(&p).map(|(_, t)| t.clone()));
+        let parent_hashes: Vec<String> = parent_oids
+            .iter()
+            .filter_map(|p| git_map.get(p).map(|(h, _)| h.clone()))
+            .collect();
+        let parent_tree = parent_oids
+            .first()
+            .and_then(|p| git_map.get(p).map(|(_, t)| t.clone()));
         let (commit_id, commit_hash) = vcs::commit_manual_with_parent(
             &conn,
             &message,
             &author,
             time,
             &tree_hash_str,
-            parent_hash.as_deref(),
+            &parent_hashes,
         )?;
         insert_manifest_for_commit(
             &conn,
@@ -535,6 +578,7 @@ pub fn import_from_git_and_purge(
             parent_tree.as_deref(),
             commit_id,
         )?;
+        crate::export::record_oid(&conn, &commit_hash, &oid.to_string())?;
         git_map.insert(oid, (commit_hash, tree_hash_str));
         pb_lys.inc(1);
     }
@@ -586,20 +630,27 @@ pub fn import_from_git_and_purge(
             }
         }
     }
+    crate::oplog::record(&conn, "import")?;
 
     Ok(())
 }
 
+/// Importe les commits Git postérieurs à `since_oid` jusqu'à `tip_oid`
+/// (le HEAD Git par défaut) et avance la branche Lys `branch_name`.
 pub fn import_updates_from_repo(
     repo_path: &Path,
     target_dir: &Path,
     since_oid: &str,
+    tip_oid: Option<&str>,
     branch_name: &str,
 ) -> Result<bool, Box<dyn std::error::Error>> {
     let repo_raw = Repository::open(repo_path)?;
     let since = Oid::from_str(since_oid)?;
     let mut revwalk = repo_raw.revwalk()?;
-    revwalk.push_head()?;
+    match tip_oid {
+        Some(tip) => revwalk.push(Oid::from_str(tip)?)?,
+        None => revwalk.push_head()?,
+    }
     revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
 
     let mut oids: Vec<Oid> = revwalk.filter_map(|id| id.ok()).collect();
@@ -615,6 +666,8 @@ pub fn import_updates_from_repo(
 
     let repo = Mutex::new(repo_raw);
     let conn = db::connect_lys(target_dir)?;
+    // L'historique de Git n'est pas signé : fsck et les échanges l'acceptent
+    db::write_config(&conn, crate::transfer::ALLOW_UNSIGNED, "true")?;
     let store_db_path = target_dir.join(".lys/db/store.db");
     let store_conn = Mutex::new(sqlite::open(store_db_path)?);
 
@@ -644,16 +697,17 @@ pub fn import_updates_from_repo(
 
     let indexed_cache = Arc::new(DashSet::new());
     for oid in oids {
-        let (tree_oid, author, message, time, parent_oid): (Oid, String, String, i64, Option<Oid>) = {
+        let (tree_oid, author, message, time, parent_oids): (Oid, String, String, i64, Vec<Oid>) = {
             let repo_guard = repo.lock().expect("Failed to lock repo");
             let commit = repo_guard.find_commit(oid)?;
-            let parent = commit.parent(0).ok().map(|p| p.id());
+            // Tous les parents git : un merge garde ses deux branches
+            let parents: Vec<Oid> = commit.parent_ids().collect();
             (
                 commit.tree_id(),
                 commit.author().name().unwrap_or("Unknown").to_string(),
                 commit.message().unwrap_or("").to_string(),
                 commit.time().seconds(),
-                parent,
+                parents,
             )
         };
         let tree_hash_str = tree_oid.to_string();
@@ -669,9 +723,20 @@ pub fn import_updates_from_repo(
             &pb,
         )?;
 
-        let parent_hash = parent_oid.and_then(|p| git_map.get(&p).map(|(h, _)| h.clone()));
-        let parent_tree = parent_oid
-            .and_then(|p| git_map.get(&p).map(|(_, t)| t.clone()))
+        // Un parent importé lors d'une synchro précédente est retrouvé dans git_map
+        let parent_hashes: Vec<String> = parent_oids
+            .iter()
+            .filter_map(|p| {
+                git_map.get(p).map(|(h, _)| h.clone()).or_else(|| {
+                    crate::export::lys_hash_for(&conn, &p.to_string())
+                        .ok()
+                        .flatten()
+                })
+            })
+            .collect();
+        let parent_tree = parent_oids
+            .first()
+            .and_then(|p| git_map.get(p).map(|(_, t)| t.clone()))
             .or_else(|| Some(prev_tree_hash.clone()));
         let (commit_id, commit_hash) = vcs::commit_manual_with_parent(
             &conn,
@@ -679,7 +744,7 @@ pub fn import_updates_from_repo(
             &author,
             time,
             &tree_hash_str,
-            parent_hash.as_deref(),
+            &parent_hashes,
         )?;
         insert_manifest_for_commit(
             &conn,
@@ -691,6 +756,7 @@ pub fn import_updates_from_repo(
 
         head_id = commit_id;
         prev_tree_hash = tree_hash_str.clone();
+        crate::export::record_oid(&conn, &commit_hash, &oid.to_string())?;
         git_map.insert(oid, (commit_hash, tree_hash_str));
         pb.inc(1);
     }
@@ -12,7 +12,7 @@ use crate::utils::run_hooks;
 use crate::Language::{CSharp, Cpp, Haskell, Js, Php, Python, Rust, Typescript, C, D};
 use breathes::validator::{validate_email, validate_summary_length};
 use clap::value_parser;
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgGroup, Command};
 use inquire::{Select, Text};
 use sqlite::State;
 use std::env::current_dir;
@@ -21,21 +21,44 @@ use std::fs::read_to_string;
 use std::fs::File;
 use std::io::{Error, Write};
 use std::path::Path;
+use std::path::PathBuf;
 use std::path::MAIN_SEPARATOR_STR;
 use std::process::{Command as Cmd, Stdio};
 
+pub mod archive;
+pub mod assets;
+pub mod backup;
+pub mod bisect;
+pub mod blame;
+pub mod bundle;
 pub mod chat;
+pub mod chunks;
 pub mod commit;
 pub mod crypto;
 pub mod db;
+pub mod diff;
+pub mod export;
+pub mod fsck;
+pub mod history;
 pub mod import;
+pub mod merge;
+pub mod mirror;
 mod mount;
+pub mod oplog;
+pub mod patch;
+pub mod pick;
+pub mod prune;
+pub mod rebase;
+pub mod remote;
 pub mod shell;
+pub mod stash;
 pub mod todo;
+pub mod transfer;
 pub mod tree;
 pub mod utils;
 pub mod vcs;
 pub mod web;
+pub mod worktree;
 
 fn cli() -> Command {
     Command::new(env!("CARGO_PKG_NAME"))
@@ -46,22 +69,115 @@ fn cli() -> Command {
         .subcommand(Command::new("new").about("Create a new lys project"))
         .subcommand(
             Command::new("verify")
-                .about("Check repository integrity and missing blobs")
+                .about("Check trees, commits, refs, signatures, blobs and season seals")
                 .arg(
                     Arg::new("deep")
                         .long("deep")
                         .action(ArgAction::SetTrue)
                         .help("Recalculate Blake3 checksums for every blob (Slower but safer)"),
+                )
+                .arg(
+                    Arg::new("repair")
+                        .long("repair")
+                        .num_args(0..=1)
+                        .default_missing_value("")
+                        .value_name("source")
+                        .help("Refetch damaged objects from a backup or a remote"),
                 ),
         )
         .subcommand(Command::new("summary").about("Show working directory infos"))
         .subcommand(Command::new("status").about("Show changes in working directory"))
-        .subcommand(Command::new("push").about("Push local commits to a remote architect"))
-        .subcommand(Command::new("pull").about("Pull commits from a remote architect"))
         .subcommand(
-            Command::new("prune").about(
-                "Maintain repository health by removing old history and reclaiming disk space.",
-            ),
+            Command::new("push")
+                .about("Push local commits to a remote architect")
+                .arg(remote_arg())
+                .arg(branch_arg()),
+        )
+        .subcommand(
+            Command::new("pull")
+                .about("Pull commits from a remote architect")
+                .arg(remote_arg())
+                .arg(branch_arg()),
+        )
+        .subcommand(
+            Command::new("fetch")
+                .about("Download commits from a remote and update its tracking refs")
+                .arg(remote_arg()),
+        )
+        .subcommand(
+            Command::new("remote")
+                .about("Manage named remotes")
+                .subcommand(
+                    Command::new("add")
+                        .about("Register a remote")
+                        .arg(Arg::new("name").required(true).action(ArgAction::Set))
+                        .arg(
+                            Arg::new("url")
+                                .required(true)
+                                .help("lys://host:port, a Git URL or a local path")
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("transport")
+                                .short('t')
+                                .long("transport")
+                                .value_parser(remote::TRANSPORTS)
+                                .help("Transport (default: guessed from the URL)")
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("key")
+                                .short('k')
+                                .long("key")
+                                .help("Trusted public key of the remote (hex)")
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List remotes"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a remote and its tracking refs")
+                        .arg(Arg::new("name").required(true).action(ArgAction::Set)),
+                ),
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Remove history outside the retention policy and reclaim disk space")
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("List the commits and bytes that would be removed"),
+                )
+                .subcommand(
+                    Command::new("policy")
+                        .about("Show or change the retention policy")
+                        .arg(
+                            Arg::new("seasons")
+                                .long("seasons")
+                                .value_parser(value_parser!(usize))
+                                .help("Keep every commit of the N most recent seasons (0: all)"),
+                        )
+                        .arg(
+                            Arg::new("branches")
+                                .long("branches")
+                                .num_args(0..)
+                                .help("Keep the whole history of these branches"),
+                        )
+                        .arg(
+                            Arg::new("tags")
+                                .long("tags")
+                                .value_parser(value_parser!(bool))
+                                .help("Keep the whole history of tags"),
+                        )
+                        .arg(
+                            Arg::new("season-first")
+                                .long("season-first")
+                                .value_parser(value_parser!(bool))
+                                .help("Keep the first commit of each season"),
+                        ),
+                ),
         )
         .subcommand(
             Command::new("shell")
@@ -116,6 +232,36 @@ fn cli() -> Command {
                         .help("Only import the last 2 years of history (Lean mode)"),
                 ),
         )
+        .subcommand(
+            Command::new("export")
+                .about("Export the Lys history to another VCS")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("git")
+                        .about("Replay commits, branches and tags into a Git repository")
+                        .arg(
+                            Arg::new("dir")
+                                .required(true)
+                                .help("Target Git repository (created if missing)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("mirror")
+                .about("Keep a Lys branch and a Git repository in sync")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("git")
+                        .about("Import new upstream Git commits and push local ones back")
+                        .arg(
+                            Arg::new("branch")
+                                .short('b')
+                                .long("branch")
+                                .default_value("origin")
+                                .help("Lys branch mirrored with the checked-out Git branch"),
+                        ),
+                ),
+        )
         .subcommand(
             Command::new("keygen").about("Generate Ed25519 identity keys for signing commits"),
         )
@@ -143,16 +289,181 @@ fn cli() -> Command {
                         .value_parser(value_parser!(usize))
                         .default_value("120") // Ta demande spécifique
                         .help("Number of commits per page"),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .long("follow")
+                        .value_name("PATH")
+                        .help("Only commits touching this file, following its renames"),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show changes between revisions or against the working tree")
+                .arg(
+                    Arg::new("revs")
+                        .num_args(0..=2)
+                        .help("Revisions: none (HEAD vs files), <rev>, <a> <b> or <a>..<b>"),
+                )
+                .arg(
+                    Arg::new("paths")
+                        .num_args(1..)
+                        .last(true)
+                        .help("Limit the diff to these paths"),
+                )
+                .arg(
+                    Arg::new("stat")
+                        .long("stat")
+                        .action(ArgAction::SetTrue)
+                        .help("Show changed lines per file"),
+                )
+                .arg(
+                    Arg::new("name-only")
+                        .long("name-only")
+                        .action(ArgAction::SetTrue)
+                        .help("Show only the names of changed files"),
+                )
+                .arg(
+                    Arg::new("name-status")
+                        .long("name-status")
+                        .action(ArgAction::SetTrue)
+                        .help("Show names with A/M/D/R status"),
+                )
+                .arg(
+                    Arg::new("word-diff")
+                        .long("word-diff")
+                        .action(ArgAction::SetTrue)
+                        .help("Highlight changed words inside lines"),
+                )
+                .group(
+                    ArgGroup::new("format")
+                        .args(["stat", "name-only", "name-status", "word-diff"])
+                        .multiple(false),
+                ),
+        )
+        .subcommand(pick_command(
+            "cherry-pick",
+            "Apply the changes of a commit to the current branch",
+        ))
+        .subcommand(pick_command(
+            "revert",
+            "Create a commit that undoes the changes of a commit",
+        ))
+        .subcommand(
+            Command::new("rebase")
+                .about("Replay the commits of the current branch on top of another one")
+                .arg(
+                    Arg::new("onto")
+                        .required_unless_present_any(["continue", "abort"])
+                        .help("Branch, tag or commit to rebase onto"),
+                )
+                .arg(
+                    Arg::new("continue")
+                        .long("continue")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["onto", "abort"])
+                        .help("Commit the resolved conflicts and replay the rest"),
+                )
+                .arg(
+                    Arg::new("abort")
+                        .long("abort")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("onto")
+                        .help("Stop and put the branch back on its original head"),
+                ),
+        )
+        .subcommand(
+            Command::new("format-patch")
+                .about("Write signed, self-contained patch files for a range of commits")
+                .arg(
+                    Arg::new("range")
+                        .required(true)
+                        .help("<a>..<b>, or <rev> for the commits since <rev>"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output-directory")
+                        .default_value(".")
+                        .help("Directory for the patch files"),
+                ),
+        )
+        .subcommand(patch_command(
+            "apply",
+            "Apply a signed patch to the working tree without committing",
+        ))
+        .subcommand(patch_command(
+            "am",
+            "Apply signed patches and record their commits",
+        ))
+        .subcommand(
+            Command::new("bisect")
+                .about("Find the commit that introduced a bug by binary search")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("start")
+                        .about("Start bisecting between a bad and a good revision")
+                        .arg(
+                            Arg::new("bad")
+                                .required(true)
+                                .help("A revision with the bug"),
+                        )
+                        .arg(
+                            Arg::new("good")
+                                .required(true)
+                                .help("A revision without it"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("good")
+                        .about("Mark a revision as good (default: the checked-out one)")
+                        .arg(Arg::new("rev")),
+                )
+                .subcommand(
+                    Command::new("bad")
+                        .about("Mark a revision as bad (default: the checked-out one)")
+                        .arg(Arg::new("rev")),
+                )
+                .subcommand(
+                    Command::new("skip")
+                        .about("Skip a revision that cannot be tested")
+                        .arg(Arg::new("rev")),
+                )
+                .subcommand(Command::new("reset").about("Stop bisecting and go back to the branch"))
+                .subcommand(
+                    Command::new("run")
+                        .about("Classify revisions by exit code (0 good, 125 skip, other bad)")
+                        .arg(Arg::new("cmd").help("Shell command run in each revision"))
+                        .arg(
+                            Arg::new("hooks")
+                                .long("hooks")
+                                .action(ArgAction::SetTrue)
+                                .help("Run the hooks of the 'lys' file in each revision"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("blame")
+                .about("Show which commit last changed each line of a file")
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .help("File path from the repository root"),
+                )
+                .arg(
+                    Arg::new("rev")
+                        .short('r')
+                        .long("rev")
+                        .help("Branch, tag or commit hash to annotate (default: HEAD)"),
                 ),
         )
-        .subcommand(Command::new("diff").about("Show changes between working tree and last commit"))
         .subcommand(
             Command::new("clone")
-                .about("Clone a Git repository into a new lys repository")
+                .about("Clone a Git repository or a Lys node into a new lys repository")
                 .arg(
                     Arg::new("url")
                         .required(true)
-                        .help("The git URL (https://...)")
+                        .help("The git URL (https://...), a Lys node (lys://host:port) or path")
                         .action(ArgAction::Set),
                 )
                 // Optionnel : permettre de forcer un nom de dossier différent
@@ -167,6 +478,18 @@ fn cli() -> Command {
                         .short('d')
                         .value_parser(value_parser!(i32))
                         .help("Truncate history to the specified number of commits"),
+                )
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help("Lys nodes only: skip history older than a season (e.g. 2025/autumn)")
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("allow-unsigned")
+                        .long("allow-unsigned")
+                        .help("Lys nodes only: accept commits that carry no signature")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(Command::new("health").about("Check the source code"))
@@ -201,6 +524,16 @@ fn cli() -> Command {
                 ),
         )
         .subcommand(Command::new("commit").about("Record changes to the repository"))
+        .subcommand(
+            Command::new("resolve")
+                .about("Mark conflicted files of the merge in progress as resolved")
+                .arg(
+                    Arg::new("path")
+                        .help("The resolved files")
+                        .required(true)
+                        .num_args(1..),
+                ),
+        )
         .subcommand(
             Command::new("restore")
                 .about("Discard changes in working directory")
@@ -234,6 +567,79 @@ fn cli() -> Command {
                         .help("Destination path"),
                 ),
         )
+        .subcommand(
+            Command::new("backup")
+                .about("Write an incremental, signed backup generation")
+                .args_conflicts_with_subcommands(true)
+                .arg(Arg::new("dest").help("Backup directory (default: the last one used)"))
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check the signature and every object of a backup generation")
+                        .arg(Arg::new("dest").help("Backup directory (default: the last one used)"))
+                        .arg(generation_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("restore-backup")
+                .about("Rebuild .lys/ from a backup generation")
+                .arg(Arg::new("dest").required(true).help("Backup directory"))
+                .arg(generation_arg())
+                .arg(
+                    Arg::new("key")
+                        .short('k')
+                        .long("key")
+                        .help("Public key (hex) that signed the backup, when .lys/ has none")
+                        .action(ArgAction::Set),
+                ),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Seal a past season: compact it, sign its manifest, make it read-only")
+                .arg(
+                    Arg::new("season")
+                        .required(true)
+                        .help("Season to seal, as <year>/<season>"),
+                )
+                .arg(
+                    Arg::new("level")
+                        .long("level")
+                        .value_parser(value_parser!(i32))
+                        .help("Recompress the season's blobs at this zstd level (e.g. 19)"),
+                ),
+        )
+        .subcommand(
+            Command::new("chunks")
+                .about("Show how large files are chunked in the store, or set the threshold")
+                .arg(
+                    Arg::new("threshold")
+                        .long("threshold")
+                        .value_parser(value_parser!(u64))
+                        .help("Store files of at least this many bytes in chunks"),
+                ),
+        )
+        .subcommand(
+            Command::new("bundle")
+                .about("Carry history between repositories in a single signed file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Write the commits of some branches to a bundle file")
+                        .arg(Arg::new("file").required(true).help("Bundle file to write"))
+                        .arg(
+                            Arg::new("refs")
+                                .num_args(1..)
+                                .help("Branches or <base>..<branch> ranges (default: all)"),
+                        ),
+                )
+                .subcommand(bundle_command(
+                    "verify",
+                    "Check a bundle against this repository",
+                ))
+                .subcommand(bundle_command(
+                    "unbundle",
+                    "Import a bundle and fast-forward its branches",
+                )),
+        )
         .subcommand(
             Command::new("branch")
                 .about("Create a new branch")
@@ -368,6 +774,200 @@ fn cli() -> Command {
                 .about("Set the image banner to display on the home page")
                 .arg(Arg::new("url").required(true).help("Image URL")),
         )
+        .subcommand(
+            Command::new("stash")
+                .about("Set aside working tree changes")
+                .subcommand(
+                    Command::new("push")
+                        .about("Save local changes and revert to HEAD")
+                        .arg(
+                            Arg::new("message")
+                                .short('m')
+                                .long("message")
+                                .help("Description of the stash")
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List saved stashes"))
+                .subcommand(
+                    Command::new("show")
+                        .about("Show the files saved in a stash")
+                        .arg(stash_arg()),
+                )
+                .subcommand(
+                    Command::new("apply")
+                        .about("Apply a stash to the working tree")
+                        .arg(stash_arg()),
+                )
+                .subcommand(
+                    Command::new("pop")
+                        .about("Apply a stash and drop it")
+                        .arg(stash_arg()),
+                )
+                .subcommand(
+                    Command::new("drop")
+                        .about("Delete a stash")
+                        .arg(stash_arg()),
+                ),
+        )
+        .subcommand(
+            Command::new("oplog")
+                .about("Show the operation log (branches, tags and HEAD snapshots)")
+                .arg(
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .value_parser(value_parser!(usize))
+                        .default_value("20")
+                        .help("Number of operations to show"),
+                ),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Undo the last operation on refs")
+                .arg(worktree_arg()),
+        )
+        .subcommand(
+            Command::new("redo")
+                .about("Redo the last undone operation")
+                .arg(worktree_arg()),
+        )
+        .subcommand(
+            Command::new("op")
+                .about("Manage the operation log")
+                .subcommand(
+                    Command::new("restore")
+                        .about("Restore refs to the state recorded by an operation")
+                        .arg(
+                            Arg::new("id")
+                                .required(true)
+                                .value_parser(value_parser!(i64)),
+                        )
+                        .arg(worktree_arg()),
+                ),
+        )
+}
+
+fn stash_arg() -> Arg {
+    Arg::new("stash")
+        .help("Stash reference, e.g. stash@{1} (default: latest)")
+        .required(false)
+        .action(ArgAction::Set)
+}
+
+fn worktree_arg() -> Arg {
+    Arg::new("worktree")
+        .long("worktree")
+        .action(ArgAction::SetTrue)
+        .help("Also update the working tree to the restored HEAD")
+}
+
+fn remote_arg() -> Arg {
+    Arg::new("remote")
+        .help("Remote name or URL (default: origin)")
+        .required(false)
+        .action(ArgAction::Set)
+}
+
+// `cherry-pick` et `revert` partagent leurs options
+fn pick_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(
+            Arg::new("hash")
+                .required_unless_present_any(["continue", "abort"])
+                .help("Commit hash, branch or tag"),
+        )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["hash", "abort"])
+                .help("Commit once the conflicts are resolved"),
+        )
+        .arg(
+            Arg::new("abort")
+                .long("abort")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("hash")
+                .help("Give up and restore the files of the branch head"),
+        )
+}
+
+fn generation_arg() -> Arg {
+    Arg::new("generation")
+        .short('g')
+        .long("generation")
+        .value_parser(value_parser!(u64))
+        .help("Generation number (default: the latest)")
+}
+
+// `bundle verify` et `bundle unbundle` partagent leurs options
+fn bundle_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(Arg::new("file").required(true).help("Bundle file"))
+        .arg(
+            Arg::new("key")
+                .short('k')
+                .long("key")
+                .help("Also trust this public key (hex) for the signatures")
+                .action(ArgAction::Set),
+        )
+}
+
+// `apply` et `am` partagent leurs options
+fn patch_command(name: &'static str, about: &'static str) -> Command {
+    // `apply` laisse ses changements non validés : un seul patch à la fois
+    let patches = Arg::new("patches")
+        .required(true)
+        .value_parser(value_parser!(PathBuf));
+    let patches = if name == "am" {
+        patches
+            .num_args(1..)
+            .help("Patch files, or directories of .patch files")
+    } else {
+        patches.help("Patch file")
+    };
+    Command::new(name).about(about).arg(patches).arg(
+        Arg::new("key")
+            .short('k')
+            .long("key")
+            .help("Also trust this public key (hex) for the signatures")
+            .action(ArgAction::Set),
+    )
+}
+
+// Pull natif sauf pour un remote Git (ou sans remote, dans un dépôt adossé à Git)
+fn native_remote(args: &clap::ArgMatches) -> bool {
+    // Hors d'un dépôt, rien à ouvrir : connect_lys créerait .lys
+    let root = current_dir().unwrap_or_default();
+    if !root.join(".lys").is_dir() {
+        return false;
+    }
+    let conn = match connect_lys(&root) {
+        Ok(conn) => conn,
+        Err(_) => return false,
+    };
+    match args.get_one::<String>("remote") {
+        // Un nom inconnu part aussi vers le pull natif, qui affichera l'erreur
+        Some(name) => match remote::resolve(&conn, name) {
+            Ok(r) => r.transport != "git",
+            Err(_) => true,
+        },
+        None => remote::find(&conn, "origin")
+            .ok()
+            .flatten()
+            .is_some_and(|r| r.transport != "git"),
+    }
+}
+
+fn branch_arg() -> Arg {
+    Arg::new("branch")
+        .short('b')
+        .long("branch")
+        .help("Branch to transfer (default: current branch)")
+        .action(ArgAction::Set)
 }
 
 fn perform_commit() -> Result<(), Error> {
@@ -776,20 +1376,60 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
             let deep = args.get_flag("deep"); // On récupère le flag
             let current_dir = current_dir()?;
             let conn = connect_lys(&current_dir).map_err(|e| Error::other(e.to_string()))?;
-            db::verify(&conn, deep).map_err(|e| Error::other(e.to_string()))?;
-            Ok(())
+            let repair = match args.get_one::<String>("repair") {
+                Some(from) => Some(
+                    fsck::source(&conn, Some(from.as_str()).filter(|f| !f.is_empty()))
+                        .map_err(|e| Error::other(e.to_string()))?,
+                ),
+                None => None,
+            };
+            let report = fsck::run(&conn, &current_dir, deep, repair.as_ref())
+                .map_err(|e| Error::other(e.to_string()))?;
+            if report.issues.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::other("the repository is damaged"))
+            }
         }
         Some(("summary", _)) => summary(),
-        Some(("prune", _)) => {
-            let conn = connect_lys(Path::new(".")).expect("failed to connect to the database");
+        Some(("prune", args)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let mut policy = prune::Policy::load(&conn).map_err(|e| Error::other(e.to_string()))?;
+            if let Some(("policy", sub)) = args.subcommand() {
+                if let Some(seasons) = sub.get_one::<usize>("seasons") {
+                    policy.seasons = *seasons;
+                }
+                if let Some(branches) = sub.get_many::<String>("branches") {
+                    policy.branches = branches.cloned().collect();
+                }
+                if let Some(tags) = sub.get_one::<bool>("tags") {
+                    policy.tags = *tags;
+                }
+                if let Some(first) = sub.get_one::<bool>("season-first") {
+                    policy.season_first = *first;
+                }
+                if sub.args_present() {
+                    policy
+                        .save(&conn)
+                        .map_err(|e| Error::other(e.to_string()))?;
+                }
+                ok(policy.to_string().as_str());
+                return Ok(());
+            }
+            let plan =
+                prune::plan(&conn, &root, &policy).map_err(|e| Error::other(e.to_string()))?;
+            prune::report(&plan);
+            if args.get_flag("dry-run") || plan.is_empty() {
+                return Ok(());
+            }
             let ans = inquire::Confirm::new("Are you sure you want to prune the repository?")
-                .with_help_message("This action will PERMANENTLY delete all commits older than 2 years and reclaim disk space.")
+                .with_help_message("This action will PERMANENTLY delete the commits listed above.")
                 .with_default(false)
                 .prompt();
             match ans {
                 Ok(true) => {
-                    // Lancement de la fonction de nettoyage que nous avons codée
-                    db::prune(&conn).expect("failed to prune");
+                    prune::apply(&conn, &root, &plan).map_err(|e| Error::other(e.to_string()))?
                 }
                 Ok(false) => println!("Prune operation cancelled."),
                 Err(_) => println!("Error during confirmation. Operation aborted."),
@@ -819,11 +1459,179 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
             ok("ready");
             Ok(())
         }
+        Some(("export", sub)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            match sub.subcommand() {
+                Some(("git", args)) => {
+                    let dir = args.get_one::<String>("dir").unwrap();
+                    export::export_git(&conn, Path::new(dir))
+                        .map(|_| ())
+                        .map_err(|e| Error::other(e.to_string()))
+                }
+                _ => Ok(()),
+            }
+        }
+        Some(("mirror", sub)) => {
+            let current_dir = current_dir()?;
+            let conn = connect_lys(&current_dir).map_err(|e| Error::other(e.to_string()))?;
+            match sub.subcommand() {
+                Some(("git", args)) => {
+                    let branch = args.get_one::<String>("branch").unwrap();
+                    mirror::mirror_git(&conn, &current_dir, branch)
+                        .map_err(|e| Error::other(e.to_string()))
+                }
+                _ => Ok(()),
+            }
+        }
+        Some((name @ ("cherry-pick" | "revert"), args)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            let kind = if name == "revert" {
+                pick::Kind::Revert
+            } else {
+                pick::Kind::CherryPick
+            };
+            let result = if args.get_flag("continue") {
+                pick::resume(&conn, kind)
+            } else if args.get_flag("abort") {
+                pick::abort(&conn, kind)
+            } else {
+                pick::run(&conn, args.get_one::<String>("hash").unwrap(), kind).map(|_| ())
+            };
+            result.map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("rebase", args)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            let result = if args.get_flag("continue") {
+                rebase::resume(&conn).map(|_| ())
+            } else if args.get_flag("abort") {
+                rebase::abort(&conn)
+            } else {
+                rebase::start(&conn, args.get_one::<String>("onto").unwrap()).map(|_| ())
+            };
+            result.map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("format-patch", args)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            let output = Path::new(args.get_one::<String>("output").unwrap());
+            patch::format_range(&conn, args.get_one::<String>("range").unwrap(), output)
+                .map(|_| ())
+                .map_err(|e| Error::other(e.to_string()))
+        }
+        Some((name @ ("apply" | "am"), args)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            let patches: Vec<PathBuf> = args
+                .get_many::<PathBuf>("patches")
+                .unwrap()
+                .cloned()
+                .collect();
+            let key = args.get_one::<String>("key").map(String::as_str);
+            patch::run(&conn, &patches, name == "am", key).map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("bisect", sub)) => {
+            let current_dir = current_dir()?;
+            let conn = connect_lys(&current_dir).map_err(|e| Error::other(e.to_string()))?;
+            let rev = |args: &clap::ArgMatches| args.get_one::<String>("rev").cloned();
+            let result = match sub.subcommand() {
+                Some(("start", args)) => bisect::start(
+                    &conn,
+                    args.get_one::<String>("bad").unwrap(),
+                    args.get_one::<String>("good").unwrap(),
+                ),
+                Some(("good", args)) => {
+                    bisect::mark(&conn, bisect::Verdict::Good, rev(args).as_deref())
+                }
+                Some(("bad", args)) => {
+                    bisect::mark(&conn, bisect::Verdict::Bad, rev(args).as_deref())
+                }
+                Some(("skip", args)) => {
+                    bisect::mark(&conn, bisect::Verdict::Skip, rev(args).as_deref())
+                }
+                Some(("reset", _)) => bisect::reset(&conn),
+                Some(("run", args)) => bisect::run(
+                    &conn,
+                    &current_dir,
+                    args.get_one::<String>("cmd").map(|c| c.as_str()),
+                    args.get_flag("hooks"),
+                )
+                .map(|_| ()),
+                _ => Ok(()),
+            };
+            result.map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("blame", args)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            let path = args.get_one::<String>("path").unwrap();
+            let rev = args.get_one::<String>("rev").map(|r| r.as_str());
+            blame::print(&conn, path, rev).map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("push", args)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let name = args
+                .get_one::<String>("remote")
+                .map_or("origin", |r| r.as_str());
+            let branch = match args.get_one::<String>("branch") {
+                Some(b) => b.clone(),
+                None => get_current_branch(&conn).map_err(|e| Error::other(e.to_string()))?,
+            };
+            remote::resolve(&conn, name)
+                .and_then(|r| transfer::push(&conn, &root, &r, &branch))
+                .map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("fetch", args)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let name = args
+                .get_one::<String>("remote")
+                .map_or("origin", |r| r.as_str());
+            remote::resolve(&conn, name)
+                .and_then(|r| transfer::fetch(&conn, &root, &r))
+                .map_err(|e| Error::other(e.to_string()))?;
+            for (remote, branch, hash) in
+                remote::tracking_refs(&conn).map_err(|e| Error::other(e.to_string()))?
+            {
+                if remote == name {
+                    ok(format!("{remote}/{branch} -> {}", &hash[..hash.len().min(7)]).as_str());
+                }
+            }
+            Ok(())
+        }
+        Some(("pull", args)) if native_remote(args) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let name = args
+                .get_one::<String>("remote")
+                .map_or("origin", |r| r.as_str());
+            let branch = match args.get_one::<String>("branch") {
+                Some(b) => b.clone(),
+                None => get_current_branch(&conn).map_err(|e| Error::other(e.to_string()))?,
+            };
+            remote::resolve(&conn, name)
+                .and_then(|r| transfer::pull(&conn, &root, &r, &branch))
+                .map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("remote", sub)) => {
+            let conn = connect_lys(&current_dir()?).map_err(|e| Error::other(e.to_string()))?;
+            let result = match sub.subcommand() {
+                Some(("add", args)) => remote::add(
+                    &conn,
+                    args.get_one::<String>("name").unwrap(),
+                    args.get_one::<String>("url").unwrap(),
+                    args.get_one::<String>("transport").map(|t| t.as_str()),
+                    args.get_one::<String>("key").map(|k| k.as_str()),
+                ),
+                Some(("remove", args)) => {
+                    remote::remove(&conn, args.get_one::<String>("name").unwrap())
+                }
+                _ => remote::list(&conn),
+            };
+            result.map_err(|e| Error::other(e.to_string()))
+        }
         Some(("pull", _)) => {
             let current_dir = current_dir()?;
             if !Path::new(".git").exists() {
                 return Err(Error::other(
-                    "No .git directory found. This is not a Git-backed repo.",
+                    "No .git directory found. Add a Lys remote with: lys remote add origin <url>",
                 ));
             }
             let conn = connect_lys(&current_dir).map_err(|e| Error::other(e.to_string()))?;
@@ -875,7 +1683,7 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
                     ok("Already up to date.");
                     return Ok(());
                 }
-                import::import_updates_from_repo(&current_dir, &current_dir, &last, "origin")
+                import::import_updates_from_repo(&current_dir, &current_dir, &last, None, "origin")
                     .map_err(|e| Error::other(e.to_string()))?;
             } else {
                 ok("No previous Git head found. Recording current HEAD.");
@@ -890,6 +1698,7 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
             stmt.bind((1, head_str.as_str()))
                 .map_err(|e| Error::other(e.to_string()))?;
             stmt.next().map_err(|e| Error::other(e.to_string()))?;
+            oplog::record(&conn, "pull").map_err(|e| Error::other(e.to_string()))?;
             ok("Git pull + Lys sync complete.");
             Ok(())
         }
@@ -942,7 +1751,18 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
                 return Ok(());
             }
 
-            // 3. Cloner et importer en conservant .git
+            // 3. Un nœud Lys (lys:// ou dépôt local) se clone nativement
+            if url.starts_with("lys://") || Path::new(url).join(".lys").is_dir() {
+                let depth = depth.map(|d| d.max(1) as usize);
+                let since = args.get_one::<String>("since").map(|s| s.as_str());
+                let unsigned = args.get_flag("allow-unsigned");
+                transfer::clone(url, &target_path, depth, since, unsigned)
+                    .map_err(|e| Error::other(e.to_string()))?;
+                ok("ready");
+                return Ok(());
+            }
+
+            // 4. Sinon cloner et importer depuis Git en conservant .git
             ok("Creation of the repository");
             import::import_from_git(url, &target_path, depth, false, true, true)
                 .expect("failed to clone");
@@ -1057,15 +1877,44 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
         Some(("log", args)) => {
             let page = *args.get_one::<usize>("page").unwrap();
             let limit = *args.get_one::<usize>("limit").unwrap();
+            let follow = args.get_one::<String>("follow").map(Path::new);
             let conn = connect_lys(Path::new(".")).expect("failed to connect to the database");
-            vcs::log(&conn, page, limit).expect("failed to parse log");
+            vcs::log(&conn, page, limit, follow).expect("failed to parse log");
             Ok(())
         }
-        Some(("diff", _)) => {
+        Some(("diff", args)) => {
             let current_dir = current_dir()?;
             let conn =
                 connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
-            vcs::diff(&conn).map_err(|e| Error::other(e.to_string()))
+            let values = |id: &str| -> Vec<String> {
+                args.get_many::<String>(id)
+                    .map(|v| v.cloned().collect())
+                    .unwrap_or_default()
+            };
+            let format = if args.get_flag("stat") {
+                diff::Format::Stat
+            } else if args.get_flag("name-only") {
+                diff::Format::NameOnly
+            } else if args.get_flag("name-status") {
+                diff::Format::NameStatus
+            } else if args.get_flag("word-diff") {
+                diff::Format::WordDiff
+            } else {
+                diff::Format::Patch
+            };
+            diff::run(&conn, &values("revs"), &values("paths"), format)
+                .map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("resolve", sub_matches)) => {
+            let current_dir = current_dir()?;
+            let conn =
+                connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
+            let paths: Vec<String> = sub_matches
+                .get_many::<String>("path")
+                .unwrap()
+                .cloned()
+                .collect();
+            merge::resolve(&conn, &paths).map_err(|e| Error::other(e.to_string()))
         }
         Some(("restore", sub_matches)) => {
             let current_dir = current_dir()?;
@@ -1147,6 +1996,146 @@ pub fn execute_matches(app: clap::ArgMatches) -> Result<(), Error> {
                 }
             }
         }
+        Some(("stash", sub_matches)) => {
+            let current_dir = current_dir()?;
+            let conn =
+                connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
+            let reference = |args: &clap::ArgMatches| args.get_one::<String>("stash").cloned();
+            let result = match sub_matches.subcommand() {
+                Some(("push", args)) => {
+                    stash::push(&conn, args.get_one::<String>("message").map(|s| s.as_str()))
+                }
+                Some(("list", _)) => stash::list(&conn),
+                Some(("show", args)) => stash::show(&conn, reference(args).as_deref()),
+                Some(("apply", args)) => {
+                    stash::apply(&conn, reference(args).as_deref()).map(|_| ())
+                }
+                Some(("pop", args)) => stash::pop(&conn, reference(args).as_deref()),
+                Some(("drop", args)) => stash::drop_stash(&conn, reference(args).as_deref()),
+                _ => {
+                    ok("Please use 'push', 'list', 'show', 'apply', 'pop' or 'drop'.");
+                    Ok(())
+                }
+            };
+            result.map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("oplog", args)) => {
+            let current_dir = current_dir()?;
+            let conn =
+                connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
+            let limit = *args.get_one::<usize>("limit").unwrap();
+            oplog::oplog(&conn, limit).map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("undo", args)) => {
+            let current_dir = current_dir()?;
+            let conn =
+                connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
+            oplog::undo(&conn, args.get_flag("worktree")).map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("redo", args)) => {
+            let current_dir = current_dir()?;
+            let conn =
+                connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
+            oplog::redo(&conn, args.get_flag("worktree")).map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("op", sub_matches)) => {
+            let current_dir = current_dir()?;
+            let conn =
+                connect_lys(current_dir.as_path()).map_err(|e| Error::other(e.to_string()))?;
+            match sub_matches.subcommand() {
+                Some(("restore", args)) => {
+                    let id = *args.get_one::<i64>("id").unwrap();
+                    oplog::restore(&conn, id, args.get_flag("worktree"))
+                        .map_err(|e| Error::other(e.to_string()))
+                }
+                _ => {
+                    ok("Please use 'restore <id>'.");
+                    Ok(())
+                }
+            }
+        }
+        Some(("backup", args)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let (args, verify) = match args.subcommand() {
+                Some(("verify", sub)) => (sub, true),
+                _ => (args, false),
+            };
+            let dest = args.get_one::<String>("dest").map(String::as_str);
+            let result = backup::destination(&conn, dest).and_then(|dest| {
+                if verify {
+                    let keys = transfer::trusted_keys(&conn, &root, None);
+                    let generation = args.get_one::<u64>("generation").copied();
+                    backup::verify(&dest, generation, &keys).map(|_| ())
+                } else {
+                    backup::backup(&conn, &root, &dest).map(|_| ())
+                }
+            });
+            result.map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("restore-backup", args)) => {
+            let dest = Path::new(args.get_one::<String>("dest").unwrap());
+            let generation = args.get_one::<u64>("generation").copied();
+            let key = args.get_one::<String>("key").map(String::as_str);
+            backup::restore(&current_dir()?, dest, generation, key)
+                .map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("archive", args)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let season = args.get_one::<String>("season").unwrap();
+            let level = args.get_one::<i32>("level").copied();
+            archive::archive(&conn, &root, season, level)
+                .map(|_| ())
+                .map_err(|e| Error::other(e.to_string()))
+        }
+        Some(("chunks", args)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            if let Some(threshold) = args.get_one::<u64>("threshold") {
+                set_config(&conn, chunks::THRESHOLD, &threshold.to_string())
+                    .map_err(|e| Error::other(e.to_string()))?;
+            }
+            let usage = chunks::usage(&conn).map_err(|e| Error::other(e.to_string()))?;
+            let threshold = chunks::threshold(&conn);
+            ok(format!("Files of {threshold} bytes or more are stored in chunks").as_str());
+            ok(format!(
+                "{} blob(s), {} of them chunked, {} chunk(s)",
+                usage.blobs, usage.chunked, usage.chunks
+            )
+            .as_str());
+            ok(format!(
+                "{} bytes of content in {} bytes",
+                usage.logical, usage.stored
+            )
+            .as_str());
+            Ok(())
+        }
+        Some(("bundle", sub)) => {
+            let root = current_dir()?;
+            let conn = connect_lys(&root).map_err(|e| Error::other(e.to_string()))?;
+            let result = match sub.subcommand() {
+                Some(("create", args)) => {
+                    let refs: Vec<String> = args
+                        .get_many::<String>("refs")
+                        .map(|v| v.cloned().collect())
+                        .unwrap_or_default();
+                    let file = Path::new(args.get_one::<String>("file").unwrap());
+                    bundle::create(&conn, &root, file, &refs).map(|_| ())
+                }
+                Some((name, args)) => {
+                    let file = Path::new(args.get_one::<String>("file").unwrap());
+                    let key = args.get_one::<String>("key").map(String::as_str);
+                    if name == "verify" {
+                        bundle::verify(&conn, &root, file, key)
+                    } else {
+                        bundle::unbundle(&conn, &root, file, key)
+                    }
+                }
+                None => Ok(()),
+            };
+            result.map_err(|e| Error::other(e.to_string()))
+        }
         Some(("sync", args)) => {
             let current_dir = current_dir()?;
             let _conn =
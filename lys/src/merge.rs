@@ -0,0 +1,543 @@
+use crate::db::{commit_parents, commit_tree_hash, config, get_current_branch, write_config};
+use crate::utils::{ko, ok};
+use crate::vcs::{
+    Node, checkout, flatten_tree, get_blob_bytes_by_hash, get_branch_head_info, insert_into_tree,
+    record_commit, status, store_tree_recursive, write_state_diff,
+};
+use anyhow::Error;
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+use sqlite::{Connection, State};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+// Clés de config décrivant un merge interrompu par des conflits
+pub const MERGE_HEAD: &str = "merge_head";
+pub const MERGE_BRANCH: &str = "merge_branch";
+pub const MERGE_CONFLICTS: &str = "merge_conflicts";
+
+/// Vrai si `ancestor` est atteignable depuis `descendant` en remontant les parents.
+pub fn is_ancestor(conn: &Connection, ancestor: &str, descendant: &str) -> Result<bool, Error> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([descendant.to_string()]);
+    while let Some(hash) = queue.pop_front() {
+        if hash == ancestor {
+            return Ok(true);
+        }
+        if seen.insert(hash.clone()) {
+            queue.extend(commit_parents(conn, &hash)?);
+        }
+    }
+    Ok(false)
+}
+
+/// Ancêtre commun le plus proche de `a` et `b` (parcours en largeur).
+pub fn merge_base(conn: &Connection, a: &str, b: &str) -> Result<Option<String>, Error> {
+    let mut ancestors_a = HashSet::new();
+    let mut queue = VecDeque::from([a.to_string()]);
+    while let Some(hash) = queue.pop_front() {
+        if ancestors_a.insert(hash.clone()) {
+            queue.extend(commit_parents(conn, &hash)?);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([b.to_string()]);
+    while let Some(hash) = queue.pop_front() {
+        if ancestors_a.contains(&hash) {
+            return Ok(Some(hash));
+        }
+        if seen.insert(hash.clone()) {
+            queue.extend(commit_parents(conn, &hash)?);
+        }
+    }
+    Ok(None)
+}
+
+/// État complet (chemin -> (hash, mode)) d'un commit identifié par son hash.
+pub(crate) fn commit_state(
+    conn: &Connection,
+    hash: &str,
+) -> Result<HashMap<PathBuf, (String, i64)>, Error> {
+    let mut state = HashMap::new();
+    if let Some(tree_hash) = commit_tree_hash(conn, hash)? {
+        flatten_tree(conn, &tree_hash, PathBuf::new(), &mut state)?;
+    }
+    Ok(state)
+}
+
+fn blob_size(conn: &Connection, hash: &str) -> Result<u64, Error> {
+    let mut stmt = conn.prepare("SELECT size FROM store.blobs WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(stmt.read::<i64, _>(0)? as u64)
+    } else {
+        Ok(0)
+    }
+}
+
+fn blob_text(conn: &Connection, hash: Option<&str>) -> Result<Option<String>, Error> {
+    let Some(hash) = hash else {
+        return Ok(Some(String::new()));
+    };
+    let bytes = get_blob_bytes_by_hash(conn, hash)?.unwrap_or_default();
+    if content_inspector::inspect(&bytes).is_binary() {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
+
+/// Résultat de la fusion d'un fichier
+enum FileMerge {
+    // Le fichier disparaît du résultat
+    Removed,
+    // Contenu déjà présent dans le store
+    Existing { hash: String, mode: i64 },
+    // Nouveau contenu produit par la fusion ligne à ligne
+    Merged { content: String, mode: i64 },
+    // Conflit : contenu à écrire sur le disque (avec marqueurs si texte)
+    Conflict { content: Vec<u8> },
+}
+
+fn merge_file(
+    conn: &Connection,
+    base: Option<&(String, i64)>,
+    ours: Option<&(String, i64)>,
+    theirs: Option<&(String, i64)>,
+    labels: (&str, &str),
+) -> Result<FileMerge, Error> {
+    let hash_of = |e: Option<&(String, i64)>| e.map(|(h, _)| h.clone());
+    let (b, o, t) = (hash_of(base), hash_of(ours), hash_of(theirs));
+
+    // Cas triviaux : un seul côté a bougé, ou les deux ont fait la même chose
+    let pick = |e: Option<&(String, i64)>| match e {
+        Some((hash, mode)) => FileMerge::Existing {
+            hash: hash.clone(),
+            mode: *mode,
+        },
+        None => FileMerge::Removed,
+    };
+    if o == t {
+        // Même contenu : on garde le mode modifié s'il y en a un
+        let mode_changed = ours.map(|e| e.1) != base.map(|e| e.1);
+        return Ok(pick(if mode_changed { ours } else { theirs }));
+    }
+    if o == b {
+        return Ok(pick(theirs));
+    }
+    if t == b {
+        return Ok(pick(ours));
+    }
+
+    // Suppression d'un côté, modification de l'autre : conflit sur le fichier entier
+    let (Some(ours_entry), Some(theirs_entry)) = (ours, theirs) else {
+        let survivor = ours.or(theirs).map(|(h, _)| h.as_str()).unwrap_or_default();
+        let content = get_blob_bytes_by_hash(conn, survivor)?.unwrap_or_default();
+        return Ok(FileMerge::Conflict { content });
+    };
+
+    let mode = if base.map(|e| e.1) == Some(ours_entry.1) {
+        theirs_entry.1
+    } else {
+        ours_entry.1
+    };
+    let texts = (
+        blob_text(conn, b.as_deref())?,
+        blob_text(conn, Some(&ours_entry.0))?,
+        blob_text(conn, Some(&theirs_entry.0))?,
+    );
+    match texts {
+        (Some(base_text), Some(ours_text), Some(theirs_text)) => {
+            let (content, conflict) = merge_text(&base_text, &ours_text, &theirs_text, labels);
+            if conflict {
+                Ok(FileMerge::Conflict {
+                    content: content.into_bytes(),
+                })
+            } else {
+                Ok(FileMerge::Merged { content, mode })
+            }
+        }
+        // Binaire : impossible de fusionner, on garde notre version sur le disque
+        _ => {
+            let content = get_blob_bytes_by_hash(conn, &ours_entry.0)?.unwrap_or_default();
+            Ok(FileMerge::Conflict { content })
+        }
+    }
+}
+
+/// Zones de `base` modifiées par un côté : (plage dans base, plage dans le côté)
+fn hunks(base: &[&str], side: &[&str]) -> Vec<(Range<usize>, Range<usize>)> {
+    capture_diff_slices(Algorithm::Myers, base, side)
+        .into_iter()
+        .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+        .map(|op| (op.old_range(), op.new_range()))
+        .collect()
+}
+
+// Lignes du côté correspondant à base[start..end], d'après les hunks qui couvrent la zone
+fn side_lines<'a>(
+    base: &[&'a str],
+    side: &[&'a str],
+    region: &[(Range<usize>, Range<usize>)],
+    start: usize,
+    end: usize,
+) -> Vec<&'a str> {
+    match (region.first(), region.last()) {
+        (Some(first), Some(last)) => {
+            let from = first.1.start - (first.0.start - start);
+            let to = last.1.end + (end - last.0.end);
+            side[from..to].to_vec()
+        }
+        _ => base[start..end].to_vec(),
+    }
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !lines.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Fusion à trois voies ligne à ligne. Renvoie le texte fusionné et `true`
+/// si des marqueurs de conflit ont été insérés.
+pub fn merge_text(base: &str, ours: &str, theirs: &str, labels: (&str, &str)) -> (String, bool) {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let ours_hunks = hunks(&base_lines, &ours_lines);
+    let theirs_hunks = hunks(&base_lines, &theirs_lines);
+
+    let mut out = String::new();
+    let mut conflict = false;
+    let mut pos = 0;
+    let (mut i, mut j) = (0, 0);
+
+    while i < ours_hunks.len() || j < theirs_hunks.len() {
+        // On démarre une zone sur le hunk le plus tôt dans base
+        let take_ours = match (ours_hunks.get(i), theirs_hunks.get(j)) {
+            (Some(o), Some(t)) => o.0.start <= t.0.start,
+            (Some(_), None) => true,
+            _ => false,
+        };
+        let start = if take_ours {
+            ours_hunks[i].0.start
+        } else {
+            theirs_hunks[j].0.start
+        };
+        let mut end = start;
+        let (first_i, first_j) = (i, j);
+
+        // Puis on agrège tout ce qui chevauche (ou touche) la zone, des deux côtés
+        loop {
+            if let Some(o) = ours_hunks.get(i).filter(|o| o.0.start <= end) {
+                end = end.max(o.0.end);
+                i += 1;
+            } else if let Some(t) = theirs_hunks.get(j).filter(|t| t.0.start <= end) {
+                end = end.max(t.0.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        push_lines(&mut out, &base_lines[pos..start]);
+        let ours_region = &ours_hunks[first_i..i];
+        let theirs_region = &theirs_hunks[first_j..j];
+        let ours_text = side_lines(&base_lines, &ours_lines, ours_region, start, end);
+        let theirs_text = side_lines(&base_lines, &theirs_lines, theirs_region, start, end);
+
+        if theirs_region.is_empty() || ours_text == theirs_text {
+            push_lines(&mut out, &ours_text);
+        } else if ours_region.is_empty() {
+            push_lines(&mut out, &theirs_text);
+        } else {
+            conflict = true;
+            out.push_str(&format!("<<<<<<< {}\n", labels.0));
+            push_lines(&mut out, &ours_text);
+            out.push_str("=======\n");
+            push_lines(&mut out, &theirs_text);
+            out.push_str(&format!(">>>>>>> {}\n", labels.1));
+        }
+        pos = end;
+    }
+    push_lines(&mut out, &base_lines[pos..]);
+
+    // On respecte l'absence de saut de ligne final si les deux côtés l'ont
+    if !conflict && !ours.ends_with('\n') && !theirs.ends_with('\n') && out.ends_with('\n') {
+        out.pop();
+    }
+    (out, conflict)
+}
+
+/// Vrai si le fichier contient encore des marqueurs de conflit non résolus.
+pub fn has_conflict_markers(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| {
+            content.lines().any(|l| l.starts_with("<<<<<<< "))
+                && content.lines().any(|l| l.starts_with(">>>>>>> "))
+        })
+        .unwrap_or(false)
+}
+
+/// `lys resolve <chemins>` : retire des conflits du merge en cours les fichiers
+/// que l'utilisateur déclare résolus. `lys commit` refuse de conclure le merge
+/// tant qu'il en reste, marqueurs ou pas (suppression contre modification,
+/// binaire).
+pub fn resolve(conn: &Connection, paths: &[String]) -> Result<(), Error> {
+    if config(conn, MERGE_HEAD)?.is_empty() {
+        return Err(anyhow::anyhow!("No merge in progress."));
+    }
+    let mut pending: Vec<String> = config(conn, MERGE_CONFLICTS)?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    for path in paths {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        let Some(position) = pending.iter().position(|p| p == path) else {
+            return Err(anyhow::anyhow!("'{path}' has no pending conflict."));
+        };
+        if has_conflict_markers(Path::new(path)) {
+            return Err(anyhow::anyhow!("'{path}' still has conflict markers."));
+        }
+        pending.remove(position);
+        ok(format!("{path}: resolved").as_str());
+    }
+    write_config(conn, MERGE_CONFLICTS, &pending.join("\n"))?;
+    Ok(())
+}
+
+/// Résultat d'une fusion à trois voies, avant d'être écrit sur le disque.
+pub(crate) struct ThreeWay {
+    pub tree: Node,
+    // chemin -> (hash, mode) des fichiers fusionnés proprement
+    pub state: HashMap<String, (String, i64)>,
+    // Contenu à écrire (avec marqueurs si texte) pour chaque conflit
+    pub conflicts: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// Fusionne fichier par fichier `ours` et `theirs` depuis `base`. Les blobs
+/// produits sont insérés dans le store : à appeler dans une transaction.
+pub(crate) fn three_way(
+    conn: &Connection,
+    base: &HashMap<PathBuf, (String, i64)>,
+    ours: &HashMap<PathBuf, (String, i64)>,
+    theirs: &HashMap<PathBuf, (String, i64)>,
+    labels: (&str, &str),
+) -> Result<ThreeWay, Error> {
+    let paths: BTreeSet<&PathBuf> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+
+    let mut merged = ThreeWay {
+        tree: Node::Directory {
+            children: BTreeMap::new(),
+        },
+        state: HashMap::new(),
+        conflicts: Vec::new(),
+    };
+    for path in paths {
+        let file = merge_file(
+            conn,
+            base.get(path),
+            ours.get(path),
+            theirs.get(path),
+            labels,
+        )?;
+        let (hash, mode) = match file {
+            FileMerge::Removed => continue,
+            FileMerge::Existing { hash, mode } => (hash, mode),
+            FileMerge::Merged { content, mode } => {
+                let hash = blake3::hash(content.as_bytes()).to_hex().to_string();
+                crate::db::insert_blob_with_conn(conn, &hash, content.as_bytes())?;
+                (hash, mode)
+            }
+            FileMerge::Conflict { content } => {
+                merged.conflicts.push((path.clone(), content));
+                continue;
+            }
+        };
+        let size = blob_size(conn, &hash)?;
+        insert_into_tree(&mut merged.tree, path, hash.clone(), mode as u32, size);
+        merged
+            .state
+            .insert(path.to_string_lossy().to_string(), (hash, mode));
+    }
+    Ok(merged)
+}
+
+/// Fusionne `source` dans `target` (utilisé par `feat finish` et `hotfix finish`).
+/// Renvoie `true` si la fusion est terminée, `false` si des conflits attendent
+/// d'être résolus puis validés avec `lys commit`.
+pub fn merge_branch(conn: &Connection, source: &str, target: &str) -> Result<bool, Error> {
+    if !config(conn, MERGE_HEAD)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A merge is already in progress. Resolve the conflicts and run 'lys commit'."
+        ));
+    }
+
+    let (_, source_hash) = get_branch_head_info(conn, source)?;
+    if source_hash.is_empty() {
+        return Err(anyhow::anyhow!("Branch '{source}' does not exist."));
+    }
+
+    // 1. On se place sur la cible avec un arbre de travail propre
+    let current_dir = std::env::current_dir()?;
+    if get_current_branch(conn)? == target {
+        if !status(conn, &current_dir.to_string_lossy(), target)?.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Please commit your changes before finishing '{source}'."
+            ));
+        }
+    } else {
+        ok(format!("Switching to '{target}' to merge changes...").as_str());
+        checkout(conn, target)?;
+        if get_current_branch(conn)? != target {
+            return Err(anyhow::anyhow!(
+                "Please commit your changes before finishing '{source}'."
+            ));
+        }
+    }
+    let (_, target_hash) = get_branch_head_info(conn, target)?;
+
+    // 2. Rien à faire : la cible contient déjà la source
+    if !target_hash.is_empty() && is_ancestor(conn, &source_hash, &target_hash)? {
+        ok(format!("'{target}' is already up to date with '{source}'").as_str());
+        return Ok(true);
+    }
+
+    let target_state = commit_state(conn, &target_hash)?;
+    let source_state = commit_state(conn, &source_hash)?;
+
+    // 3. Fast-forward seulement si la cible est un ancêtre de la source
+    if target_hash.is_empty() || is_ancestor(conn, &target_hash, &source_hash)? {
+        write_state_diff(
+            conn,
+            &to_string_map(&target_state),
+            &to_string_map(&source_state),
+        )?;
+        let query = "UPDATE branches SET head_commit_id = (SELECT id FROM commits WHERE hash = ?) WHERE name = ?";
+        let mut stmt = conn.prepare(query)?;
+        stmt.bind((1, source_hash.as_str()))?;
+        stmt.bind((2, target))?;
+        stmt.next()?;
+        ok("Fast-forward merge complete");
+        return Ok(true);
+    }
+
+    // 4. Vraie fusion à trois voies
+    let base_state = match merge_base(conn, &target_hash, &source_hash)? {
+        Some(base) => commit_state(conn, &base)?,
+        None => HashMap::new(),
+    };
+    conn.execute("BEGIN TRANSACTION;")?;
+    let ThreeWay {
+        tree: merged_tree,
+        state: merged_state,
+        conflicts,
+    } = three_way(
+        conn,
+        &base_state,
+        &target_state,
+        &source_state,
+        (target, source),
+    )?;
+
+    // Le disque reçoit toutes les parties fusionnées proprement
+    let current_files = to_string_map(&target_state);
+    write_state_diff(conn, &current_files, &merged_state)?;
+
+    if conflicts.is_empty() {
+        let root_hash = store_tree_recursive(conn, "ROOT", &merged_tree)?;
+        let message = format!("Merge branch '{source}' into {target}");
+        let commit_hash = record_commit(
+            conn,
+            &root_hash,
+            &[target_hash, source_hash],
+            &message,
+            &crate::commit::author(),
+        )?;
+        conn.execute("COMMIT;")?;
+        ok(format!("Merge commit {} created", &commit_hash[0..7]).as_str());
+        return Ok(true);
+    }
+    conn.execute("COMMIT;")?;
+
+    // 5. Conflits : on écrit les marqueurs et on mémorise le merge en cours
+    let mut listed = Vec::new();
+    for (path, content) in &conflicts {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        ko(format!("CONFLICT {}", path.display()).as_str());
+        listed.push(path.to_string_lossy().to_string());
+    }
+    write_config(conn, MERGE_HEAD, &source_hash)?;
+    write_config(conn, MERGE_BRANCH, source)?;
+    write_config(conn, MERGE_CONFLICTS, &listed.join("\n"))?;
+    ko(
+        "Automatic merge failed; fix conflicts, mark them with 'lys resolve <path>', then run 'lys commit'.",
+    );
+    Ok(false)
+}
+
+pub(crate) fn to_string_map(
+    state: &HashMap<PathBuf, (String, i64)>,
+) -> HashMap<String, (String, i64)> {
+    state
+        .iter()
+        .map(|(p, v)| (p.to_string_lossy().to_string(), v.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELS: (&str, &str) = ("main", "feature/x");
+
+    #[test]
+    fn merges_changes_on_distinct_lines() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "A\nb\nc\nd\n";
+        let theirs = "a\nb\nc\nD\n";
+        let (merged, conflict) = merge_text(base, ours, theirs, LABELS);
+        assert!(!conflict);
+        assert_eq!(merged, "A\nb\nc\nD\n");
+    }
+
+    #[test]
+    fn identical_changes_do_not_conflict() {
+        let base = "a\nb\n";
+        let both = "a\nB\nextra\n";
+        let (merged, conflict) = merge_text(base, both, both, LABELS);
+        assert!(!conflict);
+        assert_eq!(merged, both);
+    }
+
+    #[test]
+    fn overlapping_changes_produce_markers() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+        let (merged, conflict) = merge_text(base, ours, theirs, LABELS);
+        assert!(conflict);
+        assert_eq!(
+            merged,
+            "a\n<<<<<<< main\nours\n=======\ntheirs\n>>>>>>> feature/x\nc\n"
+        );
+    }
+
+    #[test]
+    fn keeps_missing_final_newline() {
+        let (merged, conflict) = merge_text("a\nb\nc", "a\nb\nc\nd", "z\nb\nc", LABELS);
+        assert!(!conflict);
+        assert_eq!(merged, "z\nb\nc\nd");
+    }
+}
@@ -0,0 +1,282 @@
+use crate::db::get_current_branch;
+use crate::export::{export_history, mapped_oid};
+use crate::utils::{ko, ok};
+use crate::vcs::get_branch_head_info;
+use anyhow::Error;
+use git2::{Cred, CredentialType, FetchOptions, Oid, PushOptions, RemoteCallbacks, Repository};
+use sqlite::Connection;
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+
+// Identifiants : agent SSH, puis l'assistant d'identification de Git
+fn callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY)
+            && let Some(username) = username
+        {
+            return Cred::ssh_key_from_agent(username);
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && let Ok(config) = git2::Config::open_default()
+        {
+            return Cred::credential_helper(&config, url, username);
+        }
+        Cred::default()
+    });
+    callbacks
+}
+
+/// Dernier commit de `head` (lui compris) déjà présent des deux côtés :
+/// `(hash Lys, OID Git)`.
+fn mirror_base(
+    conn: &Connection,
+    repo: &Repository,
+    head: &str,
+) -> Result<Option<(String, Oid)>, Error> {
+    let mut queue = VecDeque::from([head.to_string()]);
+    let mut seen = HashSet::new();
+    while let Some(hash) = queue.pop_front() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        if let Some(oid) = mapped_oid(conn, &hash)?
+            && let Ok(oid) = Oid::from_str(&oid)
+            && repo.find_commit(oid).is_ok()
+        {
+            return Ok(Some((hash, oid)));
+        }
+        queue.extend(crate::db::commit_parents(conn, &hash)?);
+    }
+    Ok(None)
+}
+
+// Commits Git atteignables depuis `tip` mais pas depuis `base`
+fn count_git_commits(repo: &Repository, tip: Oid, base: Oid) -> Result<usize, Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(tip)?;
+    revwalk.hide(base)?;
+    Ok(revwalk.count())
+}
+
+/// Synchronise la branche Lys `branch` avec la branche courante du dépôt Git
+/// de `root` et son remote `origin` : les nouveaux commits Git sont importés,
+/// les commits Lys sont exportés puis poussés. La correspondance des commits
+/// vit dans `git_map` ; si les deux côtés ont avancé, rien n'est touché.
+pub fn mirror_git(conn: &Connection, root: &Path, branch: &str) -> Result<(), Error> {
+    let repo = Repository::open(root)
+        .map_err(|_| anyhow::anyhow!("No Git repository in {}.", root.display()))?;
+    let git_head = repo.head()?;
+    let git_branch = match git_head.shorthand() {
+        Some(name) if git_head.is_branch() => name.to_string(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "The Git HEAD is detached; check out a branch."
+            ));
+        }
+    };
+    if get_current_branch(conn)? != branch {
+        return Err(anyhow::anyhow!(
+            "Switch to '{branch}' before mirroring (lys checkout {branch})."
+        ));
+    }
+    let changes = crate::vcs::status(conn, &root.to_string_lossy(), branch)?;
+    if !changes.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Working tree has changes. Commit or stash before mirroring."
+        ));
+    }
+    let (_, head) = get_branch_head_info(conn, branch)?;
+    if head.is_empty() {
+        return Err(anyhow::anyhow!("Branch '{branch}' has no commits."));
+    }
+
+    // 1. Fetch de la branche amont, sans le binaire git
+    let tracking = format!("refs/remotes/origin/{git_branch}");
+    let mut remote = repo
+        .find_remote("origin")
+        .map_err(|_| anyhow::anyhow!("The Git repository has no 'origin' remote."))?;
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks());
+    remote.fetch(
+        &[format!("+refs/heads/{git_branch}:{tracking}")],
+        Some(&mut fetch_options),
+        None,
+    )?;
+    let upstream = repo.refname_to_id(&tracking)?;
+
+    // 2. Point commun et commits nouveaux de chaque côté
+    let Some((base_hash, base_oid)) = mirror_base(conn, &repo, &head)? else {
+        return Err(anyhow::anyhow!(
+            "'{branch}' shares no commit with the Git repository; clone it with 'lys clone' first."
+        ));
+    };
+    if upstream != base_oid && !repo.graph_descendant_of(upstream, base_oid)? {
+        return Err(anyhow::anyhow!(
+            "origin/{git_branch} was rewritten upstream; refusing to mirror."
+        ));
+    }
+    let local_new =
+        crate::transfer::missing_commits(conn, std::slice::from_ref(&head), &[base_hash])?.len();
+    let upstream_new = count_git_commits(&repo, upstream, base_oid)?;
+
+    let synced = match (local_new, upstream_new) {
+        (0, 0) => {
+            ok("Already up to date.");
+            return Ok(());
+        }
+        (0, _) => {
+            // 3a. Import Git -> Lys puis mise à jour de l'arbre de travail commun
+            let (upstream_str, base_str) = (upstream.to_string(), base_oid.to_string());
+            crate::import::import_updates_from_repo(
+                root,
+                root,
+                &base_str,
+                Some(&upstream_str),
+                branch,
+            )
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            let target = repo.find_object(upstream, None)?;
+            repo.reset(&target, git2::ResetType::Hard, None)?;
+            ok(format!("Imported {upstream_new} commit(s) from origin/{git_branch}").as_str());
+            upstream
+        }
+        (_, 0) => {
+            // 3b. Export Lys -> Git puis push
+            let (oid, written) = export_history(conn, &repo, &head)?;
+            let oid = oid.ok_or_else(|| anyhow::anyhow!("Unable to export {head}."))?;
+            let target = repo.find_object(oid, None)?;
+            repo.reset(&target, git2::ResetType::Mixed, None)?;
+
+            let refspec = format!("refs/heads/{git_branch}:refs/heads/{git_branch}");
+            let rejected = RefCell::new(None);
+            {
+                let mut push_callbacks = callbacks();
+                push_callbacks.push_update_reference(|_, status| {
+                    *rejected.borrow_mut() = status.map(str::to_string);
+                    Ok(())
+                });
+                let mut push_options = PushOptions::new();
+                push_options.remote_callbacks(push_callbacks);
+                remote.push(&[refspec], Some(&mut push_options))?;
+            }
+            if let Some(reason) = rejected.into_inner() {
+                return Err(anyhow::anyhow!(
+                    "origin refused {git_branch}: {reason}. Run 'lys mirror git' again."
+                ));
+            }
+            repo.reference(&tracking, oid, true, "lys mirror")?;
+            ok(format!("Exported {written} commit(s) to origin/{git_branch}").as_str());
+            oid
+        }
+        (local, upstream) => {
+            ko(format!(
+                "'{branch}' has {local} commit(s) not in Git and origin/{git_branch} has {upstream} not in Lys"
+            )
+            .as_str());
+            return Err(anyhow::anyhow!(
+                "Both sides diverged since {}; merge them in Lys or Git before mirroring.",
+                &base_oid.to_string()[..7]
+            ));
+        }
+    };
+
+    // Même clé que `lys pull`, qui reste utilisable après un miroir
+    crate::db::write_config(conn, "git_origin_head", &synced.to_string())?;
+    crate::oplog::record(conn, "mirror")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Commit sur `master` d'un dépôt nu : un seul fichier change
+    fn git_commit(repo: &Repository, file: &str, content: &str, message: &str) -> Oid {
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let base = parent.as_ref().map(|c| c.tree().unwrap());
+        let mut builder = repo.treebuilder(base.as_ref()).unwrap();
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        builder.insert(file, blob, 0o100644).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("Upstream", "up@lys").unwrap();
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("refs/heads/master"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    // Dans le clone : commit Lys sur `origin` (le dossier courant est le dépôt)
+    fn lys_commit(
+        conn: &Connection,
+        root: &Path,
+        content: &str,
+        message: &str,
+    ) -> Result<(), Error> {
+        let _cwd = crate::utils::cwd_guard();
+        std::env::set_current_dir(root)?;
+        std::fs::write("NOTES.md", content)?;
+        crate::vcs::commit(conn, message, AUTHOR)
+    }
+
+    // Clone Git -> commit Lys exporté -> commit amont importé -> divergence refusée
+    #[test]
+    fn mirrors_both_ways_and_refuses_divergence() {
+        let upstream_dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let upstream = Repository::init_bare(upstream_dir.path()).unwrap();
+        git_commit(&upstream, "README.md", "# demo\n", "first");
+
+        let root = work.path().join("clone");
+        crate::import::import_from_git(
+            upstream_dir.path().to_str().unwrap(),
+            &root,
+            None,
+            false,
+            true,
+            true,
+        )
+        .unwrap();
+        let conn = crate::db::connect_lys(&root).unwrap();
+        crate::crypto::generate_keypair(&root).unwrap();
+        crate::db::write_config(&conn, "current_branch", "origin").unwrap();
+        mirror_git(&conn, &root, "origin").unwrap();
+
+        // Lys -> Git
+        lys_commit(&conn, &root, "from lys\n", "local").unwrap();
+        mirror_git(&conn, &root, "origin").unwrap();
+        let pushed = upstream.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(pushed.message(), Some("local"));
+        assert_eq!(pushed.author().email(), Some("tester@lys"));
+
+        // Git -> Lys
+        let second = git_commit(&upstream, "README.md", "# demo\nupstream\n", "upstream");
+        mirror_git(&conn, &root, "origin").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("README.md")).unwrap(),
+            "# demo\nupstream\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("NOTES.md")).unwrap(),
+            "from lys\n"
+        );
+        let (_, head) = get_branch_head_info(&conn, "origin").unwrap();
+        assert_eq!(mapped_oid(&conn, &head).unwrap(), Some(second.to_string()));
+
+        // Les deux côtés avancent : refus, sans rien toucher
+        let again = git_commit(&upstream, "README.md", "# demo\nupstream\nagain\n", "again");
+        lys_commit(&conn, &root, "from lys\nagain\n", "local again").unwrap();
+        assert!(mirror_git(&conn, &root, "origin").is_err());
+        assert_eq!(upstream.refname_to_id("refs/heads/master").unwrap(), again);
+        let (_, local) = get_branch_head_info(&conn, "origin").unwrap();
+        assert_eq!(mapped_oid(&conn, &local).unwrap(), None);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::db::{config, get_current_branch, remove_config, write_config};
+use crate::utils::{ko, ok};
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use sqlite::{Connection, State};
+use std::collections::BTreeMap;
+
+// Opération dont la vue correspond à l'état actuel des refs
+const OPLOG_HEAD: &str = "oplog_head";
+// Pile des opérations annulées (la dernière est rejouée par redo)
+const OPLOG_REDO: &str = "oplog_redo";
+
+/// Photographie complète des refs à un instant donné.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct ViewState {
+    pub branches: BTreeMap<String, String>,
+    pub current_branch: String,
+    pub tags: BTreeMap<String, String>,
+    // Ancien format : seul le HEAD du commit était noté
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+}
+
+impl ViewState {
+    // Les entrées de l'ancien format ne permettent pas de restaurer les refs
+    fn is_complete(&self) -> bool {
+        !self.current_branch.is_empty()
+    }
+}
+
+pub struct Operation {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub kind: String,
+    pub view: ViewState,
+    pub timestamp: String,
+}
+
+/// Capture la vue actuelle : têtes de branches, branche courante et tags.
+pub fn snapshot(conn: &Connection) -> Result<ViewState, Error> {
+    let mut view = ViewState {
+        current_branch: get_current_branch(conn)?,
+        ..ViewState::default()
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT b.name, c.hash FROM branches b JOIN commits c ON b.head_commit_id = c.id",
+    )?;
+    while let Ok(State::Row) = stmt.next() {
+        view.branches
+            .insert(stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?);
+    }
+
+    let mut stmt =
+        conn.prepare("SELECT t.name, c.hash FROM tags t JOIN commits c ON t.commit_id = c.id")?;
+    while let Ok(State::Row) = stmt.next() {
+        view.tags
+            .insert(stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?);
+    }
+    Ok(view)
+}
+
+/// Commits que nomment les vues du journal : têtes de branches, tags et HEAD
+/// de l'ancien format. `lys undo` et `lys op restore` peuvent y revenir.
+pub fn referenced_commits(conn: &Connection) -> Result<Vec<String>, Error> {
+    let mut stmt = conn.prepare("SELECT view_state FROM operations_log")?;
+    let mut hashes = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        let view: ViewState = serde_json::from_str(&stmt.read::<String, _>(0)?).unwrap_or_default();
+        hashes.extend(view.branches.into_values());
+        hashes.extend(view.tags.into_values());
+        hashes.extend(view.head);
+    }
+    Ok(hashes)
+}
+
+/// Ajoute une opération au journal avec la vue courante. Toute opération
+/// nouvelle efface la pile de redo.
+pub fn record(conn: &Connection, kind: &str) -> Result<i64, Error> {
+    let view = snapshot(conn)?;
+    let parent = head_id(conn)?;
+
+    let mut stmt = conn.prepare(
+        "INSERT INTO operations_log (operation_type, view_state, parent_id) VALUES (?, ?, ?)",
+    )?;
+    stmt.bind((1, kind))?;
+    stmt.bind((2, serde_json::to_string(&view)?.as_str()))?;
+    stmt.bind((3, parent))?;
+    stmt.next()?;
+
+    let mut stmt_id = conn.prepare("SELECT last_insert_rowid()")?;
+    stmt_id.next()?;
+    let id: i64 = stmt_id.read(0)?;
+
+    write_config(conn, OPLOG_HEAD, &id.to_string())?;
+    remove_config(conn, OPLOG_REDO)?;
+    Ok(id)
+}
+
+fn load(conn: &Connection, id: i64) -> Result<Option<Operation>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, parent_id, operation_type, view_state, timestamp FROM operations_log WHERE id = ?",
+    )?;
+    stmt.bind((1, id))?;
+    if let Ok(State::Row) = stmt.next() {
+        let raw: String = stmt.read("view_state")?;
+        Ok(Some(Operation {
+            id: stmt.read("id")?,
+            parent_id: stmt.read("parent_id")?,
+            kind: stmt.read("operation_type")?,
+            view: serde_json::from_str(&raw).unwrap_or_default(),
+            timestamp: stmt.read("timestamp")?,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+// L'opération courante : celle mémorisée, sinon la plus récente
+fn head_id(conn: &Connection) -> Result<Option<i64>, Error> {
+    if let Ok(id) = config(conn, OPLOG_HEAD)?.parse::<i64>() {
+        return Ok(Some(id));
+    }
+    let mut stmt = conn.prepare("SELECT MAX(id) FROM operations_log")?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(stmt.read::<Option<i64>, _>(0)?)
+    } else {
+        Ok(None)
+    }
+}
+
+// Les entrées antérieures à parent_id se suivent simplement par id
+fn parent_of(conn: &Connection, op: &Operation) -> Result<Option<i64>, Error> {
+    if op.parent_id.is_some() {
+        return Ok(op.parent_id);
+    }
+    let mut stmt = conn.prepare("SELECT MAX(id) FROM operations_log WHERE id < ?")?;
+    stmt.bind((1, op.id))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(stmt.read::<Option<i64>, _>(0)?)
+    } else {
+        Ok(None)
+    }
+}
+
+fn redo_stack(conn: &Connection) -> Result<Vec<i64>, Error> {
+    Ok(config(conn, OPLOG_REDO)?
+        .split(',')
+        .filter_map(|id| id.parse().ok())
+        .collect())
+}
+
+fn save_redo_stack(conn: &Connection, stack: &[i64]) -> Result<(), Error> {
+    if stack.is_empty() {
+        remove_config(conn, OPLOG_REDO)?;
+    } else {
+        let ids: Vec<String> = stack.iter().map(i64::to_string).collect();
+        write_config(conn, OPLOG_REDO, &ids.join(","))?;
+    }
+    Ok(())
+}
+
+fn commit_id(conn: &Connection, hash: &str) -> Result<Option<i64>, Error> {
+    let mut stmt = conn.prepare("SELECT id FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<i64, _>(0)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Remet les refs (et si demandé l'arbre de travail) dans l'état de `view`.
+fn apply_view(conn: &Connection, view: &ViewState, worktree: bool) -> Result<(), Error> {
+    if !view.is_complete() {
+        return Err(anyhow::anyhow!(
+            "This operation predates view snapshots and cannot be restored."
+        ));
+    }
+
+    let current_branch = get_current_branch(conn)?;
+    let current_head = crate::db::branch_head_id(conn, &current_branch)?;
+    if worktree {
+        let current_dir = std::env::current_dir()?;
+        let changes = crate::vcs::status(conn, current_dir.to_str().unwrap(), &current_branch)?;
+        if !changes.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Your changes would be overwritten. Commit or stash them first."
+            ));
+        }
+    }
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    // Une erreur en chemin n'applique rien : les refs restent celles d'avant
+    let result = (|| -> Result<(), Error> {
+        // Branches : on retire celles qui n'existaient pas, on repositionne les autres
+        let existing = crate::db::list_branches(conn);
+        for name in existing.iter().filter(|n| !view.branches.contains_key(*n)) {
+            let mut stmt = conn.prepare("DELETE FROM branches WHERE name = ?")?;
+            stmt.bind((1, name.as_str()))?;
+            stmt.next()?;
+        }
+        for (name, hash) in &view.branches {
+            let Some(id) = commit_id(conn, hash)? else {
+                ko(format!("commit {hash} of '{name}' is not in this season, skipped").as_str());
+                continue;
+            };
+            let mut stmt = conn.prepare(
+                "INSERT INTO branches (name, head_commit_id) VALUES (?, ?)
+                 ON CONFLICT(name) DO UPDATE SET head_commit_id = excluded.head_commit_id",
+            )?;
+            stmt.bind((1, name.as_str()))?;
+            stmt.bind((2, id))?;
+            stmt.next()?;
+        }
+
+        // Tags : même principe, en gardant la description des tags inchangés
+        for name in crate::db::list_tags(conn) {
+            let unchanged = view.tags.get(&name).is_some_and(|hash| {
+                crate::db::tag_hash(conn, &name).as_deref() == Some(hash.as_str())
+            });
+            if !unchanged {
+                let mut stmt = conn.prepare("DELETE FROM tags WHERE name = ?")?;
+                stmt.bind((1, name.as_str()))?;
+                stmt.next()?;
+            }
+        }
+        for (name, hash) in &view.tags {
+            if let Some(id) = commit_id(conn, hash)? {
+                let mut stmt =
+                    conn.prepare("INSERT OR IGNORE INTO tags (name, commit_id) VALUES (?, ?)")?;
+                stmt.bind((1, name.as_str()))?;
+                stmt.bind((2, id))?;
+                stmt.next()?;
+            }
+        }
+
+        write_config(conn, "current_branch", &view.current_branch)?;
+        Ok(())
+    })();
+    match result {
+        Ok(()) => conn.execute("COMMIT;")?,
+        Err(_) => conn.execute("ROLLBACK;")?,
+    }
+    result?;
+
+    // Arbre de travail : on passe du HEAD d'avant au HEAD restauré
+    let target_head = crate::db::branch_head_id(conn, &view.current_branch)?;
+    if target_head != current_head {
+        if worktree {
+            let current_files = crate::vcs::get_manifest_map(conn, current_head)?;
+            let target_files = crate::vcs::get_manifest_map(conn, target_head)?;
+            crate::vcs::write_state_diff(conn, &current_files, &target_files)?;
+            ok("Working tree updated");
+        } else {
+            ok("Working tree left untouched (use --worktree to update it)");
+        }
+    }
+    Ok(())
+}
+
+/// Affiche le journal des opérations, de la plus récente à la plus ancienne.
+pub fn oplog(conn: &Connection, limit: usize) -> Result<(), Error> {
+    let head = head_id(conn)?;
+    let mut stmt = conn.prepare("SELECT id FROM operations_log ORDER BY id DESC LIMIT ?")?;
+    stmt.bind((1, limit as i64))?;
+    let mut ids = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        ids.push(stmt.read::<i64, _>(0)?);
+    }
+    if ids.is_empty() {
+        ok("no operations yet");
+        return Ok(());
+    }
+
+    for id in ids {
+        let Some(op) = load(conn, id)? else { continue };
+        let marker = if Some(op.id) == head { "@" } else { " " };
+        let detail = if op.view.is_complete() {
+            format!(
+                "on '{}' ({} branches, {} tags)",
+                op.view.current_branch,
+                op.view.branches.len(),
+                op.view.tags.len()
+            )
+        } else {
+            let head = op.view.head.unwrap_or_default();
+            format!("head {}", head.get(..7).unwrap_or(&head))
+        };
+        ok(format!(
+            "{marker} {:>4}  {:<14} {detail}  {}",
+            op.id, op.kind, op.timestamp
+        )
+        .as_str());
+    }
+    Ok(())
+}
+
+/// Revient à la vue de l'opération précédant l'opération courante.
+pub fn undo(conn: &Connection, worktree: bool) -> Result<(), Error> {
+    let Some(current) = head_id(conn)?
+        .map(|id| load(conn, id))
+        .transpose()?
+        .flatten()
+    else {
+        ok("Nothing to undo");
+        return Ok(());
+    };
+    let Some(previous) = parent_of(conn, &current)?
+        .map(|id| load(conn, id))
+        .transpose()?
+        .flatten()
+    else {
+        ok("Nothing to undo");
+        return Ok(());
+    };
+
+    apply_view(conn, &previous.view, worktree)?;
+    let mut stack = redo_stack(conn)?;
+    stack.push(current.id);
+    save_redo_stack(conn, &stack)?;
+    write_config(conn, OPLOG_HEAD, &previous.id.to_string())?;
+    ok(format!("Undid operation #{} ({})", current.id, current.kind).as_str());
+    Ok(())
+}
+
+/// Rejoue la dernière opération annulée.
+pub fn redo(conn: &Connection, worktree: bool) -> Result<(), Error> {
+    let mut stack = redo_stack(conn)?;
+    let Some(id) = stack.pop() else {
+        ok("Nothing to redo");
+        return Ok(());
+    };
+    let op = load(conn, id)?.ok_or_else(|| anyhow::anyhow!("Operation #{id} not found."))?;
+
+    apply_view(conn, &op.view, worktree)?;
+    save_redo_stack(conn, &stack)?;
+    write_config(conn, OPLOG_HEAD, &op.id.to_string())?;
+    ok(format!("Redid operation #{} ({})", op.id, op.kind).as_str());
+    Ok(())
+}
+
+/// Restaure la vue d'une opération quelconque ; la restauration est elle-même journalisée.
+pub fn restore(conn: &Connection, id: i64, worktree: bool) -> Result<(), Error> {
+    let op = load(conn, id)?.ok_or_else(|| anyhow::anyhow!("Operation #{id} not found."))?;
+    apply_view(conn, &op.view, worktree)?;
+    record(conn, "restore")?;
+    ok(format!("Restored view of operation #{} ({})", op.id, op.kind).as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Undo puis redo ramènent refs et arbre de travail ; un échec à mi-chemin
+    // ne laisse aucune ref à moitié restaurée
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let (_repo, conn) = crate::utils::test_repo();
+        let head = |conn: &Connection| crate::vcs::get_branch_head_info(conn, "main").unwrap().1;
+        std::fs::write("a.txt", "one\n").unwrap();
+        crate::vcs::commit(&conn, "one", AUTHOR).unwrap();
+        let first = head(&conn);
+        crate::vcs::tag_create(&conn, "v1", None).unwrap();
+        std::fs::write("a.txt", "two\n").unwrap();
+        crate::vcs::commit(&conn, "two", AUTHOR).unwrap();
+        let second = head(&conn);
+
+        conn.execute("ALTER TABLE tags RENAME TO tags_away")
+            .unwrap();
+        let failed = undo(&conn, true);
+        let kept = head(&conn);
+        conn.execute("ALTER TABLE tags_away RENAME TO tags")
+            .unwrap();
+
+        undo(&conn, true).unwrap();
+        let undone = (head(&conn), std::fs::read_to_string("a.txt").unwrap());
+        redo(&conn, true).unwrap();
+        let redone = (head(&conn), std::fs::read_to_string("a.txt").unwrap());
+
+        assert!(failed.is_err());
+        assert_eq!(kept, second);
+        assert_eq!(undone, (first, "one\n".to_string()));
+        assert_eq!(redone, (second, "two\n".to_string()));
+        assert_eq!(crate::db::tag_hash(&conn, "v1"), Some(undone.0));
+    }
+}
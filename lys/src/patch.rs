@@ -0,0 +1,787 @@
+use crate::db::{commit_parents, get_current_branch};
+use crate::diff::{Change, header, is_binary};
+use crate::merge::{ThreeWay, commit_state, three_way, to_string_map};
+use crate::utils::{ko, ok};
+use crate::vcs::{
+    FileStatus, compute_commit_hash, get_blob_bytes_by_hash, get_branch_head_info, insert_commit,
+    record_commit, resolve_commit, status, store_tree_recursive, tree_changes, write_state_diff,
+};
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+
+// En-tête signé par l'exportateur, toujours la dernière ligne de l'en-tête
+const PATCH_SIGNATURE: &str = "Lys-Patch-Signature: ";
+// Mode d'un fichier créé par un patch qui n'en précise pas
+const DEFAULT_MODE: i64 = 0o100644;
+
+type FileState = HashMap<PathBuf, (String, i64)>;
+
+/// Un commit tel que transporté par un fichier `.patch`.
+#[derive(Debug)]
+pub struct Patch {
+    pub commit: String,
+    pub parents: Vec<String>,
+    pub tree: String,
+    pub author: String,
+    pub timestamp: String,
+    pub message: String,
+    // Signature Ed25519 du commit d'origine (absente pour un commit non signé)
+    pub signature: Option<String>,
+    // Clé publique de l'exportateur, affichée si elle n'est pas de confiance
+    pub key: Option<String>,
+    files: Vec<FilePatch>,
+    digest: String,
+    patch_signature: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct FilePatch {
+    old_path: Option<PathBuf>,
+    new_path: Option<PathBuf>,
+    old_hash: Option<String>,
+    mode: Option<i64>,
+    hunks: Vec<Hunk>,
+    // Contenu complet d'un fichier binaire, en hexadécimal dans le patch
+    literal: Option<Vec<u8>>,
+    // `copy from` : l'original reste en place
+    copy: bool,
+}
+
+#[derive(Debug)]
+struct Hunk {
+    // Première ligne de l'ancien contenu touchée par le hunk (0 = début)
+    old_start: usize,
+    lines: Vec<(char, String)>,
+}
+
+struct CommitRow {
+    tree: String,
+    author: String,
+    message: String,
+    timestamp: String,
+    signature: Option<String>,
+}
+
+fn commit_row(conn: &Connection, hash: &str) -> Result<CommitRow, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT tree_hash, author, message, timestamp, signature FROM commits WHERE hash = ?",
+    )?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(CommitRow {
+            tree: stmt.read(0)?,
+            author: stmt.read(1)?,
+            message: stmt.read(2)?,
+            timestamp: stmt.read(3)?,
+            signature: stmt.read::<Option<String>, _>(4)?,
+        })
+    } else {
+        Err(anyhow::anyhow!("Commit {hash} not found."))
+    }
+}
+
+fn digest(text: &str) -> String {
+    blake3::hash(text.as_bytes()).to_hex().to_string()
+}
+
+// Nom de fichier lisible tiré de la première ligne du message
+fn slug(subject: &str) -> String {
+    let mut out = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches('-');
+    out.chars()
+        .take(52)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+fn write_file_diff(
+    conn: &Connection,
+    out: &mut String,
+    change: &Change,
+    modes: (&FileState, &FileState),
+) -> Result<(), Error> {
+    header(out, change);
+    let mode = |state: &FileState, path: &Option<PathBuf>| {
+        path.as_ref()
+            .and_then(|p| state.get(p))
+            .map(|(_, mode)| *mode)
+    };
+    match change.status {
+        'A' => {
+            let mode = mode(modes.1, &change.new_path).unwrap_or(DEFAULT_MODE);
+            let _ = writeln!(out, "new file mode {mode:o}");
+        }
+        'D' => {
+            let mode = mode(modes.0, &change.old_path).unwrap_or(DEFAULT_MODE);
+            let _ = writeln!(out, "deleted file mode {mode:o}");
+        }
+        _ => {}
+    }
+    let mode = mode(modes.1, &change.new_path)
+        .or(mode(modes.0, &change.old_path))
+        .unwrap_or(DEFAULT_MODE);
+    let _ = writeln!(
+        out,
+        "index {}..{} {mode:o}",
+        change.old_hash.as_deref().unwrap_or("0000000"),
+        change.new_hash.as_deref().unwrap_or("0000000")
+    );
+    if change.old_hash == change.new_hash {
+        return Ok(());
+    }
+
+    let blob = |hash: &Option<String>| -> Result<Vec<u8>, Error> {
+        match hash {
+            Some(hash) => get_blob_bytes_by_hash(conn, hash)?
+                .ok_or_else(|| anyhow::anyhow!("Blob {hash} is missing from the store.")),
+            None => Ok(Vec::new()),
+        }
+    };
+    let (before, after) = (blob(&change.old_hash)?, blob(&change.new_hash)?);
+    if is_binary(&before) || is_binary(&after) {
+        // Le patch reste autonome : le nouveau contenu voyage en entier
+        let _ = writeln!(out, "Binary files differ\nliteral {}", hex::encode(&after));
+    } else {
+        crate::diff::patch(
+            out,
+            change,
+            &String::from_utf8_lossy(&before),
+            &String::from_utf8_lossy(&after),
+        );
+    }
+    Ok(())
+}
+
+/// Texte d'un patch autonome pour `hash` : message, auteur, date et signature du
+/// commit, puis le diff face à son premier parent. Le tout est signé par la clé
+/// du dépôt qui l'exporte.
+pub fn format(
+    conn: &Connection,
+    root: &Path,
+    hash: &str,
+    (number, total): (usize, usize),
+) -> Result<String, Error> {
+    let row = commit_row(conn, hash)?;
+    let parents = commit_parents(conn, hash)?;
+    let before = match parents.first() {
+        Some(parent) => commit_state(conn, parent)?,
+        None => HashMap::new(),
+    };
+    let after = commit_state(conn, hash)?;
+
+    let mut out = String::new();
+    let subject = row.message.lines().next().unwrap_or("");
+    let _ = writeln!(out, "From {hash} Mon Sep 17 00:00:00 2001");
+    let _ = writeln!(out, "From: {}", row.author);
+    let _ = writeln!(out, "Date: {}", row.timestamp);
+    let _ = writeln!(out, "Subject: [PATCH {number}/{total}] {subject}");
+    let _ = writeln!(out, "Lys-Commit: {hash}");
+    let _ = writeln!(out, "Lys-Parents: {}", parents.join(" "));
+    let _ = writeln!(out, "Lys-Tree: {}", row.tree);
+    if let Some(signature) = &row.signature {
+        let _ = writeln!(out, "Lys-Signature: {signature}");
+    }
+    if let Ok(key) = crate::crypto::public_key(root) {
+        let _ = writeln!(out, "Lys-Key: {}", hex::encode(key));
+    }
+
+    let mut body = format!("\n{}\n---\n", row.message);
+    for change in tree_changes(conn, &before, &after) {
+        write_file_diff(conn, &mut body, &change, (&before, &after))?;
+    }
+    let signature = crate::crypto::sign_message(root, &digest(&format!("{out}{body}")))
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let _ = writeln!(out, "{PATCH_SIGNATURE}{signature}");
+    Ok(out + &body)
+}
+
+/// `lys format-patch <range>` : un fichier `NNNN-sujet.patch` par commit de
+/// `A..B` (ou de `A..HEAD` pour une seule révision), parents d'abord.
+pub fn format_range(conn: &Connection, range: &str, output: &Path) -> Result<Vec<PathBuf>, Error> {
+    let resolve = |reference: &str| -> Result<String, Error> {
+        let reference = if reference.is_empty() {
+            "HEAD"
+        } else {
+            reference
+        };
+        resolve_commit(conn, reference)?
+            .ok_or_else(|| anyhow::anyhow!("Unknown revision '{reference}'."))
+    };
+    let (from, to) = range.split_once("..").unwrap_or((range, "HEAD"));
+    let (from, to) = (resolve(from)?, resolve(to)?);
+    let mut commits = Vec::new();
+    for hash in crate::transfer::missing_commits(conn, &[to], &[from])? {
+        if commit_parents(conn, &hash)?.len() > 1 {
+            ko(format!("skipping merge commit {}", &hash[..7]).as_str());
+        } else {
+            commits.push(hash);
+        }
+    }
+
+    let root = std::env::current_dir()?;
+    std::fs::create_dir_all(output)?;
+    let mut written = Vec::new();
+    for (index, hash) in commits.iter().enumerate() {
+        let text = format(conn, &root, hash, (index + 1, commits.len()))?;
+        let subject = commit_row(conn, hash)?.message;
+        let name = format!(
+            "{:04}-{}.patch",
+            index + 1,
+            slug(subject.lines().next().unwrap_or(""))
+        );
+        let path = output.join(name);
+        std::fs::write(&path, text)?;
+        ok(path.display().to_string().as_str());
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn parse_mode(value: &str) -> Option<i64> {
+    i64::from_str_radix(value.trim(), 8).ok()
+}
+
+// `@@ -12,3 +12,4 @@` : ligne de départ dans l'ancien contenu
+fn parse_hunk_start(line: &str) -> Option<usize> {
+    let old = line.strip_prefix("@@ -")?.split_whitespace().next()?;
+    let (start, count) = old.split_once(',').unwrap_or((old, "1"));
+    let start: usize = start.parse().ok()?;
+    // Un hunk sans ancienne ligne insère après `start`
+    Some(if count == "0" {
+        start
+    } else {
+        start.saturating_sub(1)
+    })
+}
+
+fn parse_files(diff: &str) -> Result<Vec<FilePatch>, Error> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let path = |value: &str, prefix: &str| -> Option<PathBuf> {
+        value.strip_prefix(prefix).map(PathBuf::from)
+    };
+    for line in diff.split_inclusive('\n') {
+        let text = line.trim_end_matches('\n');
+        if let Some(names) = text.strip_prefix("diff --git a/") {
+            let (old, new) = names
+                .split_once(" b/")
+                .ok_or_else(|| anyhow::anyhow!("Malformed diff header: {text}"))?;
+            files.push(FilePatch {
+                old_path: Some(PathBuf::from(old)),
+                new_path: Some(PathBuf::from(new)),
+                ..FilePatch::default()
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if let Some(hunk) = file.hunks.last_mut()
+            && let Some(tag @ (' ' | '-' | '+')) = text.chars().next()
+        {
+            hunk.lines.push((tag, line[1..].to_string()));
+        } else if text.starts_with("\\ ") {
+            // « \ No newline at end of file » concerne la ligne précédente
+            if let Some((_, last)) = file.hunks.last_mut().and_then(|h| h.lines.last_mut()) {
+                last.pop();
+            }
+        } else if text.starts_with("@@ ") {
+            let old_start = parse_hunk_start(text)
+                .ok_or_else(|| anyhow::anyhow!("Malformed hunk header: {text}"))?;
+            file.hunks.push(Hunk {
+                old_start,
+                lines: Vec::new(),
+            });
+        } else if let Some(mode) = text.strip_prefix("new file mode ") {
+            file.old_path = None;
+            file.mode = parse_mode(mode);
+        } else if text.starts_with("deleted file mode ") {
+            file.new_path = None;
+        } else if text.starts_with("copy from ") {
+            file.copy = true;
+        } else if let Some(index) = text.strip_prefix("index ") {
+            let mut parts = index.split_whitespace();
+            let old = parts.next().and_then(|range| range.split_once(".."));
+            if let Some((old, _)) = old.filter(|(old, _)| !old.starts_with("0000000")) {
+                file.old_hash = Some(old.to_string());
+            }
+            file.mode = file.mode.or(parts.next().and_then(parse_mode));
+        } else if let Some(content) = text.strip_prefix("literal ") {
+            file.literal = Some(hex::decode(content.trim())?);
+        } else if let Some(old) = path(text, "--- a/") {
+            file.old_path = Some(old);
+        } else if let Some(new) = path(text, "+++ b/") {
+            file.new_path = Some(new);
+        }
+    }
+    // Un patch ne touche qu'à l'arbre de travail : ni `..`, ni chemin absolu, ni `.lys`
+    for path in files
+        .iter()
+        .flat_map(|file| file.old_path.iter().chain(&file.new_path))
+    {
+        let inside = path.components().next().is_some()
+            && path
+                .components()
+                .all(|c| matches!(c, Component::Normal(name) if name != ".lys"));
+        if !inside {
+            return Err(anyhow::anyhow!(
+                "Patch touches a path outside the working tree: {}",
+                path.display()
+            ));
+        }
+    }
+    Ok(files)
+}
+
+/// Lit un fichier produit par `lys format-patch`.
+pub fn parse(text: &str) -> Result<Patch, Error> {
+    let (head, body) = text
+        .split_once("\n\n")
+        .ok_or_else(|| anyhow::anyhow!("Not a lys patch: no header."))?;
+    let mut fields = HashMap::new();
+    let mut signed = String::new();
+    let mut patch_signature = None;
+    for line in head.lines() {
+        if let Some(signature) = line.strip_prefix(PATCH_SIGNATURE) {
+            patch_signature = Some(signature.trim().to_string());
+            continue;
+        }
+        signed.push_str(line);
+        signed.push('\n');
+        if let Some((key, value)) = line.split_once(": ") {
+            fields.insert(key, value.to_string());
+        }
+    }
+    let field = |key: &str| -> Result<String, Error> {
+        fields
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Not a lys patch: missing '{key}' header."))
+    };
+
+    // Le message s'arrête au `---` qui précède le premier fichier
+    let split = body
+        .match_indices("\n---\n")
+        .map(|(at, _)| at)
+        .find(|&at| {
+            let rest = &body[at + 5..];
+            rest.is_empty() || rest.starts_with("diff --git ")
+        })
+        .ok_or_else(|| anyhow::anyhow!("Not a lys patch: no '---' separator."))?;
+    Ok(Patch {
+        commit: field("Lys-Commit")?,
+        parents: field("Lys-Parents")?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        tree: field("Lys-Tree")?,
+        author: field("From")?,
+        timestamp: field("Date")?,
+        message: body[..split].to_string(),
+        signature: fields.get("Lys-Signature").cloned(),
+        key: fields.get("Lys-Key").cloned(),
+        files: parse_files(&body[split + 5..])?,
+        digest: digest(&format!("{signed}\n{body}")),
+        patch_signature,
+    })
+}
+
+/// Un patch n'est appliqué que si son contenu correspond au hash du commit et
+/// que ses signatures viennent de clés de confiance (la nôtre, `trusted_keys`,
+/// les remotes, ou `extra`).
+pub fn verify(
+    conn: &Connection,
+    root: &Path,
+    patch: &Patch,
+    extra: Option<&str>,
+) -> Result<(), Error> {
+    let short = &patch.commit[..7.min(patch.commit.len())];
+    let expected = compute_commit_hash(
+        &patch.parents,
+        &patch.tree,
+        &patch.author,
+        &patch.message,
+        &patch.timestamp,
+    );
+    if expected != patch.commit {
+        return Err(anyhow::anyhow!(
+            "Patch for {short} does not match its commit hash: it was altered."
+        ));
+    }
+    let keys = crate::transfer::trusted_keys(conn, root, extra);
+    let trusted = |message: &str, signature: &str| {
+        keys.iter()
+            .any(|key| crate::crypto::verify_with_key(key, message, signature))
+    };
+    let signed = patch
+        .patch_signature
+        .as_deref()
+        .is_some_and(|signature| trusted(&patch.digest, signature));
+    if !signed {
+        return Err(anyhow::anyhow!(
+            "Patch for {short} is not signed by a trusted key{}.",
+            patch
+                .key
+                .as_deref()
+                .map(|key| format!(" (signer: {key})"))
+                .unwrap_or_default()
+        ));
+    }
+    if let Some(signature) = &patch.signature
+        && !trusted(&patch.commit, signature)
+    {
+        return Err(anyhow::anyhow!(
+            "Commit {short} carries a signature from an untrusted key."
+        ));
+    }
+    Ok(())
+}
+
+// Position où `old` apparaît dans `lines`, au plus près de `expected`
+fn locate(lines: &[&str], old: &[&str], expected: usize, min: usize) -> Option<usize> {
+    let fits =
+        |at: usize| at >= min && at + old.len() <= lines.len() && lines[at..at + old.len()] == *old;
+    (0..=lines.len()).find_map(|distance| {
+        [
+            expected.checked_add(distance),
+            expected.checked_sub(distance),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|&at| fits(at))
+    })
+}
+
+/// Applique les hunks à `content` ; `None` si le contexte ne correspond pas.
+fn apply_hunks(content: &[u8], file: &FilePatch) -> Option<Vec<u8>> {
+    if let Some(literal) = &file.literal {
+        return Some(literal.clone());
+    }
+    let text = std::str::from_utf8(content).ok()?;
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut out = String::new();
+    let (mut cursor, mut offset) = (0usize, 0isize);
+    for hunk in &file.hunks {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|(tag, _)| *tag != '+')
+            .map(|(_, line)| line.as_str())
+            .collect();
+        let expected = hunk.old_start.saturating_add_signed(offset).max(cursor);
+        let at = locate(&lines, &old, expected, cursor)?;
+        offset = at as isize - hunk.old_start as isize;
+        out.extend(lines[cursor..at].iter().copied());
+        for (tag, line) in &hunk.lines {
+            if *tag != '-' {
+                out.push_str(line);
+            }
+        }
+        cursor = at + old.len();
+    }
+    out.extend(lines[cursor..].iter().copied());
+    Some(out.into_bytes())
+}
+
+/// Base et côté « theirs » de la fusion à trois voies. Un fichier qui
+/// s'applique proprement sur la branche prend son contenu actuel pour base ;
+/// sinon on repart de la version d'origine (`index`) si elle est connue.
+fn sides(
+    conn: &Connection,
+    patch: &Patch,
+    ours: &FileState,
+) -> Result<(FileState, FileState), Error> {
+    let (mut base, mut theirs) = (ours.clone(), ours.clone());
+    for file in &patch.files {
+        let current = file.old_path.as_ref().and_then(|path| ours.get(path));
+        let direct = match (&file.old_path, current) {
+            (None, _) => Some((None, Vec::new())),
+            (Some(_), Some((hash, mode))) => get_blob_bytes_by_hash(conn, hash)?
+                .map(|bytes| (Some((hash.clone(), *mode)), bytes)),
+            (Some(_), None) => None,
+        };
+        let applied = direct.and_then(|(pre, bytes)| Some((pre, apply_hunks(&bytes, file)?)));
+        let (pre, post) = match applied {
+            Some(applied) => applied,
+            None => {
+                let name = file.old_path.as_deref().unwrap_or(Path::new("")).display();
+                let original = file
+                    .old_hash
+                    .as_ref()
+                    .and_then(|hash| Some((hash, get_blob_bytes_by_hash(conn, hash).ok()??)));
+                let Some((hash, bytes)) = original else {
+                    return Err(anyhow::anyhow!(
+                        "Patch does not apply to {name} and its original version is unknown."
+                    ));
+                };
+                let post = apply_hunks(&bytes, file).ok_or_else(|| {
+                    anyhow::anyhow!("Patch does not apply to the original version of {name}.")
+                })?;
+                let mode = current.map(|(_, mode)| *mode).or(file.mode);
+                (Some((hash.clone(), mode.unwrap_or(DEFAULT_MODE))), post)
+            }
+        };
+
+        if let Some(old) = file.old_path.as_ref().filter(|_| !file.copy) {
+            match pre {
+                Some(pre) => base.insert(old.clone(), pre),
+                None => base.remove(old),
+            };
+            theirs.remove(old);
+        }
+        if let Some(new) = &file.new_path {
+            if file.old_path.as_ref() != Some(new) {
+                base.remove(new);
+            }
+            let hash = blake3::hash(&post).to_hex().to_string();
+            crate::db::insert_blob_with_conn(conn, &hash, &post)?;
+            let mode = file
+                .mode
+                .or(current.map(|(_, mode)| *mode))
+                .unwrap_or(DEFAULT_MODE);
+            theirs.insert(new.clone(), (hash, mode));
+        }
+    }
+    Ok((base, theirs))
+}
+
+/// Issue de l'application d'un patch.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    // Rien à enregistrer (`lys apply`) ou commit créé (`lys am`)
+    Applied(Option<String>),
+    // Le contenu du patch est déjà présent
+    Empty,
+    Conflicts(Vec<PathBuf>),
+}
+
+/// Applique un patch vérifié sur l'arbre de travail et, si `record`, enregistre
+/// le commit. Sur le parent d'origine, le commit est rejoué tel quel (même hash,
+/// même signature) ; ailleurs il est recréé et signé par notre clé.
+pub fn apply(conn: &Connection, patch: &Patch, record: bool) -> Result<Outcome, Error> {
+    let branch = get_current_branch(conn)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+    let root = std::env::current_dir()?;
+    // Les fichiers non suivis (les patchs eux-mêmes, souvent) ne gênent que
+    // si le patch les crée
+    let touched: Vec<&PathBuf> = patch
+        .files
+        .iter()
+        .filter_map(|f| f.new_path.as_ref())
+        .collect();
+    let dirty = status(conn, &root.to_string_lossy(), &branch)?
+        .into_iter()
+        .any(|change| match change {
+            FileStatus::New(path) => touched.contains(&&path),
+            FileStatus::Modified(..) | FileStatus::Deleted(..) | FileStatus::Renamed(..) => true,
+            FileStatus::Unchanged => false,
+        });
+    if dirty {
+        return Err(anyhow::anyhow!(
+            "Working tree has changes. Commit or stash before applying a patch."
+        ));
+    }
+    let ours = if head.is_empty() {
+        HashMap::new()
+    } else {
+        commit_state(conn, &head)?
+    };
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    let merged = sides(conn, patch, &ours).and_then(|(base, theirs)| {
+        let label = &patch.commit[..7.min(patch.commit.len())];
+        three_way(conn, &base, &ours, &theirs, (&branch, label))
+    });
+    let ThreeWay {
+        tree,
+        state,
+        conflicts,
+    } = match merged {
+        Ok(merged) => merged,
+        Err(e) => {
+            conn.execute("ROLLBACK;")?;
+            return Err(e);
+        }
+    };
+    let current = to_string_map(&ours);
+    if conflicts.is_empty() && state == current {
+        conn.execute("COMMIT;")?;
+        return Ok(Outcome::Empty);
+    }
+    write_state_diff(conn, &current, &state)?;
+
+    let mut committed = None;
+    if conflicts.is_empty() && record {
+        let root_hash = store_tree_recursive(conn, "ROOT", &tree)?;
+        let parents: Vec<String> = [head.clone()]
+            .into_iter()
+            .filter(|h| !h.is_empty())
+            .collect();
+        if parents == patch.parents && root_hash == patch.tree {
+            insert_commit(
+                conn,
+                &patch.commit,
+                &root_hash,
+                &parents,
+                (&patch.author, &patch.message, &patch.timestamp),
+                patch.signature.as_deref(),
+            )?;
+            committed = Some(patch.commit.clone());
+        } else {
+            let hash = record_commit(conn, &root_hash, &parents, &patch.message, &patch.author)?;
+            committed = Some(hash);
+        }
+    }
+    conn.execute("COMMIT;")?;
+
+    if conflicts.is_empty() {
+        return Ok(Outcome::Applied(committed));
+    }
+    for (path, content) in &conflicts {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    }
+    Ok(Outcome::Conflicts(
+        conflicts.into_iter().map(|(path, _)| path).collect(),
+    ))
+}
+
+// Un dossier désigne ses fichiers `.patch`, dans l'ordre de leurs numéros
+fn patch_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "patch"))
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// `lys apply <patch>` (arbre de travail seulement) et `lys am <patch>...`
+/// (un commit par patch). S'arrête au premier patch en conflit.
+pub fn run(
+    conn: &Connection,
+    paths: &[PathBuf],
+    record: bool,
+    key: Option<&str>,
+) -> Result<(), Error> {
+    crate::pick::ensure_idle(conn)?;
+    let root = std::env::current_dir()?;
+    let files = patch_files(paths)?;
+    let mut applied = 0;
+    for (index, path) in files.iter().enumerate() {
+        let patch = parse(&std::fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        verify(conn, &root, &patch, key)?;
+        let subject = patch.message.lines().next().unwrap_or("").to_string();
+        match apply(conn, &patch, record)? {
+            Outcome::Applied(Some(hash)) => {
+                ok(format!("Applied {} as {}: {subject}", path.display(), &hash[..7]).as_str());
+                applied += 1;
+            }
+            Outcome::Applied(None) => ok(format!("Applied {}", path.display()).as_str()),
+            Outcome::Empty => ok(format!("Skipped {}: already applied", path.display()).as_str()),
+            Outcome::Conflicts(conflicts) => {
+                for conflict in conflicts {
+                    ko(format!("CONFLICT {}", conflict.display()).as_str());
+                }
+                if record {
+                    ko("Fix the conflicts and run 'lys commit', then apply the remaining patches:");
+                    for rest in &files[index + 1..] {
+                        ko(rest.display().to_string().as_str());
+                    }
+                }
+                if applied > 0 {
+                    crate::oplog::record(conn, "am")?;
+                }
+                return Err(anyhow::anyhow!("{} did not apply cleanly.", path.display()));
+            }
+        }
+    }
+    if applied > 0 {
+        crate::oplog::record(conn, "am")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Export d'un commit, rejeu à l'identique puis sur une base divergente
+    #[test]
+    fn formats_verifies_and_applies_patches() {
+        let (repo, conn) = crate::utils::test_repo();
+        let out = tempfile::tempdir().unwrap();
+
+        std::fs::write("a.txt", "one\ntwo\nthree\nfour\nfive\nsix\nseven\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        let (_, first) = get_branch_head_info(&conn, "main").unwrap();
+        crate::vcs::create_branch(&conn, "same").unwrap();
+        crate::vcs::create_branch(&conn, "moved").unwrap();
+        std::fs::write("a.txt", "one\ntwo\nthree\nfour\nfive\nsix\nSEVEN\n").unwrap();
+        std::fs::write("b.txt", "new").unwrap();
+        crate::vcs::commit(&conn, "second\n\nWith a body.", AUTHOR).unwrap();
+        let (_, second) = get_branch_head_info(&conn, "main").unwrap();
+
+        let written = format_range(&conn, &first[..8], out.path()).unwrap();
+        assert_eq!(written.len(), 1);
+        let text = std::fs::read_to_string(&written[0]).unwrap();
+        let patch = parse(&text).unwrap();
+        assert_eq!(patch.commit, second);
+        assert_eq!(patch.message, "second\n\nWith a body.");
+        verify(&conn, repo.path(), &patch, None).unwrap();
+
+        // Toucher au diff invalide la signature du patch
+        let forged = parse(&text.replace("+SEVEN", "+EVIL")).unwrap();
+        assert!(verify(&conn, repo.path(), &forged, None).is_err());
+        // Un chemin qui sort de l'arbre de travail est refusé dès la lecture
+        assert!(parse(&text.replace("b.txt", "x/c.txt")).is_ok());
+        for path in [
+            "../b.txt",
+            "/tmp/b.txt",
+            ".lys/b.txt",
+            "x/.lys/b.txt",
+            "./b.txt",
+        ] {
+            assert!(parse(&text.replace("b.txt", path)).is_err(), "{path}");
+        }
+
+        // Sur le parent d'origine, le commit est rejoué avec son hash
+        crate::vcs::checkout(&conn, "same").unwrap();
+        let outcome = apply(&conn, &patch, true).unwrap();
+        assert_eq!(outcome, Outcome::Applied(Some(second.clone())));
+        assert_eq!(std::fs::read_to_string("b.txt").unwrap(), "new");
+
+        // Le contexte a changé : repli sur la fusion à trois voies, nouveau commit
+        crate::vcs::checkout(&conn, "moved").unwrap();
+        std::fs::write("a.txt", "zero\none\ntwo\nthree\nfour\nFIVE\nsix\nseven\n").unwrap();
+        crate::vcs::commit(&conn, "diverge", AUTHOR).unwrap();
+        let outcome = apply(&conn, &patch, true).unwrap();
+        let merged = std::fs::read_to_string("a.txt").unwrap();
+        let audited = crate::crypto::audit(&conn).unwrap();
+        assert!(matches!(outcome, Outcome::Applied(Some(hash)) if hash != second));
+        assert_eq!(merged, "zero\none\ntwo\nthree\nfour\nFIVE\nsix\nSEVEN\n");
+        assert!(audited);
+    }
+}
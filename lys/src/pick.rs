@@ -0,0 +1,362 @@
+use crate::db::{commit_parents, config, get_current_branch, remove_config, write_config};
+use crate::merge::{ThreeWay, commit_state, has_conflict_markers, three_way, to_string_map};
+use crate::utils::{ko, ok};
+use crate::vcs::{
+    get_branch_head_info, record_commit, resolve_commit, status, store_tree_recursive,
+    write_state_diff,
+};
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Clés de config décrivant un cherry-pick ou un revert interrompu par des conflits
+pub const PICK_HEAD: &str = "pick_head";
+pub const PICK_KIND: &str = "pick_kind";
+pub const PICK_MESSAGE: &str = "pick_message";
+pub const PICK_CONFLICTS: &str = "pick_conflicts";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+    CherryPick,
+    Revert,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::CherryPick => "cherry-pick",
+            Kind::Revert => "revert",
+        }
+    }
+}
+
+/// Issue de l'application d'un commit sur la branche courante.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Committed(String),
+    // Le delta est déjà présent : aucun commit créé
+    Empty,
+    Conflicts(Vec<PathBuf>),
+}
+
+type FileState = HashMap<PathBuf, (String, i64)>;
+
+pub(crate) fn commit_message(conn: &Connection, hash: &str) -> Result<String, Error> {
+    let mut stmt = conn.prepare("SELECT message FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(stmt.read::<String, _>(0)?)
+    } else {
+        Err(anyhow::anyhow!("Commit {hash} not found."))
+    }
+}
+
+/// Message du nouveau commit, qui cite toujours le commit source.
+pub fn message_for(conn: &Connection, source: &str, kind: Kind) -> Result<String, Error> {
+    let original = commit_message(conn, source)?;
+    Ok(match kind {
+        Kind::CherryPick => format!(
+            "{}\n\n(cherry picked from commit {source})",
+            original.trim_end()
+        ),
+        Kind::Revert => format!(
+            "Revert \"{}\"\n\nThis reverts commit {source}.",
+            original.lines().next().unwrap_or("")
+        ),
+    })
+}
+
+// Base et côté « theirs » de la fusion : le delta du commit face à son premier
+// parent, ou son inverse pour un revert
+fn delta(conn: &Connection, source: &str, kind: Kind) -> Result<(FileState, FileState), Error> {
+    let after = commit_state(conn, source)?;
+    let before = match commit_parents(conn, source)?.first() {
+        Some(parent) => commit_state(conn, parent)?,
+        None => HashMap::new(),
+    };
+    Ok(match kind {
+        Kind::CherryPick => (before, after),
+        Kind::Revert => (after, before),
+    })
+}
+
+pub(crate) fn ensure_idle(conn: &Connection) -> Result<(), Error> {
+    if !config(conn, crate::rebase::REBASE_BRANCH)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A rebase is in progress. Run 'lys rebase --continue' or 'lys rebase --abort'."
+        ));
+    }
+    let kind = config(conn, PICK_KIND)?;
+    if !kind.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A {kind} is in progress. Run 'lys {kind} --continue' or 'lys {kind} --abort'."
+        ));
+    }
+    if !config(conn, crate::merge::MERGE_HEAD)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A merge is in progress. Resolve the conflicts and run 'lys commit'."
+        ));
+    }
+    Ok(())
+}
+
+/// Applique le delta de `source` (ou son inverse) à la branche courante et crée
+/// un commit signé. En cas de conflit, les marqueurs sont écrits sur le disque
+/// et l'opération attend `--continue` ou `--abort`.
+pub fn apply(
+    conn: &Connection,
+    source: &str,
+    kind: Kind,
+    message: &str,
+    author: &str,
+) -> Result<Outcome, Error> {
+    let branch = get_current_branch(conn)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+    if head.is_empty() {
+        return Err(anyhow::anyhow!(
+            "'{branch}' has no commits; check out a branch first."
+        ));
+    }
+    let root = std::env::current_dir()?;
+    if !status(conn, &root.to_string_lossy(), &branch)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Working tree has changes. Commit or stash before a {}.",
+            kind.name()
+        ));
+    }
+
+    let (base, theirs) = delta(conn, source, kind)?;
+    let ours = commit_state(conn, &head)?;
+    let label = &source[..7.min(source.len())];
+    conn.execute("BEGIN TRANSACTION;")?;
+    // Une erreur avant COMMIT annule tout : la transaction ne reste pas ouverte
+    let mut conflicts = Vec::new();
+    let result = (|| -> Result<Option<Outcome>, Error> {
+        let ThreeWay {
+            tree,
+            state,
+            conflicts: found,
+        } = three_way(conn, &base, &ours, &theirs, (&branch, label))?;
+        let current = to_string_map(&ours);
+        write_state_diff(conn, &current, &state)?;
+
+        if !found.is_empty() {
+            conflicts = found;
+            return Ok(None);
+        }
+        if state == current {
+            return Ok(Some(Outcome::Empty));
+        }
+        let root_hash = store_tree_recursive(conn, "ROOT", &tree)?;
+        let hash = record_commit(conn, &root_hash, &[head], message, author)?;
+        Ok(Some(Outcome::Committed(hash)))
+    })();
+    match result {
+        Ok(_) => conn.execute("COMMIT;")?,
+        Err(_) => conn.execute("ROLLBACK;")?,
+    }
+    if let Some(outcome) = result? {
+        return Ok(outcome);
+    }
+
+    let mut listed = Vec::new();
+    for (path, content) in &conflicts {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+        listed.push(path.to_string_lossy().to_string());
+    }
+    write_config(conn, PICK_HEAD, source)?;
+    write_config(conn, PICK_KIND, kind.name())?;
+    write_config(conn, PICK_MESSAGE, message)?;
+    write_config(conn, PICK_CONFLICTS, &listed.join("\n"))?;
+    Ok(Outcome::Conflicts(
+        conflicts.into_iter().map(|(path, _)| path).collect(),
+    ))
+}
+
+fn report(outcome: &Outcome, kind: Kind, source: &str) {
+    match outcome {
+        Outcome::Committed(hash) => ok(format!(
+            "{} of {} committed as {}",
+            kind.name(),
+            &source[..7],
+            &hash[..7]
+        )
+        .as_str()),
+        Outcome::Empty => ok(format!(
+            "Nothing to {}: the changes of {} are already there",
+            kind.name(),
+            &source[..7]
+        )
+        .as_str()),
+        Outcome::Conflicts(paths) => {
+            for path in paths {
+                ko(format!("CONFLICT {}", path.display()).as_str());
+            }
+            ko(format!(
+                "Fix the conflicts, then run 'lys {0} --continue' (or 'lys {0} --abort').",
+                kind.name()
+            )
+            .as_str());
+        }
+    }
+}
+
+/// `lys cherry-pick <hash>` et `lys revert <hash>`.
+pub fn run(conn: &Connection, reference: &str, kind: Kind) -> Result<Outcome, Error> {
+    ensure_idle(conn)?;
+    let source = resolve_commit(conn, reference)?
+        .ok_or_else(|| anyhow::anyhow!("Commit '{reference}' not found."))?;
+    let message = message_for(conn, &source, kind)?;
+    let outcome = apply(conn, &source, kind, &message, &crate::commit::author())?;
+    report(&outcome, kind, &source);
+    if matches!(outcome, Outcome::Committed(_)) {
+        crate::oplog::record(conn, kind.name())?;
+    }
+    Ok(outcome)
+}
+
+fn pending(conn: &Connection, kind: Kind) -> Result<String, Error> {
+    let source = config(conn, PICK_HEAD)?;
+    if source.is_empty() || config(conn, PICK_KIND)? != kind.name() {
+        return Err(anyhow::anyhow!("No {} in progress.", kind.name()));
+    }
+    Ok(source)
+}
+
+pub(crate) fn clear(conn: &Connection) -> Result<(), Error> {
+    for key in [PICK_HEAD, PICK_KIND, PICK_MESSAGE, PICK_CONFLICTS] {
+        remove_config(conn, key)?;
+    }
+    Ok(())
+}
+
+/// `--continue` : crée le commit une fois les marqueurs de conflit retirés.
+pub fn resume(conn: &Connection, kind: Kind) -> Result<(), Error> {
+    pending(conn, kind)?;
+    let conflicts = config(conn, PICK_CONFLICTS)?;
+    let unresolved: Vec<&str> = conflicts
+        .lines()
+        .filter(|p| has_conflict_markers(Path::new(p)))
+        .collect();
+    if !unresolved.is_empty() {
+        for path in &unresolved {
+            ko(format!("unresolved conflict in {path}").as_str());
+        }
+        return Err(anyhow::anyhow!(
+            "Fix the conflict markers before continuing."
+        ));
+    }
+    let saved: Vec<(&str, String)> = [PICK_HEAD, PICK_KIND, PICK_MESSAGE, PICK_CONFLICTS]
+        .into_iter()
+        .map(|key| Ok((key, config(conn, key)?)))
+        .collect::<Result<_, Error>>()?;
+    clear(conn)?;
+    let result = crate::vcs::commit(conn, &saved[2].1, &crate::commit::author());
+    if result.is_err() {
+        // L'opération reste en cours si le commit échoue
+        for (key, value) in &saved {
+            write_config(conn, key, value)?;
+        }
+    }
+    result
+}
+
+/// `--abort` : remet les fichiers touchés dans l'état de la tête de branche.
+pub fn abort(conn: &Connection, kind: Kind) -> Result<(), Error> {
+    let source = pending(conn, kind)?;
+    let branch = get_current_branch(conn)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+    let ours = commit_state(conn, &head)?;
+    let (base, theirs) = delta(conn, &source, kind)?;
+
+    // On rejoue la fusion pour savoir quels fichiers elle a écrits
+    conn.execute("BEGIN TRANSACTION;")?;
+    let merged = three_way(conn, &base, &ours, &theirs, ("", ""));
+    conn.execute("ROLLBACK;")?;
+    let ThreeWay {
+        mut state,
+        conflicts,
+        ..
+    } = merged?;
+    for (path, _) in conflicts {
+        // Hash vide : toujours réécrit depuis la tête (ou supprimé)
+        state.insert(path.to_string_lossy().to_string(), (String::new(), 0));
+    }
+    write_state_diff(conn, &state, &to_string_map(&ours))?;
+    clear(conn)?;
+    ok(format!("{} of {} aborted", kind.name(), &source[..7]).as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Portage d'un correctif vers une autre branche, revert, puis conflit annulé
+    #[test]
+    fn picks_reverts_and_aborts() {
+        let (_repo, conn) = crate::utils::test_repo();
+
+        std::fs::write("a.txt", "one\ntwo\nthree\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        crate::vcs::create_branch(&conn, "stable").unwrap();
+        std::fs::write("a.txt", "one\ntwo\nTHREE\n").unwrap();
+        crate::vcs::commit(&conn, "feature", AUTHOR).unwrap();
+        std::fs::write("a.txt", "ONE\ntwo\nTHREE\n").unwrap();
+        crate::vcs::commit(&conn, "fix", AUTHOR).unwrap();
+        let (_, fix) = get_branch_head_info(&conn, "main").unwrap();
+
+        crate::vcs::checkout(&conn, "stable").unwrap();
+        let outcome = run(&conn, &fix[..8], Kind::CherryPick).unwrap();
+        assert!(matches!(outcome, Outcome::Committed(_)));
+        assert_eq!(
+            std::fs::read_to_string("a.txt").unwrap(),
+            "ONE\ntwo\nthree\n"
+        );
+        let (_, picked) = get_branch_head_info(&conn, "stable").unwrap();
+        assert!(commit_message(&conn, &picked).unwrap().contains(&fix));
+        assert_eq!(run(&conn, &fix, Kind::CherryPick).unwrap(), Outcome::Empty);
+
+        crate::vcs::checkout(&conn, "main").unwrap();
+        run(&conn, &fix, Kind::Revert).unwrap();
+        assert_eq!(
+            std::fs::read_to_string("a.txt").unwrap(),
+            "one\ntwo\nTHREE\n"
+        );
+
+        // Le correctif touche une ligne modifiée depuis : conflit, puis abandon
+        std::fs::write("a.txt", "one!\ntwo\nTHREE\n").unwrap();
+        crate::vcs::commit(&conn, "again", AUTHOR).unwrap();
+        let outcome = run(&conn, &fix, Kind::CherryPick).unwrap();
+        assert!(matches!(outcome, Outcome::Conflicts(_)));
+        assert!(has_conflict_markers(Path::new("a.txt")));
+        assert!(run(&conn, &fix, Kind::Revert).is_err());
+        abort(&conn, Kind::CherryPick).unwrap();
+        let restored = std::fs::read_to_string("a.txt").unwrap();
+        assert_eq!(restored, "one!\ntwo\nTHREE\n");
+
+        // Une écriture qui échoue en cours de route annule toute la transaction
+        crate::vcs::checkout(&conn, "stable").unwrap();
+        std::fs::write("b.txt", "new\n").unwrap();
+        crate::vcs::commit(&conn, "add b", AUTHOR).unwrap();
+        let (_, added) = get_branch_head_info(&conn, "stable").unwrap();
+        crate::vcs::checkout(&conn, "main").unwrap();
+        let (_, before) = get_branch_head_info(&conn, "main").unwrap();
+        let count = |conn: &Connection| crate::history::all_commits(conn).unwrap().len();
+        let commits = count(&conn);
+        conn.execute("ALTER TABLE manifest RENAME TO manifest_away")
+            .unwrap();
+        let failed = run(&conn, &added, Kind::CherryPick);
+        conn.execute("ALTER TABLE manifest_away RENAME TO manifest")
+            .unwrap();
+        assert!(failed.is_err());
+        assert_eq!(get_branch_head_info(&conn, "main").unwrap().1, before);
+        assert_eq!(count(&conn), commits);
+        conn.execute("BEGIN TRANSACTION; ROLLBACK;").unwrap();
+    }
+}
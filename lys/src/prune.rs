@@ -0,0 +1,766 @@
+use crate::db::{config, season_start, set_config};
+use crate::history::{self, Entry};
+use crate::utils::ok;
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// Clés de config de la politique de rétention.
+pub const SEASONS: &str = "retention_seasons";
+pub const BRANCHES: &str = "retention_branches";
+pub const TAGS: &str = "retention_tags";
+pub const SEASON_FIRST: &str = "retention_season_first";
+
+/// Ce que `lys prune` garde. Les têtes de branches, les refs de suivi, les
+/// bases de stash et les commits du journal d'opérations sont toujours
+/// gardés, comme les saisons scellées.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    // Saisons gardées entières, la courante comprise (0 : toutes)
+    pub seasons: usize,
+    // Branches dont tout l'historique est gardé
+    pub branches: Vec<String>,
+    pub tags: bool,
+    pub season_first: bool,
+}
+
+impl Default for Policy {
+    // Deux ans d'historique, comme l'ancien `prune`
+    fn default() -> Self {
+        Self {
+            seasons: 8,
+            branches: Vec::new(),
+            tags: true,
+            season_first: false,
+        }
+    }
+}
+
+impl Policy {
+    pub fn load(conn: &Connection) -> Result<Self, Error> {
+        let mut policy = Self::default();
+        let seasons = config(conn, SEASONS)?;
+        if !seasons.is_empty() {
+            policy.seasons = seasons.parse()?;
+        }
+        policy.branches = config(conn, BRANCHES)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        let tags = config(conn, TAGS)?;
+        if !tags.is_empty() {
+            policy.tags = tags.parse()?;
+        }
+        let first = config(conn, SEASON_FIRST)?;
+        if !first.is_empty() {
+            policy.season_first = first.parse()?;
+        }
+        Ok(policy)
+    }
+
+    pub fn save(&self, conn: &Connection) -> Result<(), Error> {
+        set_config(conn, SEASONS, &self.seasons.to_string())?;
+        set_config(conn, BRANCHES, &self.branches.join(" "))?;
+        set_config(conn, TAGS, &self.tags.to_string())?;
+        set_config(conn, SEASON_FIRST, &self.season_first.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes = |kept: bool| if kept { "kept" } else { "not kept" };
+        if self.seasons == 0 {
+            write!(f, "Keep every season")?;
+        } else {
+            write!(f, "Keep the last {} season(s)", self.seasons)?;
+        }
+        write!(
+            f,
+            "; tag history {}; first commit of each season {}; protected branches: {}",
+            yes(self.tags),
+            yes(self.season_first),
+            if self.branches.is_empty() {
+                "none".to_string()
+            } else {
+                self.branches.join(", ")
+            }
+        )
+    }
+}
+
+/// Nouveaux parents d'un commit dont des ancêtres sont supprimés. `original`
+/// reste la liste sur laquelle son hash se calcule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graft {
+    pub commit: String,
+    pub original: Vec<String>,
+    pub parents: Vec<String>,
+}
+
+/// Ce que `lys prune` supprimerait, calculé sans rien modifier.
+#[derive(Debug, Default)]
+pub struct Plan {
+    // (saison, commit)
+    pub pruned: Vec<(String, Entry)>,
+    pub grafts: Vec<(String, Graft)>,
+    pub trees: Vec<String>,
+    pub blobs: Vec<String>,
+    pub bytes: u64,
+    // Saisons hors politique gardées entières parce que scellées
+    pub sealed: Vec<String>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.pruned.is_empty() && self.trees.is_empty() && self.blobs.is_empty()
+    }
+}
+
+struct Season {
+    shard: String,
+    sealed: bool,
+    commits: Vec<Entry>,
+}
+
+fn digest(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+// Ce que signe une greffe : le commit, ses parents d'origine et les nouveaux
+fn graft_digest(graft: &Graft) -> String {
+    digest(
+        format!(
+            "{}\n{}\n{}",
+            graft.commit,
+            graft.original.join(" "),
+            graft.parents.join(" ")
+        )
+        .as_bytes(),
+    )
+}
+
+fn column(conn: &Connection, query: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    if let Ok(mut stmt) = conn.prepare(query) {
+        while let Ok(State::Row) = stmt.next() {
+            if let Ok(value) = stmt.read::<String, _>(0) {
+                values.push(value);
+            }
+        }
+    }
+    values
+}
+
+fn split(list: &str) -> Vec<String> {
+    list.split_whitespace().map(str::to_string).collect()
+}
+
+/// Greffes enregistrées et signées par l'une des clés données.
+pub fn grafts(conn: &Connection, keys: &[Vec<u8>]) -> HashMap<String, Graft> {
+    let mut found = HashMap::new();
+    let Ok(mut stmt) =
+        conn.prepare("SELECT commit_hash, original, parents, signature FROM store.grafts")
+    else {
+        return found;
+    };
+    while let Ok(State::Row) = stmt.next() {
+        let (Ok(commit), Ok(original), Ok(parents), Ok(signature)) = (
+            stmt.read::<String, _>(0),
+            stmt.read::<String, _>(1),
+            stmt.read::<String, _>(2),
+            stmt.read::<String, _>(3),
+        ) else {
+            continue;
+        };
+        let graft = Graft {
+            commit,
+            original: split(&original),
+            parents: split(&parents),
+        };
+        if keys
+            .iter()
+            .any(|key| crate::crypto::verify_with_key(key, &graft_digest(&graft), &signature))
+        {
+            found.insert(graft.commit.clone(), graft);
+        }
+    }
+    found
+}
+
+/// Parents sur lesquels le hash de `commit` se recalcule : ceux d'origine si
+/// une greffe vérifiée décrit ses parents actuels.
+pub fn hashed_parents<'a>(
+    grafts: &'a HashMap<String, Graft>,
+    commit: &str,
+    parents: &'a [String],
+) -> &'a [String] {
+    match grafts.get(commit) {
+        Some(graft) if graft.parents == parents => &graft.original,
+        _ => parents,
+    }
+}
+
+// Tout ce qu'atteignent `roots` en suivant `edges`
+fn closure(edges: &HashMap<String, Vec<String>>, roots: Vec<String>) -> HashSet<String> {
+    let mut reached = HashSet::new();
+    let mut pending = roots;
+    while let Some(hash) = pending.pop() {
+        if let Some(next) = edges.get(&hash)
+            && !reached.contains(&hash)
+        {
+            pending.extend(next.iter().cloned());
+        }
+        reached.insert(hash);
+    }
+    reached
+}
+
+/// Applique la politique à toutes les saisons, sans rien modifier.
+pub fn plan(conn: &Connection, root: &Path, policy: &Policy) -> Result<Plan, Error> {
+    let db_dir = root.join(".lys/db");
+    let mut seasons: Vec<Season> = Vec::new();
+    // Commits toujours gardés : têtes de branches, refs de suivi, bases de stash
+    // et vues du journal d'opérations, auxquelles `lys undo` peut revenir
+    let mut heads = column(conn, "SELECT hash FROM remote_refs");
+    heads.extend(column(conn, "SELECT base_commit FROM stashes"));
+    heads.extend(crate::oplog::referenced_commits(conn)?);
+    let mut branches = HashSet::new();
+    let mut tagged = Vec::new();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    history::each_season(conn, |schema| {
+        let shard = history::shard_name(conn, schema).unwrap_or_else(|| schema.to_string());
+        if seasons.iter().any(|season| season.shard == shard) {
+            return Ok(());
+        }
+        branches.extend(column(conn, &format!("SELECT name FROM {schema}.branches")));
+        tagged.extend(column(
+            conn,
+            &format!(
+                "SELECT c.hash FROM {schema}.tags t JOIN {schema}.commits c ON c.id = t.commit_id"
+            ),
+        ));
+        let mut stmt = conn.prepare(format!(
+            "SELECT parent_tree_hash, hash FROM {schema}.tree_nodes"
+        ))?;
+        while let Ok(State::Row) = stmt.next() {
+            children
+                .entry(stmt.read(0)?)
+                .or_default()
+                .push(stmt.read(1)?);
+        }
+        seasons.push(Season {
+            sealed: crate::archive::is_sealed(&db_dir, &shard),
+            commits: history::season_commits(conn, schema)?,
+            shard,
+        });
+        Ok(())
+    })?;
+
+    // Saisons hors politique : au-delà des N plus récentes d'après leur dossier
+    let mut dated: Vec<(String, usize)> = seasons
+        .iter()
+        .enumerate()
+        .filter_map(|(i, season)| {
+            let (spec, _) = season.shard.rsplit_once('/')?;
+            Some((season_start(spec)?, i))
+        })
+        .collect();
+    dated.sort_by(|a, b| b.0.cmp(&a.0));
+    let old: HashSet<usize> = if policy.seasons == 0 {
+        HashSet::new()
+    } else {
+        dated.iter().skip(policy.seasons).map(|(_, i)| *i).collect()
+    };
+
+    let parents: HashMap<String, Vec<String>> = seasons
+        .iter()
+        .flat_map(|season| &season.commits)
+        .map(|entry| (entry.hash.clone(), entry.parents.clone()))
+        .collect();
+    // Une vieille saison garde l'ancienne tête de ses branches : seule la plus récente compte
+    heads.extend(
+        branches
+            .iter()
+            .filter_map(|branch| history::branch_head(conn, branch)),
+    );
+    let mut keep: HashSet<String> = heads.into_iter().collect();
+    // Historiques gardés entiers : branches protégées, tags, saisons scellées
+    let mut protected: Vec<String> = policy
+        .branches
+        .iter()
+        .filter_map(|branch| history::branch_head(conn, branch))
+        .collect();
+    if policy.tags {
+        protected.extend(tagged);
+    }
+    let mut sealed = Vec::new();
+    for (i, season) in seasons.iter().enumerate() {
+        let hashes = season.commits.iter().map(|entry| entry.hash.clone());
+        if !old.contains(&i) {
+            keep.extend(hashes);
+        } else if season.sealed {
+            protected.extend(hashes);
+            sealed.push(season.shard.clone());
+        } else if policy.season_first {
+            keep.extend(season.commits.first().map(|entry| entry.hash.clone()));
+        }
+    }
+    keep.extend(closure(&parents, protected));
+
+    let mut plan = Plan {
+        sealed,
+        ..Plan::default()
+    };
+    for season in &seasons {
+        for entry in &season.commits {
+            if !keep.contains(&entry.hash) {
+                plan.pruned.push((season.shard.clone(), entry.clone()));
+            }
+        }
+    }
+    let pruned: HashSet<&str> = plan
+        .pruned
+        .iter()
+        .map(|(_, entry)| entry.hash.as_str())
+        .collect();
+
+    // Greffes : un parent supprimé est remplacé par ses plus proches ancêtres gardés
+    let recorded = grafts(conn, &crate::transfer::trusted_keys(conn, root, None));
+    for season in seasons.iter().filter(|season| !season.sealed) {
+        for entry in &season.commits {
+            if !keep.contains(&entry.hash)
+                || !entry.parents.iter().any(|p| pruned.contains(p.as_str()))
+            {
+                continue;
+            }
+            let mut new_parents: Vec<String> = Vec::new();
+            let mut seen = HashSet::new();
+            for parent in &entry.parents {
+                let mut pending = vec![parent.clone()];
+                while let Some(hash) = pending.pop() {
+                    if !seen.insert(hash.clone()) {
+                        continue;
+                    }
+                    if pruned.contains(hash.as_str()) {
+                        pending.extend(parents[&hash].iter().rev().cloned());
+                    } else if !new_parents.contains(&hash) {
+                        new_parents.push(hash);
+                    }
+                }
+            }
+            // Un commit déjà greffé garde les parents d'origine de sa première greffe
+            let original = hashed_parents(&recorded, &entry.hash, &entry.parents).to_vec();
+            plan.grafts.push((
+                season.shard.clone(),
+                Graft {
+                    commit: entry.hash.clone(),
+                    original,
+                    parents: new_parents,
+                },
+            ));
+        }
+    }
+
+    // Arbres et blobs que seuls des commits supprimés atteignent
+    let mut live_roots: Vec<String> = seasons
+        .iter()
+        .flat_map(|season| &season.commits)
+        .filter(|entry| keep.contains(&entry.hash))
+        .map(|entry| entry.tree.clone())
+        .collect();
+    live_roots.extend(column(conn, "SELECT tree_hash FROM stashes"));
+    let live = closure(&children, live_roots);
+    let dead = closure(
+        &children,
+        plan.pruned
+            .iter()
+            .map(|(_, entry)| entry.tree.clone())
+            .collect(),
+    );
+    let mut chunks = HashSet::new();
+    for hash in dead.into_iter().filter(|hash| !live.contains(hash)) {
+        if children.contains_key(&hash) {
+            plan.trees.push(hash);
+            continue;
+        }
+        // Un blob découpé n'a pas de contenu propre : ses morceaux comptent à part
+        if let Some(bytes) = stored_bytes(conn, "blobs", &hash)? {
+            plan.bytes += bytes;
+            chunks.extend(crate::chunks::chunk_list(conn, &hash)?);
+            plan.blobs.push(hash);
+        }
+    }
+    // Un morceau n'est libéré que si aucun blob gardé ne le partage
+    let freed: HashSet<&str> = plan.blobs.iter().map(String::as_str).collect();
+    for chunk in &chunks {
+        let mut stmt =
+            conn.prepare("SELECT blob_hash FROM store.blob_chunks WHERE chunk_hash = ?")?;
+        stmt.bind((1, chunk.as_str()))?;
+        let mut shared = false;
+        while let Ok(State::Row) = stmt.next() {
+            shared |= !freed.contains(stmt.read::<String, _>(0)?.as_str());
+        }
+        if !shared {
+            plan.bytes += stored_bytes(conn, "chunks", chunk)?.unwrap_or(0);
+        }
+    }
+    plan.trees.sort();
+    plan.blobs.sort();
+    Ok(plan)
+}
+
+// Octets qu'occupe dans le store le contenu (compressé) d'un blob ou d'un morceau
+fn stored_bytes(conn: &Connection, table: &str, hash: &str) -> Result<Option<u64>, Error> {
+    let mut stmt = conn.prepare(format!(
+        "SELECT length(content) FROM store.{table} WHERE hash = ?"
+    ))?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<Option<i64>, _>(0)?.unwrap_or(0) as u64))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Affiche les commits d'un plan et ce qu'il libérerait.
+pub fn report(plan: &Plan) {
+    for shard in &plan.sealed {
+        ok(format!("{shard} is sealed and kept whole").as_str());
+    }
+    for (shard, entry) in &plan.pruned {
+        let summary = entry.message.lines().next().unwrap_or("");
+        println!(
+            "  {} {} {shard} {summary}",
+            &entry.hash[..7.min(entry.hash.len())],
+            entry.timestamp
+        );
+    }
+    ok(format!(
+        "{} commit(s) to prune, {} graft(s), {} tree(s) and {} blob(s) to reclaim ({} bytes)",
+        plan.pruned.len(),
+        plan.grafts.len(),
+        plan.trees.len(),
+        plan.blobs.len(),
+        plan.bytes
+    )
+    .as_str());
+}
+
+/// Supprime les commits du plan, réécrit les parents des commits greffés et
+/// enregistre leurs greffes signées, puis libère arbres et blobs.
+pub fn apply(conn: &Connection, root: &Path, plan: &Plan) -> Result<(), Error> {
+    let db_dir = root.join(".lys/db");
+    let mut signed = Vec::new();
+    for (_, graft) in &plan.grafts {
+        let signature = crate::crypto::sign_message(root, &graft_digest(graft))
+            .map_err(|e| anyhow::anyhow!(e))?;
+        signed.push((graft, signature));
+    }
+
+    for shard in history::shards(&db_dir) {
+        if crate::archive::is_sealed(&db_dir, &shard) {
+            continue;
+        }
+        let pruned: Vec<&Entry> = plan
+            .pruned
+            .iter()
+            .filter(|(owner, _)| *owner == shard)
+            .map(|(_, entry)| entry)
+            .collect();
+        let grafts: Vec<&Graft> = plan
+            .grafts
+            .iter()
+            .filter(|(owner, _)| *owner == shard)
+            .map(|(_, graft)| graft)
+            .collect();
+        if pruned.is_empty() && grafts.is_empty() && plan.trees.is_empty() {
+            continue;
+        }
+        // ATTACH est impossible dans une transaction : une saison à la fois
+        let schema = history::attach(conn, &shard)?;
+        conn.execute("BEGIN TRANSACTION;")?;
+        let outcome = prune_season(conn, &schema, &pruned, &grafts, &plan.trees);
+        conn.execute(if outcome.is_ok() {
+            "COMMIT;"
+        } else {
+            "ROLLBACK;"
+        })?;
+        outcome?;
+        conn.execute(format!("VACUUM {schema};"))?;
+    }
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    let outcome = prune_store(conn, plan, &signed);
+    conn.execute(if outcome.is_ok() {
+        "COMMIT;"
+    } else {
+        "ROLLBACK;"
+    })?;
+    outcome?;
+    conn.execute("VACUUM store;")?;
+    ok(format!(
+        "Pruned {} commit(s), {} tree(s) and {} blob(s), {} bytes reclaimed",
+        plan.pruned.len(),
+        plan.trees.len(),
+        plan.blobs.len(),
+        plan.bytes
+    )
+    .as_str());
+    Ok(())
+}
+
+fn prune_season(
+    conn: &Connection,
+    schema: &str,
+    pruned: &[&Entry],
+    grafts: &[&Graft],
+    trees: &[String],
+) -> Result<(), Error> {
+    for entry in pruned {
+        // Une branche n'y pointe que depuis une vieille saison : sa tête actuelle est gardée
+        for query in [
+            format!(
+                "DELETE FROM {schema}.branches WHERE head_commit_id IN
+                 (SELECT id FROM {schema}.commits WHERE hash = ?)"
+            ),
+            format!(
+                "DELETE FROM {schema}.tags WHERE commit_id IN
+                 (SELECT id FROM {schema}.commits WHERE hash = ?)"
+            ),
+            format!(
+                "DELETE FROM {schema}.manifest WHERE commit_id IN
+                 (SELECT id FROM {schema}.commits WHERE hash = ?)"
+            ),
+            format!("DELETE FROM {schema}.commit_parents WHERE commit_hash = ?"),
+            format!("DELETE FROM {schema}.git_map WHERE lys_hash = ?"),
+            format!("DELETE FROM {schema}.commits WHERE hash = ?"),
+        ] {
+            // Une vieille saison n'a pas toutes les tables
+            if let Ok(mut stmt) = conn.prepare(query) {
+                stmt.bind((1, entry.hash.as_str()))?;
+                stmt.next()?;
+            }
+        }
+    }
+    for graft in grafts {
+        if let Ok(mut stmt) = conn.prepare(format!(
+            "DELETE FROM {schema}.commit_parents WHERE commit_hash = ?"
+        )) {
+            stmt.bind((1, graft.commit.as_str()))?;
+            stmt.next()?;
+        }
+        for (position, parent) in graft.parents.iter().enumerate() {
+            let mut stmt = conn.prepare(format!(
+                "INSERT INTO {schema}.commit_parents (commit_hash, position, parent_hash)
+                 VALUES (?, ?, ?)"
+            ))?;
+            stmt.bind((1, graft.commit.as_str()))?;
+            stmt.bind((2, position as i64))?;
+            stmt.bind((3, parent.as_str()))?;
+            stmt.next()?;
+        }
+        let mut stmt = conn.prepare(format!(
+            "UPDATE {schema}.commits SET parent_hash = ? WHERE hash = ?"
+        ))?;
+        stmt.bind((1, graft.parents.first().map(String::as_str)))?;
+        stmt.bind((2, graft.commit.as_str()))?;
+        stmt.next()?;
+    }
+    for tree in trees {
+        let mut stmt = conn.prepare(format!(
+            "DELETE FROM {schema}.tree_nodes WHERE parent_tree_hash = ?"
+        ))?;
+        stmt.bind((1, tree.as_str()))?;
+        stmt.next()?;
+    }
+    Ok(())
+}
+
+fn prune_store(conn: &Connection, plan: &Plan, signed: &[(&Graft, String)]) -> Result<(), Error> {
+    for (graft, signature) in signed {
+        let mut stmt = conn.prepare(
+            "INSERT INTO store.grafts (commit_hash, original, parents, signature)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(commit_hash) DO UPDATE SET
+                parents = excluded.parents, signature = excluded.signature",
+        )?;
+        stmt.bind((1, graft.commit.as_str()))?;
+        stmt.bind((2, graft.original.join(" ").as_str()))?;
+        stmt.bind((3, graft.parents.join(" ").as_str()))?;
+        stmt.bind((4, signature.as_str()))?;
+        stmt.next()?;
+    }
+    for hash in &plan.blobs {
+        let mut stmt = conn.prepare("DELETE FROM store.blobs WHERE hash = ?")?;
+        stmt.bind((1, hash.as_str()))?;
+        stmt.next()?;
+    }
+    // Les morceaux des blobs découpés supprimés, s'ils ne servent plus ailleurs
+    crate::chunks::collect(conn)?;
+    for (_, entry) in &plan.pruned {
+        let mut stmt = conn.prepare("DELETE FROM store.history WHERE hash = ?")?;
+        stmt.bind((1, entry.hash.as_str()))?;
+        stmt.next()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{AUTHOR, TestRepo};
+
+    fn head(conn: &Connection) -> String {
+        crate::vcs::get_branch_head_info(conn, "main").unwrap().1
+    }
+
+    // Un commit par contenu de a.txt, puis la saison est rangée en 2001/winter :
+    // les commits suivants ouvrent une saison neuve. Au-delà de 1 Kio, un blob
+    // est découpé.
+    fn old_season(contents: &[&[u8]]) -> (TestRepo, Connection, Vec<String>) {
+        let (repo, conn) = crate::utils::test_repo();
+        crate::db::write_config(&conn, crate::chunks::THRESHOLD, "1024").unwrap();
+        let mut old = Vec::new();
+        for (i, content) in contents.iter().enumerate() {
+            std::fs::write("a.txt", content).unwrap();
+            crate::vcs::commit(&conn, &format!("old {i}"), AUTHOR).unwrap();
+            old.push(head(&conn));
+        }
+        conn.execute("PRAGMA wal_checkpoint(TRUNCATE);").unwrap();
+        let current = conn
+            .prepare("PRAGMA database_list")
+            .and_then(|mut stmt| {
+                stmt.next()?;
+                stmt.read::<String, _>("file")
+            })
+            .unwrap();
+        drop(conn);
+        let target = repo.path().join(".lys/db/2001/winter/winter.db");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::rename(&current, &target).unwrap();
+
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        (repo, conn, old)
+    }
+
+    // Deux commits d'une vieille saison supprimés : le premier commit de la
+    // saison courante est greffé et l'historique restant se vérifie toujours
+    #[test]
+    fn prunes_old_seasons_with_signed_grafts() {
+        let (repo, conn, old) = old_season(&[b"one\n", b"two\n"]);
+        let mut recent = Vec::new();
+        for content in ["three\n", "four\n"] {
+            std::fs::write("a.txt", content).unwrap();
+            crate::vcs::commit(&conn, content.trim(), AUTHOR).unwrap();
+            recent.push(head(&conn));
+        }
+        let policy = Policy {
+            seasons: 1,
+            ..Policy::default()
+        };
+        let planned = plan(&conn, repo.path(), &policy).unwrap();
+        apply(&conn, repo.path(), &planned).unwrap();
+        drop(conn);
+
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+        let audited = crate::crypto::audit(&conn).unwrap();
+        let verified = crate::fsck::run(&conn, repo.path(), true, None).unwrap();
+        let remaining = history::ancestry(&conn, std::slice::from_ref(&recent[1])).unwrap();
+        let gone = history::find(&conn, &old[0]).is_none();
+        let again = plan(&conn, repo.path(), &policy).unwrap();
+
+        // Les greffes signées voyagent avec la sauvegarde
+        let (media, rebuilt) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        crate::backup::backup(&conn, repo.path(), media.path()).unwrap();
+        let key = hex::encode(crate::crypto::public_key(repo.path()).unwrap());
+        crate::backup::restore(rebuilt.path(), media.path(), None, Some(&key)).unwrap();
+        std::env::set_current_dir(rebuilt.path()).unwrap();
+        let restored = crate::db::connect_lys(rebuilt.path()).unwrap();
+        let restored_audit = crate::crypto::audit(&restored).unwrap();
+        let restored_fsck = crate::fsck::run(&restored, rebuilt.path(), false, None).unwrap();
+
+        let mut pruned: Vec<&str> = planned
+            .pruned
+            .iter()
+            .map(|(_, entry)| entry.hash.as_str())
+            .collect();
+        pruned.sort_unstable();
+        let mut expected: Vec<&str> = old.iter().map(String::as_str).collect();
+        expected.sort_unstable();
+        assert_eq!(pruned, expected);
+        let grafted: Vec<&Graft> = planned.grafts.iter().map(|(_, graft)| graft).collect();
+        assert_eq!(
+            grafted,
+            vec![&Graft {
+                commit: recent[0].clone(),
+                original: vec![old[1].clone()],
+                parents: Vec::new(),
+            }]
+        );
+        assert_eq!(planned.blobs.len(), 2);
+        assert!(planned.bytes > 0);
+        assert!(audited);
+        assert!(verified.issues.is_empty(), "{:?}", verified.issues);
+        let hashes: Vec<&str> = remaining.iter().map(|entry| entry.hash.as_str()).collect();
+        assert_eq!(hashes, vec![recent[1].as_str(), recent[0].as_str()]);
+        assert!(gone);
+        assert!(again.is_empty());
+        assert!(restored_audit);
+        assert!(
+            restored_fsck.issues.is_empty(),
+            "{:?}",
+            restored_fsck.issues
+        );
+    }
+
+    // Une vue du journal d'opérations nomme encore le premier commit : il reste,
+    // et les morceaux du gros blob supprimé comptent dans la place libérée
+    #[test]
+    fn keeps_oplog_commits_and_counts_freed_chunks() {
+        let mut big = vec![0; 300_000];
+        blake3::Hasher::new().finalize_xof().fill(&mut big);
+        let (repo, conn, old) = old_season(&[b"one\n", &big, b"two\n"]);
+        let view = crate::oplog::ViewState {
+            branches: [("spike".to_string(), old[0].clone())].into(),
+            current_branch: "spike".to_string(),
+            ..Default::default()
+        };
+        let mut stmt = conn
+            .prepare("INSERT INTO operations_log (operation_type, view_state) VALUES ('branch', ?)")
+            .unwrap();
+        stmt.bind((1, serde_json::to_string(&view).unwrap().as_str()))
+            .unwrap();
+        stmt.next().unwrap();
+        std::fs::write("a.txt", "three\n").unwrap();
+        crate::vcs::commit(&conn, "three", AUTHOR).unwrap();
+
+        let policy = Policy {
+            seasons: 1,
+            ..Policy::default()
+        };
+        let planned = plan(&conn, repo.path(), &policy).unwrap();
+        let mut pruned: Vec<&str> = planned
+            .pruned
+            .iter()
+            .map(|(_, entry)| entry.hash.as_str())
+            .collect();
+        pruned.sort_unstable();
+        let mut expected = vec![old[1].as_str(), old[2].as_str()];
+        expected.sort_unstable();
+        assert_eq!(pruned, expected);
+        assert_eq!(planned.grafts[0].1.parents, vec![old[0].clone()]);
+        assert!(planned.bytes > big.len() as u64);
+
+        apply(&conn, repo.path(), &planned).unwrap();
+        let big_hash = blake3::hash(&big).to_hex().to_string();
+        assert!(
+            crate::chunks::chunk_list(&conn, &big_hash)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(history::find(&conn, &old[0]).is_some());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::db::{commit_parents, config, get_current_branch, remove_config, write_config};
+use crate::merge::{commit_state, has_conflict_markers, is_ancestor, to_string_map};
+use crate::pick::{Kind, Outcome, commit_message};
+use crate::utils::{ko, ok};
+use crate::vcs::{get_branch_head_info, hash_entry, resolve_commit, status, write_state_diff};
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+// Clés de config décrivant un rebase en cours
+pub const REBASE_BRANCH: &str = "rebase_branch";
+pub const REBASE_ONTO: &str = "rebase_onto";
+pub const REBASE_ORIG: &str = "rebase_orig_head";
+// Commits restant à rejouer, un hash par ligne
+pub const REBASE_TODO: &str = "rebase_todo";
+// Commit arrêté sur des conflits et fichiers concernés
+pub const REBASE_CURRENT: &str = "rebase_current";
+pub const REBASE_CONFLICTS: &str = "rebase_conflicts";
+
+const KEYS: [&str; 6] = [
+    REBASE_BRANCH,
+    REBASE_ONTO,
+    REBASE_ORIG,
+    REBASE_TODO,
+    REBASE_CURRENT,
+    REBASE_CONFLICTS,
+];
+
+fn commit_author(conn: &Connection, hash: &str) -> Result<String, Error> {
+    let mut stmt = conn.prepare("SELECT author FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(stmt.read::<String, _>(0)?)
+    } else {
+        Err(anyhow::anyhow!("Commit {hash} not found."))
+    }
+}
+
+fn set_branch_head(conn: &Connection, branch: &str, hash: &str) -> Result<(), Error> {
+    let query = "UPDATE branches SET head_commit_id = (SELECT id FROM commits WHERE hash = ?) WHERE name = ?";
+    let mut stmt = conn.prepare(query)?;
+    stmt.bind((1, hash))?;
+    stmt.bind((2, branch))?;
+    stmt.next()?;
+    Ok(())
+}
+
+fn todo(conn: &Connection) -> Result<Vec<String>, Error> {
+    Ok(config(conn, REBASE_TODO)?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// `lys rebase <onto>` : rejoue les commits de la branche courante absents de
+/// `onto` au-dessus de celui-ci. Chaque commit réécrit est signé à nouveau par
+/// l'identité du dépôt ; l'ancienne tête reste dans le journal des opérations.
+pub fn start(conn: &Connection, onto: &str) -> Result<bool, Error> {
+    crate::pick::ensure_idle(conn)?;
+    let branch = get_current_branch(conn)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+    if head.is_empty() {
+        return Err(anyhow::anyhow!(
+            "'{branch}' has no commits; check out a branch first."
+        ));
+    }
+    let onto_hash = resolve_commit(conn, onto)?
+        .ok_or_else(|| anyhow::anyhow!("Reference '{onto}' not found."))?;
+    let root = std::env::current_dir()?;
+    if !status(conn, &root.to_string_lossy(), &branch)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Working tree has changes. Commit or stash before rebasing."
+        ));
+    }
+    if is_ancestor(conn, &onto_hash, &head)? {
+        ok(format!("'{branch}' is already based on {onto}").as_str());
+        return Ok(true);
+    }
+
+    // Ordre topologique, parents d'abord ; les merges ne sont pas rejoués
+    let mut replayed = Vec::new();
+    for hash in crate::transfer::missing_commits(
+        conn,
+        std::slice::from_ref(&head),
+        std::slice::from_ref(&onto_hash),
+    )? {
+        if commit_parents(conn, &hash)?.len() > 1 {
+            ko(format!("Dropping merge commit {}", &hash[..7]).as_str());
+        } else {
+            replayed.push(hash);
+        }
+    }
+
+    // La vue d'avant le rebase (ancienne tête comprise) permet `lys undo`
+    crate::oplog::record(conn, "rebase start")?;
+    write_config(conn, REBASE_BRANCH, &branch)?;
+    write_config(conn, REBASE_ONTO, &onto_hash)?;
+    write_config(conn, REBASE_ORIG, &head)?;
+    write_config(conn, REBASE_TODO, &replayed.join("\n"))?;
+    write_state_diff(
+        conn,
+        &to_string_map(&commit_state(conn, &head)?),
+        &to_string_map(&commit_state(conn, &onto_hash)?),
+    )?;
+    set_branch_head(conn, &branch, &onto_hash)?;
+    ok(format!(
+        "Rebasing {} commit(s) of '{branch}' onto {}",
+        replayed.len(),
+        &onto_hash[..7]
+    )
+    .as_str());
+    replay(conn)
+}
+
+// Rejoue la liste restante ; s'arrête au premier conflit
+fn replay(conn: &Connection) -> Result<bool, Error> {
+    let mut remaining = todo(conn)?;
+    while !remaining.is_empty() {
+        let hash = remaining.remove(0);
+        let message = commit_message(conn, &hash)?;
+        let author = commit_author(conn, &hash)?;
+        let outcome = crate::pick::apply(conn, &hash, Kind::CherryPick, &message, &author)?;
+        write_config(conn, REBASE_TODO, &remaining.join("\n"))?;
+        match outcome {
+            Outcome::Committed(new) => {
+                ok(format!("Applied {} as {}", &hash[..7], &new[..7]).as_str());
+            }
+            Outcome::Empty => {
+                ok(format!("Skipped {}: already applied", &hash[..7]).as_str());
+            }
+            Outcome::Conflicts(paths) => {
+                // Le rebase reprend la main sur le cherry-pick interrompu
+                crate::pick::clear(conn)?;
+                let listed: Vec<String> = paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                write_config(conn, REBASE_CURRENT, &hash)?;
+                write_config(conn, REBASE_CONFLICTS, &listed.join("\n"))?;
+                for path in &listed {
+                    ko(format!("CONFLICT {path}").as_str());
+                }
+                ko(format!(
+                    "Could not apply {}. Fix the conflicts, then run 'lys rebase --continue' (or 'lys rebase --abort').",
+                    &hash[..7]
+                )
+                .as_str());
+                return Ok(false);
+            }
+        }
+    }
+
+    let branch = config(conn, REBASE_BRANCH)?;
+    let onto = config(conn, REBASE_ONTO)?;
+    for key in KEYS {
+        remove_config(conn, key)?;
+    }
+    crate::oplog::record(conn, "rebase")?;
+    ok(format!("Successfully rebased '{branch}' onto {}", &onto[..7]).as_str());
+    Ok(true)
+}
+
+fn require_rebase(conn: &Connection) -> Result<String, Error> {
+    let branch = config(conn, REBASE_BRANCH)?;
+    if branch.is_empty() {
+        return Err(anyhow::anyhow!("No rebase in progress."));
+    }
+    Ok(branch)
+}
+
+/// `lys rebase --continue` : valide la résolution puis reprend la liste.
+pub fn resume(conn: &Connection) -> Result<bool, Error> {
+    let branch = require_rebase(conn)?;
+    let current = config(conn, REBASE_CURRENT)?;
+    if !current.is_empty() {
+        let conflicts = config(conn, REBASE_CONFLICTS)?;
+        let unresolved: Vec<&str> = conflicts
+            .lines()
+            .filter(|p| has_conflict_markers(Path::new(p)))
+            .collect();
+        if !unresolved.is_empty() {
+            for path in &unresolved {
+                ko(format!("unresolved conflict in {path}").as_str());
+            }
+            return Err(anyhow::anyhow!(
+                "Fix the conflict markers before continuing."
+            ));
+        }
+        // Rien à valider si la résolution revient à la tête actuelle
+        let root = std::env::current_dir()?;
+        if !status(conn, &root.to_string_lossy(), &branch)?.is_empty() {
+            let message = commit_message(conn, &current)?;
+            crate::vcs::commit(conn, &message, &commit_author(conn, &current)?)?;
+        }
+        remove_config(conn, REBASE_CURRENT)?;
+        remove_config(conn, REBASE_CONFLICTS)?;
+    }
+    replay(conn)
+}
+
+/// `lys rebase --abort` : remet la branche et les fichiers sur l'ancienne tête.
+pub fn abort(conn: &Connection) -> Result<(), Error> {
+    let branch = require_rebase(conn)?;
+    let orig = config(conn, REBASE_ORIG)?;
+    let (_, head) = get_branch_head_info(conn, &branch)?;
+
+    // Fichiers que le rebase a pu écrire : tête actuelle, commit interrompu et son parent
+    let mut states = vec![commit_state(conn, &head)?];
+    let current = config(conn, REBASE_CURRENT)?;
+    if !current.is_empty() {
+        states.push(commit_state(conn, &current)?);
+        if let Some(parent) = commit_parents(conn, &current)?.first() {
+            states.push(commit_state(conn, parent)?);
+        }
+    }
+    let target = to_string_map(&commit_state(conn, &orig)?);
+    let paths: BTreeSet<String> = states
+        .iter()
+        .flat_map(|s| s.keys().map(|p| p.to_string_lossy().to_string()))
+        .chain(target.keys().cloned())
+        .collect();
+    let mut on_disk = HashMap::new();
+    for path in paths {
+        if let Ok(hash) = hash_entry(Path::new(&path)) {
+            on_disk.insert(path, (hash, 0));
+        }
+    }
+    write_state_diff(conn, &on_disk, &target)?;
+    set_branch_head(conn, &branch, &orig)?;
+    for key in KEYS {
+        remove_config(conn, key)?;
+    }
+    crate::oplog::record(conn, "rebase abort")?;
+    ok(format!("Rebase aborted; '{branch}' is back on {}", &orig[..7]).as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    // Une branche de fonctionnalité rejouée sur main, avec un conflit résolu
+    #[test]
+    fn replays_branch_and_resolves_conflict() {
+        let (_repo, conn) = crate::utils::test_repo();
+
+        std::fs::write("a.txt", "one\ntwo\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        crate::vcs::create_branch(&conn, "feature/x").unwrap();
+        std::fs::write("a.txt", "ONE\ntwo\n").unwrap();
+        crate::vcs::commit(&conn, "main change", AUTHOR).unwrap();
+
+        crate::vcs::checkout(&conn, "feature/x").unwrap();
+        std::fs::write("b.txt", "feature\n").unwrap();
+        crate::vcs::commit(&conn, "add b", "Dev <dev@lys>").unwrap();
+        std::fs::write("a.txt", "uno\ntwo\n").unwrap();
+        crate::vcs::commit(&conn, "touch a", "Dev <dev@lys>").unwrap();
+        let (_, old_head) = get_branch_head_info(&conn, "feature/x").unwrap();
+
+        assert!(!start(&conn, "main").unwrap());
+        assert!(resume(&conn).is_err());
+        std::fs::write("a.txt", "uno\ntwo\n").unwrap();
+        assert!(resume(&conn).unwrap());
+
+        let (_, head) = get_branch_head_info(&conn, "feature/x").unwrap();
+        let (_, main) = get_branch_head_info(&conn, "main").unwrap();
+        assert_ne!(head, old_head);
+        assert!(is_ancestor(&conn, &main, &head).unwrap());
+        assert_eq!(commit_author(&conn, &head).unwrap(), "Dev <dev@lys>");
+        assert_eq!(commit_message(&conn, &head).unwrap(), "touch a");
+        assert!(crate::crypto::audit(&conn).unwrap());
+        assert_eq!(std::fs::read_to_string("b.txt").unwrap(), "feature\n");
+
+        // Deuxième rebase sur un main qui a encore avancé, puis abandon
+        crate::vcs::checkout(&conn, "main").unwrap();
+        std::fs::write("a.txt", "1\ntwo\n").unwrap();
+        crate::vcs::commit(&conn, "main again", AUTHOR).unwrap();
+        crate::vcs::checkout(&conn, "feature/x").unwrap();
+        assert!(!start(&conn, "main").unwrap());
+        abort(&conn).unwrap();
+        let (_, restored) = get_branch_head_info(&conn, "feature/x").unwrap();
+        let content = std::fs::read_to_string("a.txt").unwrap();
+        assert_eq!(restored, head);
+        assert_eq!(content, "uno\ntwo\n");
+    }
+}
@@ -0,0 +1,259 @@
+use crate::utils::{ko, ok};
+use anyhow::Error;
+use sqlite::{Connection, State};
+
+pub const TRANSPORTS: [&str; 3] = ["lys-http", "git", "path"];
+
+/// Un remote nommé (`origin`) ou une URL passée telle quelle (`saved == false`).
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+    pub transport: String,
+    pub public_key: Option<String>,
+    pub saved: bool,
+}
+
+/// Devine le transport d'après l'URL : `lys://` et `http(s)://` parlent à un
+/// `lys serve`, les URL Git classiques à Git, le reste est un chemin local.
+pub fn infer_transport(url: &str) -> &'static str {
+    if url.starts_with("lys://") {
+        "lys-http"
+    } else if url.ends_with(".git") || url.starts_with("git@") || url.starts_with("ssh://") {
+        "git"
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        "lys-http"
+    } else {
+        "path"
+    }
+}
+
+pub fn add(
+    conn: &Connection,
+    name: &str,
+    url: &str,
+    transport: Option<&str>,
+    public_key: Option<&str>,
+) -> Result<(), Error> {
+    if name.is_empty() || name.contains('/') || name.contains(char::is_whitespace) {
+        return Err(anyhow::anyhow!("Invalid remote name '{name}'."));
+    }
+    if find(conn, name)?.is_some() {
+        return Err(anyhow::anyhow!("Remote '{name}' already exists."));
+    }
+    let transport = transport.unwrap_or_else(|| infer_transport(url));
+    if !TRANSPORTS.contains(&transport) {
+        return Err(anyhow::anyhow!(
+            "Unknown transport '{transport}' (expected one of {}).",
+            TRANSPORTS.join(", ")
+        ));
+    }
+    if let Some(key) = public_key
+        && hex::decode(key).map(|k| k.len()) != Ok(32)
+    {
+        return Err(anyhow::anyhow!(
+            "The public key must be 64 hexadecimal characters."
+        ));
+    }
+
+    let mut stmt =
+        conn.prepare("INSERT INTO remotes (name, url, transport, public_key) VALUES (?, ?, ?, ?)")?;
+    stmt.bind((1, name))?;
+    stmt.bind((2, url))?;
+    stmt.bind((3, transport))?;
+    stmt.bind((4, public_key))?;
+    stmt.next()?;
+    ok(format!("Remote '{name}' added ({transport})").as_str());
+    Ok(())
+}
+
+pub fn all(conn: &Connection) -> Result<Vec<Remote>, Error> {
+    let mut stmt =
+        conn.prepare("SELECT name, url, transport, public_key FROM remotes ORDER BY name")?;
+    let mut remotes = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        remotes.push(Remote {
+            name: stmt.read("name")?,
+            url: stmt.read("url")?,
+            transport: stmt.read("transport")?,
+            public_key: stmt.read("public_key")?,
+            saved: true,
+        });
+    }
+    Ok(remotes)
+}
+
+pub fn find(conn: &Connection, name: &str) -> Result<Option<Remote>, Error> {
+    Ok(all(conn)?.into_iter().find(|remote| remote.name == name))
+}
+
+/// Un nom de remote enregistré, sinon l'argument est pris comme URL.
+pub fn resolve(conn: &Connection, name_or_url: &str) -> Result<Remote, Error> {
+    if let Some(remote) = find(conn, name_or_url)? {
+        return Ok(remote);
+    }
+    if !name_or_url.contains("://") && !std::path::Path::new(name_or_url).exists() {
+        return Err(anyhow::anyhow!(
+            "No remote named '{name_or_url}'. Add it with 'lys remote add'."
+        ));
+    }
+    Ok(Remote {
+        name: name_or_url.to_string(),
+        url: name_or_url.to_string(),
+        transport: infer_transport(name_or_url).to_string(),
+        public_key: None,
+        saved: false,
+    })
+}
+
+pub fn list(conn: &Connection) -> Result<(), Error> {
+    let remotes = all(conn)?;
+    if remotes.is_empty() {
+        ok("no remotes yet");
+    }
+    for remote in remotes {
+        let key = remote
+            .public_key
+            .as_deref()
+            .map(|k| format!("key {}", k.get(..16).unwrap_or(k)))
+            .unwrap_or_else(|| String::from("key not pinned"));
+        ok(format!(
+            "{}\t{} ({}, {key})",
+            remote.name, remote.url, remote.transport
+        )
+        .as_str());
+    }
+    Ok(())
+}
+
+pub fn remove(conn: &Connection, name: &str) -> Result<(), Error> {
+    if find(conn, name)?.is_none() {
+        return Err(anyhow::anyhow!("No remote named '{name}'."));
+    }
+    for query in [
+        "DELETE FROM remotes WHERE name = ?",
+        "DELETE FROM remote_refs WHERE remote = ?",
+    ] {
+        let mut stmt = conn.prepare(query)?;
+        stmt.bind((1, name))?;
+        stmt.next()?;
+    }
+    ok(format!("Remote '{name}' removed").as_str());
+    Ok(())
+}
+
+/// Clé à utiliser pour vérifier les commits du remote. La première clé annoncée
+/// est épinglée ; ensuite toute autre clé est refusée.
+pub fn pin_key(
+    conn: &Connection,
+    remote: &Remote,
+    advertised: &str,
+) -> Result<Option<String>, Error> {
+    match &remote.public_key {
+        Some(pinned) if !advertised.is_empty() && pinned != advertised => Err(anyhow::anyhow!(
+            "The identity of '{}' changed (expected key {pinned}, got {advertised}). \
+             Remove and re-add the remote to trust the new key.",
+            remote.name
+        )),
+        Some(pinned) => Ok(Some(pinned.clone())),
+        None if advertised.is_empty() => Ok(None),
+        None => {
+            if remote.saved {
+                let mut stmt = conn.prepare("UPDATE remotes SET public_key = ? WHERE name = ?")?;
+                stmt.bind((1, advertised))?;
+                stmt.bind((2, remote.name.as_str()))?;
+                stmt.next()?;
+                ko(format!("Trusting key {advertised} for '{}'", remote.name).as_str());
+            }
+            Ok(Some(advertised.to_string()))
+        }
+    }
+}
+
+/// Refs de suivi `(remote, branche, hash)`, triées.
+pub fn tracking_refs(conn: &Connection) -> Result<Vec<(String, String, String)>, Error> {
+    let mut stmt =
+        conn.prepare("SELECT remote, branch, hash FROM remote_refs ORDER BY remote, branch")?;
+    let mut refs = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        refs.push((stmt.read(0)?, stmt.read(1)?, stmt.read(2)?));
+    }
+    Ok(refs)
+}
+
+/// Remplace les refs de suivi d'un remote par ses têtes de branches actuelles.
+pub fn update_tracking_refs<'a>(
+    conn: &Connection,
+    remote: &str,
+    heads: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare("DELETE FROM remote_refs WHERE remote = ?")?;
+    stmt.bind((1, remote))?;
+    stmt.next()?;
+    for (branch, hash) in heads {
+        set_tracking_ref(conn, remote, branch, hash)?;
+    }
+    Ok(())
+}
+
+pub fn set_tracking_ref(
+    conn: &Connection,
+    remote: &str,
+    branch: &str,
+    hash: &str,
+) -> Result<(), Error> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO remote_refs (remote, branch, hash) VALUES (?, ?, ?)
+         ON CONFLICT(remote, branch) DO UPDATE SET hash = excluded.hash, updated_at = CURRENT_TIMESTAMP",
+    )?;
+    stmt.bind((1, remote))?;
+    stmt.bind((2, branch))?;
+    stmt.bind((3, hash))?;
+    stmt.next()?;
+    Ok(())
+}
+
+/// Nombre de commits de `local` absents de `upstream`, et inversement.
+pub fn ahead_behind(
+    conn: &Connection,
+    local: &str,
+    upstream: &str,
+) -> Result<(usize, usize), Error> {
+    let local = [local.to_string()];
+    let upstream = [upstream.to_string()];
+    let ahead = crate::transfer::missing_commits(conn, &local, &upstream)?.len();
+    let behind = crate::transfer::missing_commits(conn, &upstream, &local)?.len();
+    Ok((ahead, behind))
+}
+
+/// `main...origin/main [2 ahead, 1 behind]` pour chaque ref de suivi de `branch`.
+pub fn tracking_summary(conn: &Connection, branch: &str, head: &str) -> Result<Vec<String>, Error> {
+    let mut lines = Vec::new();
+    for (remote, remote_branch, hash) in tracking_refs(conn)? {
+        if remote_branch != branch {
+            continue;
+        }
+        let (ahead, behind) = ahead_behind(conn, head, &hash)?;
+        let state = match (ahead, behind) {
+            (0, 0) => String::from("up to date"),
+            (a, 0) => format!("{a} ahead"),
+            (0, b) => format!("{b} behind"),
+            (a, b) => format!("{a} ahead, {b} behind"),
+        };
+        lines.push(format!("{branch}...{remote}/{remote_branch} [{state}]"));
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_transport_from_url() {
+        assert_eq!(infer_transport("lys://node:3000"), "lys-http");
+        assert_eq!(infer_transport("http://127.0.0.1:3000"), "lys-http");
+        assert_eq!(infer_transport("https://github.com/hackia/lys.git"), "git");
+        assert_eq!(infer_transport("git@github.com:hackia/lys"), "git");
+        assert_eq!(infer_transport("../backup"), "path");
+    }
+}
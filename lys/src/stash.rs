@@ -0,0 +1,372 @@
+use crate::db::get_current_branch;
+use crate::utils::{ko, ok};
+use crate::vcs::{
+    FileStatus, Node, empty_dirs, flatten_tree, get_blob_bytes_by_hash, get_branch_head_info,
+    get_file_mode, get_head_state, insert_into_tree, read_entry, remove_empty_parents, status,
+    store_tree_recursive, write_empty_dirs, write_entry,
+};
+use anyhow::Error;
+use sqlite::{Connection, State};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Un stash : les fichiers nouveaux/modifiés sont dans `tree_hash`,
+/// les suppressions dans `deleted`, le tout relatif à `base_commit`.
+pub struct Stash {
+    pub id: i64,
+    pub message: String,
+    pub branch: String,
+    pub base_commit: String,
+    pub tree_hash: String,
+    pub deleted: Vec<String>,
+    pub created_at: String,
+}
+
+// Les stashes les plus récents d'abord : stash@{0} est le dernier poussé
+fn all(conn: &Connection) -> Result<Vec<Stash>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, message, branch, base_commit, tree_hash, deleted, created_at
+         FROM stashes ORDER BY id DESC",
+    )?;
+    let mut out = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        let deleted: String = stmt.read("deleted")?;
+        out.push(Stash {
+            id: stmt.read("id")?,
+            message: stmt.read("message")?,
+            branch: stmt.read("branch")?,
+            base_commit: stmt.read("base_commit")?,
+            tree_hash: stmt.read("tree_hash")?,
+            deleted: serde_json::from_str(&deleted).unwrap_or_default(),
+            created_at: stmt.read("created_at")?,
+        });
+    }
+    Ok(out)
+}
+
+/// Accepte `stash@{N}` ou `N` (0 par défaut).
+fn find(conn: &Connection, reference: Option<&str>) -> Result<(usize, Stash), Error> {
+    let reference = reference.unwrap_or("0");
+    let index: usize = reference
+        .strip_prefix("stash@{")
+        .and_then(|r| r.strip_suffix('}'))
+        .unwrap_or(reference)
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid stash reference '{reference}'."))?;
+    all(conn)?
+        .into_iter()
+        .enumerate()
+        .nth(index)
+        .ok_or_else(|| anyhow::anyhow!("stash@{{{index}}} does not exist."))
+}
+
+fn stash_files(conn: &Connection, stash: &Stash) -> Result<HashMap<PathBuf, (String, i64)>, Error> {
+    let mut files = HashMap::new();
+    flatten_tree(conn, &stash.tree_hash, PathBuf::new(), &mut files)?;
+    Ok(files)
+}
+
+fn base_state(
+    conn: &Connection,
+    base_commit: &str,
+) -> Result<HashMap<PathBuf, (String, i64)>, Error> {
+    let mut state = HashMap::new();
+    if let Some(tree_hash) = crate::db::commit_tree_hash(conn, base_commit)? {
+        flatten_tree(conn, &tree_hash, PathBuf::new(), &mut state)?;
+    }
+    Ok(state)
+}
+
+fn disk_hash(path: &Path) -> Option<String> {
+    read_entry(path)
+        .ok()
+        .map(|content| blake3::hash(&content).to_hex().to_string())
+}
+
+/// Met de côté les modifications de l'arbre de travail et le ramène au HEAD.
+pub fn push(conn: &Connection, message: Option<&str>) -> Result<(), Error> {
+    let current_dir = std::env::current_dir()?;
+    let branch = get_current_branch(conn)?;
+    let changes = status(conn, current_dir.to_str().unwrap(), &branch)?;
+    if changes.is_empty() {
+        ok("No local changes to save");
+        return Ok(());
+    }
+    let (_, base_commit) = get_branch_head_info(conn, &branch)?;
+
+    // 1. Les fichiers nouveaux/modifiés partent dans le store sous forme d'arbre
+    let mut tree = Node::Directory {
+        children: BTreeMap::new(),
+    };
+    let mut deleted = Vec::new();
+    conn.execute("BEGIN TRANSACTION;")?;
+    for change in &changes {
+        match change {
+            // Un dossier vide reste en place : il n'a rien à mettre de côté
+            FileStatus::New(path) if path.is_dir() => {}
+            FileStatus::New(path)
+            | FileStatus::Modified(path, _)
+            | FileStatus::Renamed(_, path, _) => {
+                let content = read_entry(path)?;
+                let hash = blake3::hash(&content).to_hex().to_string();
+                crate::db::insert_blob_with_conn(conn, &hash, &content)?;
+                let mode = get_file_mode(path).unwrap_or(0);
+                insert_into_tree(&mut tree, path, hash, mode, content.len() as u64);
+                // Un renommage est mis de côté comme une suppression et un ajout
+                if let FileStatus::Renamed(from, _, _) = change {
+                    deleted.push(from.to_string_lossy().to_string());
+                }
+            }
+            FileStatus::Deleted(path, _) => deleted.push(path.to_string_lossy().to_string()),
+            FileStatus::Unchanged => {}
+        }
+    }
+    let tree_hash = store_tree_recursive(conn, "ROOT", &tree)?;
+
+    let message = message
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("WIP on {branch}: {}", base_commit.get(..7).unwrap_or("")));
+    let mut stmt = conn.prepare(
+        "INSERT INTO stashes (message, branch, base_commit, tree_hash, deleted) VALUES (?, ?, ?, ?, ?)",
+    )?;
+    stmt.bind((1, message.as_str()))?;
+    stmt.bind((2, branch.as_str()))?;
+    stmt.bind((3, base_commit.as_str()))?;
+    stmt.bind((4, tree_hash.as_str()))?;
+    stmt.bind((5, serde_json::to_string(&deleted)?.as_str()))?;
+    stmt.next()?;
+    conn.execute("COMMIT;")?;
+
+    // 2. On remet l'arbre de travail dans l'état du HEAD, sans les dossiers
+    //    que seuls les fichiers mis de côté occupaient
+    let head = get_head_state(conn, &branch)?;
+    for change in &changes {
+        match change {
+            FileStatus::New(path) if path.is_dir() => {}
+            FileStatus::New(path) => {
+                std::fs::remove_file(path)?;
+                remove_empty_parents(path);
+            }
+            FileStatus::Modified(path, _) | FileStatus::Deleted(path, _) => {
+                if let Some((hash, mode)) = head.get(path) {
+                    write_entry(conn, hash, *mode, Path::new("."), path)?;
+                }
+            }
+            FileStatus::Renamed(from, to, _) => {
+                std::fs::remove_file(to)?;
+                remove_empty_parents(to);
+                if let Some((hash, mode)) = head.get(from) {
+                    write_entry(conn, hash, *mode, Path::new("."), from)?;
+                }
+            }
+            FileStatus::Unchanged => {}
+        }
+    }
+    // Les dossiers vides du HEAD restent en place
+    if let Some(tree) = crate::db::commit_tree_hash(conn, &base_commit)? {
+        write_empty_dirs(&BTreeSet::new(), &empty_dirs(conn, &tree)?)?;
+    }
+    ok(format!("Saved working directory: {message}").as_str());
+    Ok(())
+}
+
+pub fn list(conn: &Connection) -> Result<(), Error> {
+    let stashes = all(conn)?;
+    if stashes.is_empty() {
+        ok("no stashes yet");
+    }
+    for (index, stash) in stashes.iter().enumerate() {
+        ok(format!(
+            "stash@{{{index}}}: On {}: {} ({})",
+            stash.branch, stash.message, stash.created_at
+        )
+        .as_str());
+    }
+    Ok(())
+}
+
+/// Liste les fichiers d'un stash par rapport à son commit de base.
+pub fn show(conn: &Connection, reference: Option<&str>) -> Result<(), Error> {
+    let (index, stash) = find(conn, reference)?;
+    let base = base_state(conn, &stash.base_commit)?;
+    let mut lines: Vec<String> = stash_files(conn, &stash)?
+        .keys()
+        .map(|path| {
+            let tag = if base.contains_key(path) { "M" } else { "A" };
+            format!("{tag} {}", path.display())
+        })
+        .chain(stash.deleted.iter().map(|path| format!("D {path}")))
+        .collect();
+    lines.sort_by(|a, b| a[2..].cmp(&b[2..]));
+
+    ok(format!("stash@{{{index}}}: {}", stash.message).as_str());
+    for line in lines {
+        println!("  {line}");
+    }
+    Ok(())
+}
+
+/// Ré-applique un stash. Un fichier modifié depuis la base du stash est
+/// fusionné ligne à ligne ; s'il y a conflit, les marqueurs sont écrits.
+/// Renvoie `false` si des conflits ont été rencontrés.
+pub fn apply(conn: &Connection, reference: Option<&str>) -> Result<bool, Error> {
+    let (index, stash) = find(conn, reference)?;
+    let base = base_state(conn, &stash.base_commit)?;
+    let mut conflicts = 0;
+
+    for (path, (hash, mode)) in stash_files(conn, &stash)? {
+        let base_hash = base.get(&path).map(|(h, _)| h.clone());
+        let current = disk_hash(&path);
+        if current.as_deref() == Some(hash.as_str()) {
+            continue;
+        }
+        // L'arbre de travail n'a pas bougé depuis la base : on écrit directement
+        if current == base_hash {
+            write_entry(conn, &hash, mode, Path::new("."), &path)?;
+            continue;
+        }
+        let content = get_blob_bytes_by_hash(conn, &hash)?.unwrap_or_default();
+
+        // Sinon fusion à trois voies (base, disque, stash)
+        let ours = std::fs::read(&path).unwrap_or_default();
+        let base_content = match &base_hash {
+            Some(h) => get_blob_bytes_by_hash(conn, h)?.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let texts = (
+            String::from_utf8(base_content).ok(),
+            String::from_utf8(ours).ok(),
+            String::from_utf8(content).ok(),
+        );
+        let (Some(base_text), Some(ours_text), Some(theirs_text)) = texts else {
+            ko(format!("CONFLICT {} (binary, left untouched)", path.display()).as_str());
+            conflicts += 1;
+            continue;
+        };
+        let (merged, conflict) = crate::merge::merge_text(
+            &base_text,
+            &ours_text,
+            &theirs_text,
+            ("Updated upstream", "Stashed changes"),
+        );
+        // Le résultat passe par le store pour retrouver le mode du stash
+        let merged_hash = blake3::hash(merged.as_bytes()).to_hex().to_string();
+        crate::db::insert_blob_with_conn(conn, &merged_hash, merged.as_bytes())?;
+        write_entry(conn, &merged_hash, mode, Path::new("."), &path)?;
+        if conflict {
+            ko(format!("CONFLICT {}", path.display()).as_str());
+            conflicts += 1;
+        }
+    }
+
+    for path in &stash.deleted {
+        let path = Path::new(path);
+        let Some(current) = disk_hash(path) else {
+            continue;
+        };
+        if base.get(path).map(|(h, _)| h) == Some(&current) {
+            std::fs::remove_file(path)?;
+        } else {
+            ko(format!(
+                "CONFLICT {} (deleted in stash, modified here)",
+                path.display()
+            )
+            .as_str());
+            conflicts += 1;
+        }
+    }
+
+    if conflicts == 0 {
+        ok(format!("Applied stash@{{{index}}}").as_str());
+    } else {
+        ko(format!("stash@{{{index}}} applied with {conflicts} conflict(s)").as_str());
+    }
+    Ok(conflicts == 0)
+}
+
+/// Applique puis supprime le stash, sauf en cas de conflit.
+pub fn pop(conn: &Connection, reference: Option<&str>) -> Result<(), Error> {
+    if apply(conn, reference)? {
+        drop_stash(conn, reference)?;
+    } else {
+        ok("The stash is kept in case you need it again.");
+    }
+    Ok(())
+}
+
+pub fn drop_stash(conn: &Connection, reference: Option<&str>) -> Result<(), Error> {
+    let (index, stash) = find(conn, reference)?;
+    let mut stmt = conn.prepare("DELETE FROM stashes WHERE id = ?")?;
+    stmt.bind((1, stash.id))?;
+    stmt.next()?;
+    ok(format!("Dropped stash@{{{index}}}").as_str());
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &str) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    // push, list, apply (fusion comprise, avec le mode du stash), drop, puis
+    // un apply en conflit que pop ne supprime pas
+    #[test]
+    fn pushes_applies_pops_and_drops() {
+        let (_repo, conn) = crate::utils::test_repo();
+        let read = |path: &str| std::fs::read_to_string(path).unwrap();
+        std::fs::write("a.txt", "one\ntwo\nthree\n").unwrap();
+        std::fs::write("run.sh", "echo a\necho b\necho c\n").unwrap();
+        crate::vcs::commit(&conn, "base", AUTHOR).unwrap();
+
+        std::fs::write("run.sh", "echo A\necho b\necho c\n").unwrap();
+        std::fs::set_permissions("run.sh", std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::create_dir_all("new/dir").unwrap();
+        std::fs::write("new/dir/b.txt", "fresh\n").unwrap();
+        push(&conn, Some("work")).unwrap();
+        let pushed = (read("run.sh"), mode("run.sh"), Path::new("new").exists());
+        list(&conn).unwrap();
+        let listed: Vec<String> = all(&conn).unwrap().into_iter().map(|s| s.message).collect();
+
+        // run.sh a bougé depuis : fusion à trois voies, le mode vient du stash
+        std::fs::write("run.sh", "echo a\necho b\necho C\n").unwrap();
+        let applied = apply(&conn, None).unwrap();
+        let merged = (read("run.sh"), mode("run.sh"), read("new/dir/b.txt"));
+        drop_stash(&conn, Some("stash@{0}")).unwrap();
+        let dropped = all(&conn).unwrap().is_empty();
+        crate::vcs::commit(&conn, "applied", AUTHOR).unwrap();
+
+        std::fs::write("a.txt", "one\nTWO\nthree\n").unwrap();
+        push(&conn, None).unwrap();
+        std::fs::write("a.txt", "one\ndeux\nthree\n").unwrap();
+        let conflicted = apply(&conn, None).unwrap();
+        let markers = read("a.txt");
+        pop(&conn, None).unwrap();
+        let kept = all(&conn).unwrap().len();
+        std::fs::write("a.txt", "one\ntwo\nthree\n").unwrap();
+        pop(&conn, None).unwrap();
+
+        assert_eq!(
+            pushed,
+            ("echo a\necho b\necho c\n".to_string(), 0o644, false)
+        );
+        assert_eq!(listed, vec!["work".to_string()]);
+        assert!(applied);
+        let expected = (
+            "echo A\necho b\necho C\n".to_string(),
+            0o755,
+            "fresh\n".to_string(),
+        );
+        assert_eq!(merged, expected);
+        assert!(dropped);
+        assert!(!conflicted);
+        assert!(markers.contains("<<<<<<<") && markers.contains("deux"));
+        assert_eq!(kept, 1);
+        assert_eq!(read("a.txt"), "one\nTWO\nthree\n");
+        assert!(all(&conn).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1551 @@
+use crate::db::{commit_parents, commit_tree_hash, get_current_branch, insert_tree_node};
+use crate::merge::is_ancestor;
+use crate::remote::{Remote, set_tracking_ref, tracking_refs, update_tracking_refs};
+use crate::utils::ok;
+use crate::vcs::{
+    compute_commit_hash, flatten_tree, get_branch_head_info, get_manifest_map,
+    insert_manifest_delta, is_directory, write_state_diff,
+};
+use anyhow::Error;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlite::{Connection, State};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Clé de config listant les commits reçus sans leurs parents (clone partiel).
+pub const SHALLOW: &str = "shallow";
+
+/// Clé de config qui, à `true`, accepte des commits sans signature (historique
+/// importé de Git, dépôt antérieur aux signatures), reçus ou vérifiés par fsck.
+pub const ALLOW_UNSIGNED: &str = "allow_unsigned";
+
+/// Clé de config listant les clés publiques (hex) autorisées à pousser vers
+/// `lys serve`. Vide, le nœud refuse tout push.
+pub const PUSH_KEYS: &str = "push_keys";
+
+/// Taille maximale d'un push reçu par `lys serve`, compressé.
+pub const MAX_BODY: usize = 256 << 20;
+
+// Taille maximale de l'en-tête JSON d'un paquet, une fois décompressé
+const MAX_HEADER: u64 = 1 << 30;
+
+/// Têtes de branches annoncées par un nœud, avec sa clé publique (hex).
+#[derive(Serialize, Deserialize, Default)]
+pub struct Refs {
+    pub branches: BTreeMap<String, String>,
+    pub public_key: String,
+    #[serde(default)]
+    pub head: String, // branche courante du nœud, extraite par un clone
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommitRecord {
+    pub hash: String,
+    pub parents: Vec<String>,
+    pub tree_hash: String,
+    pub author: String,
+    pub message: String,
+    pub timestamp: String,
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TreeRecord {
+    pub parent_tree_hash: String,
+    pub name: String,
+    pub hash: String,
+    pub mode: i64,
+    pub size: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlobRecord {
+    pub hash: String,
+    pub size: u64, // le contenu suit l'en-tête du paquet, brut
+}
+
+/// Lot d'objets manquants, commits dans l'ordre topologique (parents d'abord).
+#[derive(Serialize, Deserialize, Default)]
+pub struct Batch {
+    pub commits: Vec<CommitRecord>,
+    pub tree_nodes: Vec<TreeRecord>,
+    pub blobs: Vec<BlobRecord>,
+    // Commits envoyés sans leurs parents (--depth, --since). Seul un clone qui
+    // a demandé une profondeur en tient compte : ailleurs la liste est vidée.
+    #[serde(default)]
+    pub shallow: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FetchRequest {
+    pub wants: Vec<String>,
+    pub haves: Vec<String>,
+    #[serde(default)]
+    pub depth: Option<usize>,
+    #[serde(default)]
+    pub since: Option<String>, // YYYY-MM-DD
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PushRequest {
+    pub branch: String,
+    pub old: String,
+    pub new: String,
+    pub batch: Batch,
+    #[serde(default)]
+    pub public_key: String, // hex, clé de l'auteur du push
+    #[serde(default)]
+    pub signature: String, // signature de `push_message`
+}
+
+pub enum PushOutcome {
+    Updated,
+    UpToDate,
+    Rejected(String),
+    Denied(String), // push non signé ou par une clé absente de `push_keys`
+}
+
+/// Écrit un paquet du protocole, compressé en flux (zstd) : la taille puis le
+/// JSON de `header`, puis le contenu brut de chaque blob de `blobs`, dans
+/// l'ordre, lu morceau par morceau depuis le store.
+pub fn write_pack<T: Serialize>(
+    conn: &Connection,
+    header: &T,
+    blobs: &[BlobRecord],
+    out: impl Write,
+) -> Result<(), Error> {
+    let mut encoder = zstd::stream::write::Encoder::new(out, 0)?;
+    let json = serde_json::to_vec(header)?;
+    encoder.write_all(&(json.len() as u64).to_be_bytes())?;
+    encoder.write_all(&json)?;
+    for blob in blobs {
+        let mut content = crate::chunks::open(conn, &blob.hash)?
+            .ok_or_else(|| anyhow::anyhow!("Blob {} is missing from the store.", blob.hash))?;
+        if std::io::copy(&mut content, &mut encoder)? != blob.size {
+            return Err(anyhow::anyhow!(
+                "Blob {} does not have its recorded size.",
+                blob.hash
+            ));
+        }
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Paquet reçu, lu en flux : l'en-tête d'abord, puis les blobs qu'il annonce.
+pub struct Pack<R: Read> {
+    reader: zstd::stream::read::Decoder<'static, std::io::BufReader<R>>,
+}
+
+impl<R: Read> Pack<R> {
+    pub fn new(reader: R) -> Result<Self, Error> {
+        Ok(Pack {
+            reader: zstd::stream::read::Decoder::new(reader)?,
+        })
+    }
+
+    /// En-tête du paquet, sans dépasser `MAX_HEADER` octets quel que soit le
+    /// taux de compression.
+    pub fn header<T: DeserializeOwned>(&mut self) -> Result<T, Error> {
+        let mut size = [0; 8];
+        self.reader.read_exact(&mut size)?;
+        let size = u64::from_be_bytes(size);
+        if size > MAX_HEADER {
+            return Err(anyhow::anyhow!("Pack header is too large."));
+        }
+        let mut json = Vec::new();
+        (&mut self.reader).take(size).read_to_end(&mut json)?;
+        if json.len() as u64 != size {
+            return Err(anyhow::anyhow!("Pack is truncated."));
+        }
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    // Contenu du blob suivant, tel qu'annoncé par l'en-tête
+    fn blob(&mut self, size: u64) -> impl Read + '_ {
+        (&mut self.reader).take(size)
+    }
+}
+
+/// `lys://hote:port` est servi en HTTP par `lys serve`.
+pub fn base_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    match url.strip_prefix("lys://") {
+        Some(rest) => format!("http://{rest}"),
+        None => url.to_string(),
+    }
+}
+
+pub fn local_refs(conn: &Connection) -> Result<BTreeMap<String, String>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT b.name, c.hash FROM branches b JOIN commits c ON b.head_commit_id = c.id",
+    )?;
+    let mut refs = BTreeMap::new();
+    while let Ok(State::Row) = stmt.next() {
+        refs.insert(stmt.read::<String, _>(0)?, stmt.read::<String, _>(1)?);
+    }
+    Ok(refs)
+}
+
+pub fn serve_refs(conn: &Connection, root: &Path) -> Result<Refs, Error> {
+    Ok(Refs {
+        branches: local_refs(conn)?,
+        head: get_current_branch(conn)?,
+        public_key: crate::crypto::public_key(root)
+            .map(hex::encode)
+            .unwrap_or_default(),
+    })
+}
+
+/// Clés acceptées pour l'historique déjà reçu : celles de `peer_keys` et
+/// celles épinglées pour tous les remotes.
+pub fn trusted_keys(conn: &Connection, root: &Path, extra: Option<&str>) -> Vec<Vec<u8>> {
+    let mut keys = peer_keys(conn, root, extra);
+    keys.extend(
+        crate::remote::all(conn)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|remote| remote.public_key)
+            .filter_map(|key| hex::decode(key).ok()),
+    );
+    keys
+}
+
+/// Clés acceptées pour les objets d'un seul pair : la nôtre, celles de
+/// `trusted_keys` (hex séparés par des espaces) et celle de ce pair. Les clés
+/// épinglées pour les autres remotes n'y sont pas.
+pub fn peer_keys(conn: &Connection, root: &Path, peer: Option<&str>) -> Vec<Vec<u8>> {
+    let configured = crate::db::config(conn, "trusted_keys").unwrap_or_default();
+    crate::crypto::public_key(root)
+        .into_iter()
+        .chain(
+            configured
+                .split_whitespace()
+                .chain(peer)
+                .filter_map(|key| hex::decode(key).ok()),
+        )
+        .collect()
+}
+
+fn commit_id(conn: &Connection, hash: &str) -> Result<Option<i64>, Error> {
+    let mut stmt = conn.prepare("SELECT id FROM commits WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<i64, _>(0)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn blob_exists(conn: &Connection, hash: &str) -> Result<bool, Error> {
+    Ok(blob_size(conn, hash)?.is_some())
+}
+
+fn blob_size(conn: &Connection, hash: &str) -> Result<Option<u64>, Error> {
+    let mut stmt = conn.prepare("SELECT size FROM store.blobs WHERE hash = ?")?;
+    stmt.bind((1, hash))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read::<i64, _>(0)? as u64))
+    } else {
+        Ok(None)
+    }
+}
+
+fn tree_children(conn: &Connection, tree_hash: &str) -> Result<Vec<TreeRecord>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT name, hash, mode, size FROM tree_nodes WHERE parent_tree_hash = ? ORDER BY name",
+    )?;
+    stmt.bind((1, tree_hash))?;
+    let mut children = Vec::new();
+    while let Ok(State::Row) = stmt.next() {
+        children.push(TreeRecord {
+            parent_tree_hash: tree_hash.to_string(),
+            name: stmt.read("name")?,
+            hash: stmt.read("hash")?,
+            mode: stmt.read("mode")?,
+            size: stmt.read::<Option<i64>, _>("size")?.unwrap_or(0),
+        });
+    }
+    Ok(children)
+}
+
+/// Commits atteignables depuis `wants` mais pas depuis `haves`, parents d'abord.
+pub fn missing_commits(
+    conn: &Connection,
+    wants: &[String],
+    haves: &[String],
+) -> Result<Vec<String>, Error> {
+    let request = FetchRequest {
+        wants: wants.to_vec(),
+        haves: haves.to_vec(),
+        depth: None,
+        since: None,
+    };
+    Ok(select_commits(conn, &request)?.0)
+}
+
+/// Comme `missing_commits`, en s'arrêtant à `depth` générations ou avant `since`.
+/// Renvoie aussi la frontière « shallow » : les commits envoyés sans leurs parents.
+fn select_commits(
+    conn: &Connection,
+    request: &FetchRequest,
+) -> Result<(Vec<String>, Vec<String>), Error> {
+    let mut known = HashSet::new();
+    let mut queue: VecDeque<String> = request.haves.iter().cloned().collect();
+    while let Some(hash) = queue.pop_front() {
+        if known.insert(hash.clone()) {
+            queue.extend(commit_parents(conn, &hash)?);
+        }
+    }
+
+    let mut missing = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<(String, usize)> =
+        request.wants.iter().map(|hash| (hash.clone(), 0)).collect();
+    while let Some((hash, generation)) = queue.pop_front() {
+        if known.contains(&hash) || !seen.insert(hash.clone()) {
+            continue;
+        }
+        if request.depth.is_some_and(|depth| generation >= depth) {
+            continue;
+        }
+        let mut stmt = conn.prepare("SELECT id, timestamp FROM commits WHERE hash = ?")?;
+        stmt.bind((1, hash.as_str()))?;
+        // Un commit absent d'ici (autre saison, pair en avance) arrête la remontée
+        if let Ok(State::Row) = stmt.next() {
+            let timestamp: String = stmt.read("timestamp")?;
+            // Les deux formats de date commencent par YYYY-MM-DD ; les têtes restent
+            if generation > 0
+                && request
+                    .since
+                    .as_deref()
+                    .is_some_and(|since| timestamp.get(..10).unwrap_or("") < since)
+            {
+                continue;
+            }
+            missing.push((stmt.read::<i64, _>("id")?, hash.clone()));
+            for parent in commit_parents(conn, &hash)? {
+                queue.push_back((parent, generation + 1));
+            }
+        }
+    }
+    // Un commit est toujours inséré après ses parents : l'id donne l'ordre topologique
+    missing.sort();
+    let selected: HashSet<&str> = missing.iter().map(|(_, hash)| hash.as_str()).collect();
+    let mut shallow = Vec::new();
+    for (_, hash) in &missing {
+        if commit_parents(conn, hash)?
+            .iter()
+            .any(|p| !selected.contains(p.as_str()) && !known.contains(p))
+        {
+            shallow.push(hash.clone());
+        }
+    }
+    Ok((missing.into_iter().map(|(_, hash)| hash).collect(), shallow))
+}
+
+// Ajoute au lot les nœuds et blobs d'un arbre, sauf ce que le pair possède déjà.
+// Les blobs n'y sont qu'annoncés : `write_pack` en envoie le contenu
+fn pack_tree(
+    conn: &Connection,
+    tree_hash: &str,
+    skip: &HashSet<String>,
+    sent: &mut HashSet<String>,
+    batch: &mut Batch,
+) -> Result<(), Error> {
+    if skip.contains(tree_hash) || !sent.insert(tree_hash.to_string()) {
+        return Ok(());
+    }
+    for child in tree_children(conn, tree_hash)? {
+        let hash = child.hash.clone();
+        batch.tree_nodes.push(child);
+        if is_directory(conn, &hash)? {
+            pack_tree(conn, &hash, skip, sent, batch)?;
+        } else if !skip.contains(&hash) && sent.insert(hash.clone()) {
+            let size = blob_size(conn, &hash)?
+                .ok_or_else(|| anyhow::anyhow!("Blob {hash} is missing from the store."))?;
+            batch.blobs.push(BlobRecord { hash, size });
+        }
+    }
+    Ok(())
+}
+
+// Tous les hashes (dossiers et fichiers) d'un arbre
+fn collect_tree(
+    conn: &Connection,
+    tree_hash: &str,
+    out: &mut HashSet<String>,
+) -> Result<(), Error> {
+    if !out.insert(tree_hash.to_string()) {
+        return Ok(());
+    }
+    for child in tree_children(conn, tree_hash)? {
+        if is_directory(conn, &child.hash)? {
+            collect_tree(conn, &child.hash, out)?;
+        } else {
+            out.insert(child.hash);
+        }
+    }
+    Ok(())
+}
+
+pub fn build_batch(conn: &Connection, request: &FetchRequest) -> Result<Batch, Error> {
+    let haves: Vec<String> = request
+        .haves
+        .iter()
+        .filter(|hash| commit_id(conn, hash).ok().flatten().is_some())
+        .cloned()
+        .collect();
+
+    // Les arbres des têtes communes : inutile de renvoyer ce qu'ils contiennent
+    let mut skip = HashSet::new();
+    for have in &haves {
+        if let Some(tree_hash) = commit_tree_hash(conn, have)? {
+            collect_tree(conn, &tree_hash, &mut skip)?;
+        }
+    }
+
+    let (commits, shallow) = select_commits(
+        conn,
+        &FetchRequest {
+            haves,
+            ..request.clone()
+        },
+    )?;
+    let mut batch = Batch {
+        shallow,
+        ..Batch::default()
+    };
+    let mut sent = HashSet::new();
+    for hash in commits {
+        let mut stmt = conn.prepare(
+            "SELECT tree_hash, author, message, timestamp, signature FROM commits WHERE hash = ?",
+        )?;
+        stmt.bind((1, hash.as_str()))?;
+        if let Ok(State::Row) = stmt.next() {
+            let record = CommitRecord {
+                parents: commit_parents(conn, &hash)?,
+                tree_hash: stmt.read("tree_hash")?,
+                author: stmt.read("author")?,
+                message: stmt.read("message")?,
+                timestamp: stmt.read("timestamp")?,
+                signature: stmt
+                    .read::<Option<String>, _>("signature")?
+                    .filter(|s| !s.is_empty()),
+                hash,
+            };
+            pack_tree(conn, &record.tree_hash, &skip, &mut sent, &mut batch)?;
+            batch.commits.push(record);
+        }
+    }
+    Ok(batch)
+}
+
+// Un dossier Lys est le blake3 de (nom + hash) de ses enfants triés par nom.
+// Un dossier importé de Git garde son OID (40 caractères), recalculé comme Git
+// le fait : ses blobs doivent déjà être dans le store.
+fn verify_tree_hashes(conn: &Connection, batch: &Batch) -> Result<(), Error> {
+    let mut dirs: HashMap<&str, Vec<(&str, &str, i64)>> = HashMap::new();
+    for node in &batch.tree_nodes {
+        dirs.entry(&node.parent_tree_hash)
+            .or_default()
+            .push((&node.name, &node.hash, node.mode));
+    }
+    for (dir_hash, children) in dirs {
+        let valid = if dir_hash.len() == 40 {
+            git_tree_oid(conn, children)?.is_some_and(|oid| oid.to_string() == dir_hash)
+        } else {
+            crate::vcs::tree_matches(dir_hash, children.into_iter())
+        };
+        if !valid {
+            return Err(anyhow::anyhow!(
+                "Tree {dir_hash} does not match its content."
+            ));
+        }
+    }
+    Ok(())
+}
+
+// OID Git d'un dossier importé : entrées triées comme Git (un dossier compte
+// pour `nom/`), chacune `mode nom\0` suivi de l'OID brut. Un blob est gardé sous
+// son blake3 : son OID Git est recalculé depuis le store. `None` si un blob manque.
+fn git_tree_oid(
+    conn: &Connection,
+    mut children: Vec<(&str, &str, i64)>,
+) -> Result<Option<git2::Oid>, Error> {
+    children.sort_by_key(|&(name, _, mode)| {
+        let mut key = name.as_bytes().to_vec();
+        if mode == 0o40000 {
+            key.push(b'/');
+        }
+        key
+    });
+    let mut content = Vec::new();
+    for (name, hash, mode) in children {
+        let oid = if hash.len() == 40 {
+            git2::Oid::from_str(hash)?
+        } else {
+            match crate::chunks::read(conn, hash)? {
+                Some(blob) => git2::Oid::hash_object(git2::ObjectType::Blob, &blob)?,
+                None => return Ok(None),
+            }
+        };
+        content.extend_from_slice(format!("{mode:o} {name}\0").as_bytes());
+        content.extend_from_slice(oid.as_bytes());
+    }
+    Ok(Some(git2::Oid::hash_object(
+        git2::ObjectType::Tree,
+        &content,
+    )?))
+}
+
+// Un nom d'entrée est un seul composant de chemin, hors `.lys` : rien
+// d'extrait ne peut sortir du dépôt ni toucher à ses métadonnées
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\', '\0']) && !matches!(name, "." | ".." | ".lys")
+}
+
+/// Vérifie l'en-tête d'un paquet sans rien écrire : noms des entrées, hash et
+/// signature Ed25519 des commits, présence de tous les objets référencés. Un
+/// commit non signé n'est accepté qu'avec `ALLOW_UNSIGNED`. Les blobs, puis
+/// les arbres qui en dépendent, sont vérifiés à la lecture du paquet (`apply_pack`).
+pub(crate) fn check_batch(conn: &Connection, batch: &Batch, keys: &[Vec<u8>]) -> Result<(), Error> {
+    if let Some(node) = batch.tree_nodes.iter().find(|n| !valid_name(&n.name)) {
+        return Err(anyhow::anyhow!(
+            "Tree {} has an invalid entry name {:?}.",
+            node.parent_tree_hash,
+            node.name
+        ));
+    }
+    let allow_unsigned = crate::db::config(conn, ALLOW_UNSIGNED)? == "true";
+
+    // Chaque enfant doit venir du lot ou être déjà présent localement ; l'arbre
+    // vide (commit sans fichier, dossier vide) n'a aucune ligne
+    let empty_tree = blake3::Hasher::new().finalize().to_hex().to_string();
+    let batch_dirs: HashSet<&str> = batch
+        .tree_nodes
+        .iter()
+        .map(|n| n.parent_tree_hash.as_str())
+        .collect();
+    let batch_blobs: HashSet<&str> = batch.blobs.iter().map(|b| b.hash.as_str()).collect();
+    for node in &batch.tree_nodes {
+        let hash = node.hash.as_str();
+        if hash != empty_tree
+            && !batch_dirs.contains(hash)
+            && !batch_blobs.contains(hash)
+            && !blob_exists(conn, hash)?
+            && !is_directory(conn, hash)?
+        {
+            return Err(anyhow::anyhow!("Object {hash} ({}) is missing.", node.name));
+        }
+    }
+
+    let mut batch_commits = HashSet::new();
+    for commit in &batch.commits {
+        let expected = compute_commit_hash(
+            &commit.parents,
+            &commit.tree_hash,
+            &commit.author,
+            &commit.message,
+            &commit.timestamp,
+        );
+        if expected != commit.hash
+            && !crate::crypto::is_legacy_hash(
+                &commit.hash,
+                &commit.parents,
+                &commit.tree_hash,
+                &commit.author,
+                &commit.message,
+                &commit.timestamp,
+            )
+        {
+            return Err(anyhow::anyhow!(
+                "Commit {} has an invalid hash.",
+                commit.hash
+            ));
+        }
+        match &commit.signature {
+            Some(signature)
+                if !keys
+                    .iter()
+                    .any(|key| crate::crypto::verify_with_key(key, &commit.hash, signature)) =>
+            {
+                return Err(anyhow::anyhow!(
+                    "Commit {} is signed by an untrusted key.",
+                    commit.hash
+                ));
+            }
+            None if !allow_unsigned => {
+                return Err(anyhow::anyhow!(
+                    "Commit {} is not signed (set {ALLOW_UNSIGNED} to accept it).",
+                    commit.hash
+                ));
+            }
+            _ => {}
+        }
+        let shallow = batch.shallow.contains(&commit.hash);
+        for parent in &commit.parents {
+            if !shallow
+                && !batch_commits.contains(parent.as_str())
+                && commit_id(conn, parent)?.is_none()
+            {
+                return Err(anyhow::anyhow!(
+                    "Parent {parent} of {} is missing.",
+                    commit.hash
+                ));
+            }
+        }
+        if commit.tree_hash != empty_tree
+            && !batch_dirs.contains(commit.tree_hash.as_str())
+            && !is_directory(conn, &commit.tree_hash)?
+        {
+            return Err(anyhow::anyhow!(
+                "Tree of commit {} is missing.",
+                commit.hash
+            ));
+        }
+        batch_commits.insert(commit.hash.as_str());
+    }
+    Ok(())
+}
+
+/// Vérifie (`check_batch`) puis importe un paquet dont l'en-tête `batch` a
+/// déjà été lu. Rien n'est écrit si une seule vérification échoue, blobs
+/// compris. Renvoie le nombre de commits ajoutés.
+pub fn apply_pack<R: Read>(
+    conn: &Connection,
+    batch: &Batch,
+    pack: &mut Pack<R>,
+    keys: &[Vec<u8>],
+) -> Result<usize, Error> {
+    import_pack(conn, batch, pack, keys, true)
+}
+
+/// Comme `apply_pack`, sans rien garder : chaque blob est lu et vérifié.
+pub fn check_pack<R: Read>(
+    conn: &Connection,
+    batch: &Batch,
+    pack: &mut Pack<R>,
+    keys: &[Vec<u8>],
+) -> Result<(), Error> {
+    import_pack(conn, batch, pack, keys, false).map(|_| ())
+}
+
+fn import_pack<R: Read>(
+    conn: &Connection,
+    batch: &Batch,
+    pack: &mut Pack<R>,
+    keys: &[Vec<u8>],
+    keep: bool,
+) -> Result<usize, Error> {
+    check_batch(conn, batch, keys)?;
+    conn.execute("BEGIN TRANSACTION;")?;
+    let result = (|| -> Result<usize, Error> {
+        store_blobs(conn, &batch.blobs, pack)?;
+        verify_tree_hashes(conn, batch)?;
+        if keep {
+            insert_batch(conn, batch)
+        } else {
+            Ok(0)
+        }
+    })();
+    match result {
+        Ok(_) if keep => conn.execute("COMMIT;")?,
+        _ => conn.execute("ROLLBACK;")?,
+    }
+    result
+}
+
+// Range les blobs du paquet, chacun revérifié contre son hash. Au-delà du seuil
+// de découpage, le contenu passe en flux jusqu'aux morceaux sans être chargé
+fn store_blobs<R: Read>(
+    conn: &Connection,
+    blobs: &[BlobRecord],
+    pack: &mut Pack<R>,
+) -> Result<(), Error> {
+    let threshold = crate::chunks::threshold(conn);
+    for blob in blobs {
+        let mut content = pack.blob(blob.size);
+        if blob_exists(conn, &blob.hash)? {
+            std::io::copy(&mut content, &mut std::io::sink())?;
+        } else if blob.size >= threshold {
+            crate::chunks::write(conn, content, Some(&blob.hash))?;
+        } else {
+            let mut bytes = Vec::with_capacity(blob.size as usize);
+            content.read_to_end(&mut bytes)?;
+            if blake3::hash(&bytes).to_hex().as_str() != blob.hash {
+                return Err(anyhow::anyhow!("Blob {} is corrupted.", blob.hash));
+            }
+            crate::db::insert_blob_with_conn(conn, &blob.hash, &bytes)?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_batch(conn: &Connection, batch: &Batch) -> Result<usize, Error> {
+    if !batch.shallow.is_empty() {
+        let mut shallow = crate::db::config(conn, SHALLOW)?;
+        for hash in &batch.shallow {
+            if !shallow.split_whitespace().any(|known| known == hash) {
+                shallow = format!("{shallow} {hash}").trim().to_string();
+            }
+        }
+        crate::db::write_config(conn, SHALLOW, &shallow)?;
+    }
+    for node in &batch.tree_nodes {
+        let size = Some(node.size);
+        insert_tree_node(
+            conn,
+            &node.parent_tree_hash,
+            &node.name,
+            &node.hash,
+            node.mode,
+            size,
+        )?;
+    }
+
+    let mut added = 0;
+    for commit in &batch.commits {
+        if commit_id(conn, &commit.hash)?.is_some() {
+            continue;
+        }
+        let mut stmt = conn.prepare(
+            "INSERT INTO commits (hash, parent_hash, tree_hash, author, message, timestamp, signature)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        stmt.bind((1, commit.hash.as_str()))?;
+        stmt.bind((2, commit.parents.first().map(String::as_str)))?;
+        stmt.bind((3, commit.tree_hash.as_str()))?;
+        stmt.bind((4, commit.author.as_str()))?;
+        stmt.bind((5, commit.message.as_str()))?;
+        stmt.bind((6, commit.timestamp.as_str()))?;
+        stmt.bind((7, commit.signature.as_deref()))?;
+        stmt.next()?;
+        crate::db::insert_commit_parents(conn, &commit.hash, &commit.parents)?;
+
+        let id = commit_id(conn, &commit.hash)?.unwrap_or_default();
+        let mut parent_state = HashMap::new();
+        if let Some(parent) = commit.parents.first()
+            && let Some(tree_hash) = commit_tree_hash(conn, parent)?
+        {
+            flatten_tree(conn, &tree_hash, PathBuf::new(), &mut parent_state)?;
+        }
+        insert_manifest_delta(conn, id, &commit.tree_hash, &parent_state)?;
+        added += 1;
+    }
+    Ok(added)
+}
+
+pub(crate) fn set_branch_head(conn: &Connection, branch: &str, hash: &str) -> Result<(), Error> {
+    let id = commit_id(conn, hash)?.ok_or_else(|| anyhow::anyhow!("Unknown commit {hash}."))?;
+    let mut stmt = conn.prepare(
+        "INSERT INTO branches (name, head_commit_id) VALUES (?, ?)
+         ON CONFLICT(name) DO UPDATE SET head_commit_id = excluded.head_commit_id",
+    )?;
+    stmt.bind((1, branch))?;
+    stmt.bind((2, id))?;
+    stmt.next()?;
+    Ok(())
+}
+
+// Ce que signe l'auteur d'un push : la mise à jour demandée et l'empreinte du lot
+fn push_message(request: &PushRequest) -> Result<String, Error> {
+    let batch = blake3::hash(&serde_json::to_vec(&request.batch)?);
+    Ok(format!(
+        "push {} {} {} {}",
+        request.branch,
+        request.old,
+        request.new,
+        batch.to_hex()
+    ))
+}
+
+fn sign_push(root: &Path, request: &mut PushRequest) -> Result<(), Error> {
+    request.public_key = hex::encode(crate::crypto::public_key(root).map_err(Error::msg)?);
+    request.signature =
+        crate::crypto::sign_message(root, &push_message(request)?).map_err(Error::msg)?;
+    Ok(())
+}
+
+// `new` descend-il de `old` ? Les parents viennent du lot, puis du dépôt local
+fn batch_reaches(conn: &Connection, batch: &Batch, old: &str, new: &str) -> Result<bool, Error> {
+    let parents: HashMap<&str, &[String]> = batch
+        .commits
+        .iter()
+        .map(|c| (c.hash.as_str(), c.parents.as_slice()))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([new.to_string()]);
+    while let Some(hash) = queue.pop_front() {
+        if hash == old {
+            return Ok(true);
+        }
+        if seen.insert(hash.clone()) {
+            match parents.get(hash.as_str()) {
+                Some(parents) => queue.extend(parents.iter().cloned()),
+                None => queue.extend(commit_parents(conn, &hash)?),
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Côté serveur : applique un push, en avance rapide uniquement. Avec
+/// `authenticate` (push reçu par `lys serve`), la requête doit être signée par
+/// une clé de `PUSH_KEYS`. Rien n'est écrit pour un push refusé.
+pub fn receive_push(
+    conn: &Connection,
+    root: &Path,
+    body: impl Read,
+    authenticate: bool,
+) -> Result<PushOutcome, Error> {
+    let mut pack = Pack::new(body)?;
+    let mut request: PushRequest = pack.header()?;
+    if authenticate {
+        let allowed = crate::db::config(conn, PUSH_KEYS)?;
+        if allowed.split_whitespace().next().is_none() {
+            return Ok(PushOutcome::Denied(format!(
+                "Push is disabled on this node (no {PUSH_KEYS} configured)."
+            )));
+        }
+        let message = push_message(&request)?;
+        let signed = allowed
+            .split_whitespace()
+            .any(|key| key == request.public_key)
+            && hex::decode(&request.public_key).is_ok_and(|key| {
+                crate::crypto::verify_with_key(&key, &message, &request.signature)
+            });
+        if !signed {
+            return Ok(PushOutcome::Denied(
+                "Push is not signed by a key allowed to push.".to_string(),
+            ));
+        }
+    }
+    // Un pair ne décide pas des commits dont ce nœud ignore les parents
+    request.batch.shallow.clear();
+    let (_, current) = get_branch_head_info(conn, &request.branch)?;
+    if current == request.new {
+        return Ok(PushOutcome::UpToDate);
+    }
+    // Quelqu'un a poussé entre-temps
+    if current != request.old {
+        return Ok(PushOutcome::Rejected(format!(
+            "'{}' has moved on the remote, pull first.",
+            request.branch
+        )));
+    }
+
+    let known = request.batch.commits.iter().any(|c| c.hash == request.new)
+        || commit_id(conn, &request.new)?.is_some();
+    if !known {
+        return Ok(PushOutcome::Rejected(format!(
+            "Commit {} is not in the push.",
+            request.new
+        )));
+    }
+    if !current.is_empty() && !batch_reaches(conn, &request.batch, &current, &request.new)? {
+        return Ok(PushOutcome::Rejected(format!(
+            "'{}' is not a fast-forward.",
+            request.branch
+        )));
+    }
+
+    // Les commits viennent de l'auteur du push ou de clés déjà reconnues
+    let pusher = Some(request.public_key.as_str()).filter(|key| !key.is_empty());
+    let keys = peer_keys(conn, root, pusher);
+    apply_pack(conn, &request.batch, &mut pack, &keys)?;
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    set_branch_head(conn, &request.branch, &request.new)?;
+    crate::oplog::record(conn, "push")?;
+    conn.execute("COMMIT;")?;
+    Ok(PushOutcome::Updated)
+}
+
+// Canal vers un autre nœud : `lys serve` en HTTP, ou dépôt local ouvert directement
+enum Link {
+    Http {
+        client: reqwest::blocking::Client,
+        url: String,
+    },
+    Path(PathBuf),
+}
+
+impl Link {
+    fn open(remote: &Remote) -> Result<Self, Error> {
+        match remote.transport.as_str() {
+            "lys-http" => Ok(Link::Http {
+                client: reqwest::blocking::Client::new(),
+                url: base_url(&remote.url),
+            }),
+            "path" => Ok(Link::Path(PathBuf::from(&remote.url))),
+            other => Err(anyhow::anyhow!(
+                "'{}' uses the {other} transport, which cannot exchange Lys commits.",
+                remote.name
+            )),
+        }
+    }
+
+    fn refs(&self) -> Result<Refs, Error> {
+        match self {
+            Link::Http { client, url } => {
+                let response = client.get(format!("{url}/sync/refs")).send()?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("{url} answered {}", response.status()));
+                }
+                Ok(serde_json::from_slice(&response.bytes()?)?)
+            }
+            Link::Path(root) => serve_refs(&crate::db::connect_lys(root)?, root),
+        }
+    }
+
+    // L'en-tête du paquet, et le paquet prêt à livrer ses blobs
+    fn fetch(&self, request: &FetchRequest) -> Result<(Batch, Pack<Box<dyn Read>>), Error> {
+        let reader: Box<dyn Read> = match self {
+            Link::Http { client, url } => {
+                let response = client
+                    .post(format!("{url}/sync/fetch"))
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_vec(request)?)
+                    .send()?;
+                if !response.status().is_success() {
+                    return Err(anyhow::anyhow!("{url} answered {}", response.status()));
+                }
+                Box::new(response)
+            }
+            Link::Path(root) => {
+                let conn = crate::db::connect_lys(root)?;
+                let batch = build_batch(&conn, request)?;
+                let mut file = tempfile::tempfile()?;
+                write_pack(&conn, &batch, &batch.blobs, &mut file)?;
+                file.rewind()?;
+                Box::new(file)
+            }
+        };
+        let mut pack = Pack::new(reader)?;
+        Ok((pack.header()?, pack))
+    }
+
+    fn push(&self, conn: &Connection, request: &PushRequest) -> Result<(), Error> {
+        let mut body = tempfile::tempfile()?;
+        write_pack(conn, request, &request.batch.blobs, &mut body)?;
+        body.rewind()?;
+        match self {
+            Link::Http { client, url } => {
+                let response = client
+                    .post(format!("{url}/sync/push"))
+                    .header("Content-Type", "application/zstd")
+                    .body(body)
+                    .send()?;
+                let status = response.status();
+                let text = response.text().unwrap_or_default();
+                if !status.is_success() {
+                    return Err(anyhow::anyhow!("Push rejected ({status}): {text}"));
+                }
+                Ok(())
+            }
+            // Qui peut écrire le dossier du dépôt n'a pas à s'authentifier
+            Link::Path(root) => {
+                match receive_push(&crate::db::connect_lys(root)?, root, body, false)? {
+                    PushOutcome::Rejected(reason) | PushOutcome::Denied(reason) => {
+                        Err(anyhow::anyhow!("Push rejected: {reason}"))
+                    }
+                    PushOutcome::Updated | PushOutcome::UpToDate => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+fn short(hash: &str) -> &str {
+    hash.get(..7).unwrap_or(hash)
+}
+
+/// Blobs reçus sans être importés, gardés dans un fichier temporaire.
+pub(crate) struct Spool {
+    file: std::fs::File,
+    offsets: HashMap<String, (u64, u64)>,
+}
+
+impl Spool {
+    /// Contenu brut d'un blob reçu, tel quel : à revérifier avant usage.
+    pub(crate) fn blob(&self, hash: &str) -> Option<Vec<u8>> {
+        let (offset, size) = *self.offsets.get(hash)?;
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut content = Vec::with_capacity(size as usize);
+        file.take(size).read_to_end(&mut content).ok()?;
+        Some(content)
+    }
+}
+
+/// Tout l'historique des branches du remote, sans rien appliquer : `lys verify
+/// --repair` y reprend les objets abîmés, qu'il revérifie un à un.
+pub(crate) fn fetch_all(remote: &Remote) -> Result<(Batch, Spool), Error> {
+    let link = Link::open(remote)?;
+    let refs = link.refs()?;
+    let (batch, mut pack) = link.fetch(&FetchRequest {
+        wants: refs.branches.into_values().collect(),
+        haves: Vec::new(),
+        depth: None,
+        since: None,
+    })?;
+    let mut spool = Spool {
+        file: tempfile::tempfile()?,
+        offsets: HashMap::new(),
+    };
+    let mut offset = 0;
+    for blob in &batch.blobs {
+        let size = std::io::copy(&mut pack.blob(blob.size), &mut spool.file)?;
+        spool.offsets.insert(blob.hash.clone(), (offset, size));
+        offset += size;
+    }
+    Ok((batch, spool))
+}
+
+/// Récupère tous les commits manquants du remote et met à jour ses refs de suivi.
+/// Renvoie les têtes de branches distantes.
+pub fn fetch(
+    conn: &Connection,
+    root: &Path,
+    remote: &Remote,
+) -> Result<BTreeMap<String, String>, Error> {
+    let link = Link::open(remote)?;
+    let refs = link.refs()?;
+    let pinned = crate::remote::pin_key(conn, remote, &refs.public_key)?;
+
+    let wants: Vec<String> = refs
+        .branches
+        .values()
+        .filter(|hash| commit_id(conn, hash).ok().flatten().is_none())
+        .cloned()
+        .collect();
+    if !wants.is_empty() {
+        let mut haves: Vec<String> = local_refs(conn)?.into_values().collect();
+        haves.extend(tracking_refs(conn)?.into_iter().map(|(_, _, hash)| hash));
+        let (mut batch, mut pack) = link.fetch(&FetchRequest {
+            wants,
+            haves,
+            depth: None,
+            since: None,
+        })?;
+        // Rien n'a été demandé en profondeur limitée
+        batch.shallow.clear();
+        let keys = peer_keys(conn, root, pinned.as_deref());
+        let added = apply_pack(conn, &batch, &mut pack, &keys)?;
+        ok(format!("{added} commit(s) received from '{}'", remote.name).as_str());
+    }
+
+    if remote.saved {
+        conn.execute("BEGIN TRANSACTION;")?;
+        update_tracking_refs(conn, &remote.name, &refs.branches)?;
+        conn.execute("COMMIT;")?;
+    }
+    Ok(refs.branches)
+}
+
+/// Envoie les commits de `branch` qui manquent au remote.
+pub fn push(conn: &Connection, root: &Path, remote: &Remote, branch: &str) -> Result<(), Error> {
+    let link = Link::open(remote)?;
+    let refs = link.refs()?;
+
+    let (_, local) = get_branch_head_info(conn, branch)?;
+    if local.is_empty() {
+        return Err(anyhow::anyhow!("Branch '{branch}' has no commits."));
+    }
+    let upstream = refs.branches.get(branch).cloned().unwrap_or_default();
+    if upstream == local {
+        ok("Everything up-to-date");
+        return Ok(());
+    }
+    if !upstream.is_empty()
+        && (commit_id(conn, &upstream)?.is_none() || !is_ancestor(conn, &upstream, &local)?)
+    {
+        return Err(anyhow::anyhow!(
+            "Updates were rejected: the remote '{branch}' has commits you do not have. Pull first."
+        ));
+    }
+
+    let request = FetchRequest {
+        wants: vec![local.clone()],
+        haves: refs.branches.values().cloned().collect(),
+        depth: None,
+        since: None,
+    };
+    let batch = build_batch(conn, &request)?;
+    let count = batch.commits.len();
+    let mut request = PushRequest {
+        branch: branch.to_string(),
+        old: upstream.clone(),
+        new: local.clone(),
+        batch,
+        public_key: String::new(),
+        signature: String::new(),
+    };
+    sign_push(root, &mut request)?;
+    link.push(conn, &request)?;
+    if remote.saved {
+        set_tracking_ref(conn, &remote.name, branch, &local)?;
+    }
+    ok(format!(
+        "{branch}: {}..{} ({count} commit(s) sent to '{}')",
+        short(&upstream),
+        short(&local),
+        remote.name
+    )
+    .as_str());
+    Ok(())
+}
+
+/// Récupère `branch` depuis le remote et l'avance rapidement en local.
+pub fn pull(conn: &Connection, root: &Path, remote: &Remote, branch: &str) -> Result<(), Error> {
+    let is_current = get_current_branch(conn)? == branch;
+    if is_current && !crate::vcs::status(conn, &root.to_string_lossy(), branch)?.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Working tree has changes. Commit or stash before pulling."
+        ));
+    }
+
+    let heads = fetch(conn, root, remote)?;
+    let upstream = heads
+        .get(branch)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("'{}' has no branch '{branch}'.", remote.name))?;
+    let (local_id, local) = get_branch_head_info(conn, branch)?;
+    if local == upstream || is_ancestor(conn, &upstream, &local)? {
+        ok("Already up to date.");
+        return Ok(());
+    }
+    if !local.is_empty() && !is_ancestor(conn, &local, &upstream)? {
+        return Err(anyhow::anyhow!(
+            "Not possible to fast-forward: '{branch}' has diverged from '{}'.",
+            remote.name
+        ));
+    }
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    set_branch_head(conn, branch, &upstream)?;
+    crate::oplog::record(conn, "pull")?;
+    conn.execute("COMMIT;")?;
+    if is_current {
+        let current_files = get_manifest_map(conn, local_id)?;
+        let target_files = get_manifest_map(conn, commit_id(conn, &upstream)?)?;
+        write_state_diff(conn, &current_files, &target_files)?;
+    }
+    ok(format!("{branch}: {}..{}", short(&local), short(&upstream)).as_str());
+    Ok(())
+}
+
+/// Crée `target` à partir d'un autre nœud Lys : historique (éventuellement
+/// limité par `depth` ou `since`), arbres et blobs vérifiés, branche par défaut
+/// extraite et source enregistrée comme remote `origin`. `allow_unsigned`
+/// accepte, et retient pour ce dépôt, un historique aux commits non signés.
+/// Tout l'historique reçu va dans la saison courante, celle que lisent les commandes.
+pub fn clone(
+    url: &str,
+    target: &Path,
+    depth: Option<usize>,
+    since: Option<&str>,
+    allow_unsigned: bool,
+) -> Result<(), Error> {
+    let since = match since {
+        Some(spec) => Some(crate::db::season_start(spec).ok_or_else(|| {
+            anyhow::anyhow!("Invalid season '{spec}', expected YEAR/SEASON (e.g. 2025/autumn).")
+        })?),
+        None => None,
+    };
+    let transport = crate::remote::infer_transport(url);
+    // Un chemin relatif doit survivre au changement de dossier
+    let url = match transport {
+        "path" => std::fs::canonicalize(url)?.to_string_lossy().to_string(),
+        _ => url.to_string(),
+    };
+    let source = Remote {
+        name: String::from("origin"),
+        url: url.clone(),
+        transport: transport.to_string(),
+        public_key: None,
+        saved: false,
+    };
+    let link = Link::open(&source)?;
+    let refs = link.refs()?;
+    if refs.branches.is_empty() {
+        return Err(anyhow::anyhow!("The remote repository is empty."));
+    }
+
+    let previous_dir = std::env::current_dir()?;
+    std::fs::create_dir_all(target)?;
+    std::env::set_current_dir(target)?;
+    let result = clone_into(&link, &refs, &url, transport, depth, since, allow_unsigned);
+    std::env::set_current_dir(&previous_dir)?;
+    if result.is_err() {
+        std::fs::remove_dir_all(target).ok();
+    }
+    result
+}
+
+// Le dossier courant est le nouveau dépôt
+fn clone_into(
+    link: &Link,
+    refs: &Refs,
+    url: &str,
+    transport: &str,
+    depth: Option<usize>,
+    since: Option<String>,
+    allow_unsigned: bool,
+) -> Result<(), Error> {
+    let root = Path::new(".");
+    // connect_lys crée .lys/db/store.db et .lys/db/<année>/<saison>/<saison>.db
+    let conn = crate::db::connect_lys(root)?;
+    crate::crypto::generate_keypair(root).map_err(|e| anyhow::anyhow!(e))?;
+    let key = Some(refs.public_key.as_str()).filter(|k| !k.is_empty());
+    crate::remote::add(&conn, "origin", url, Some(transport), key)?;
+    if allow_unsigned {
+        crate::db::write_config(&conn, ALLOW_UNSIGNED, "true")?;
+    }
+
+    let limited = depth.is_some() || since.is_some();
+    let (mut batch, mut pack) = link.fetch(&FetchRequest {
+        wants: refs.branches.values().cloned().collect(),
+        haves: Vec::new(),
+        depth,
+        since,
+    })?;
+    if !limited {
+        batch.shallow.clear();
+    }
+    let keys = peer_keys(&conn, root, key);
+    let added = apply_pack(&conn, &batch, &mut pack, &keys)?;
+    if !batch.shallow.is_empty() {
+        ok(format!(
+            "Shallow clone: {} commit(s) without history",
+            batch.shallow.len()
+        )
+        .as_str());
+    }
+
+    // Branche par défaut : celle du nœud source, sinon main, sinon la première
+    let default = [refs.head.as_str(), "main"]
+        .into_iter()
+        .find(|name| refs.branches.contains_key(*name))
+        .or_else(|| refs.branches.keys().next().map(String::as_str))
+        .unwrap_or("main")
+        .to_string();
+    let head = refs.branches[&default].clone();
+
+    conn.execute("BEGIN TRANSACTION;")?;
+    update_tracking_refs(&conn, "origin", &refs.branches)?;
+    set_branch_head(&conn, &default, &head)?;
+    crate::db::write_config(&conn, "current_branch", &default)?;
+    crate::oplog::record(&conn, "clone")?;
+    conn.execute("COMMIT;")?;
+
+    let target_files = get_manifest_map(&conn, commit_id(&conn, &head)?)?;
+    write_state_diff(&conn, &HashMap::new(), &target_files)?;
+    ok(format!(
+        "{added} commit(s) received, '{default}' checked out at {}",
+        short(&head)
+    )
+    .as_str());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+
+    fn node(parent: &str, name: &str, hash: &str) -> TreeRecord {
+        TreeRecord {
+            parent_tree_hash: parent.to_string(),
+            name: name.to_string(),
+            hash: hash.to_string(),
+            mode: 0o100644,
+            size: 0,
+        }
+    }
+
+    // Le corps d'un push tel que l'envoie `Link::push`
+    fn pack(conn: &Connection, request: &PushRequest) -> std::fs::File {
+        let mut body = tempfile::tempfile().unwrap();
+        write_pack(conn, request, &request.batch.blobs, &mut body).unwrap();
+        body.rewind().unwrap();
+        body
+    }
+
+    fn node_named(name: &str) -> TreeRecord {
+        node(
+            &blake3::hash(b"dir").to_hex(),
+            name,
+            &blake3::hash(b"x").to_hex(),
+        )
+    }
+
+    #[test]
+    fn maps_lys_scheme_to_http() {
+        assert_eq!(base_url("lys://node:3000/"), "http://node:3000");
+        assert_eq!(base_url("http://node:3000"), "http://node:3000");
+    }
+
+    #[test]
+    fn rejects_tampered_tree() {
+        let (repo, conn) = crate::utils::test_repo();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"a.txt");
+        hasher.update(b"h1");
+        let dir = hasher.finalize().to_hex().to_string();
+
+        let mut batch = Batch::default();
+        batch.tree_nodes.push(node(&dir, "a.txt", "h1"));
+        assert!(verify_tree_hashes(&conn, &batch).is_ok());
+
+        batch.tree_nodes[0].hash = "h2".to_string();
+        assert!(verify_tree_hashes(&conn, &batch).is_err());
+
+        // Un dossier importé de Git, avec un sous-dossier trié comme `a/`
+        std::fs::write("a.txt", "hi\n").unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        let git = git2::Repository::init(repo.path().join("git")).unwrap();
+        let blob = git.blob(b"hi\n").unwrap();
+        let mut sub = git.treebuilder(None).unwrap();
+        sub.insert("x", blob, 0o100644).unwrap();
+        let sub = sub.write().unwrap();
+        let mut root = git.treebuilder(None).unwrap();
+        root.insert("a.txt", blob, 0o100644).unwrap();
+        root.insert("a", sub, 0o040000).unwrap();
+        let root = root.write().unwrap().to_string();
+
+        let lys_blob = blake3::hash(b"hi\n").to_hex().to_string();
+        let mut batch = Batch::default();
+        batch.tree_nodes.push(node(&root, "a.txt", &lys_blob));
+        batch.tree_nodes.push(TreeRecord {
+            mode: 0o40000,
+            ..node(&root, "a", &sub.to_string())
+        });
+        batch
+            .tree_nodes
+            .push(node(&sub.to_string(), "x", &lys_blob));
+        assert!(verify_tree_hashes(&conn, &batch).is_ok());
+
+        batch.tree_nodes[2].name = "y".to_string();
+        assert!(verify_tree_hashes(&conn, &batch).is_err());
+        batch.tree_nodes[2].name = "x".to_string();
+        batch.tree_nodes[0].hash = blake3::hash(b"other\n").to_hex().to_string();
+        assert!(verify_tree_hashes(&conn, &batch).is_err());
+    }
+
+    // Un `lys serve` sur la boucle locale, cloné en entier puis avec --depth 1
+    #[test]
+    fn clones_from_a_loopback_node() {
+        let origin = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        let cwd = crate::utils::cwd_guard();
+
+        std::env::set_current_dir(origin.path()).unwrap();
+        crate::crypto::generate_keypair(origin.path()).unwrap();
+        std::fs::create_dir_all("src").unwrap();
+        std::fs::write("src/main.rs", "fn main() {}\n").unwrap();
+        std::fs::write("README.md", "# demo\n").unwrap();
+        let conn = crate::db::connect_lys(origin.path()).unwrap();
+        crate::vcs::commit(&conn, "first", AUTHOR).unwrap();
+        std::fs::write("README.md", "# demo\nsecond\n").unwrap();
+        crate::vcs::commit(&conn, "second", AUTHOR).unwrap();
+        let (_, head) = get_branch_head_info(&conn, "main").unwrap();
+        drop(cwd);
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let root = origin.path().to_string_lossy().to_string();
+        std::thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(crate::web::start_server(&root, port));
+        });
+        let client = reqwest::blocking::Client::new();
+        for _ in 0..50 {
+            if client
+                .get(format!("http://127.0.0.1:{port}/sync/refs"))
+                .send()
+                .is_ok()
+            {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        let url = format!("lys://127.0.0.1:{port}");
+
+        let full = workspace.path().join("full");
+        clone(&url, &full, None, None, false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(full.join("README.md")).unwrap(),
+            "# demo\nsecond\n"
+        );
+        assert!(full.join("src/main.rs").exists());
+        let conn = crate::db::connect_lys(&full).unwrap();
+        assert_eq!(get_branch_head_info(&conn, "main").unwrap().1, head);
+        assert_eq!(
+            missing_commits(&conn, std::slice::from_ref(&head), &[])
+                .unwrap()
+                .len(),
+            2
+        );
+        let origin_remote = crate::remote::find(&conn, "origin").unwrap().unwrap();
+        assert_eq!(origin_remote.transport, "lys-http");
+        assert!(origin_remote.public_key.is_some());
+
+        let shallow = workspace.path().join("shallow");
+        clone(&url, &shallow, Some(1), None, false).unwrap();
+        let conn = crate::db::connect_lys(&shallow).unwrap();
+        assert_eq!(crate::db::config(&conn, SHALLOW).unwrap(), head);
+        assert_eq!(
+            missing_commits(&conn, std::slice::from_ref(&head), &[])
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    // Noms d'entrée dangereux, push non autorisé, commit dépouillé de sa
+    // signature, arbre modifié, frontière shallow inventée, push sans avance rapide
+    #[test]
+    fn refuses_hostile_batches() {
+        let (origin, conn) = crate::utils::test_repo();
+        let node = tempfile::tempdir().unwrap();
+        crate::crypto::generate_keypair(node.path()).unwrap();
+        let remote = crate::db::connect_lys(node.path()).unwrap();
+        let key = hex::encode(crate::crypto::public_key(origin.path()).unwrap());
+
+        let mut heads = Vec::new();
+        for content in ["one\n", "two\n", "three\n"] {
+            std::fs::write("a.txt", content).unwrap();
+            crate::vcs::commit(&conn, content.trim(), AUTHOR).unwrap();
+            heads.push(get_branch_head_info(&conn, "main").unwrap().1);
+            if heads.len() == 1 {
+                crate::vcs::create_branch(&conn, "side").unwrap();
+            }
+        }
+        crate::vcs::checkout(&conn, "side").unwrap();
+        for content in ["side\n", "side again\n"] {
+            std::fs::write("a.txt", content).unwrap();
+            crate::vcs::commit(&conn, content.trim(), AUTHOR).unwrap();
+        }
+        let (_, side) = get_branch_head_info(&conn, "side").unwrap();
+
+        let request = |branch: &str, old: &str, new: &str, haves: &[String]| {
+            let batch = build_batch(
+                &conn,
+                &FetchRequest {
+                    wants: vec![new.to_string()],
+                    haves: haves.to_vec(),
+                    depth: None,
+                    since: None,
+                },
+            )
+            .unwrap();
+            PushRequest {
+                branch: branch.to_string(),
+                old: old.to_string(),
+                new: new.to_string(),
+                batch,
+                public_key: String::new(),
+                signature: String::new(),
+            }
+        };
+        let push = |mut request: PushRequest| {
+            sign_push(origin.path(), &mut request).unwrap();
+            receive_push(&remote, node.path(), pack(&conn, &request), true)
+        };
+
+        for name in ["", ".", "..", ".lys", "a/../../x", "/etc", "a\\b"] {
+            let mut batch = Batch::default();
+            batch.tree_nodes.push(node_named(name));
+            assert!(check_batch(&remote, &batch, &[]).is_err(), "{name:?}");
+        }
+
+        let first = request("main", "", &heads[1], &[]);
+        assert!(matches!(push(first), Ok(PushOutcome::Denied(_))));
+        crate::db::write_config(&remote, PUSH_KEYS, &key).unwrap();
+        let mut forged = request("main", "", &heads[1], &[]);
+        sign_push(origin.path(), &mut forged).unwrap();
+        forged.old = heads[0].clone();
+        let forged = receive_push(&remote, node.path(), pack(&conn, &forged), true);
+        assert!(matches!(forged, Ok(PushOutcome::Denied(_))));
+        let accepted = push(request("main", "", &heads[1], &[]));
+        assert!(matches!(accepted, Ok(PushOutcome::Updated)));
+
+        let mut unsigned = request("main", &heads[1], &heads[2], &heads[1..2]);
+        unsigned.batch.commits[0].signature = None;
+        assert!(push(unsigned).is_err());
+        // Un arbre qui ne correspond plus à son hash fait tout refuser, blobs compris
+        let mut tampered = request("main", &heads[1], &heads[2], &heads[1..2]);
+        tampered.batch.tree_nodes[0].hash = blake3::hash(b"one\n").to_hex().to_string();
+        assert!(push(tampered).is_err());
+        assert!(!blob_exists(&remote, blake3::hash(b"three\n").to_hex().as_str()).unwrap());
+
+        // Le premier commit de `side` manque, annoncé comme coupure voulue
+        let mut shallow = request("other", "", &side, &heads[..1]);
+        shallow.batch.commits.remove(0);
+        shallow.batch.shallow = vec![side.clone()];
+        assert!(push(shallow).is_err());
+        assert_eq!(crate::db::config(&remote, SHALLOW).unwrap(), "");
+
+        let diverged = push(request("main", &heads[1], &side, &heads[..1]));
+        assert!(matches!(diverged, Ok(PushOutcome::Rejected(_))));
+        assert_eq!(commit_id(&remote, &side).unwrap(), None);
+        assert!(!blob_exists(&remote, blake3::hash(b"side\n").to_hex().as_str()).unwrap());
+    }
+
+    // Un gros blob passe en flux jusqu'aux morceaux du destinataire ; un octet
+    // modifié dans le paquet fait tout refuser
+    #[test]
+    fn packs_stream_blobs_into_chunks() {
+        let (origin, conn) = crate::utils::test_repo();
+        let node = tempfile::tempdir().unwrap();
+        crate::crypto::generate_keypair(node.path()).unwrap();
+        let remote = crate::db::connect_lys(node.path()).unwrap();
+        let key = hex::encode(crate::crypto::public_key(origin.path()).unwrap());
+        for repo in [&conn, &remote] {
+            crate::db::write_config(repo, crate::chunks::THRESHOLD, "1024").unwrap();
+        }
+
+        std::fs::write("a.txt", "small\n").unwrap();
+        crate::vcs::commit(&conn, "small", AUTHOR).unwrap();
+        let mut big = vec![0; 600_000];
+        blake3::Hasher::new().finalize_xof().fill(&mut big);
+        std::fs::write("big.bin", &big).unwrap();
+        crate::vcs::commit(&conn, "big", AUTHOR).unwrap();
+        let (_, head) = get_branch_head_info(&conn, "main").unwrap();
+        let request = FetchRequest {
+            wants: vec![head.clone()],
+            haves: Vec::new(),
+            depth: None,
+            since: None,
+        };
+        let batch = build_batch(&conn, &request).unwrap();
+        let mut bytes = Vec::new();
+        write_pack(&conn, &batch, &batch.blobs, &mut bytes).unwrap();
+        let keys = peer_keys(&remote, node.path(), Some(&key));
+        let big_hash = blake3::hash(&big).to_hex().to_string();
+
+        let mut raw = zstd::decode_all(&bytes[..]).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 1;
+        let tampered = zstd::encode_all(&raw[..], 0).unwrap();
+        let mut tampered = Pack::new(&tampered[..]).unwrap();
+        let header: Batch = tampered.header().unwrap();
+        assert!(apply_pack(&remote, &header, &mut tampered, &keys).is_err());
+        assert_eq!(commit_id(&remote, &head).unwrap(), None);
+        assert!(!blob_exists(&remote, &big_hash).unwrap());
+
+        let mut pack = Pack::new(&bytes[..]).unwrap();
+        let header: Batch = pack.header().unwrap();
+        assert_eq!(apply_pack(&remote, &header, &mut pack, &keys).unwrap(), 2);
+        assert!(crate::chunks::chunk_list(&remote, &big_hash).unwrap().len() > 1);
+        assert_eq!(crate::chunks::read(&remote, &big_hash).unwrap(), Some(big));
+    }
+}
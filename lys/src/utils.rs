@@ -10,6 +10,65 @@ use std::process::Command;
 
 use crate::vcs::FileStatus;
 
+// Les tests qui changent de dossier courant passent l'un après l'autre
+#[cfg(test)]
+static CWD_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+pub(crate) const AUTHOR: &str = "Tester <tester@lys>";
+
+/// Verrou du dossier courant pour un test, qui rétablit l'ancien dossier en
+/// étant relâché, y compris quand le test échoue.
+#[cfg(test)]
+pub(crate) struct CwdGuard {
+    previous: std::path::PathBuf,
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl Drop for CwdGuard {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.previous);
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn cwd_guard() -> CwdGuard {
+    // Un test en échec empoisonne le verrou sans rien laisser d'incohérent
+    let lock = CWD_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    CwdGuard {
+        previous: std::env::current_dir().unwrap(),
+        _lock: lock,
+    }
+}
+
+/// Dépôt vide dans un dossier temporaire, avec sa paire de clés, devenu le
+/// dossier courant jusqu'à la destruction du `TestRepo`.
+#[cfg(test)]
+pub(crate) struct TestRepo {
+    _cwd: CwdGuard,
+    dir: tempfile::TempDir,
+}
+
+#[cfg(test)]
+impl TestRepo {
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_repo() -> (TestRepo, sqlite::Connection) {
+    let cwd = cwd_guard();
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_current_dir(dir.path()).unwrap();
+    crate::crypto::generate_keypair(dir.path()).unwrap();
+    let conn = crate::db::connect_lys(dir.path()).unwrap();
+    (TestRepo { _cwd: cwd, dir }, conn)
+}
+
 pub fn ok(description: &str) {
     let x = term_width();
 
@@ -96,6 +155,16 @@ pub fn ok_status(verb: &FileStatus) {
                 format_args!("\x1b[1;31m-\x1b[1;37m {}\x1b[0m", p.display())
             );
         }
+        FileStatus::Renamed(from, to, _) => {
+            println!(
+                "{}",
+                format_args!(
+                    "\x1b[1;36mR\x1b[1;37m {} -> {}\x1b[0m",
+                    from.display(),
+                    to.display()
+                )
+            );
+        }
         _ => {}
     }
 }
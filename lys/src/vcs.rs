@@ -16,15 +16,16 @@ use similar::{ChangeTag, TextDiff};
 use sqlite::Connection;
 use sqlite::State;
 use std::collections::BTreeMap;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::File;
 use std::fs::copy;
 use std::fs::create_dir_all;
 use std::fs::remove_dir_all;
 use std::io::Error as IoError;
+use std::io::Result as IoResult;
 use std::io::Write;
-use std::io::{Read, Result as IoResult};
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
@@ -33,12 +34,163 @@ use std::process::Stdio;
 #[derive(Debug)]
 pub enum Node {
     File { hash: String, mode: u32, size: u64 },
+    // Le blob d'un lien symbolique est sa cible, comme pour Git
+    Symlink { hash: String, mode: u32, size: u64 },
     Directory { children: BTreeMap<String, Node> },
 }
 
+/// Lien symbolique : type `S_IFLNK` du mode.
+pub(crate) fn is_symlink(mode: i64) -> bool {
+    mode & 0o170000 == 0o120000
+}
+
+/// Dossier : 0o755 pour Lys, type `S_IFDIR` pour un arbre importé de Git.
+pub(crate) fn is_dir_mode(mode: i64) -> bool {
+    mode == 0o755 || mode & 0o170000 == 0o040000
+}
+
+// Ce que le mode d'un enfant ajoute au hash de son dossier. Un fichier ordinaire
+// ou un dossier n'ajoute rien : les arbres d'avant gardent leur hash.
+pub(crate) fn mode_marker(mode: i64) -> &'static [u8] {
+    if is_symlink(mode) {
+        b"\0l"
+    } else if !is_dir_mode(mode) && mode & 0o111 != 0 {
+        b"\0x"
+    } else {
+        b""
+    }
+}
+
+/// Vrai si `hash` est le hash d'un arbre aux enfants `(nom, hash, mode)`,
+/// calculé comme `store_tree_recursive`, ou sans les modes comme avant.
+pub(crate) fn tree_matches<'a>(
+    hash: &str,
+    entries: impl Iterator<Item = (&'a str, &'a str, i64)>,
+) -> bool {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_unstable();
+    let (mut current, mut legacy) = (blake3::Hasher::new(), blake3::Hasher::new());
+    for (name, child, mode) in entries {
+        for hasher in [&mut current, &mut legacy] {
+            hasher.update(name.as_bytes());
+            hasher.update(child.as_bytes());
+        }
+        current.update(mode_marker(mode));
+    }
+    current.finalize().to_hex().as_str() == hash || legacy.finalize().to_hex().as_str() == hash
+}
+
+/// Contenu d'une entrée de l'arbre de travail : la cible d'un lien
+/// symbolique (jamais suivi), sinon le fichier.
+pub fn read_entry(path: &Path) -> IoResult<Vec<u8>> {
+    if !std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return std::fs::read(path);
+    }
+    let target = std::fs::read_link(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(target.as_os_str().as_bytes().to_vec())
+    }
+    #[cfg(not(unix))]
+    Ok(target.to_string_lossy().as_bytes().to_vec())
+}
+
+/// Hash d'une entrée de l'arbre de travail (voir `read_entry`).
+pub fn hash_entry(path: &Path) -> IoResult<String> {
+    if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Ok(blake3::hash(&read_entry(path)?).to_hex().to_string());
+    }
+    calculate_hash(path)
+}
+
+// Premier dossier de `path` sous `root` qui est un lien symbolique : écrire
+// au travers sortirait de l'arbre de travail
+fn symlinked_parent<'a>(root: &Path, path: &'a Path) -> Option<&'a Path> {
+    path.parent()?
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && *dir != root)
+        .find(|dir| std::fs::symlink_metadata(dir).is_ok_and(|m| m.file_type().is_symlink()))
+}
+
+/// Écrit l'entrée `hash` d'un arbre à `path`, sous `root` (`.` pour un chemin
+/// relatif) : un lien symbolique, ou un fichier lu en flux depuis le store avec
+/// ses permissions. Un lien déjà en place est remplacé, jamais suivi, et aucun
+/// dossier entre `root` et `path` ne doit en être un. `false` si le blob manque.
+pub(crate) fn write_entry(
+    conn: &Connection,
+    hash: &str,
+    mode: i64,
+    root: &Path,
+    path: &Path,
+) -> IoResult<bool> {
+    if let Some(link) = symlinked_parent(root, path) {
+        return Err(IoError::other(format!(
+            "'{}' is beyond a symbolic link ({})",
+            path.display(),
+            link.display()
+        )));
+    }
+    let Some(mut blob) = crate::chunks::open(conn, hash).map_err(IoError::other)? else {
+        return Ok(false);
+    };
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink() || is_symlink(mode))
+    {
+        std::fs::remove_file(path)?;
+    }
+    #[cfg(unix)]
+    if is_symlink(mode) {
+        use std::os::unix::ffi::OsStrExt;
+        let mut target = Vec::new();
+        std::io::Read::read_to_end(&mut blob, &mut target)?;
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(&target), path)?;
+        return Ok(true);
+    }
+    // Sans liens symboliques, la cible est écrite comme un fichier
+    let mut f = File::create(path)?;
+    std::io::copy(&mut blob, &mut f)?;
+    f.sync_data()?;
+    #[cfg(unix)]
+    if mode != 0 {
+        f.set_permissions(std::fs::Permissions::from_mode((mode as u32) & 0o7777))?;
+    }
+    Ok(true)
+}
+
+// Remonte depuis `path` en retirant les dossiers restés vides (jamais la racine)
+pub(crate) fn remove_empty_parents(path: &Path) {
+    let mut dir = path.parent();
+    while let Some(parent) = dir
+        && !parent.as_os_str().is_empty()
+        && std::fs::remove_dir(parent).is_ok()
+    {
+        dir = parent.parent();
+    }
+}
+
+/// Crée les dossiers vides de `target` et retire ceux de `current` qu'il n'a
+/// plus, s'ils sont toujours vides.
+pub(crate) fn write_empty_dirs(
+    current: &BTreeSet<PathBuf>,
+    target: &BTreeSet<PathBuf>,
+) -> IoResult<()> {
+    for dir in target {
+        create_dir_all(dir)?;
+    }
+    for dir in current.difference(target) {
+        if std::fs::remove_dir(dir).is_ok() {
+            remove_empty_parents(dir);
+        }
+    }
+    Ok(())
+}
+
 #[cfg(unix)]
 pub fn get_file_mode(path: &Path) -> Option<u32> {
-    let metadata = std::fs::metadata(path).ok()?;
+    let metadata = std::fs::symlink_metadata(path).ok()?;
     Some(metadata.permissions().mode())
 }
 
@@ -49,9 +201,10 @@ pub fn get_file_mode(_path: &Path) -> Option<u32> {
 
 #[derive(Debug)]
 pub enum FileStatus {
-    New(PathBuf),           // N'existe pas en base -> Nouvel Asset
-    Modified(PathBuf, i64), // Existe mais hash différent -> Même Asset
-    Deleted(PathBuf, i64),  // Existe en base mais plus sur disque
+    New(PathBuf),                   // N'existe pas en base -> Nouvel Asset
+    Modified(PathBuf, i64),         // Existe mais hash différent -> Même Asset
+    Deleted(PathBuf, i64),          // Existe en base mais plus sur disque
+    Renamed(PathBuf, PathBuf, i64), // Déplacé, contenu identique ou proche -> Même Asset
     Unchanged,
 }
 
@@ -83,7 +236,8 @@ pub fn push_atoms(conn: &Connection, remote_url: &str) -> Result<(), Box<dyn std
 }
 
 pub fn sync(destination_path: &str) -> Result<(), IoError> {
-    let files: Vec<Result<PathBuf, GlobError>> = glob("./.lys/db/*.db").expect("a").collect();
+    // Les saisons sont rangées dans `.lys/db/ANNEE/saison/` : on descend dans tout l'arbre
+    let files: Vec<Result<PathBuf, GlobError>> = glob(".lys/db/**/*.db").expect("a").collect();
     let total_files = files.len();
 
     // Création de la barre
@@ -101,13 +255,14 @@ pub fn sync(destination_path: &str) -> Result<(), IoError> {
     create_dir_all(format!("{destination_path}/.lys/db"))?;
     if x.exists() {
         for file in files.iter().flatten() {
-            let z = file.file_name().expect("failed to get filename");
-            pb.set_message(format!("Syncing {}", z.to_string_lossy()));
+            let z = file.strip_prefix(".lys/db").unwrap_or(file);
+            pb.set_message(format!("Syncing {}", z.display()));
 
-            copy(
-                file.as_path().to_str().expect("failed to get file path"),
-                x.join(format!(".lys/db/{}", z.display())),
-            )?;
+            let target = x.join(".lys/db").join(z);
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent)?;
+            }
+            copy(file, target)?;
 
             pb.inc(1); // On avance la barre
         }
@@ -121,15 +276,7 @@ pub fn fetch_blob_with_conn(
     conn: &Connection,
     hash: &str,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let mut stmt = conn.prepare("SELECT content FROM store.blobs WHERE hash = ?")?;
-    stmt.bind((1, hash))?;
-
-    if let Ok(State::Row) = stmt.next() {
-        let compressed: Vec<u8> = stmt.read(0)?;
-        let decompressed = crate::db::decompress(&compressed);
-        return Ok(decompressed);
-    }
-    Err(format!("Blob {hash} not found").into())
+    crate::chunks::read(conn, hash)?.ok_or_else(|| format!("Blob {hash} not found").into())
 }
 
 /// Va chercher un blob en utilisant un chemin absolu ou calculé
@@ -149,16 +296,9 @@ pub fn fetch_blob(repo_root: &Path, hash: &str) -> Result<Vec<u8>, Box<dyn std::
     // Petite optimisation pour la lecture seule
     conn.execute("PRAGMA query_only = ON;")?;
 
-    let mut stmt = conn.prepare("SELECT content FROM store.blobs WHERE hash = ?")?;
-    stmt.bind((1, hash))?;
-
-    if let Ok(State::Row) = stmt.next() {
-        let compressed: Vec<u8> = stmt.read(0)?;
-        let decompressed = crate::db::decompress(&compressed);
-        return Ok(decompressed);
-    }
-
-    Err(format!("Blob {hash} not found in the store {db_path:?}").into())
+    // Connexion au seul store.db : les tables n'y sont pas préfixées par `store.`
+    crate::chunks::read(&conn, hash)?
+        .ok_or_else(|| format!("Blob {hash} not found in the store {db_path:?}").into())
 }
 
 fn restore_tree(
@@ -182,37 +322,18 @@ fn restore_tree(
     }
 
     for (name, hash, mode) in nodes {
-        #[cfg(not(unix))]
-        let _ = mode;
-
         let path = current_path.join(&name);
-        let is_dir =
-            is_directory(conn, &hash).map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
+        // Un dossier vide n'a pas d'enfants : seul son mode le distingue
+        let is_dir = is_dir_mode(mode)
+            || is_directory(conn, &hash).map_err(|e| -> Box<dyn std::error::Error> { e.into() })?;
 
         if is_dir {
             create_dir_all(&path)?;
             // Récursion : on va chercher les fichiers DANS ce dossier
             restore_tree(conn, &hash, &path, repo_root)?;
         } else {
-            // C'est un fichier : on l'extrait de store.db
-            if let Ok(content) = fetch_blob_with_conn(conn, &hash) {
-                // Création du dossier parent au cas où
-                if let Some(parent) = path.parent() {
-                    create_dir_all(parent)?;
-                }
-                let mut f = File::create(&path)?;
-                f.write_all(&content)?;
-                f.sync_data()?;
-                // Sur Unix, on restaure les permissions du système
-                #[cfg(unix)]
-                {
-                    use std::fs::Permissions;
-                    if mode != 0 {
-                        let perm_bits = (mode as u32) & 0o7777;
-                        f.set_permissions(Permissions::from_mode(perm_bits))?;
-                    }
-                }
-            }
+            // Fichier (lu en flux depuis store.db, avec ses permissions) ou lien symbolique
+            write_entry(conn, &hash, mode, repo_root, &path)?;
         }
     }
     Ok(())
@@ -440,46 +561,43 @@ pub fn checkout_head(
     Ok(())
 }
 
-fn get_manifest_map(
+pub(crate) fn get_manifest_map(
     conn: &Connection,
     commit_id: Option<i64>,
 ) -> Result<HashMap<String, (String, i64)>, Error> {
-    let mut map = HashMap::new();
-    if let Some(id) = commit_id {
-        // On récupère le tree_hash du commit spécifique
-        let query = "SELECT tree_hash FROM commits WHERE id = ?";
-        let mut stmt = conn.prepare(query)?;
-        stmt.bind((1, id))?;
-
-        if let Ok(State::Row) = stmt.next() {
-            let tree_hash: String = stmt.read(0)?;
-            let mut path_map = HashMap::new();
-            // On utilise ton flatten_tree pour obtenir l'état complet
-            flatten_tree(conn, &tree_hash, PathBuf::new(), &mut path_map)?;
-            // Conversion PathBuf -> String pour rester compatible avec la logique de checkout
-            for (p, (h, a)) in path_map {
-                map.insert(p.to_string_lossy().to_string(), (h, a));
-            }
-        }
+    match commit_tree(conn, commit_id)? {
+        Some(tree_hash) => tree_files(conn, &tree_hash),
+        None => Ok(HashMap::new()),
     }
-    Ok(map)
 }
 
-fn get_blob_bytes(conn: &Connection, branch: &str, path: &Path) -> Result<Option<Vec<u8>>, Error> {
-    // 1. On récupère l'état complet du HEAD via l'arbre Merkle
-    let state = get_head_state(conn, branch).expect("failed");
-    // On nettoie le chemin pour la recherche dans la map
-    let relative_path = path.strip_prefix("./").unwrap_or(path).to_path_buf();
-
-    if let Some((hash, _)) = state.get(&relative_path) {
-        // 2. Si trouvé, on récupère les octets via le hash
-        return get_blob_bytes_by_hash(conn, hash);
+// Arbre d'un commit de la saison courante, par son id
+fn commit_tree(conn: &Connection, commit_id: Option<i64>) -> Result<Option<String>, Error> {
+    let Some(id) = commit_id else {
+        return Ok(None);
+    };
+    let mut stmt = conn.prepare("SELECT tree_hash FROM commits WHERE id = ?")?;
+    stmt.bind((1, id))?;
+    if let Ok(State::Row) = stmt.next() {
+        Ok(Some(stmt.read(0)?))
+    } else {
+        Ok(None)
     }
-    Ok(None)
+}
+
+/// Fichiers d'un arbre, chemins en chaînes comme pour la logique de checkout.
+fn tree_files(conn: &Connection, tree_hash: &str) -> Result<HashMap<String, (String, i64)>, Error> {
+    let mut path_map = HashMap::new();
+    // On utilise ton flatten_tree pour obtenir l'état complet
+    flatten_tree(conn, tree_hash, PathBuf::new(), &mut path_map)?;
+    Ok(path_map
+        .into_iter()
+        .map(|(p, entry)| (p.to_string_lossy().to_string(), entry))
+        .collect())
 }
 
 // Helper pour savoir si un hash est un dossier (présent en tant que parent)
-fn is_directory(conn: &Connection, hash: &str) -> Result<bool, Error> {
+pub(crate) fn is_directory(conn: &Connection, hash: &str) -> Result<bool, Error> {
     let query = "SELECT 1 FROM tree_nodes WHERE parent_tree_hash = ? LIMIT 1";
     let mut stmt = conn.prepare(query)?;
     stmt.bind((1, hash))?;
@@ -617,6 +735,9 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> IoResult<()> {
             copy_dir_recursive(&from, &to)?;
         } else if file_type.is_file() {
             copy(&from, &to)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(&from)?, &to)?;
         }
     }
     Ok(())
@@ -636,7 +757,7 @@ fn ensure_empty_dir(path: &Path) -> IoResult<()> {
     Ok(())
 }
 
-fn reconstruct_to_path(
+pub(crate) fn reconstruct_to_path(
     conn: &Connection,
     tree_hash: &str,
     dest: &Path,
@@ -647,7 +768,7 @@ fn reconstruct_to_path(
     }
 
     // 2. On lance l'extraction récursive
-    extract_tree_recursive(conn, tree_hash, dest)?;
+    extract_tree_recursive(conn, tree_hash, dest, dest)?;
 
     Ok(())
 }
@@ -655,14 +776,11 @@ fn reconstruct_to_path(
 fn extract_tree_recursive(
     conn: &Connection,
     tree_hash: &str,
+    root: &Path,
     current_dest: &Path,
 ) -> Result<(), sqlite::Error> {
-    // On récupère les enfants et on joint avec store.blobs pour avoir le contenu
-    let query = "
-        SELECT tn.name, tn.hash, tn.mode, b.content 
-        FROM tree_nodes tn
-        LEFT JOIN store.blobs b ON tn.hash = b.hash
-        WHERE tn.parent_tree_hash = ?";
+    // Le contenu est lu ensuite en flux : un gros blob n'est jamais chargé en entier
+    let query = "SELECT name, hash, mode FROM tree_nodes WHERE parent_tree_hash = ?";
 
     let mut stmt = conn.prepare(query)?;
     stmt.bind((1, tree_hash))?;
@@ -673,38 +791,27 @@ fn extract_tree_recursive(
             stmt.read::<String, _>("name")?,
             stmt.read::<String, _>("hash")?,
             stmt.read::<i64, _>("mode")?,
-            stmt.read::<Option<Vec<u8>>, _>("content")?,
         ));
     }
 
-    for (name, hash, mode, content) in entries {
-        #[cfg(not(unix))]
-        let _ = mode;
-
+    for (name, hash, mode) in entries {
         let full_path = current_dest.join(name);
 
-        let is_dir = is_directory(conn, &hash).map_err(|e| sqlite::Error {
-            code: Some(1),
-            message: Some(format!("is_directory failed: {e}")),
-        })?;
+        let is_dir = is_dir_mode(mode)
+            || is_directory(conn, &hash).map_err(|e| sqlite::Error {
+                code: Some(1),
+                message: Some(format!("is_directory failed: {e}")),
+            })?;
         if is_dir {
-            // C'est un dossier
+            // C'est un dossier (éventuellement vide)
             create_dir_all(&full_path).unwrap();
-            extract_tree_recursive(conn, &hash, &full_path)?;
-        } else if let Some(raw_data) = content {
-            // C'est un fichier : on décompresse et on écrit
-            let decoded = crate::db::decompress(&raw_data);
-            let mut f = File::create(full_path).expect("");
-            f.write_all(&decoded).expect("a");
-            f.sync_all().expect("a");
-            #[cfg(unix)]
-            {
-                use std::fs::Permissions;
-                if mode != 0 {
-                    let perm_bits = (mode as u32) & 0o7777;
-                    f.set_permissions(Permissions::from_mode(perm_bits)).ok();
-                }
-            }
+            extract_tree_recursive(conn, &hash, root, &full_path)?;
+        } else {
+            // Fichier avec ses permissions, ou lien symbolique
+            write_entry(conn, &hash, mode, root, &full_path).map_err(|e| sqlite::Error {
+                code: Some(1),
+                message: Some(format!("failed to write {}: {e}", full_path.display())),
+            })?;
         }
     }
     Ok(())
@@ -717,20 +824,17 @@ pub fn commit_manual(
     timestamp: i64,
     tree_hash: &str, // Ajout du paramètre
 ) -> Result<i64, sqlite::Error> {
-    let query_last = "SELECT hash FROM commits ORDER BY id DESC LIMIT 1";
-    let mut stmt_last = conn.prepare(query_last)?;
-    let parent_hash = if let Ok(State::Row) = stmt_last.next() {
-        stmt_last.read::<String, _>(0)?
-    } else {
-        String::from("")
-    };
-    let parent_ref = if parent_hash.is_empty() {
-        None
-    } else {
-        Some(parent_hash.as_str())
-    };
-    let (id, _) =
-        commit_manual_with_parent(conn, message, author, timestamp, tree_hash, parent_ref)?;
+    // Le parent est la tête de la branche courante, pas le dernier commit global
+    let branch = get_current_branch(conn).unwrap_or_else(|_| String::from("main"));
+    let (_, parent_hash) = get_branch_head_info(conn, &branch).map_err(|e| sqlite::Error {
+        code: Some(1),
+        message: Some(e.to_string()),
+    })?;
+    let parents: Vec<String> = Some(parent_hash)
+        .into_iter()
+        .filter(|h| !h.is_empty())
+        .collect();
+    let (id, _) = commit_manual_with_parent(conn, message, author, timestamp, tree_hash, &parents)?;
     Ok(id)
 }
 
@@ -740,22 +844,26 @@ pub fn commit_manual_with_parent(
     author: &str,
     timestamp: i64,
     tree_hash: &str,
-    parent_hash: Option<&str>,
+    parents: &[String],
 ) -> Result<(i64, String), sqlite::Error> {
-    let parent_for_hash = parent_hash.unwrap_or("");
-    let commit_data = format!("{parent_for_hash}{author}{message}{timestamp}{tree_hash}");
-    let lys_hash = blake3::hash(commit_data.as_bytes()).to_hex().to_string();
+    // Même format que datetime(?, 'unixepoch') pour que le hash reste recalculable
+    let at = chrono::DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let lys_hash = compute_commit_hash(parents, tree_hash, author, message, &at);
 
     let query = "INSERT INTO commits (hash, parent_hash, tree_hash, author, message, timestamp) 
-                 VALUES (?, ?, ?, ?, ?, datetime(?, 'unixepoch'))";
+                 VALUES (?, ?, ?, ?, ?, ?)";
     let mut stmt = conn.prepare(query)?;
     stmt.bind((1, lys_hash.as_str()))?;
-    stmt.bind((2, parent_hash))?;
+    stmt.bind((2, parents.first().map(String::as_str)))?;
     stmt.bind((3, tree_hash))?;
     stmt.bind((4, author))?;
     stmt.bind((5, message))?;
-    stmt.bind((6, timestamp))?;
+    stmt.bind((6, at.as_str()))?;
     stmt.next()?;
+    crate::db::insert_commit_parents(conn, &lys_hash, parents)?;
 
     let id_query = "SELECT last_insert_rowid()";
     let mut stmt_id = conn.prepare(id_query)?;
@@ -764,6 +872,27 @@ pub fn commit_manual_with_parent(
     Ok((id, lys_hash))
 }
 
+/// Hash Merkle d'un commit : parents ordonnés, arbre, auteur, message et date.
+/// Chaque champ est terminé par un octet nul pour éviter les ambiguïtés.
+pub fn compute_commit_hash(
+    parents: &[String],
+    tree_hash: &str,
+    author: &str,
+    message: &str,
+    timestamp: &str,
+) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for parent in parents {
+        hasher.update(parent.as_bytes());
+        hasher.update(b"\0");
+    }
+    for field in [tree_hash, author, message, timestamp] {
+        hasher.update(field.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
 pub fn tag_create(conn: &Connection, name: &str, message: Option<&str>) -> Result<(), IoError> {
     // 1. On récupère le commit actuel (HEAD)
     let current_branch = get_current_branch(conn).expect("failed to get current branch");
@@ -794,6 +923,7 @@ pub fn tag_create(conn: &Connection, name: &str, message: Option<&str>) -> Resul
         )),
         Err(_) => return Err(IoError::other(format!("Tag '{name}' already exists."))),
     }
+    crate::oplog::record(conn, "tag").map_err(|e| IoError::other(e.to_string()))?;
     Ok(())
 }
 
@@ -869,16 +999,12 @@ pub fn hotfix_finish(conn: &Connection, name: &str) -> Result<(), Error> {
     if hf_head_id.is_none() {
         return Err(anyhow::anyhow!("hotfix not exist"));
     }
-    ok(format!("Switching to '{target_branch}' to apply hotfix...").as_str());
-    checkout(conn, target_branch)?;
-
-    // Fast-Forward Merge
-    let query = "UPDATE branches SET head_commit_id = ? WHERE name = ?";
-    let mut stmt = conn.prepare(query)?;
-    stmt.bind((1, hf_head_id.unwrap()))?;
-    stmt.bind((2, target_branch))?;
-    stmt.next()?;
-
+    if !crate::merge::merge_branch(conn, &hotfix_branch, target_branch)? {
+        // Conflits : la branche est conservée jusqu'au commit de résolution
+        return Err(anyhow::anyhow!(
+            "Merge of '{hotfix_branch}' stopped on conflicts; resolve them with 'lys resolve <path>' and run 'lys commit'."
+        ));
+    }
     ok("Hotfix applied to main");
 
     // Nettoyage
@@ -886,6 +1012,7 @@ pub fn hotfix_finish(conn: &Connection, name: &str) -> Result<(), Error> {
     let mut del_stmt = conn.prepare(delete_query)?;
     del_stmt.bind((1, hotfix_branch.as_str()))?;
     del_stmt.next()?;
+    crate::oplog::record(conn, "hotfix finish")?;
     ok(&format!("Hotfix '{name}' finished and branch deleted."));
     Ok(())
 }
@@ -910,28 +1037,23 @@ pub fn feature_finish(conn: &Connection, name: &str) -> Result<(), Error> {
     // 1. Sécurité : On vérifie que la branche feature existe
     let (feat_head_id, _) = get_branch_head_info(conn, &feat_branch)?;
     if feat_head_id.is_none() {
-        return Err(anyhow::anyhow!("main branch not exist"));
+        return Err(anyhow::anyhow!("feature branch not exist"));
     }
 
-    // 2. On bascule sur 'main' pour préparer la fusion
-    ok(format!("Switching to '{target_branch}' to merge changes...").as_str());
-    checkout(conn, target_branch)?;
-
-    // 3. LE FAST-FORWARD (L'optimisation ultime)
-    // Au lieu de calculer un diff, on déplace juste le pointeur de main sur la tête de la feature
-    let query = "UPDATE branches SET head_commit_id = ? WHERE name = ?";
-    let mut stmt = conn.prepare(query)?;
-    stmt.bind((1, feat_head_id.unwrap()))?;
-    stmt.bind((2, target_branch))?;
-    stmt.next()?;
-
-    ok("Fast-forward merge complete");
+    // 2. Fusion dans 'main' : fast-forward si possible, sinon merge à trois voies
+    if !crate::merge::merge_branch(conn, &feat_branch, target_branch)? {
+        // Conflits : la branche est conservée jusqu'au commit de résolution
+        return Err(anyhow::anyhow!(
+            "Merge of '{feat_branch}' stopped on conflicts; resolve them with 'lys resolve <path>' and run 'lys commit'."
+        ));
+    }
 
-    // 4. Nettoyage : On supprime la branche temporaire
+    // 3. Nettoyage : On supprime la branche temporaire
     let delete_query = "DELETE FROM branches WHERE name = ?";
     let mut del_stmt = conn.prepare(delete_query)?;
     del_stmt.bind((1, feat_branch.as_str()))?;
     del_stmt.next()?;
+    crate::oplog::record(conn, "feat finish")?;
     ok(&format!("Feature '{name}' finished and branch deleted."));
     Ok(())
 }
@@ -949,7 +1071,10 @@ pub fn create_branch(conn: &Connection, new_branch_name: &str) -> Result<(), Err
         stmt.bind((2, id))?;
 
         match stmt.next() {
-            Ok(_) => ok(&format!("Branch '{new_branch_name}' created.")),
+            Ok(_) => {
+                crate::oplog::record(conn, "branch")?;
+                ok(&format!("Branch '{new_branch_name}' created."))
+            }
             Err(_) => ko(format!("Error: branch '{new_branch_name}' already exists.").as_str()),
         }
     } else {
@@ -977,56 +1102,38 @@ pub fn checkout(conn: &Connection, target_ref: &str) -> Result<(), Error> {
     }
 
     // 2. PRÉPARATION DES DONNÉES (C'est ici qu'on change la logique !)
-    let (current_head_id, _) = get_branch_head_info(conn, &current_branch)?;
+    let (current_head_id, current_head) = get_branch_head_info(conn, &current_branch)?;
 
     // A. Est-ce une BRANCHE ?
     let (branch_head_id, _) = get_branch_head_info(conn, target_ref)?;
 
-    // B. Sinon, est-ce un HASH (Time Travel) ?
-    let target_head_id = if branch_head_id.is_some() {
-        branch_head_id
+    // B. Sinon, est-ce un HASH (Time Travel) ? Il peut venir de n'importe quelle saison
+    let target_tree = if branch_head_id.is_some() {
+        commit_tree(conn, branch_head_id)?.unwrap_or_default()
+    } else if let Some(entry) = crate::history::commit(conn, target_ref)? {
+        entry.tree
     } else {
-        get_commit_id_by_hash(conn, target_ref)?
-    };
-
-    // Si introuvable ni en branche, ni en commit
-    if target_head_id.is_none() {
+        // Introuvable ni en branche, ni en commit
         return Err(anyhow::anyhow!(
             "Reference '{target_ref}' (branch or commit) not found."
         ));
-    }
+    };
     // On charge les deux manifestes en mémoire pour comparer
-    let current_files = get_manifest_map(conn, current_head_id)?;
-    let target_files = get_manifest_map(conn, target_head_id)?;
+    let current_tree = match crate::history::commit(conn, &current_head)? {
+        Some(entry) if current_head_id.is_none() => entry.tree,
+        _ => commit_tree(conn, current_head_id)?.unwrap_or_default(),
+    };
+    let target_files = tree_files(conn, &target_tree)?;
+    let current_files = tree_files(conn, &current_tree)?;
     ok(format!("Switched to branch '{target_ref}'").as_str());
 
-    // 3. MISE À JOUR DU DISQUE (Différentiel)
+    // 3. MISE À JOUR DU DISQUE (Différentiel), dossiers vides compris
+    write_state_diff(conn, &current_files, &target_files)?;
+    write_empty_dirs(
+        &empty_dirs(conn, &current_tree)?,
+        &empty_dirs(conn, &target_tree)?,
+    )?;
 
-    // A. Gérer les AJOUTS et MODIFICATIONS (Target vs Current)
-    for (path, (target_hash, _)) in &target_files {
-        let should_write = match current_files.get(path) {
-            Some((current_hash, _)) => current_hash != target_hash, // Modifié
-            None => true,                                           // Nouveau fichier
-        };
-
-        if should_write {
-            // On récupère le contenu binaire depuis le store
-            if let Some(content) = get_blob_bytes_by_hash(conn, target_hash)?
-                && let Some(parent) = Path::new(path).parent()
-            {
-                create_dir_all(parent).expect("failed to create directory");
-                std::fs::write(path, content).expect("failed to write content");
-            }
-        }
-    }
-
-    // B. Gérer les SUPPRESSIONS (Ce qui est dans Current mais plus dans Target)
-    for path in current_files.keys() {
-        if !target_files.contains_key(path) && Path::new(path).exists() {
-            std::fs::remove_file(path).expect("failed to remove the file");
-            // Optionnel : Supprimer les dossiers vides parents
-        }
-    }
     let query = "INSERT INTO config (key, value) VALUES ('current_branch', ?) 
                  ON CONFLICT(key) DO UPDATE SET value = excluded.value";
     let mut stmt = conn.prepare(query)?;
@@ -1039,33 +1146,74 @@ pub fn checkout(conn: &Connection, target_ref: &str) -> Result<(), Error> {
         stmt.bind((1, "DETACHED"))?;
     }
     stmt.next()?;
+    crate::oplog::record(conn, "checkout")?;
     Ok(())
 }
 
-// Récupère les octets via le hash (plus rapide que via le path)
-fn get_blob_bytes_by_hash(conn: &Connection, hash: &str) -> Result<Option<Vec<u8>>, Error> {
-    let query = "SELECT content FROM store.blobs WHERE hash = ?";
-    let mut stmt = conn.prepare(query)?;
-    stmt.bind((1, hash))?;
-    if let Ok(State::Row) = stmt.next() {
-        let raw: Vec<u8> = stmt.read("content")?;
-        Ok(Some(crate::db::decompress(&raw)))
-    } else {
-        Ok(None)
+/// Fait passer le disque de l'état `current` à l'état `target` en ne touchant
+/// que les fichiers qui diffèrent.
+pub(crate) fn write_state_diff(
+    conn: &Connection,
+    current_files: &HashMap<String, (String, i64)>,
+    target_files: &HashMap<String, (String, i64)>,
+) -> Result<(), Error> {
+    // A. Gérer les SUPPRESSIONS (Ce qui est dans Current mais plus dans Target),
+    //    avant les ajouts : un fichier peut laisser place à un dossier du même nom
+    for path in current_files.keys() {
+        let path = Path::new(path);
+        if !target_files.contains_key(path.to_string_lossy().as_ref())
+            && std::fs::symlink_metadata(path).is_ok()
+        {
+            std::fs::remove_file(path).expect("failed to remove the file");
+            remove_empty_parents(path);
+        }
+    }
+
+    // B. Gérer les AJOUTS et MODIFICATIONS (Target vs Current)
+    for (path, (target_hash, target_mode)) in target_files {
+        let should_write = match current_files.get(path) {
+            // Modifié, ou seulement rendu exécutable / changé en lien
+            Some((current_hash, current_mode)) => {
+                current_hash != target_hash
+                    || mode_marker(*current_mode) != mode_marker(*target_mode)
+            }
+            None => true, // Nouveau fichier
+        };
+
+        if should_write {
+            // Le contenu est lu en flux depuis le store, avec son mode
+            write_entry(
+                conn,
+                target_hash,
+                *target_mode,
+                Path::new("."),
+                Path::new(path),
+            )?;
+        }
     }
+    Ok(())
+}
+
+// Récupère les octets via le hash (plus rapide que via le path)
+pub(crate) fn get_blob_bytes_by_hash(
+    conn: &Connection,
+    hash: &str,
+) -> Result<Option<Vec<u8>>, Error> {
+    Ok(crate::chunks::read(conn, hash)?)
 }
 
 pub fn restore(conn: &Connection, path_str: &str) -> Result<(), Error> {
     let path = Path::new(path_str);
     let branch = get_current_branch(conn).expect("failed to get current branch");
-    // 1. On cherche le contenu original dans la BDD
-    match get_blob_bytes(conn, &branch, path)? {
-        Some(content) => {
-            // 2. Le fichier existe dans le HEAD, on l'écrase sur le disque
-            std::fs::write(path, content).expect("failed to restore");
+    // 1. On cherche l'entrée originale dans l'arbre du HEAD
+    let state = get_head_state(conn, &branch)?;
+    let relative_path = path.strip_prefix("./").unwrap_or(path);
+    match state.get(relative_path) {
+        Some((hash, mode)) if write_entry(conn, hash, *mode, Path::new("."), path)? => {
+            // 2. Le fichier existe dans le HEAD : il est réécrit avec son mode
             ok(&format!("Restored '{}' from HEAD.", path.display()));
         }
-        None => {
+        _ => {
             ko(format!(
                 "Error: File '{}' does not exist in the last commit.",
                 path.display()
@@ -1076,71 +1224,6 @@ pub fn restore(conn: &Connection, path_str: &str) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn diff(conn: &Connection) -> Result<(), Error> {
-    let current_dir = std::env::current_dir().expect("failed to get current dir");
-    let current_dir_str = current_dir.to_str().unwrap();
-    let branch = get_current_branch(conn).expect("failed to get current branch");
-    // 1. On récupère les changements (on réutilise ta logique de status)
-    let changes = status(conn, current_dir_str, &branch)?;
-
-    if changes.is_empty() {
-        return Ok(());
-    }
-
-    for change in changes {
-        match change {
-            FileStatus::Modified(path, _) => {
-                println!("\n\x1b[1;33mDiff: {}\x1b[0m", path.display());
-                println!("\x1b[90m==================================================\x1b[0m");
-
-                // A. Lire les octets du fichier sur le disque
-                let new_bytes = match std::fs::read(&path) {
-                    Ok(c) => c,
-                    Err(_) => {
-                        println!("(Unreadable file)");
-                        continue;
-                    }
-                };
-
-                // B. Récupérer les octets depuis le HEAD
-                let old_bytes = get_blob_bytes(conn, &branch, &path)?.unwrap_or_default();
-
-                let is_binary = |buf: &[u8]| buf.iter().any(|b| *b == 0);
-                if is_binary(&new_bytes) || is_binary(&old_bytes) {
-                    println!("(Binary file diff not shown)");
-                    continue;
-                }
-
-                let new_content = String::from_utf8_lossy(&new_bytes);
-                let old_content = String::from_utf8_lossy(&old_bytes);
-
-                // C. Calculer et afficher le Diff
-                let diff = TextDiff::from_lines(&old_content, &new_content);
-
-                for change in diff.iter_all_changes() {
-                    let (sign, color) = match change.tag() {
-                        ChangeTag::Delete => ("-", "\x1b[31m"), // Rouge
-                        ChangeTag::Insert => ("+", "\x1b[32m"), // Vert
-                        ChangeTag::Equal => (" ", "\x1b[0m"),   // Blanc
-                    };
-                    print!("{}{}{}\x1b[0m", color, sign, change);
-                }
-            }
-            FileStatus::New(path) => {
-                println!(
-                    "\n\x1b[1;32mNew File: {}\x1b[0m (All content is new)",
-                    path.display()
-                );
-            }
-            FileStatus::Deleted(path, _) => {
-                println!("\n\x1b[1;31mDeleted File: {}\x1b[0m", path.display());
-            }
-            _ => {}
-        }
-    }
-    Ok(())
-}
-
 pub fn count_lines(content: &[u8]) -> usize {
     match String::from_utf8(content.to_vec()) {
         Ok(s) => {
@@ -1170,136 +1253,139 @@ pub fn count_line_changes(old: &[u8], new: &[u8]) -> (usize, usize) {
     (added, deleted)
 }
 
-pub fn log(conn: &Connection, page: usize, per_page: usize) -> Result<(), sqlite::Error> {
+/// Page `page` du journal de la branche courante (tous les commits si HEAD
+/// est détaché), et le nombre de pages.
+pub(crate) fn log_entries(
+    conn: &Connection,
+    page: usize,
+    per_page: usize,
+    follow: Option<&Path>,
+) -> Result<(Vec<Log>, usize), sqlite::Error> {
     // Calcul de l'offset (Page 1 = Offset 0)
     let offset = (page - 1) * per_page;
 
-    // Get total number of commits
-    let mut count_stmt = conn.prepare("SELECT COUNT(*) FROM commits")?;
-    let total_commits: i64 = if let Ok(State::Row) = count_stmt.next() {
-        count_stmt.read(0)?
-    } else {
-        0
+    // On parcourt le DAG depuis la tête de la branche courante (tous les commits si HEAD
+    // détaché), à travers toutes les saisons
+    let branch = get_current_branch(conn).unwrap_or_default();
+    let history = match crate::history::branch_head(conn, &branch) {
+        Some(head) => crate::history::ancestry(conn, &[head])?,
+        None => crate::history::all_commits(conn)?,
     };
-    let total_pages = (total_commits as f64 / per_page as f64).ceil() as usize;
-
-    // Requête avec LIMIT et OFFSET (inclut tree_hash pour construire l'arborescence)
-    let query = "
-        SELECT hash, author, message, timestamp, tree_hash 
-        FROM commits 
-        ORDER BY timestamp DESC 
-        LIMIT ? OFFSET ?";
-
-    let mut stmt = conn.prepare(query)?;
-    stmt.bind((1, per_page as i64))?;
-    stmt.bind((2, offset as i64))?;
-
-    let mut rendered = Vec::new();
-    let mut idx_in_page = 0usize;
-    while let Ok(State::Row) = stmt.next() {
+    // --follow : seulement les commits qui touchent le fichier, sous ses noms successifs
+    let history: Vec<_> = match follow {
+        Some(path) => follow_renames(conn, history, path)?
+            .into_iter()
+            .map(|(entry, path)| (entry, Some(path)))
+            .collect(),
+        None => history.into_iter().map(|entry| (entry, None)).collect(),
+    };
+    let total_pages = (history.len() as f64 / per_page as f64).ceil() as usize;
+
+    // Branches locales et refs de suivi (origin/main) pointant sur chaque commit
+    let mut decorations: HashMap<String, Vec<String>> = HashMap::new();
+    let local_refs = crate::transfer::local_refs(conn).unwrap_or_default();
+    for (name, hash) in &local_refs {
+        decorations
+            .entry(hash.clone())
+            .or_default()
+            .push(name.clone());
+    }
+    for (remote, name, hash) in crate::remote::tracking_refs(conn).unwrap_or_default() {
+        decorations
+            .entry(hash)
+            .or_default()
+            .push(format!("{remote}/{name}"));
+    }
+
+    let mut logs = Vec::new();
+    for (entry, followed) in history.into_iter().skip(offset).take(per_page) {
         // On tronque le hash pour l'affichage (7 premiers chars)
-        let full_hash: String = stmt.read(0)?;
+        let full_hash = entry.hash.clone();
+        let parents = entry.parents.clone();
+        let refs = decorations.get(&full_hash).cloned().unwrap_or_default();
         let short_hash = if full_hash.len() > 7 {
             full_hash[0..7].to_string()
         } else {
             full_hash
         };
 
-        let tree_hash: String = stmt.read(4)?;
-        // Construit la liste des fichiers à partir du tree courant
-        let mut new_state: HashMap<PathBuf, (String, i64)> = HashMap::new();
-        flatten_tree(conn, &tree_hash, PathBuf::new(), &mut new_state)?;
-
-        // Récupère le tree du parent (commit suivant dans l'ordre DESC par timestamp)
-        let global_offset = offset + idx_in_page + 1; // +1 pour le parent suivant
-        let mut parent_tree: Option<String> = None;
-        if let Ok(mut parent_stmt) =
-            conn.prepare("SELECT tree_hash FROM commits ORDER BY timestamp DESC LIMIT 1 OFFSET ?")
-        {
-            parent_stmt.bind((1, global_offset as i64))?;
-            if let Ok(State::Row) = parent_stmt.next() {
-                parent_tree = Some(parent_stmt.read(0)?);
-            }
-        }
-        let mut old_state: HashMap<PathBuf, (String, i64)> = HashMap::new();
-        if let Some(pth) = parent_tree.as_deref() {
-            flatten_tree(conn, pth, PathBuf::new(), &mut old_state)?;
+        // Les changements sont affichés par rapport au premier parent
+        let (old_state, new_state) = first_parent_states(conn, &entry)?;
+        let mut found = tree_changes(conn, &old_state, &new_state);
+        if let Some(path) = &followed {
+            found.retain(|change| change.path() == path);
         }
-
-        // Calcule les changements
+        let mode = |state: &FileState, path: &Option<PathBuf>| {
+            path.as_ref()
+                .and_then(|path| state.get(path))
+                .map(|(_, mode)| *mode)
+        };
         let mut changes: Vec<(String, FileChange)> = Vec::new();
-
-        // Ajouts et modifications
-        for (path, (new_hash, new_mode)) in &new_state {
-            if let Some((old_hash, _)) = old_state.get(path) {
-                if old_hash != new_hash {
-                    // Modified
-                    let old_bytes = get_blob_bytes_by_hash(conn, old_hash)
-                        .ok()
-                        .flatten()
-                        .unwrap_or_default();
-                    let new_bytes = get_blob_bytes_by_hash(conn, new_hash)
-                        .ok()
-                        .flatten()
-                        .unwrap_or_default();
-                    let (a, d) = count_line_changes(&old_bytes, &new_bytes);
-                    changes.push((
-                        path.to_string_lossy().to_string(),
-                        FileChange::Modified {
-                            added: a,
-                            deleted: d,
-                            mode: Some(*new_mode),
+        for change in found {
+            let bytes = |hash: &Option<String>| {
+                hash.as_deref()
+                    .and_then(|hash| get_blob_bytes_by_hash(conn, hash).ok().flatten())
+                    .unwrap_or_default()
+            };
+            let (before, after) = (bytes(&change.old_hash), bytes(&change.new_hash));
+            let file_change = match change.status {
+                'A' => FileChange::Added {
+                    added: count_lines(&after),
+                    mode: mode(&new_state, &change.new_path),
+                },
+                'D' => FileChange::Deleted {
+                    deleted: count_lines(&before),
+                    mode: mode(&old_state, &change.old_path),
+                },
+                status => {
+                    let (added, deleted) = count_line_changes(&before, &after);
+                    let mode = mode(&new_state, &change.new_path);
+                    match &change.old_path {
+                        Some(from) if status != 'M' => FileChange::Renamed {
+                            from: from.to_string_lossy().to_string(),
+                            copy: status == 'C',
+                            added,
+                            deleted,
+                            mode,
                         },
-                    ));
-                } // else unchanged -> ignore
-            } else {
-                // Added
-                let nb = if let Some(bytes) = get_blob_bytes_by_hash(conn, new_hash).ok().flatten()
-                {
-                    count_lines(&bytes)
-                } else {
-                    0
-                };
-                changes.push((
-                    path.to_string_lossy().to_string(),
-                    FileChange::Added {
-                        added: nb,
-                        mode: Some(*new_mode),
-                    },
-                ));
-            }
-        }
-        // Suppressions
-        for (path, (old_hash, old_mode)) in &old_state {
-            if !new_state.contains_key(path) {
-                let nb = if let Some(bytes) = get_blob_bytes_by_hash(conn, old_hash).ok().flatten()
-                {
-                    count_lines(&bytes)
-                } else {
-                    0
-                };
-                changes.push((
-                    path.to_string_lossy().to_string(),
-                    FileChange::Deleted {
-                        deleted: nb,
-                        mode: Some(*old_mode),
-                    },
-                ));
-            }
+                        _ => FileChange::Modified {
+                            added,
+                            deleted,
+                            mode,
+                        },
+                    }
+                }
+            };
+            changes.push((change.path().to_string_lossy().to_string(), file_change));
         }
         // Trie pour affichage stable
         changes.sort_by(|a, b| a.0.cmp(&b.0));
 
         let log = Log {
-            author: stmt.read(1)?,
-            at: stmt.read(3)?,
-            message: stmt.read(2)?,
+            author: entry.author,
+            at: entry.timestamp,
+            message: entry.message,
             signature: short_hash,
+            parents: parents
+                .iter()
+                .map(|p| p.chars().take(7).collect())
+                .collect(),
+            refs,
             changes,
         };
-        rendered.push(log.to_string());
-        idx_in_page += 1;
+        logs.push(log);
     }
+    Ok((logs, total_pages))
+}
+
+pub fn log(
+    conn: &Connection,
+    page: usize,
+    per_page: usize,
+    follow: Option<&Path>,
+) -> Result<(), sqlite::Error> {
+    let (logs, total_pages) = log_entries(conn, page, per_page, follow)?;
+    let rendered: Vec<String> = logs.iter().map(Log::to_string).collect();
 
     if rendered.is_empty() {
         if page == 1 {
@@ -1335,11 +1421,81 @@ pub fn log(conn: &Connection, page: usize, per_page: usize) -> Result<(), sqlite
             footer.push_str(&format!(" Last: --page {total_pages}"));
         }
         ok(footer.as_str());
+        let branch = get_current_branch(conn).unwrap_or_default();
+        let local_refs = crate::transfer::local_refs(conn).unwrap_or_default();
+        if let Some(head) = local_refs.get(&branch) {
+            for line in crate::remote::tracking_summary(conn, &branch, head).unwrap_or_default() {
+                ok(line.as_str());
+            }
+        }
         println!("\n");
     }
     Ok(())
 }
 
+type FileState = HashMap<PathBuf, (String, i64)>;
+
+// États (hash, mode) du premier parent d'un commit et du commit lui-même
+fn first_parent_states(
+    conn: &Connection,
+    entry: &crate::history::Entry,
+) -> Result<(FileState, FileState), sqlite::Error> {
+    let mut new_state = HashMap::new();
+    flatten_tree(conn, &entry.tree, PathBuf::new(), &mut new_state)?;
+    let parent_tree = match entry.parents.first() {
+        Some(parent) => crate::db::commit_tree_hash(conn, parent)?,
+        None => None,
+    };
+    let mut old_state = HashMap::new();
+    if let Some(tree) = parent_tree.as_deref() {
+        flatten_tree(conn, tree, PathBuf::new(), &mut old_state)?;
+    }
+    Ok((old_state, new_state))
+}
+
+/// Changements entre deux états d'arbre, renommages (même modifiés) et copies compris.
+pub(crate) fn tree_changes(
+    conn: &Connection,
+    old: &FileState,
+    new: &FileState,
+) -> Vec<crate::diff::Change> {
+    let hashes = |state: &FileState| -> HashMap<PathBuf, String> {
+        state
+            .iter()
+            .map(|(path, (hash, _))| (path.clone(), hash.clone()))
+            .collect()
+    };
+    let blob = |_: &Path, hash: &str| get_blob_bytes_by_hash(conn, hash).ok().flatten();
+    crate::diff::changes_with(&hashes(old), &hashes(new), &[], &blob, &blob)
+}
+
+/// Commits de `history` (du plus récent au plus ancien) qui touchent `path`,
+/// avec le nom qu'il y porte : un renommage fait suivre l'ancien nom.
+pub(crate) fn follow_renames(
+    conn: &Connection,
+    history: Vec<crate::history::Entry>,
+    path: &Path,
+) -> Result<Vec<(crate::history::Entry, PathBuf)>, sqlite::Error> {
+    let mut tracked = path.to_path_buf();
+    let mut kept = Vec::new();
+    for entry in history {
+        let (old_state, new_state) = first_parent_states(conn, &entry)?;
+        let changes = tree_changes(conn, &old_state, &new_state);
+        let Some(change) = changes.iter().find(|change| change.path() == tracked) else {
+            continue;
+        };
+        let previous = match change.status {
+            'R' | 'C' => change.old_path.clone(),
+            _ => None,
+        };
+        kept.push((entry, tracked.clone()));
+        if let Some(previous) = previous {
+            tracked = previous;
+        }
+    }
+    Ok(kept)
+}
+
 pub fn files() -> Vec<String> {
     let mut all: Vec<String> = Vec::new();
     let walk = ignore::WalkBuilder::new(".")
@@ -1365,7 +1521,7 @@ pub fn files() -> Vec<String> {
     all
 }
 
-fn insert_into_tree(root: &mut Node, path: &Path, hash: String, mode: u32, size: u64) {
+pub(crate) fn insert_into_tree(root: &mut Node, path: &Path, hash: String, mode: u32, size: u64) {
     let mut current = root;
 
     // On parcourt chaque composant du chemin (ex: ["src", "ui", "main.rs"])
@@ -1381,17 +1537,34 @@ fn insert_into_tree(root: &mut Node, path: &Path, hash: String, mode: u32, size:
     }
 
     // Une fois arrivé au bout du chemin, on remplace le nœud par le fichier réel
-    *current = Node::File { hash, mode, size };
+    *current = if is_symlink(mode as i64) {
+        Node::Symlink { hash, mode, size }
+    } else {
+        Node::File { hash, mode, size }
+    };
+}
+
+/// Ajoute un dossier vide (et ses parents) à l'arbre en mémoire.
+pub(crate) fn insert_empty_dir(root: &mut Node, path: &Path) {
+    let mut current = root;
+    for component in path.components() {
+        let name = component.as_os_str().to_string_lossy().to_string();
+        if let Node::Directory { children } = current {
+            current = children.entry(name).or_insert_with(|| Node::Directory {
+                children: BTreeMap::new(),
+            });
+        }
+    }
 }
 
-fn store_tree_recursive(
+pub(crate) fn store_tree_recursive(
     conn: &Connection,
     _name: &str,
     node: &Node,
 ) -> Result<String, sqlite::Error> {
     match node {
-        // Si c'est un fichier, on retourne juste son hash (déjà calculé)
-        Node::File { hash, .. } => Ok(hash.clone()),
+        // Si c'est un fichier ou un lien, on retourne juste son hash (déjà calculé)
+        Node::File { hash, .. } | Node::Symlink { hash, .. } => Ok(hash.clone()),
 
         // Si c'est un dossier, on doit traiter ses enfants
         Node::Directory { children } => {
@@ -1403,13 +1576,17 @@ fn store_tree_recursive(
                 let child_hash = store_tree_recursive(conn, name, child_node)?;
 
                 let (mode, size) = match child_node {
-                    Node::File { mode, size, .. } => (*mode, Some(*size as i64)),
+                    Node::File { mode, size, .. } | Node::Symlink { mode, size, .. } => {
+                        (*mode, Some(*size as i64))
+                    }
                     Node::Directory { .. } => (0o755, None), // Mode par défaut pour les répertoires
                 };
 
-                // On nourrit le hash du dossier avec les données de l'enfant (Nom + Hash)
+                // On nourrit le hash du dossier avec les données de l'enfant
+                // (Nom + Hash, + type pour un lien ou un exécutable)
                 hasher.update(name.as_bytes());
                 hasher.update(child_hash.as_bytes());
+                hasher.update(mode_marker(mode as i64));
 
                 children_data.push((name, child_hash, mode, size));
             }
@@ -1432,105 +1609,168 @@ pub fn commit(conn: &Connection, message: &str, author: &str) -> Result<(), Erro
     let mut root_tree = Node::Directory {
         children: BTreeMap::new(),
     };
-    let walk = ignore::WalkBuilder::new(".")
-        .threads(4)
-        .add_custom_ignore_filename("syl")
-        .standard_filters(true)
-        .build();
-
-    for result in walk.flatten() {
-        let path = result.path();
-        if path.is_dir() || path.components().any(|c| c.as_os_str() == ".lys") {
-            continue;
+    let head = get_head_state(conn, &get_current_branch(conn)?)?;
+    let scan = crate::worktree::scan(conn, Path::new("."))?;
+    let threshold = crate::chunks::threshold(conn);
+
+    for (relative, file) in scan.files {
+        let mut content_hash = file.hash;
+        // Seuls les fichiers modifiés ou nouveaux apportent un blob au store
+        if head.get(&relative).map(|(hash, _)| hash) != Some(&content_hash) {
+            // Le fichier a pu changer depuis le scan : le blob fait foi
+            if file.size >= threshold && !is_symlink(file.mode as i64) {
+                // Gros fichier : découpé en lisant, sans être chargé en mémoire
+                let reader = File::open(&relative).expect("failed to read file");
+                content_hash = crate::chunks::write(conn, reader, None)?.hash;
+            } else {
+                let content = read_entry(&relative).expect("failed to read file");
+                content_hash = blake3::hash(&content).to_hex().to_string();
+                crate::db::insert_blob_with_conn(conn, &content_hash, &content)
+                    .expect("failed to insert blob");
+            }
         }
 
-        let relative = path.strip_prefix("./").unwrap_or(path);
-        let content = std::fs::read(path).expect("failed to read file");
-        let content_hash = blake3::hash(&content).to_hex().to_string();
-        let metadata = std::fs::metadata(path).expect("failed to get metadata");
+        // Insertion du fichier dans notre structure d'arbre en mémoire
+        insert_into_tree(
+            &mut root_tree,
+            &relative,
+            content_hash,
+            file.mode,
+            file.size,
+        );
+    }
+    for dir in &scan.dirs {
+        insert_empty_dir(&mut root_tree, dir);
+    }
 
-        // On insère le blob dans la base de données
-        crate::db::insert_blob_with_conn(conn, &content_hash, &content)
-            .expect("failed to insert blob");
+    let pick = crate::db::config(conn, crate::pick::PICK_KIND)?;
+    if !pick.is_empty() {
+        return Err(anyhow::anyhow!(
+            "A {pick} is in progress. Run 'lys {pick} --continue' or 'lys {pick} --abort'."
+        ));
+    }
 
-        // Insertion du fichier dans notre structure d'arbre en mémoire
-        let mode = get_file_mode(path).unwrap_or(0);
-        insert_into_tree(&mut root_tree, relative, content_hash, mode, metadata.len());
+    // Un merge en conflit se conclut par ce commit, une fois chaque conflit
+    // marqué résolu avec `lys resolve`
+    let merge_head = crate::db::config(conn, crate::merge::MERGE_HEAD)?;
+    if !merge_head.is_empty() {
+        let pending = crate::db::config(conn, crate::merge::MERGE_CONFLICTS)?;
+        if !pending.is_empty() {
+            for path in pending.lines() {
+                ko(format!("unresolved conflict in {path}").as_str());
+            }
+            return Err(anyhow::anyhow!(
+                "Resolve the conflicts and mark them with 'lys resolve <path>' before committing the merge."
+            ));
+        }
     }
 
     // 2. On calcule les hashes de chaque dossier et on insère dans SQLite
     // Le hash du dossier racine (root) sera notre tree_hash pour le commit
     conn.execute("BEGIN TRANSACTION;")?;
-    // Récupération du parent pour le chaînage immuable
-    let query_last = "SELECT hash FROM commits ORDER BY id DESC LIMIT 1";
-    let mut stmt_last = conn.prepare(query_last)?;
-    let parent_hash = if let Ok(State::Row) = stmt_last.next() {
-        stmt_last.read::<String, _>(0)?
-    } else {
-        String::from("")
-    };
+    // Récupération du parent pour le chaînage immuable : la tête de la branche courante
+    let (_, head_hash) = get_branch_head_info(conn, &get_current_branch(conn)?)?;
+    let mut parents: Vec<String> = Some(head_hash)
+        .into_iter()
+        .filter(|h| !h.is_empty())
+        .collect();
+    if !merge_head.is_empty() {
+        parents.push(merge_head.clone());
+    }
     let root_hash = store_tree_recursive(conn, "ROOT", &root_tree)?;
-    // 3. Création du commit avec le lien vers l'arbre racine
-    let timestamp = chrono::Utc::now().to_rfc3339();
-    let commit_hash = blake3::hash(format!("{root_hash}{author}{message}").as_bytes())
-        .to_hex()
-        .to_string();
-    let signature = sign_message(Path::new("."), &commit_hash).expect("aaa");
-
-    let query_commit =
-        "INSERT INTO commits (hash, parent_hash, tree_hash, author, message, timestamp, signature)
-         VALUES (?, ?, ?, ?, ?, ?, ?)";
-    let mut stmt = conn.prepare(query_commit)?;
-    stmt.bind((1, commit_hash.as_str()))?;
-    stmt.bind((2, parent_hash.as_str()))?;
-    stmt.bind((3, root_hash.as_str()))?;
-    stmt.bind((4, author))?;
-    stmt.bind((5, message))?;
-    stmt.bind((6, timestamp.as_str()))?;
-    stmt.bind((7, signature.as_str()))?;
-    stmt.next()?;
+    let commit_hash = record_commit(conn, &root_hash, &parents, message, author)?;
+
+    if !merge_head.is_empty() {
+        // La branche fusionnée a désormais rempli son rôle
+        let merged_branch = crate::db::config(conn, crate::merge::MERGE_BRANCH)?;
+        if !merged_branch.is_empty() {
+            let mut del_stmt = conn.prepare("DELETE FROM branches WHERE name = ?")?;
+            del_stmt.bind((1, merged_branch.as_str()))?;
+            del_stmt.next()?;
+            ok(&format!(
+                "Merge of '{merged_branch}' concluded and branch deleted."
+            ));
+        }
+        crate::db::remove_config(conn, crate::merge::MERGE_HEAD)?;
+        crate::db::remove_config(conn, crate::merge::MERGE_BRANCH)?;
+        crate::db::remove_config(conn, crate::merge::MERGE_CONFLICTS)?;
+    }
 
     // 4. On enregistre l'opération dans l'OpLog pour le Undo
-    let log_query = "INSERT INTO operations_log (operation_type, view_state) VALUES ('commit', ?)";
-    let mut log_stmt = conn.prepare(log_query)?;
-    log_stmt.bind((1, format!("{{\"head\": \"{commit_hash}\"}}").as_str()))?;
-    log_stmt.next()?;
-
-    let id_query = "SELECT last_insert_rowid()";
-    let mut stmt_id = conn.prepare(id_query)?;
-    stmt_id.next()?;
-    let commit_id: i64 = stmt_id.read(0)?;
+    crate::oplog::record(conn, "commit")?;
+    conn.execute("COMMIT;")?;
+    commit_created(&commit_hash[0..7]);
+    Ok(())
+}
 
-    // 5. Remplissage du manifest pour la vue tree (seulement si modifié)
-    let mut state_map = HashMap::new();
-    flatten_tree(conn, &root_hash, PathBuf::new(), &mut state_map)?;
+/// Enregistre un commit pointant sur `root_hash` et avance la branche courante.
+/// Le premier parent est l'ancienne tête de la branche ; les suivants sont les
+/// branches fusionnées. L'appelant gère la transaction.
+pub(crate) fn record_commit(
+    conn: &Connection,
+    root_hash: &str,
+    parents: &[String],
+    message: &str,
+    author: &str,
+) -> Result<String, Error> {
+    // 3. Création du commit avec le lien vers l'arbre racine
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let commit_hash = compute_commit_hash(parents, root_hash, author, message, &timestamp);
+    let signature = sign_message(Path::new("."), &commit_hash).expect("aaa");
+    insert_commit(
+        conn,
+        &commit_hash,
+        root_hash,
+        parents,
+        (author, message, &timestamp),
+        Some(&signature),
+    )?;
+    Ok(commit_hash)
+}
 
-    // On récupère l'état du parent pour comparer
+/// Enregistre un commit déjà haché et signé (rejoué depuis un patch, par
+/// exemple) puis avance la branche courante dessus.
+pub(crate) fn insert_commit(
+    conn: &Connection,
+    commit_hash: &str,
+    root_hash: &str,
+    parents: &[String],
+    (author, message, timestamp): (&str, &str, &str),
+    signature: Option<&str>,
+) -> Result<(), Error> {
     let branch = get_current_branch(conn)?;
-    let parent_state = get_head_state(conn, &branch).unwrap_or_default();
-
-    for (path, (blob_hash, _)) in state_map {
-        // On n'insère dans le manifest QUE si le fichier a changé
-        let should_insert = match parent_state.get(&path) {
-            Some((old_hash, _)) => old_hash != &blob_hash,
-            None => true, // Nouveau fichier
-        };
-
-        if should_insert {
-            let mut stmt_blob = conn.prepare("SELECT id FROM store.blobs WHERE hash = ?")?;
-            stmt_blob.bind((1, blob_hash.as_str()))?;
-            if let Ok(State::Row) = stmt_blob.next() {
-                let blob_id: i64 = stmt_blob.read(0)?;
-                let query_manifest = "INSERT INTO manifest (commit_id, asset_id, blob_id, file_path) VALUES (?, ?, ?, ?)";
-                let mut stmt_m = conn.prepare(query_manifest)?;
-                stmt_m.bind((1, commit_id))?;
-                stmt_m.bind((2, 0))?; // dummy asset_id
-                stmt_m.bind((3, blob_id))?;
-                stmt_m.bind((4, path.to_string_lossy().as_ref()))?;
-                stmt_m.next()?;
-            }
+    // Un commit déjà connu (patch appliqué deux fois) : seule la branche avance
+    let commit_id = match get_commit_id_by_hash(conn, commit_hash)? {
+        Some(id) => id,
+        None => {
+            let parent_hash = parents.first().map(String::as_str).unwrap_or("");
+            let query_commit =
+                "INSERT INTO commits (hash, parent_hash, tree_hash, author, message, timestamp, signature)
+                 VALUES (?, ?, ?, ?, ?, ?, ?)";
+            let mut stmt = conn.prepare(query_commit)?;
+            stmt.bind((1, commit_hash))?;
+            stmt.bind((2, parent_hash))?;
+            stmt.bind((3, root_hash))?;
+            stmt.bind((4, author))?;
+            stmt.bind((5, message))?;
+            stmt.bind((6, timestamp))?;
+            stmt.bind((7, signature))?;
+            stmt.next()?;
+
+            let id_query = "SELECT last_insert_rowid()";
+            let mut stmt_id = conn.prepare(id_query)?;
+            stmt_id.next()?;
+            let commit_id: i64 = stmt_id.read(0)?;
+
+            // Parents ordonnés (un merge en a deux)
+            crate::db::insert_commit_parents(conn, commit_hash, parents)?;
+
+            // 5. Remplissage du manifest pour la vue tree (seulement si modifié)
+            let parent_state = get_head_state(conn, &branch).unwrap_or_default();
+            insert_manifest_delta(conn, commit_id, root_hash, &parent_state)?;
+            commit_id
         }
-    }
+    };
 
     // On récupère la branche actuelle et on met à jour son pointeur HEAD
     let update_branch = "INSERT INTO branches (name, head_commit_id) VALUES (?, ?) 
@@ -1539,9 +1779,56 @@ pub fn commit(conn: &Connection, message: &str, author: &str) -> Result<(), Erro
     stmt_br.bind((1, branch.as_str()))?;
     stmt_br.bind((2, commit_id))?;
     stmt_br.next()?;
+    Ok(())
+}
 
-    conn.execute("COMMIT;")?;
-    commit_created(&commit_hash[0..7]);
+/// Remplit le manifest d'un commit avec les fichiers qui diffèrent de `parent_state`.
+pub(crate) fn insert_manifest_delta(
+    conn: &Connection,
+    commit_id: i64,
+    root_hash: &str,
+    parent_state: &HashMap<PathBuf, (String, i64)>,
+) -> Result<(), Error> {
+    let mut state_map = HashMap::new();
+    flatten_tree(conn, root_hash, PathBuf::new(), &mut state_map)?;
+    let renamed: HashMap<PathBuf, PathBuf> = tree_changes(conn, parent_state, &state_map)
+        .into_iter()
+        .filter(|change| change.status == 'R')
+        .filter_map(|change| Some((change.new_path?, change.old_path?)))
+        .collect();
+
+    for (path, (blob_hash, _)) in &state_map {
+        // On n'insère dans le manifest QUE si le fichier a changé ; il garde son
+        // asset s'il est modifié ou renommé, un nouveau fichier (ou une copie) en reçoit un
+        let previous = match parent_state.get(path) {
+            Some((old_hash, _)) if old_hash == blob_hash => continue,
+            Some((old_hash, _)) => Some((path, old_hash)),
+            None => renamed
+                .get(path)
+                .and_then(|from| Some((from, &parent_state.get(from)?.0))),
+        };
+
+        let mut stmt_blob = conn.prepare("SELECT id FROM store.blobs WHERE hash = ?")?;
+        stmt_blob.bind((1, blob_hash.as_str()))?;
+        if let Ok(State::Row) = stmt_blob.next() {
+            let blob_id: i64 = stmt_blob.read(0)?;
+            let asset_id = match previous {
+                Some((from, hash)) => crate::assets::asset_of(conn, from, hash)?,
+                None => None,
+            };
+            let asset_id = match asset_id {
+                Some(asset_id) => asset_id,
+                None => crate::db::create_asset(conn)?,
+            };
+            let query_manifest = "INSERT INTO manifest (commit_id, asset_id, blob_id, file_path) VALUES (?, ?, ?, ?)";
+            let mut stmt_m = conn.prepare(query_manifest)?;
+            stmt_m.bind((1, commit_id))?;
+            stmt_m.bind((2, asset_id))?;
+            stmt_m.bind((3, blob_id))?;
+            stmt_m.bind((4, path.to_string_lossy().as_ref()))?;
+            stmt_m.next()?;
+        }
+    }
     Ok(())
 }
 
@@ -1551,7 +1838,16 @@ pub fn get_head_state(
 ) -> Result<HashMap<PathBuf, (String, i64)>, sqlite::Error> {
     let mut state_map = HashMap::new();
 
-    // On va chercher le tree_hash du dernier commit de la branche
+    if let Some(root_hash) = head_tree(conn, branch)? {
+        // On "aplatit" l'arbre Merkle pour obtenir une liste de fichiers utilisable
+        flatten_tree(conn, &root_hash, PathBuf::new(), &mut state_map)?;
+    }
+
+    Ok(state_map)
+}
+
+// On va chercher le tree_hash du dernier commit de la branche
+fn head_tree(conn: &Connection, branch: &str) -> Result<Option<String>, sqlite::Error> {
     let query = "
         SELECT c.tree_hash 
         FROM branches b 
@@ -1561,15 +1857,16 @@ pub fn get_head_state(
     stmt.bind((1, branch))?;
 
     if let Ok(State::Row) = stmt.next() {
-        let root_hash: String = stmt.read(0)?;
-        // On "aplatit" l'arbre Merkle pour obtenir une liste de fichiers utilisable
-        flatten_tree(conn, &root_hash, PathBuf::new(), &mut state_map)?;
+        Ok(Some(stmt.read(0)?))
+    } else {
+        Ok(None)
     }
-
-    Ok(state_map)
 }
 // On met à jour get_branch_head_info pour chercher dans 'old' si besoin
-fn get_branch_head_info(conn: &Connection, branch: &str) -> Result<(Option<i64>, String), Error> {
+pub(crate) fn get_branch_head_info(
+    conn: &Connection,
+    branch: &str,
+) -> Result<(Option<i64>, String), Error> {
     // 1. Base actuelle
     let query = "SELECT c.id, c.hash FROM branches b JOIN commits c ON b.head_commit_id = c.id WHERE b.name = ?";
     let mut stmt = conn.prepare(query)?;
@@ -1579,17 +1876,12 @@ fn get_branch_head_info(conn: &Connection, branch: &str) -> Result<(Option<i64>,
         return Ok((Some(stmt.read("id")?), stmt.read("hash")?));
     }
 
-    // 2. Repli sur la base 'old' (la "Dernière Base Connue")
-    let query_old = "SELECT c.hash FROM old.branches b JOIN old.commits c ON b.head_commit_id = c.id WHERE b.name = ?";
-    if let Ok(mut stmt_old) = conn.prepare(query_old) {
-        stmt_old.bind((1, branch))?;
-        if let Ok(State::Row) = stmt_old.next() {
-            // On renvoie l'ID à None (car l'ID de 'old' n'existe pas ici) mais le HASH pour le chaînage
-            return Ok((None, stmt_old.read("hash")?));
-        }
-    }
-
-    Ok((None, String::new()))
+    // 2. Repli sur la saison la plus récente qui connaît la branche
+    // On renvoie l'ID à None (car l'ID d'une autre saison n'existe pas ici) mais le HASH pour le chaînage
+    Ok((
+        None,
+        crate::history::branch_head(conn, branch).unwrap_or_default(),
+    ))
 }
 
 pub fn flatten_tree(
@@ -1598,32 +1890,63 @@ pub fn flatten_tree(
     current_path: PathBuf,
     state: &mut HashMap<PathBuf, (String, i64)>,
 ) -> Result<(), sqlite::Error> {
-    let query = "SELECT name, hash, mode FROM tree_nodes WHERE parent_tree_hash = ?";
-    let mut stmt = conn.prepare(query)?;
-    stmt.bind((1, tree_hash))?;
+    // L'arbre d'un commit d'une autre saison est rangé dans la base de celle-ci
+    let schema = crate::history::tree_schema(conn, tree_hash);
+    flatten_tree_in(conn, &schema, tree_hash, current_path, state)
+}
 
-    let mut entries = Vec::new();
-    while let Ok(State::Row) = stmt.next() {
-        entries.push((
-            stmt.read::<String, _>("name")?,
-            stmt.read::<String, _>("hash")?,
-            stmt.read::<i64, _>("mode")?,
-        ));
-    }
+fn flatten_tree_in(
+    conn: &Connection,
+    schema: &str,
+    tree_hash: &str,
+    current_path: PathBuf,
+    state: &mut HashMap<PathBuf, (String, i64)>,
+) -> Result<(), sqlite::Error> {
+    leaves(conn, schema, tree_hash, |path, hash, mode| {
+        // On stocke le fichier avec son hash et son mode ; un dossier vide n'en est pas un
+        if !is_dir_mode(mode) {
+            state.insert(current_path.join(path), (hash, mode));
+        }
+    })
+}
 
-    for (name, hash, mode) in entries {
-        let path = current_path.join(name);
-        let is_dir = is_directory(conn, &hash).map_err(|e| sqlite::Error {
-            code: Some(1),
-            message: Some(format!("is_directory failed: {e}")),
-        })?;
-        if is_dir {
-            // C'est un répertoire
-            flatten_tree(conn, &hash, path, state)?;
-        } else {
-            // On stocke le fichier avec son hash et son mode
-            state.insert(path, (hash, mode));
+/// Dossiers vides d'un arbre, chemins relatifs à sa racine.
+pub(crate) fn empty_dirs(
+    conn: &Connection,
+    tree_hash: &str,
+) -> Result<BTreeSet<PathBuf>, sqlite::Error> {
+    let schema = crate::history::tree_schema(conn, tree_hash);
+    let mut dirs = BTreeSet::new();
+    leaves(conn, &schema, tree_hash, |path, _, mode| {
+        if is_dir_mode(mode) {
+            dirs.insert(PathBuf::from(path));
         }
+    })?;
+    Ok(dirs)
+}
+
+// Feuilles de l'arbre (fichiers, liens et dossiers vides) : `(chemin, hash, mode)`
+fn leaves(
+    conn: &Connection,
+    schema: &str,
+    tree_hash: &str,
+    mut each: impl FnMut(String, String, i64),
+) -> Result<(), sqlite::Error> {
+    // Tout l'arbre en une requête : un nœud qui est lui-même parent est un dossier
+    let query = format!(
+        "WITH RECURSIVE walk(path, hash, mode) AS (
+             SELECT name, hash, mode FROM {schema}.tree_nodes WHERE parent_tree_hash = ?
+             UNION ALL
+             SELECT walk.path || '/' || t.name, t.hash, t.mode
+             FROM walk JOIN {schema}.tree_nodes t ON t.parent_tree_hash = walk.hash
+         )
+         SELECT path, hash, mode FROM walk
+         WHERE NOT EXISTS (SELECT 1 FROM {schema}.tree_nodes d WHERE d.parent_tree_hash = walk.hash)"
+    );
+    let mut stmt = conn.prepare(query)?;
+    stmt.bind((1, tree_hash))?;
+    while let Ok(State::Row) = stmt.next() {
+        each(stmt.read("path")?, stmt.read("hash")?, stmt.read("mode")?);
     }
     Ok(())
 }
@@ -1631,49 +1954,65 @@ pub fn flatten_tree(
 pub fn status(conn: &Connection, root_path: &str, branch: &str) -> Result<Vec<FileStatus>, Error> {
     let db_state = get_head_state(conn, branch).expect("failed to get db state");
     let mut changes = Vec::new();
-    let mut files_on_disk: HashSet<PathBuf> = HashSet::new();
-    let walk = ignore::WalkBuilder::new(root_path)
-        .add_custom_ignore_filename("syl")
-        .threads(4)
-        .standard_filters(true)
-        .build()
-        .flatten()
-        .collect::<Vec<DirEntry>>();
-
-    for path in &walk {
-        if path.path().components().any(|c| c.as_os_str() == ".lys") || path.path().is_dir() {
-            continue;
-        }
-
-        let relative_path = path
-            .path()
-            .strip_prefix(root_path)
-            .expect("failed to get relative path")
-            .to_path_buf();
-        files_on_disk.insert(relative_path.clone());
-
-        let current_hash = match calculate_hash(path.path()) {
-            Ok(h) => h,
-            Err(_) => continue, // On ignore les fichiers illisibles (ou on log un warning)
-        };
-        // Comparaison
-        match db_state.get(&relative_path) {
-            Some((db_hash, asset_id)) => {
-                if *db_hash != current_hash {
-                    changes.push(FileStatus::Modified(relative_path, *asset_id));
+    // Seuls les fichiers dont le stat a changé depuis le dernier scan sont relus
+    let scan = crate::worktree::scan(conn, Path::new(root_path))?;
+
+    let (mut added, mut deleted) = (Vec::new(), Vec::new());
+    for (relative_path, file) in &scan.files {
+        // Comparaison : contenu, puis bit exécutable ou passage en lien symbolique
+        match db_state.get(relative_path) {
+            Some((db_hash, mode)) => {
+                if *db_hash != file.hash || mode_marker(*mode) != mode_marker(file.mode as i64) {
+                    let asset = crate::assets::asset_of(conn, relative_path, db_hash)?;
+                    changes.push(FileStatus::Modified(
+                        relative_path.clone(),
+                        asset.unwrap_or(0),
+                    ));
                 }
             }
-            None => {
-                // Le fichier n'est pas dans le manifest -> New
-                changes.push(FileStatus::New(relative_path));
-            }
+            // Le fichier n'est pas dans le manifest -> New
+            None => added.push((relative_path.clone(), file.hash.clone())),
+        }
+    }
+    for (path, (hash, _)) in &db_state {
+        if !scan.files.contains_key(path) {
+            deleted.push((path.clone(), hash.clone()));
+        }
+    }
+    // Un fichier disparu dont le contenu (ou presque) réapparaît ailleurs a été renommé
+    let root = Path::new(root_path);
+    let renames = crate::assets::renames(
+        &deleted,
+        &added,
+        |_, hash| get_blob_bytes_by_hash(conn, hash).ok().flatten(),
+        |path, _| read_entry(&root.join(path)).ok(),
+    );
+    for (path, _) in added {
+        if !renames.iter().any(|pair| pair.to == path) {
+            changes.push(FileStatus::New(path));
         }
     }
-    for (path, (_, asset_id)) in db_state {
-        if !files_on_disk.contains(&path) {
-            changes.push(FileStatus::Deleted(path, asset_id));
+    for (path, hash) in deleted {
+        if !renames.iter().any(|pair| pair.from == path) {
+            let asset = crate::assets::asset_of(conn, &path, &hash)?;
+            changes.push(FileStatus::Deleted(path, asset.unwrap_or(0)));
         }
     }
+    for pair in renames {
+        let asset = crate::assets::asset_of(conn, &pair.from, &db_state[&pair.from].0)?;
+        changes.push(FileStatus::Renamed(pair.from, pair.to, asset.unwrap_or(0)));
+    }
+    // Dossiers vides ajoutés ou disparus
+    let db_dirs = match head_tree(conn, branch)? {
+        Some(tree) => empty_dirs(conn, &tree)?,
+        None => BTreeSet::new(),
+    };
+    for dir in scan.dirs.difference(&db_dirs) {
+        changes.push(FileStatus::New(dir.clone()));
+    }
+    for dir in db_dirs.difference(&scan.dirs) {
+        changes.push(FileStatus::Deleted(dir.clone(), 0));
+    }
     if changes.is_empty() {
         ok("No changes detected. Working tree is clean.");
     } else {
@@ -1685,17 +2024,10 @@ pub fn status(conn: &Connection, root_path: &str, branch: &str) -> Result<Vec<Fi
 }
 
 pub fn calculate_hash(path: &Path) -> IoResult<String> {
-    let mut file = File::open(path)?;
+    let file = File::open(path)?;
     let mut hasher = blake3::Hasher::new();
-    let mut buffer = [0; 1024]; // Buffer de lecture
-
-    loop {
-        let count = file.read(&mut buffer)?;
-        if count == 0 {
-            break;
-        }
-        hasher.update(&buffer[..count]);
-    }
+    // Lecture par blocs de 64 Kio, sans charger le fichier entier
+    hasher.update_reader(file)?;
     Ok(hex::encode(hasher.finalize().as_bytes()))
 }
 
@@ -1734,15 +2066,301 @@ pub fn is_file_in_state(path: &Path, tree_hash: &str, conn: &Connection) -> bool
     }
 }
 
+/// Id du commit désigné par un hash ou son début, cherché dans toutes les
+/// saisons. Les ids sont propres à chaque base : seul un commit de la saison
+/// courante en a un que les branches et le manifest peuvent désigner.
 pub fn get_commit_id_by_hash(conn: &Connection, partial_hash: &str) -> Result<Option<i64>, Error> {
-    // On cherche un hash qui COMMENCE par la chaîne donnée (LIKE 'abc%')
-    let query = "SELECT id FROM commits WHERE hash LIKE ? || '%' LIMIT 1";
-    let mut stmt = conn.prepare(query)?;
-    stmt.bind((1, partial_hash))?;
+    Ok(crate::history::commit(conn, partial_hash)?
+        .filter(|entry| entry.schema == "main")
+        .map(|entry| entry.id))
+}
 
-    if let Ok(State::Row) = stmt.next() {
-        Ok(stmt.read("id")?)
-    } else {
-        Ok(None)
+/// Hash complet désigné par `reference` : branche, tag, début de hash ou
+/// `HEAD`, éventuellement suivi de `~N` (N premiers parents) ou `^N` (N-ième parent).
+pub(crate) fn resolve_commit(conn: &Connection, reference: &str) -> Result<Option<String>, Error> {
+    if reference.is_empty() {
+        return Ok(None);
+    }
+    if reference == "HEAD" {
+        let (_, head) = get_branch_head_info(conn, &get_current_branch(conn)?)?;
+        return Ok(Some(head).filter(|h| !h.is_empty()));
+    }
+    let (_, head) = get_branch_head_info(conn, reference)?;
+    if !head.is_empty() {
+        return Ok(Some(head));
+    }
+    if let Some(hash) = crate::db::tag_hash(conn, reference) {
+        return Ok(Some(hash));
+    }
+    if let Some(pos) = reference.rfind(['~', '^']) {
+        let (base, suffix) = reference.split_at(pos);
+        let Ok(count) = (if suffix.len() == 1 {
+            Ok(1)
+        } else {
+            suffix[1..].parse::<usize>()
+        }) else {
+            return Ok(None);
+        };
+        let Some(mut hash) = resolve_commit(conn, base)? else {
+            return Ok(None);
+        };
+        if suffix.starts_with('^') {
+            // `^0` désigne le commit lui-même
+            if count == 0 {
+                return Ok(Some(hash));
+            }
+            return Ok(crate::db::commit_parents(conn, &hash)?
+                .into_iter()
+                .nth(count - 1));
+        }
+        for _ in 0..count {
+            match crate::db::commit_parents(conn, &hash)?.into_iter().next() {
+                Some(parent) => hash = parent,
+                None => return Ok(None),
+            }
+        }
+        return Ok(Some(hash));
+    }
+    // Un début de hash, dans la saison courante comme dans les anciennes
+    match crate::history::matches(conn, reference).as_slice() {
+        [] => Ok(None),
+        [(_, hash)] => Ok(Some(hash.clone())),
+        _ => Err(anyhow::anyhow!(
+            "Ambiguous reference '{reference}': several commits start with it."
+        )),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::utils::AUTHOR;
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    // Ce que l'arbre de travail contient, `.lys` exclu : contenu et permissions
+    // des fichiers, cible des liens, dossiers
+    fn snapshot(root: &Path) -> BTreeMap<PathBuf, String> {
+        let mut entries = BTreeMap::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(root).unwrap().to_path_buf();
+                if relative == Path::new(".lys") {
+                    continue;
+                }
+                let metadata = std::fs::symlink_metadata(&path).unwrap();
+                let kind = if metadata.file_type().is_symlink() {
+                    format!("-> {}", std::fs::read_link(&path).unwrap().display())
+                } else if metadata.is_dir() {
+                    pending.push(path);
+                    "dir".to_string()
+                } else {
+                    let content = std::fs::read_to_string(&path).unwrap();
+                    format!("{:o} {content}", metadata.permissions().mode() & 0o7777)
+                };
+                entries.insert(relative, kind);
+            }
+        }
+        entries
+    }
+
+    // Exécutables, liens (vers un fichier, un dossier, nulle part) et dossiers
+    // vides survivent à un commit, une extraction et des allers-retours de checkout
+    #[test]
+    fn round_trips_symlinks_modes_and_empty_dirs() {
+        let (repo, conn) = crate::utils::test_repo();
+
+        std::fs::create_dir_all("bin").unwrap();
+        std::fs::write("bin/run.sh", "#!/bin/sh\necho run\n").unwrap();
+        std::fs::set_permissions("bin/run.sh", std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write("notes.txt", "plain\n").unwrap();
+        symlink("notes.txt", "link.txt").unwrap();
+        symlink("bin", "tools").unwrap();
+        symlink("nowhere/at/all", "dangling").unwrap();
+        std::fs::create_dir_all("a/b/c").unwrap();
+        std::fs::create_dir_all("empty").unwrap();
+        let original = snapshot(repo.path());
+        commit(&conn, "everything", AUTHOR).unwrap();
+        let clean = status(&conn, ".", "main").unwrap();
+
+        let (_, head) = get_branch_head_info(&conn, "main").unwrap();
+        let tree = crate::db::commit_tree_hash(&conn, &head).unwrap().unwrap();
+        let extracted = tempfile::tempdir().unwrap();
+        reconstruct_to_path(&conn, &tree, extracted.path()).unwrap();
+        let report = crate::fsck::run(&conn, repo.path(), true, None).unwrap();
+
+        create_branch(&conn, "bare").unwrap();
+        checkout(&conn, "bare").unwrap();
+        for entry in std::fs::read_dir(".").unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().is_some_and(|name| name == ".lys") {
+                continue;
+            }
+            match std::fs::symlink_metadata(&path).unwrap().is_dir() {
+                true => std::fs::remove_dir_all(&path).unwrap(),
+                false => std::fs::remove_file(&path).unwrap(),
+            }
+        }
+        commit(&conn, "nothing left", AUTHOR).unwrap();
+        checkout(&conn, "main").unwrap();
+        let back = snapshot(repo.path());
+        checkout(&conn, "bare").unwrap();
+        let bare = snapshot(repo.path());
+        checkout(&conn, "main").unwrap();
+        let again = snapshot(repo.path());
+
+        std::fs::set_permissions("bin/run.sh", std::fs::Permissions::from_mode(0o644)).unwrap();
+        let chmod = status(&conn, ".", "main").unwrap();
+        restore(&conn, "bin/run.sh").unwrap();
+        let restored = snapshot(repo.path());
+
+        // Un lien déjà sur le disque ne sert jamais de chemin vers l'extérieur
+        let outside = tempfile::tempdir().unwrap();
+        symlink(outside.path(), "escape").unwrap();
+        let plain = blake3::hash(b"plain\n").to_hex().to_string();
+        let root = Path::new(".");
+        let escaped = write_entry(&conn, &plain, 0o100644, root, Path::new("escape/owned.txt"));
+        let through = write_entry(&conn, &plain, 0o100644, root, Path::new("tools/owned.txt"));
+        std::fs::remove_file("escape").unwrap();
+
+        assert!(clean.is_empty(), "{clean:?}");
+        assert_eq!(snapshot(extracted.path()), original);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!(back, original);
+        assert!(bare.is_empty(), "{bare:?}");
+        assert_eq!(again, original);
+        assert!(
+            matches!(&chmod[..], [FileStatus::Modified(path, _)] if path == Path::new("bin/run.sh")),
+            "{chmod:?}"
+        );
+        assert_eq!(restored, original);
+        assert!(escaped.is_err() && through.is_err());
+        assert_eq!(std::fs::read_dir(outside.path()).unwrap().count(), 0);
+        assert!(!Path::new("bin/owned.txt").exists());
+    }
+
+    // Une fin de branche bloquée par des conflits doit échouer sans la supprimer
+    #[test]
+    fn feature_finish_fails_on_conflicts() {
+        let (_repo, conn) = crate::utils::test_repo();
+        std::fs::write("a.txt", "base\n").unwrap();
+        commit(&conn, "base", AUTHOR).unwrap();
+        feature_start(&conn, "clash").unwrap();
+        std::fs::write("a.txt", "feature\n").unwrap();
+        commit(&conn, "feature side", AUTHOR).unwrap();
+        checkout(&conn, "main").unwrap();
+        std::fs::write("a.txt", "main\n").unwrap();
+        commit(&conn, "main side", AUTHOR).unwrap();
+
+        let finished = feature_finish(&conn, "clash");
+
+        assert!(finished.is_err());
+        let (branch_id, _) = get_branch_head_info(&conn, "feature/clash").unwrap();
+        assert!(branch_id.is_some());
+        assert!(
+            !crate::db::config(&conn, crate::merge::MERGE_HEAD)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // Deux branches qui ont divergé sans se toucher : commit de fusion à deux parents
+    #[test]
+    fn feature_finish_merges_diverged_branches() {
+        let (_repo, conn) = crate::utils::test_repo();
+        std::fs::write("a.txt", "base\n").unwrap();
+        commit(&conn, "base", AUTHOR).unwrap();
+        feature_start(&conn, "side").unwrap();
+        std::fs::write("b.txt", "feature\n").unwrap();
+        commit(&conn, "feature side", AUTHOR).unwrap();
+        let (_, feature) = get_branch_head_info(&conn, "feature/side").unwrap();
+        checkout(&conn, "main").unwrap();
+        std::fs::write("a.txt", "main\n").unwrap();
+        commit(&conn, "main side", AUTHOR).unwrap();
+        let (_, main) = get_branch_head_info(&conn, "main").unwrap();
+
+        feature_finish(&conn, "side").unwrap();
+
+        let (_, head) = get_branch_head_info(&conn, "main").unwrap();
+        let parents = crate::db::commit_parents(&conn, &head).unwrap();
+        let (branch_id, _) = get_branch_head_info(&conn, "feature/side").unwrap();
+        assert_eq!(parents, vec![main, feature]);
+        assert!(branch_id.is_none());
+        assert_eq!(std::fs::read_to_string("a.txt").unwrap(), "main\n");
+        assert_eq!(std::fs::read_to_string("b.txt").unwrap(), "feature\n");
+        assert!(status(&conn, ".", "main").unwrap().is_empty());
+        assert!(crate::crypto::audit(&conn).unwrap());
+    }
+
+    // Un fichier supprimé d'un côté et modifié de l'autre n'a pas de marqueurs :
+    // le merge ne se conclut qu'une fois le conflit déclaré résolu
+    #[test]
+    fn merge_commit_waits_for_resolved_conflicts() {
+        let (_repo, conn) = crate::utils::test_repo();
+        std::fs::write("a.txt", "base\n").unwrap();
+        commit(&conn, "base", AUTHOR).unwrap();
+        feature_start(&conn, "drop").unwrap();
+        std::fs::remove_file("a.txt").unwrap();
+        std::fs::write("b.txt", "feature\n").unwrap();
+        commit(&conn, "feature side", AUTHOR).unwrap();
+        let (_, feature) = get_branch_head_info(&conn, "feature/drop").unwrap();
+        checkout(&conn, "main").unwrap();
+        std::fs::write("a.txt", "main\n").unwrap();
+        commit(&conn, "main side", AUTHOR).unwrap();
+        let (_, main) = get_branch_head_info(&conn, "main").unwrap();
+
+        assert!(feature_finish(&conn, "drop").is_err());
+        let survivor = std::fs::read_to_string("a.txt").unwrap();
+        let early = commit(&conn, "too early", AUTHOR);
+        let unknown = crate::merge::resolve(&conn, &["b.txt".to_string()]);
+        crate::merge::resolve(&conn, &["./a.txt".to_string()]).unwrap();
+        commit(&conn, "merge", AUTHOR).unwrap();
+
+        let (_, head) = get_branch_head_info(&conn, "main").unwrap();
+        let (branch_id, _) = get_branch_head_info(&conn, "feature/drop").unwrap();
+        assert_eq!(survivor, "main\n");
+        assert!(early.is_err());
+        assert!(unknown.is_err());
+        assert_eq!(
+            crate::db::commit_parents(&conn, &head).unwrap(),
+            vec![main, feature]
+        );
+        assert!(branch_id.is_none());
+        assert!(
+            crate::db::config(&conn, crate::merge::MERGE_HEAD)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // Un début de hash se compare tel quel et doit désigner un seul commit
+    #[test]
+    fn resolves_hash_prefixes_exactly() {
+        let (_repo, conn) = crate::utils::test_repo();
+        std::fs::write("a.txt", "one\n").unwrap();
+        commit(&conn, "one", AUTHOR).unwrap();
+        let (_, head) = get_branch_head_info(&conn, "main").unwrap();
+        for fill in ["a", "b"] {
+            conn.execute(format!(
+                "INSERT INTO commits (hash, parent_hash, tree_hash, author, message, timestamp)
+                 VALUES ('deadbeef{}', '', '', 'x', 'x', '2020-01-01 00:00:00')",
+                fill.repeat(56)
+            ))
+            .unwrap();
+        }
+
+        let ambiguous = resolve_commit(&conn, "deadbeef");
+        let single = resolve_commit(&conn, "deadbeefa").unwrap();
+        let short = resolve_commit(&conn, &head[..8]).unwrap();
+        let upper = resolve_commit(&conn, &head[..8].to_uppercase()).unwrap();
+
+        let message = ambiguous.unwrap_err().to_string();
+        assert!(message.contains("Ambiguous reference"), "{message}");
+        assert_eq!(single, Some(format!("deadbeef{}", "a".repeat(56))));
+        assert_eq!(short, Some(head.clone()));
+        assert!(upper.is_none() || head[..8] == head[..8].to_uppercase());
+        assert_eq!(resolve_commit(&conn, "%").unwrap(), None);
+        assert_eq!(resolve_commit(&conn, "_").unwrap(), None);
     }
 }
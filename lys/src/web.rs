@@ -14,10 +14,12 @@ use axum::{
 };
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
+use futures_util::StreamExt;
 use once_cell::sync::OnceCell;
 use pulldown_cmark::{Options, Parser, html as cmark_html};
 use serde::Deserialize;
 use sqlite::Connection;
+use std::io::Read;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::path::PathBuf;
@@ -272,6 +274,13 @@ pub struct DiffParams {
     pub mode: Option<String>,
 }
 
+// Contexte facultatif de /file/{hash} : commit et chemin d'origine (lien Blame)
+#[derive(Deserialize)]
+pub struct FileContext {
+    pub commit: Option<i64>,
+    pub path: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct HooksNotice {
     pub hooks: Option<String>,
@@ -1381,6 +1390,7 @@ pub async fn start_server(repo_path: &str, port: u16) {
         .route("/working/restore", post(restore_working_deleted))
         .route("/commit/{id}/tree", get(show_commit_tree))
         .route("/commit/{id}/tree/{*path}", get(show_commit_tree))
+        .route("/commit/{id}/blame/{*path}", get(show_blame))
         .route("/editor", get(editor_list))
         .route("/editor/new", post(editor_new))
         .route("/editor/delete", post(editor_delete_form))
@@ -1392,6 +1402,10 @@ pub async fn start_server(repo_path: &str, port: u16) {
         .route("/file/{hash}", get(show_file))
         .route("/raw/{hash}", get(download_raw)) // <-- new: reliable way to view binary / huge files
         .route("/upload/{hash}", post(upload_atom))
+        .route("/sync/refs", get(sync_refs))
+        .route("/sync/fetch", post(sync_fetch))
+        // Lu en flux jusqu'à `MAX_BODY`, bien au-delà de la limite par défaut de 2 Mo
+        .route("/sync/push", post(sync_push))
         .route("/api/commits", get(api_commits))
         .route("/api/commits/query", get(api_commit_query))
         .fallback_service(tower_http::services::ServeDir::new("."))
@@ -1556,16 +1570,25 @@ fn render_query_results_html(
             html_escape(&item.timestamp),
             html_escape(&time_ago(&item.timestamp))
         );
-        let message_html = format!(
-            "<a href='/commit/{}' class='query-message'>{}</a>",
-            item.id,
-            html_escape(&summary)
-        );
+        // Les pages /commit/{id} ne connaissent que la saison courante
+        let message_html = if item.schema == "main" {
+            format!(
+                "<a href='/commit/{}' class='query-message'>{}</a>",
+                item.id,
+                html_escape(&summary)
+            )
+        } else {
+            format!(
+                "<span class='query-message'>{}</span>",
+                html_escape(&summary)
+            )
+        };
 
         let mut files_html = String::new();
         if fields.files {
-            let (files, total_files) = crate::db::commit_files_preview(conn, item.id, 6)
-                .unwrap_or_else(|_| (Vec::new(), 0));
+            let (files, total_files) =
+                crate::db::commit_files_preview(conn, &item.schema, item.id, 6)
+                    .unwrap_or_else(|_| (Vec::new(), 0));
             if files.is_empty() {
                 files_html.push_str("<span class='meta'>No files</span>");
             } else {
@@ -2343,7 +2366,34 @@ pub async fn idx_commits(
          </div>",
         total_commits, contributors.len()
     ));
-    stats_tab.push_str("</div></div>");
+    stats_tab.push_str("</div>");
+
+    // Branches locales et refs de suivi, avec l'avance/le retard sur chaque remote
+    stats_tab.push_str("<div class='card table-card'><h3>Branches</h3><table><thead><tr><th>Branch</th><th>Head</th><th>Tracking</th></tr></thead><tbody>");
+    let local_refs = crate::transfer::local_refs(&conn).unwrap_or_default();
+    for (name, hash) in &local_refs {
+        let tracking = crate::remote::tracking_summary(&conn, name, hash)
+            .unwrap_or_default()
+            .iter()
+            .map(|line| html_escape(line.split_once("...").map_or(line.as_str(), |(_, r)| r)))
+            .collect::<Vec<_>>()
+            .join("<br>");
+        stats_tab.push_str(&format!(
+            "<tr><td>{}</td><td><code>{}</code></td><td>{}</td></tr>",
+            html_escape(name),
+            short_hash(hash),
+            tracking
+        ));
+    }
+    for (remote, name, hash) in crate::remote::tracking_refs(&conn).unwrap_or_default() {
+        stats_tab.push_str(&format!(
+            "<tr><td>{}/{}</td><td><code>{}</code></td><td>remote</td></tr>",
+            html_escape(&remote),
+            html_escape(&name),
+            short_hash(&hash)
+        ));
+    }
+    stats_tab.push_str("</tbody></table></div></div>");
 
     let (rows, nav_html) = render_commits_list(&conn, page_num);
     let query_tab = format!(
@@ -2576,6 +2626,34 @@ async fn show_commit(
             }
         }
     }
+    // Un commit de merge a plusieurs parents : on les liste tous
+    let parents_html = {
+        let links: Vec<String> = crate::db::commit_parents(&conn, &hash)
+            .unwrap_or_default()
+            .iter()
+            .map(
+                |parent| match crate::vcs::get_commit_id_by_hash(&conn, parent) {
+                    Ok(Some(id)) => format!(
+                        "<a href='/commit/{id}' class='hash'>{}</a>",
+                        html_escape(short_hash(parent))
+                    ),
+                    _ => format!(
+                        "<span class='hash'>{}</span>",
+                        html_escape(short_hash(parent))
+                    ),
+                },
+            )
+            .collect();
+        if links.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "<tr><td><b>parents</b></td><td>{}</td></tr>",
+                links.join(" ")
+            )
+        }
+    };
+
     let tags_html = if tags.is_empty() {
         String::new()
     } else {
@@ -2710,6 +2788,7 @@ async fn show_commit(
                  <tr><td><b>date</b></td><td>{} ({})</td></tr>
                  <tr><td><b>commit</b></td><td class='hash'>{}</td></tr>
                  {}
+                 {}
                  <tr><td><b>tree</b></td><td class='hash'><a href='/commit/{}/tree'>{}</a></td></tr>
                  <tr>
                    <td><b>actions</b></td>
@@ -2729,6 +2808,7 @@ async fn show_commit(
             html_escape(&date),
             time_ago(&date),
             html_escape(&hash),
+            parents_html,
             tags_html,
             commit_id,
             html_escape(&tree_hash),
@@ -3203,8 +3283,9 @@ fn render_tree_html_flat(
                 format!("{}/{}", current_path, name)
             };
             let link = format!(
-                "<a href='/file/{}' class='file'>{}</a>",
+                "<a href='/file/{}?commit={commit_id}&amp;path={}' class='file'>{}</a>",
                 html_escape(&hash),
+                html_escape(&full_path),
                 html_escape(&name)
             );
             (full_path, link)
@@ -3628,16 +3709,7 @@ async fn show_commit_diff(
 }
 
 fn get_raw_blob_opt(conn: &Connection, hash: &str) -> Option<Vec<u8>> {
-    if let Ok(mut stmt) = conn.prepare("SELECT content FROM store.blobs WHERE hash = ?") {
-        if stmt.bind((1, hash)).is_ok() {
-            if let Ok(sqlite::State::Row) = stmt.next() {
-                if let Ok(content) = stmt.read::<Vec<u8>, _>(0) {
-                    return Some(decompress(&content));
-                }
-            }
-        }
-    }
-    None
+    crate::chunks::read(conn, hash).ok().flatten()
 }
 
 fn get_raw_blob(conn: &Connection, hash: &str) -> Vec<u8> {
@@ -3789,6 +3861,7 @@ fn render_diff(old: &[u8], new: &[u8], mode: &str) -> String {
 async fn show_file(
     State(state): State<Arc<AppState>>,
     UrlPath(hash): UrlPath<String>,
+    Query(context): Query<FileContext>,
 ) -> impl IntoResponse {
     let conn = match state.conn.lock() {
         Ok(g) => g,
@@ -3806,7 +3879,7 @@ async fn show_file(
     }
 
     if let Ok(sqlite::State::Row) = stmt.next() {
-        let content: Vec<u8> = match stmt.read("content") {
+        let content: Option<Vec<u8>> = match stmt.read("content") {
             Ok(v) => v,
             Err(_) => return http_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read blob"),
         };
@@ -3823,24 +3896,49 @@ async fn show_file(
             }
         }
 
-        // Decompress (falls back to raw if it's not zlib-compressed)
-        let bytes = decompress(&content);
-
         const MAX_PREVIEW_BYTES: usize = 512 * 1024; // 512 KiB
 
+        let bytes = match content {
+            // Decompress (falls back to raw if it's not zlib-compressed)
+            Some(content) => decompress(&content),
+            // Chunked blob: only the start is read for the preview
+            None => {
+                let mut head = Vec::new();
+                if let Ok(Some(blob)) = crate::chunks::open(&conn, &hash) {
+                    let _ = blob.take(MAX_PREVIEW_BYTES as u64).read_to_end(&mut head);
+                }
+                // A character cut at the end of the preview doesn't make the text binary
+                if let Err(e) = std::str::from_utf8(&head)
+                    && e.error_len().is_none()
+                {
+                    head.truncate(e.valid_up_to());
+                }
+                head
+            }
+        };
+
         let mut body = String::new();
         body.push_str("<div style='margin-bottom: 20px;'><a href='javascript:history.back()'>&larr; Back</a></div>");
+        let blame_link = match (context.commit, context.path.as_deref()) {
+            (Some(commit), Some(path)) => format!(
+                " — <a href='/commit/{commit}/blame/{}'>Blame</a>",
+                html_escape(path)
+            ),
+            _ => String::new(),
+        };
         body.push_str(&format!(
-            "<p class='age' style='margin-bottom: 15px;'>file: <strong>{}</strong> — hash: <span class='hash'>{}</span> — size: {} bytes — <a href='/raw/{}'>Download raw</a></p>",
+            "<p class='age' style='margin-bottom: 15px;'>file: <strong>{}</strong> — hash: <span class='hash'>{}</span> — size: {} bytes — <a href='/raw/{}'>Download raw</a>{}</p>",
             html_escape(&filename),
             html_escape(&hash),
             original_size.max(0),
             html_escape(&hash),
+            blame_link,
         ));
 
         match String::from_utf8(bytes) {
             Ok(mut text) => {
-                let truncated = text.len() > MAX_PREVIEW_BYTES;
+                let truncated =
+                    text.len() > MAX_PREVIEW_BYTES || text.len() < original_size.max(0) as usize;
                 if truncated {
                     text.truncate(MAX_PREVIEW_BYTES);
                     text.push_str("\n\n[... truncated preview ...]");
@@ -3915,17 +4013,92 @@ async fn show_file(
     }
 }
 
+// Blame d'un fichier tel qu'il est dans le commit `id`
+async fn show_blame(
+    State(state): State<Arc<AppState>>,
+    UrlPath((commit_id, path)): UrlPath<(i64, String)>,
+) -> impl IntoResponse {
+    let conn = match state.conn.lock() {
+        Ok(g) => g,
+        Err(_) => return http_error(StatusCode::INTERNAL_SERVER_ERROR, "DB lock poisoned"),
+    };
+    let mut head = String::new();
+    if let Ok(mut stmt) = conn.prepare("SELECT hash FROM commits WHERE id = ?")
+        && stmt.bind((1, commit_id)).is_ok()
+        && let Ok(sqlite::State::Row) = stmt.next()
+    {
+        head = stmt.read("hash").unwrap_or_default();
+    }
+    if head.is_empty() {
+        return http_error(StatusCode::NOT_FOUND, "Commit not found");
+    }
+    let lines = match crate::blame::blame(&conn, &path, &head) {
+        Ok(lines) => lines,
+        Err(e) => return http_error(StatusCode::NOT_FOUND, &e.to_string()),
+    };
+
+    let mut ids = std::collections::HashMap::new();
+    let mut rows = String::new();
+    let mut previous = "";
+    for line in &lines {
+        // Commit, auteur et date seulement en tête de chaque bloc
+        let (commit_html, author_html, age_html) = if line.commit != previous {
+            let id = *ids.entry(line.commit.as_str()).or_insert_with(|| {
+                crate::vcs::get_commit_id_by_hash(&conn, &line.commit)
+                    .ok()
+                    .flatten()
+            });
+            let hash_html = format!("<code class='hash'>{}</code>", short_hash(&line.commit));
+            let commit_html = match id {
+                Some(id) => format!("<a href='/commit/{id}'>{hash_html}</a>"),
+                None => hash_html,
+            };
+            (
+                commit_html,
+                html_escape(&line.author),
+                html_escape(&time_ago(&line.timestamp)),
+            )
+        } else {
+            Default::default()
+        };
+        previous = &line.commit;
+        rows.push_str(&format!(
+            "<tr><td>{commit_html}</td><td>{author_html}</td><td class='age'>{age_html}</td>\
+             <td class='blame-no'>{}</td><td><pre class='blame-code'>{}</pre></td></tr>",
+            line.number,
+            html_escape(&line.content)
+        ));
+    }
+
+    let parent = path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+    let body = format!(
+        "<div style='margin-bottom: 20px;'><a href='/commit/{commit_id}/tree/{}'>&larr; Back</a></div>\
+         <p class='age' style='margin-bottom: 15px;'>blame: <strong>{}</strong> at <span class='hash'>{}</span> — {} lines</p>\
+         <table class='blame-table'>{rows}</table>",
+        html_escape(parent),
+        html_escape(&path),
+        short_hash(&head),
+        lines.len()
+    );
+    let style = ".blame-table { width: 100%; border-collapse: collapse; font-size: 13px; }\
+        .blame-table td { padding: 0 8px; vertical-align: top; white-space: nowrap; }\
+        .blame-no { color: var(--muted); text-align: right; }\
+        .blame-code { margin: 0; background: none; padding: 0; }";
+    page("Blame", style, &body).into_response()
+}
+
 // New: raw download endpoint (fixes “display” for binary files and huge blobs)
 async fn download_raw(
     State(state): State<Arc<AppState>>,
     UrlPath(hash): UrlPath<String>,
 ) -> impl IntoResponse {
+    let shared = Arc::clone(&state);
     let conn = match state.conn.lock() {
         Ok(g) => g,
         Err(_) => return http_error(StatusCode::INTERNAL_SERVER_ERROR, "DB lock poisoned"),
     };
 
-    let query = "SELECT content FROM store.blobs WHERE hash = ?";
+    let query = "SELECT content, size FROM store.blobs WHERE hash = ?";
     let mut stmt = match conn.prepare(query) {
         Ok(s) => s,
         Err(_) => return http_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to query blob"),
@@ -3936,14 +4109,40 @@ async fn download_raw(
     }
 
     if let Ok(sqlite::State::Row) = stmt.next() {
-        let content: Vec<u8> = match stmt.read("content") {
+        let content: Option<Vec<u8>> = match stmt.read("content") {
             Ok(v) => v,
             Err(_) => return http_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read blob"),
         };
 
-        let bytes = decompress(&content);
-
         let mut headers = HeaderMap::new();
+        let body = match content {
+            Some(content) => axum::body::Body::from(decompress(&content)),
+            // Chunked blob: streamed chunk by chunk, the lock is held only while reading one
+            None => {
+                let size: i64 = stmt.read("size").unwrap_or(0);
+                let chunks = match crate::chunks::chunk_list(&conn, &hash) {
+                    Ok(chunks) => chunks,
+                    Err(_) => {
+                        return http_error(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to read blob chunks",
+                        );
+                    }
+                };
+                headers.insert(header::CONTENT_LENGTH, size.max(0).into());
+                let stream = futures_util::stream::iter(chunks).map(move |chunk| {
+                    let conn = shared
+                        .conn
+                        .lock()
+                        .map_err(|_| std::io::Error::other("DB lock poisoned"))?;
+                    crate::chunks::read_chunk(&conn, &chunk)
+                        .map_err(std::io::Error::other)?
+                        .map(Bytes::from)
+                        .ok_or_else(|| std::io::Error::other(format!("chunk {chunk} is missing")))
+                });
+                axum::body::Body::from_stream(stream)
+            }
+        };
         headers.insert(
             header::CONTENT_TYPE,
             axum::http::HeaderValue::from_static("application/octet-stream"),
@@ -3955,7 +4154,7 @@ async fn download_raw(
             headers.insert(header::CONTENT_DISPOSITION, v);
         }
 
-        (StatusCode::OK, headers, bytes).into_response()
+        (StatusCode::OK, headers, body).into_response()
     } else {
         http_error(StatusCode::NOT_FOUND, "File not found")
     }
@@ -4342,16 +4541,19 @@ async fn new_commit_form(
     } else {
         for s in &status {
             let (prefix, color, path) = match s {
-                FileStatus::New(p) => ("+", "#6ce9a6", p),
-                FileStatus::Modified(p, _) => ("~", "#ffd166", p),
-                FileStatus::Deleted(p, _) => ("-", "#ff5d6c", p),
+                FileStatus::New(p) => ("+", "#6ce9a6", p.display().to_string()),
+                FileStatus::Modified(p, _) => ("~", "#ffd166", p.display().to_string()),
+                FileStatus::Deleted(p, _) => ("-", "#ff5d6c", p.display().to_string()),
+                FileStatus::Renamed(from, to, _) => (
+                    "R",
+                    "#7cc4ff",
+                    format!("{} -> {}", from.display(), to.display()),
+                ),
                 _ => continue,
             };
             status_html.push_str(&format!(
                 "<div style='color: {}'>{} {}</div>",
-                color,
-                prefix,
-                path.display()
+                color, prefix, path
             ));
         }
     }
@@ -4409,6 +4611,27 @@ async fn new_commit_form(
                 ));
                 diff_index += 1;
             }
+            FileStatus::Renamed(from, to, _) => {
+                // Renamed files show what changed on the way, if anything
+                let old_bytes = head_state
+                    .get(from)
+                    .map(|(hash, _)| get_raw_blob(&conn, hash))
+                    .unwrap_or_default();
+                let new_bytes =
+                    crate::vcs::read_entry(&Path::new(".").join(to)).unwrap_or_default();
+                let open_attr = if diff_index == 0 { " open" } else { "" };
+                diff_accordions.push_str(&format!(
+                    "<details class='diff-accordion'{}>\
+                       <summary><span class='diff-tag diff-tag-modified'>Renamed</span><span class='diff-path'>{} &rarr; {}</span></summary>\
+                       {}\
+                     </details>",
+                    open_attr,
+                    html_escape(&from.display().to_string()),
+                    html_escape(&to.display().to_string()),
+                    render_diff(&old_bytes, &new_bytes, "unified")
+                ));
+                diff_index += 1;
+            }
             FileStatus::New(path) => {
                 let new_path = Path::new(".").join(path);
                 let new_bytes = std::fs::read(&new_path).unwrap_or_default();
@@ -4977,3 +5200,101 @@ async fn upload_atom(
         _ => StatusCode::FORBIDDEN, // Signature invalide !
     }
 }
+
+// Protocole natif entre nœuds Lys (voir transfer.rs)
+async fn sync_refs(State(state): State<Arc<AppState>>) -> Response {
+    let conn = match state.conn.lock() {
+        Ok(g) => g,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "DB lock poisoned").into_response(),
+    };
+    match crate::transfer::serve_refs(&conn, &state.repo_root)
+        .and_then(|refs| Ok(serde_json::to_vec(&refs)?))
+    {
+        Ok(json) => ([(header::CONTENT_TYPE, "application/json")], json).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn sync_fetch(State(state): State<Arc<AppState>>, body: Bytes) -> Response {
+    let request: crate::transfer::FetchRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    // Le paquet est écrit dans un fichier temporaire sous le verrou, puis envoyé en flux
+    let pack = {
+        let conn = match state.conn.lock() {
+            Ok(g) => g,
+            Err(_) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "DB lock poisoned").into_response();
+            }
+        };
+        crate::transfer::build_batch(&conn, &request).and_then(|batch| {
+            let mut file = tempfile::tempfile()?;
+            crate::transfer::write_pack(&conn, &batch, &batch.blobs, &mut file)?;
+            std::io::Seek::rewind(&mut file)?;
+            Ok(file)
+        })
+    };
+    let file = match pack {
+        Ok(file) => tokio::fs::File::from_std(file),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let stream = futures_util::stream::unfold(file, |mut file| async move {
+        let mut buf = vec![0; 64 * 1024];
+        match tokio::io::AsyncReadExt::read(&mut file, &mut buf).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buf.truncate(read);
+                Some((Ok(Bytes::from(buf)), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    });
+    (
+        [(header::CONTENT_TYPE, "application/zstd")],
+        axum::body::Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+async fn sync_push(State(state): State<Arc<AppState>>, body: axum::body::Body) -> Response {
+    use crate::transfer::PushOutcome;
+    // Le paquet reçu attend dans un fichier temporaire, jamais en mémoire
+    let mut spool = match tempfile::tempfile() {
+        Ok(file) => tokio::fs::File::from_std(file),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let (mut stream, mut received) = (body.into_data_stream(), 0);
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        };
+        received += chunk.len();
+        if received > crate::transfer::MAX_BODY {
+            return (StatusCode::PAYLOAD_TOO_LARGE, "Push is too large.").into_response();
+        }
+        if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut spool, &chunk).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    }
+    if let Err(e) = tokio::io::AsyncWriteExt::flush(&mut spool).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    let mut body = spool.into_std().await;
+    if let Err(e) = std::io::Seek::rewind(&mut body) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+    }
+    let conn = match state.conn.lock() {
+        Ok(g) => g,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "DB lock poisoned").into_response(),
+    };
+    // Une erreur ici vient d'un lot refusé à la vérification (hash, signature, objet manquant)
+    match crate::transfer::receive_push(&conn, &state.repo_root, body, true) {
+        Ok(PushOutcome::Updated) => (StatusCode::OK, "updated").into_response(),
+        Ok(PushOutcome::UpToDate) => (StatusCode::OK, "up to date").into_response(),
+        Ok(PushOutcome::Rejected(reason)) => (StatusCode::CONFLICT, reason).into_response(),
+        Ok(PushOutcome::Denied(reason)) => (StatusCode::FORBIDDEN, reason).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,295 @@
+use crate::vcs::hash_entry;
+use ignore::WalkState;
+use rayon::prelude::*;
+use sqlite::{Connection, Error, State};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Un fichier du répertoire de travail : son hash et ce qu'en dit `stat`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    pub hash: String,
+    pub size: u64,
+    pub mode: u32,
+}
+
+/// Résultat de `scan` : tous les fichiers suivis (liens symboliques compris),
+/// les dossiers vides, et combien de fichiers ont dû être relus.
+#[derive(Debug, Default)]
+pub struct Scan {
+    pub files: BTreeMap<PathBuf, File>,
+    pub dirs: BTreeSet<PathBuf>,
+    pub hashed: usize,
+}
+
+// Ce que l'index garde d'un fichier pour savoir s'il a pu changer
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stat {
+    size: u64,
+    mtime: i64,
+    inode: i64,
+}
+
+// Ligne de store.worktree : le stat du fichier quand il a été haché, et quand
+struct Cached {
+    stat: Stat,
+    hash: String,
+    checked: i64,
+}
+
+// Contenu d'un fichier selon le scan : inchangé depuis l'index, relu, ou
+// illisible (son entrée d'index ne vaut alors plus rien)
+enum Hashed {
+    Cached,
+    Fresh(String),
+    Failed,
+}
+
+fn nanos(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn inode_and_mode(metadata: &Metadata) -> (i64, u32) {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    (metadata.ino() as i64, metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn inode_and_mode(metadata: &Metadata) -> (i64, u32) {
+    if metadata.file_type().is_symlink() {
+        (0, 0o120777)
+    } else {
+        (0, 0o100644)
+    }
+}
+
+// Un fichier écrit pendant ou après son hachage peut avoir changé sans que son
+// stat le montre : il est relu. Sans nanosecondes (FAT, certains montages),
+// on compare à la seconde près.
+fn is_racy(stat: &Stat, checked: i64) -> bool {
+    const SECOND: i64 = 1_000_000_000;
+    if stat.mtime % SECOND == 0 {
+        stat.mtime / SECOND >= checked / SECOND
+    } else {
+        stat.mtime >= checked
+    }
+}
+
+fn load(conn: &Connection) -> Result<HashMap<PathBuf, Cached>, Error> {
+    let mut cache = HashMap::new();
+    let mut stmt =
+        conn.prepare("SELECT path, size, mtime, inode, hash, checked FROM store.worktree")?;
+    while let Ok(State::Row) = stmt.next() {
+        cache.insert(
+            PathBuf::from(stmt.read::<String, _>("path")?),
+            Cached {
+                stat: Stat {
+                    size: stmt.read::<i64, _>("size")? as u64,
+                    mtime: stmt.read("mtime")?,
+                    inode: stmt.read("inode")?,
+                },
+                hash: stmt.read("hash")?,
+                checked: stmt.read("checked")?,
+            },
+        );
+    }
+    Ok(cache)
+}
+
+// Un parcours qui a paniqué n'a laissé que des entrées complètes
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Parcourt le répertoire de travail comme `status` et `commit`, en ne
+/// rehachant (en parallèle) que les fichiers dont le stat a changé depuis
+/// l'index `store.worktree`, qui est ensuite mis à jour.
+pub fn scan(conn: &Connection, root: &Path) -> Result<Scan, Error> {
+    let walk = ignore::WalkBuilder::new(root)
+        .add_custom_ignore_filename("syl")
+        .threads(4)
+        .standard_filters(true)
+        .build_parallel();
+    // Parcours et stat en parallèle ; `.lys` n'est jamais descendu
+    let found = Mutex::new(Vec::new());
+    let dirs = Mutex::new(Vec::new());
+    walk.run(|| {
+        let (found, dirs) = (&found, &dirs);
+        Box::new(move |entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            let path = entry.path();
+            if path.file_name().is_some_and(|name| name == ".lys") {
+                return WalkState::Skip;
+            }
+            // Un fichier illisible (ou disparu entre-temps) est ignoré, comme avant ;
+            // un lien symbolique est suivi comme tel, jamais déréférencé
+            let (Ok(metadata), Ok(relative)) =
+                (std::fs::symlink_metadata(path), path.strip_prefix(root))
+            else {
+                return WalkState::Continue;
+            };
+            if metadata.is_dir() {
+                if !relative.as_os_str().is_empty() {
+                    lock(dirs).push(relative.to_path_buf());
+                }
+            } else {
+                let (inode, mode) = inode_and_mode(&metadata);
+                let stat = Stat {
+                    size: metadata.len(),
+                    mtime: metadata.modified().map(nanos).unwrap_or(0),
+                    inode,
+                };
+                let item = (relative.to_path_buf(), path.to_path_buf(), stat, mode);
+                lock(found).push(item);
+            }
+            WalkState::Continue
+        })
+    });
+    let found = found
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    // Un dossier est vide s'il n'est le parent d'aucun fichier ni dossier suivi,
+    // et qu'il ne contient pas non plus de fichiers ignorés
+    let dirs = dirs
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let parents: BTreeSet<&Path> = found
+        .iter()
+        .map(|(relative, ..)| relative)
+        .chain(&dirs)
+        .filter_map(|relative| relative.parent())
+        .collect();
+    let empty: BTreeSet<PathBuf> = dirs
+        .iter()
+        .filter(|dir| !parents.contains(dir.as_path()))
+        .filter(|dir| {
+            std::fs::read_dir(root.join(dir)).is_ok_and(|mut entries| entries.next().is_none())
+        })
+        .cloned()
+        .collect();
+
+    let mut cache = load(conn)?;
+    let checked = nanos(SystemTime::now());
+    let hashed: Vec<Hashed> = found
+        .par_iter()
+        .map(|(relative, path, stat, _)| match cache.get(relative) {
+            Some(cached) if cached.stat == *stat && !is_racy(stat, cached.checked) => {
+                Hashed::Cached
+            }
+            _ => hash_entry(path).map_or(Hashed::Failed, Hashed::Fresh),
+        })
+        .collect();
+
+    let mut scan = Scan {
+        dirs: empty,
+        ..Scan::default()
+    };
+    // SAVEPOINT plutôt que BEGIN : `status` peut être appelé dans une transaction
+    conn.execute("SAVEPOINT worktree;")?;
+    let outcome = (|| -> Result<(), Error> {
+        for ((relative, _, stat, mode), hash) in found.into_iter().zip(hashed) {
+            // Ce qui reste dans `cache` après la boucle a disparu du disque ou
+            // n'a pas pu être relu : son entrée est retirée
+            let hash = match hash {
+                Hashed::Fresh(hash) => {
+                    cache.remove(&relative);
+                    let mut stmt = conn.prepare(
+                        "INSERT OR REPLACE INTO store.worktree
+                         (path, size, mtime, inode, hash, checked) VALUES (?, ?, ?, ?, ?, ?)",
+                    )?;
+                    stmt.bind((1, relative.to_string_lossy().as_ref()))?;
+                    stmt.bind((2, stat.size as i64))?;
+                    stmt.bind((3, stat.mtime))?;
+                    stmt.bind((4, stat.inode))?;
+                    stmt.bind((5, hash.as_str()))?;
+                    stmt.bind((6, checked))?;
+                    stmt.next()?;
+                    scan.hashed += 1;
+                    hash
+                }
+                Hashed::Cached => match cache.remove(&relative) {
+                    Some(cached) => cached.hash,
+                    None => continue,
+                },
+                // Illisible : absent du scan, comme s'il n'existait pas
+                Hashed::Failed => continue,
+            };
+            scan.files.insert(
+                relative,
+                File {
+                    hash,
+                    size: stat.size,
+                    mode,
+                },
+            );
+        }
+        for gone in cache.keys() {
+            let mut stmt = conn.prepare("DELETE FROM store.worktree WHERE path = ?")?;
+            stmt.bind((1, gone.to_string_lossy().as_ref()))?;
+            stmt.next()?;
+        }
+        Ok(())
+    })();
+    if outcome.is_ok() {
+        conn.execute("RELEASE worktree;")?;
+    } else {
+        conn.execute("ROLLBACK TO worktree; RELEASE worktree;")?;
+    }
+    outcome?;
+    Ok(scan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 100 000 fichiers : un premier scan qui hache tout, un second qui ne relit
+    // rien, puis quelques fichiers modifiés
+    #[test]
+    fn skips_unchanged_files_on_a_large_tree() {
+        let repo = tempfile::tempdir().unwrap();
+        for dir in 0..1000 {
+            let dir_path = repo.path().join(format!("src/d{dir:03}"));
+            std::fs::create_dir_all(&dir_path).unwrap();
+            for file in 0..100 {
+                std::fs::write(
+                    dir_path.join(format!("f{file:02}.txt")),
+                    format!("{dir} {file}\n"),
+                )
+                .unwrap();
+            }
+        }
+        let conn = crate::db::connect_lys(repo.path()).unwrap();
+
+        let cold = scan(&conn, repo.path()).unwrap();
+        let warm = scan(&conn, repo.path()).unwrap();
+        for dir in 0..10 {
+            let path = repo.path().join(format!("src/d{dir:03}/f00.txt"));
+            std::fs::write(path, "changed\n").unwrap();
+        }
+        std::fs::remove_file(repo.path().join("src/d999/f99.txt")).unwrap();
+        let touched = scan(&conn, repo.path()).unwrap();
+
+        assert_eq!(cold.files.len(), 100_000);
+        assert_eq!(cold.hashed, 100_000);
+        assert_eq!(warm.hashed, 0);
+        assert_eq!(warm.files, cold.files);
+        assert_eq!(touched.hashed, 10);
+        assert_eq!(touched.files.len(), 99_999);
+        let changed = &touched.files[Path::new("src/d000/f00.txt")];
+        assert_eq!(
+            changed.hash,
+            blake3::hash(b"changed\n").to_hex().to_string()
+        );
+    }
+}